@@ -20,7 +20,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     input.insert("buyout_total", "45,250,000".to_string());
     input.insert("pawn_total", "37,200,000".to_string());
     input.insert("userName", "田中 花子".to_string());
-    input.insert("qrCode", "QR".to_string()); // Placeholder for QR code content
+    input.insert(
+        "qrCode",
+        "https://example.com/quotes/2025-0813-1".to_string(),
+    );
     input.insert("dateTime", print_datetime.clone());
 
     inputs.push(input);
@@ -7,6 +7,7 @@ use std::cell::RefCell;
 use std::cmp::max;
 use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::ops::Range;
 use std::sync::Arc;
 
 // Thread-local WordSegmenter instance cached for performance
@@ -70,6 +71,8 @@ pub enum LineBreakMode {
 #[derive(Debug, Clone)]
 pub struct FontSpec {
     font: Arc<ParsedFont>,
+    /// Fonts measuring the clusters `font` has no glyphs for, in order.
+    fallbacks: Vec<Arc<ParsedFont>>,
     line_break_mode: LineBreakMode,
 }
 
@@ -77,6 +80,7 @@ impl FontSpec {
     pub fn new(font: Arc<ParsedFont>) -> Self {
         Self {
             font,
+            fallbacks: Vec::new(),
             line_break_mode: LineBreakMode::Word,
         }
     }
@@ -86,6 +90,21 @@ impl FontSpec {
         self
     }
 
+    pub fn with_fallbacks(mut self, fallbacks: Vec<Arc<ParsedFont>>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    /// The font `cluster` is drawn in: the first of the chain that has it.
+    fn font_for_cluster(&self, cluster: &str) -> &ParsedFont {
+        let fonts = std::iter::once(&self.font).chain(&self.fallbacks);
+        let index = font_index_for_cluster(cluster, fonts.map(|font| font.as_ref()));
+        match index {
+            0 => &self.font,
+            index => &self.fallbacks[index - 1],
+        }
+    }
+
     fn apply_japanese_kinsoku(lines: Vec<String>) -> Vec<String> {
         filter_end_jp(filter_start_jp(lines))
     }
@@ -114,100 +133,13 @@ impl FontSpec {
         box_width: Pt,
         character_spacing: Pt,
     ) -> Result<Vec<String>, Error> {
-        let segments = match self.line_break_mode {
-            LineBreakMode::Word => Self::split_text_by_word_segmenter(paragraph),
-            LineBreakMode::Char => Self::split_text_by_grapheme_cluster(paragraph),
-        };
-
-        let mut lines = Vec::new();
-        let mut current_line = String::new();
-
-        for segment in segments {
-            if segment.is_empty() {
-                continue;
-            }
-
-            if current_line.is_empty() {
-                let text_width =
-                    self.width_of_text_at_size(&segment, font_size, character_spacing)?;
-
-                if text_width <= box_width {
-                    current_line = segment;
-                } else {
-                    current_line = self.push_grapheme_wrapped_lines(
-                        &mut lines,
-                        segment,
-                        font_size,
-                        box_width,
-                        character_spacing,
-                    )?;
-                }
-                continue;
-            }
-
-            let combined = format!("{}{}", current_line, segment);
-            let combined_width =
-                self.width_of_text_at_size(&combined, font_size, character_spacing)?;
-
-            if combined_width <= box_width {
-                current_line = combined;
-                continue;
-            }
-
-            let segment_width =
-                self.width_of_text_at_size(&segment, font_size, character_spacing)?;
-
-            if segment_width <= box_width {
-                lines.push(std::mem::take(&mut current_line));
-                current_line = segment;
-            } else {
-                lines.push(std::mem::take(&mut current_line));
-                current_line = self.push_grapheme_wrapped_lines(
-                    &mut lines,
-                    segment,
-                    font_size,
-                    box_width,
-                    character_spacing,
-                )?;
-            }
-        }
-
-        if !current_line.is_empty() {
-            lines.push(current_line);
-        }
-
-        Ok(lines)
-    }
-
-    fn push_grapheme_wrapped_lines(
-        &self,
-        lines: &mut Vec<String>,
-        segment: String,
-        font_size: Pt,
-        box_width: Pt,
-        character_spacing: Pt,
-    ) -> Result<String, Error> {
-        let mut current_line = String::new();
-
-        for cluster in Self::split_text_by_grapheme_cluster(&segment) {
-            if current_line.is_empty() {
-                current_line = cluster;
-                continue;
-            }
-
-            let combined = format!("{}{}", current_line, cluster);
-            let combined_width =
-                self.width_of_text_at_size(&combined, font_size, character_spacing)?;
-
-            if combined_width <= box_width {
-                current_line = combined;
-            } else {
-                lines.push(std::mem::take(&mut current_line));
-                current_line = cluster;
-            }
-        }
-
-        Ok(current_line)
+        let lines = wrap_paragraph(paragraph, self.line_break_mode, box_width, |range| {
+            self.width_of_text_at_size(&paragraph[range], font_size, character_spacing)
+        })?;
+        Ok(lines
+            .into_iter()
+            .map(|range| paragraph[range].to_string())
+            .collect())
     }
 
     fn split_text_by_word_segmenter(paragraph: &str) -> Vec<String> {
@@ -226,7 +158,7 @@ impl FontSpec {
         segments
     }
 
-    fn split_text_by_grapheme_cluster(paragraph: &str) -> Vec<String> {
+    pub(crate) fn split_text_by_grapheme_cluster(paragraph: &str) -> Vec<String> {
         let mut segments: Vec<String> = Vec::new();
         let mut last_breakpoint = 0;
 
@@ -242,7 +174,12 @@ impl FontSpec {
         segments
     }
 
-    fn glyph_width_for_char(&self, ch: char, percentage_font_scaling: f32, tofu_width: f32) -> f32 {
+    fn glyph_width_for_char(
+        font: &ParsedFont,
+        ch: char,
+        percentage_font_scaling: f32,
+        tofu_width: f32,
+    ) -> f32 {
         if is_non_rendering_cluster_char(ch) {
             return 0.0;
         }
@@ -252,63 +189,11 @@ impl FontSpec {
         // attached), so space goes through the same `lookup_glyph_index` ->
         // `get_horizontal_advance` lookup as every other character — the
         // metric the renderer actually draws with.
-        self.font
-            .lookup_glyph_index(ch as u32)
-            .map(|glyph_index| self.font.get_horizontal_advance(glyph_index))
+        font.lookup_glyph_index(ch as u32)
+            .map(|glyph_index| font.get_horizontal_advance(glyph_index))
             .map(|width| width as f32 * percentage_font_scaling)
             .unwrap_or(tofu_width * percentage_font_scaling)
     }
-
-    fn calculate_constraints(
-        &self,
-        font_size: Pt,
-        line_height: Option<f32>,
-        character_spacing: Pt,
-        width: Mm,
-        dynamic: DynamicFontSize,
-        content: &str,
-    ) -> Result<(Mm, Mm), Error> {
-        let mut total_width_in_mm: Mm = Mm(0.0);
-        let mut total_height_in_mm: Mm = Mm(0.0);
-        let line_height: f32 = line_height.unwrap_or(1.0);
-        let first_line_text_height: Pt = self.height_of_font_at_size(font_size);
-        let first_line_height_in_mm: Mm = (first_line_text_height * line_height).into();
-        let other_row_height_in_mm: Mm = (font_size * line_height).into();
-        let splitted_paragraphs =
-            self.split_text_to_size(content, font_size, width.into(), character_spacing)?;
-
-        let needs_width = dynamic.is_fit_vertical() || dynamic.is_fit_horizontal();
-
-        for (line_index, line) in splitted_paragraphs.into_iter().enumerate() {
-            // Calculate width only once if needed
-            if needs_width {
-                // Remove trailing newline if present
-                let line_to_measure = if line.ends_with('\n') {
-                    &line[..line.len() - 1]
-                } else {
-                    &line
-                };
-
-                let text_width =
-                    self.width_of_text_at_size(line_to_measure, font_size, character_spacing)?;
-
-                total_width_in_mm = max(total_width_in_mm, text_width.into());
-            }
-
-            // Calculate height
-            if line_index == 0 {
-                total_height_in_mm += first_line_height_in_mm;
-            } else {
-                total_height_in_mm += other_row_height_in_mm;
-            }
-        }
-
-        Ok((total_width_in_mm, total_height_in_mm))
-    }
-
-    fn height_of_font_at_size(&self, font_size: Pt) -> Pt {
-        font_size
-    }
 }
 
 impl FontSpecTrait for FontSpec {
@@ -339,60 +224,15 @@ impl FontSpecTrait for FontSpec {
         height: Mm,
         content: &str,
     ) -> Result<Pt, Error> {
-        let mut font_size: Pt = dynamic.get_min();
-        let (mut total_width_in_mm, mut total_height_in_mm) = self.calculate_constraints(
-            font_size,
+        fit_dynamic_font_size(
+            self,
+            dynamic,
             line_height,
             character_spacing,
             width,
-            dynamic.clone(),
-            content,
-        )?;
-
-        while dynamic.should_font_grow_to_fit(
-            font_size,
-            width,
             height,
-            total_width_in_mm,
-            total_height_in_mm,
-        ) {
-            font_size += Pt(0.25);
-            let (new_total_width_in_mm, new_total_height_in_mm) = self.calculate_constraints(
-                font_size,
-                line_height,
-                character_spacing,
-                width,
-                dynamic.clone(),
-                content,
-            )?;
-            if new_total_height_in_mm < height {
-                total_width_in_mm = new_total_width_in_mm;
-                total_height_in_mm = new_total_height_in_mm
-            } else {
-                font_size -= Pt(0.25);
-                break;
-            }
-        }
-
-        while dynamic.should_font_shrink_to_fit(
-            font_size,
-            width,
-            height,
-            total_width_in_mm,
-            total_height_in_mm,
-        ) {
-            font_size -= Pt(0.25);
-            (total_width_in_mm, total_height_in_mm) = self.calculate_constraints(
-                font_size,
-                line_height,
-                character_spacing,
-                width,
-                dynamic.clone(),
-                content,
-            )?;
-        }
-
-        Ok(font_size)
+            content,
+        )
     }
 
     fn width_of_text_at_size(
@@ -406,8 +246,6 @@ impl FontSpecTrait for FontSpec {
             return Ok(Pt(0.0));
         }
 
-        let percentage_font_scaling = 1000.0 / (self.font.font_metrics.units_per_em as f32);
-
         // TOFU (replacement character) width - use a reasonable default based on font size
         let tofu_width = 500.0; // Units in font metrics
 
@@ -415,9 +253,12 @@ impl FontSpecTrait for FontSpec {
         let mut cluster_count = 0_usize;
 
         for cluster in Self::split_text_by_grapheme_cluster(text) {
-            let sanitized_cluster = sanitize_cluster_for_font(&cluster, &self.font);
+            // フォールバックのフォントは em あたりのユニット数が異なりうる
+            let font = self.font_for_cluster(&cluster);
+            let percentage_font_scaling = 1000.0 / (font.font_metrics.units_per_em as f32);
+            let sanitized_cluster = sanitize_cluster_for_font(&cluster, font);
             let cluster_width = sanitized_cluster.chars().fold(0.0_f32, |acc, ch| {
-                acc + self.glyph_width_for_char(ch, percentage_font_scaling, tofu_width)
+                acc + Self::glyph_width_for_char(font, ch, percentage_font_scaling, tofu_width)
             });
 
             total_width += if cluster_width > 0.0 {
@@ -530,9 +371,261 @@ impl DynamicFontSize {
     }
 }
 
+/// A schema's `fontName`: one loaded font, or several tried in order for
+/// each glyph.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum FontName {
+    Single(String),
+    Chain(Vec<String>),
+}
+
+impl FontName {
+    pub fn names(&self) -> &[String] {
+        match self {
+            FontName::Single(name) => std::slice::from_ref(name),
+            FontName::Chain(names) => names,
+        }
+    }
+}
+
+impl From<String> for FontName {
+    fn from(name: String) -> Self {
+        FontName::Single(name)
+    }
+}
+
+impl From<&str> for FontName {
+    fn from(name: &str) -> Self {
+        FontName::Single(name.to_string())
+    }
+}
+
+/// A CSS-style font weight, from 1 (hairline) to 1000; 400 is regular and
+/// 700 bold. Templates write it as a number or as `"normal"` / `"bold"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(try_from = "JsonFontWeight")]
+pub struct FontWeight(u16);
+
+impl FontWeight {
+    pub const NORMAL: FontWeight = FontWeight(400);
+    pub const BOLD: FontWeight = FontWeight(700);
+
+    /// `None` outside 1..=1000.
+    pub fn new(weight: u16) -> Option<Self> {
+        (1..=1000).contains(&weight).then_some(FontWeight(weight))
+    }
+
+    pub fn get(self) -> u16 {
+        self.0
+    }
+
+    /// Whether text asking for this weight is drawn bold.
+    fn is_bold(self) -> bool {
+        self.0 >= 600
+    }
+
+    /// Sort key ranking `face` as a match for this weight, lowest first, the
+    /// way CSS font matching does: a weight of 400–500 tries heavier faces up
+    /// to 500, then lighter ones, then heavier ones; a lighter weight prefers
+    /// lighter faces and a heavier weight heavier ones.
+    fn preference(self, face: FontWeight) -> (u8, u16) {
+        let (wanted, face) = (self.0, face.0);
+        let heavier = face >= wanted;
+        let rank = match wanted {
+            400..=500 if heavier && face <= 500 => 0,
+            400..=500 if !heavier => 1,
+            400..=500 => 2,
+            0..=399 if !heavier => 0,
+            0..=399 => 1,
+            _ if heavier => 0,
+            _ => 1,
+        };
+        (rank, wanted.abs_diff(face))
+    }
+}
+
+impl Default for FontWeight {
+    fn default() -> Self {
+        FontWeight::NORMAL
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonFontWeight {
+    Number(u16),
+    Keyword(String),
+}
+
+impl TryFrom<JsonFontWeight> for FontWeight {
+    type Error = String;
+
+    fn try_from(json: JsonFontWeight) -> Result<Self, Self::Error> {
+        match json {
+            JsonFontWeight::Number(weight) => FontWeight::new(weight)
+                .ok_or_else(|| format!("font weight {weight} is outside 1..=1000")),
+            JsonFontWeight::Keyword(keyword) => match keyword.as_str() {
+                "normal" => Ok(FontWeight::NORMAL),
+                "bold" => Ok(FontWeight::BOLD),
+                _ => Err(format!(
+                    "invalid font weight {keyword:?}; expected a number, \"normal\" or \"bold\""
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+impl FontStyle {
+    /// The styles a family's faces are tried in for this one: italic and
+    /// oblique stand in for each other before falling back to upright.
+    fn preference(self) -> [FontStyle; 3] {
+        match self {
+            FontStyle::Normal => [FontStyle::Normal, FontStyle::Oblique, FontStyle::Italic],
+            FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Normal],
+            FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Normal],
+        }
+    }
+}
+
+/// What a schema asks to be drawn in: fonts by name (`fontName`), a face of
+/// a registered family (`fontFamily`), or both, at a weight and style. The
+/// family's face comes first and the named fonts are its fallbacks.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FontSelector {
+    pub name: Option<FontName>,
+    pub family: Option<String>,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+}
+
+impl FontSelector {
+    /// Whether the selector names no font at all.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.family.is_none()
+    }
+}
+
+impl From<FontName> for FontSelector {
+    fn from(name: FontName) -> Self {
+        FontSelector {
+            name: Some(name),
+            ..FontSelector::default()
+        }
+    }
+}
+
+/// A face registered in a family: the weight and style it is drawn at and
+/// the name its font is loaded under.
+#[derive(Debug, Clone, PartialEq)]
+struct FontFace {
+    weight: FontWeight,
+    style: FontStyle,
+    font_name: String,
+}
+
+/// Loaded fonts in fallback order. Each grapheme cluster is drawn in the
+/// first font that has all of its glyphs; only clusters none of them has
+/// become tofu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontChain {
+    /// Never empty.
+    fonts: Vec<(FontId, Arc<ParsedFont>)>,
+    /// What the chain was selected by, so another weight or style of the
+    /// same family can be picked from it.
+    selector: FontSelector,
+    /// Drawn bold by stroking the outlines, since no bold face was loaded.
+    bold: bool,
+    /// Drawn oblique by skewing the text matrix, since no italic or oblique
+    /// face was loaded.
+    oblique: bool,
+}
+
+impl FontChain {
+    pub fn new(font_id: FontId, font: Arc<ParsedFont>) -> Self {
+        FontChain {
+            fonts: vec![(font_id, font)],
+            selector: FontSelector::default(),
+            bold: false,
+            oblique: false,
+        }
+    }
+
+    pub fn selector(&self) -> &FontSelector {
+        &self.selector
+    }
+
+    pub fn synthetic_bold(&self) -> bool {
+        self.bold
+    }
+
+    pub fn synthetic_oblique(&self) -> bool {
+        self.oblique
+    }
+
+    /// The first font's id.
+    pub fn id(&self) -> &FontId {
+        &self.fonts[0].0
+    }
+
+    /// The first font.
+    pub fn font(&self) -> &Arc<ParsedFont> {
+        &self.fonts[0].1
+    }
+
+    /// Every font of the chain with its id, in fallback order.
+    pub(crate) fn fonts(&self) -> &[(FontId, Arc<ParsedFont>)] {
+        &self.fonts
+    }
+
+    /// Index in [`Self::fonts`] of the font `cluster` is drawn in.
+    pub(crate) fn font_index(&self, cluster: &str) -> usize {
+        font_index_for_cluster(cluster, self.fonts.iter().map(|(_, font)| font.as_ref()))
+    }
+
+    /// A spec measuring text the way [`Self::runs`] draws it.
+    pub fn spec(&self) -> FontSpec {
+        FontSpec::new(self.font().clone()).with_fallbacks(
+            self.fonts[1..]
+                .iter()
+                .map(|(_, font)| font.clone())
+                .collect(),
+        )
+    }
+
+    /// Splits `text` into runs per resolved font, each ready to draw in it.
+    pub fn runs(&self, text: &str) -> Vec<(&FontId, String)> {
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for cluster in FontSpec::split_text_by_grapheme_cluster(text) {
+            let index = self.font_index(&cluster);
+            let cluster = sanitize_cluster_for_font(&cluster, &self.fonts[index].1);
+            match runs.last_mut() {
+                Some((run_index, run)) if *run_index == index => run.push_str(&cluster),
+                _ => runs.push((index, cluster)),
+            }
+        }
+        runs.into_iter()
+            .map(|(index, run)| (&self.fonts[index].0, run))
+            .collect()
+    }
+}
+
 #[derive(Debug, PartialEq, Default, Clone)]
 pub struct FontMap {
     map: BTreeMap<String, (FontId, Arc<ParsedFont>)>,
+    /// Fonts every chain falls back to after the ones a schema names.
+    fallbacks: Vec<String>,
+    /// Faces of each family, by the names their fonts are loaded under.
+    families: BTreeMap<String, Vec<FontFace>>,
 }
 
 impl FontMap {
@@ -559,16 +652,103 @@ impl FontMap {
         self.map.get(font_name)
     }
 
+    pub fn set_fallbacks(&mut self, font_names: Vec<String>) {
+        self.fallbacks = font_names;
+    }
+
+    /// Registers the font loaded as `font_name` as the `weight` and `style`
+    /// face of `family`, replacing any face already registered for them.
+    pub fn add_face(
+        &mut self,
+        family: String,
+        weight: FontWeight,
+        style: FontStyle,
+        font_name: String,
+    ) {
+        let faces = self.families.entry(family).or_default();
+        faces.retain(|face| face.weight != weight || face.style != style);
+        faces.push(FontFace {
+            weight,
+            style,
+            font_name,
+        });
+    }
+
+    /// The face of `family` closest to `weight` and `style`, matched the way
+    /// CSS does: style first, then weight.
+    fn match_face(&self, family: &str, weight: FontWeight, style: FontStyle) -> Option<&FontFace> {
+        let faces = self.families.get(family)?;
+        let style = style
+            .preference()
+            .into_iter()
+            .find(|style| faces.iter().any(|face| face.style == *style))?;
+        faces
+            .iter()
+            .filter(|face| face.style == style)
+            .min_by_key(|face| weight.preference(face.weight))
+    }
+
+    /// The fonts `font_name` names followed by the global fallbacks, each
+    /// once, or `None` when one of them is not loaded.
+    pub fn find_chain(&self, font_name: &FontName) -> Option<FontChain> {
+        self.select(&FontSelector::from(font_name.clone()))
+    }
+
+    /// The chain `selector` picks: the family's closest face, then the named
+    /// fonts, then the global fallbacks. Weight and style the picked face
+    /// lacks are synthesized. `None` when the family has no faces or a named
+    /// font is not loaded.
+    pub fn select(&self, selector: &FontSelector) -> Option<FontChain> {
+        let face = match &selector.family {
+            Some(family) => Some(self.match_face(family, selector.weight, selector.style)?),
+            None => None,
+        };
+        let names = face
+            .map(|face| &face.font_name)
+            .into_iter()
+            .chain(selector.name.iter().flat_map(|name| name.names()))
+            .chain(&self.fallbacks);
+
+        let mut fonts: Vec<(FontId, Arc<ParsedFont>)> = Vec::new();
+        for name in names {
+            let font = self.find(name)?;
+            if !fonts.iter().any(|(id, _)| *id == font.0) {
+                fonts.push(font.clone());
+            }
+        }
+        if fonts.is_empty() {
+            return None;
+        }
+
+        // ファミリーの字形がない場合、名前で指定したフォントは標準の太さ・立体とみなす
+        let (weight, style) = face.map_or((FontWeight::NORMAL, FontStyle::Normal), |face| {
+            (face.weight, face.style)
+        });
+        Some(FontChain {
+            fonts,
+            selector: selector.clone(),
+            bold: selector.weight.is_bold() && !weight.is_bold(),
+            oblique: selector.style != FontStyle::Normal && style == FontStyle::Normal,
+        })
+    }
+
     pub fn register_fonts_for_document(&self, doc: &mut PdfDocument) -> FontMap {
         let mut registered = FontMap::default();
         for (font_name, (_, parsed_font)) in &self.map {
             let font_id = doc.add_font(parsed_font.as_ref());
             registered.add_font_arc(font_name.clone(), font_id, Arc::clone(parsed_font));
         }
+        registered.fallbacks = self.fallbacks.clone();
+        registered.families = self.families.clone();
         registered
     }
 }
 
+/// Outline width of synthetic bold, as a fraction of the font size.
+pub(crate) const BOLD_STROKE: f32 = 0.03;
+/// Horizontal skew of synthetic oblique, tan 12°.
+pub(crate) const OBLIQUE_SKEW: f32 = 0.2126;
+
 // Line breaking constants for Japanese typography rules
 const LINE_START_FORBIDDEN_CHARS_JA: &[char] = &[
     // Closing brackets / quotes
@@ -606,12 +786,258 @@ const FALLBACK_CHARS: &[char] = &[
     ' ',        // Space (should exist in any font)
 ];
 
+/// Grows or shrinks a dynamic font size until `content`, as `spec` wraps and
+/// measures it, fits the `width` × `height` box.
+pub(crate) fn fit_dynamic_font_size(
+    spec: &(impl FontSpecTrait + ?Sized),
+    dynamic: DynamicFontSize,
+    line_height: Option<f32>,
+    character_spacing: Pt,
+    width: Mm,
+    height: Mm,
+    content: &str,
+) -> Result<Pt, Error> {
+    fit_font_size(dynamic.clone(), width, height, |font_size| {
+        calculate_constraints(
+            spec,
+            font_size,
+            line_height,
+            character_spacing,
+            width,
+            dynamic.clone(),
+            content,
+        )
+    })
+}
+
+/// Grows or shrinks a dynamic font size until the content fits the
+/// `width` × `height` box. `measure` lays the content out at a size and
+/// returns its widest line and total height, so the same search serves text
+/// in one font and rich text mixing several sizes.
+pub(crate) fn fit_font_size<E>(
+    dynamic: DynamicFontSize,
+    width: Mm,
+    height: Mm,
+    mut measure: impl FnMut(Pt) -> Result<(Mm, Mm), E>,
+) -> Result<Pt, E> {
+    let mut font_size: Pt = dynamic.get_min();
+    let (mut total_width_in_mm, mut total_height_in_mm) = measure(font_size)?;
+
+    while dynamic.should_font_grow_to_fit(
+        font_size,
+        width,
+        height,
+        total_width_in_mm,
+        total_height_in_mm,
+    ) {
+        font_size += Pt(0.25);
+        let (new_total_width_in_mm, new_total_height_in_mm) = measure(font_size)?;
+        if new_total_height_in_mm < height {
+            total_width_in_mm = new_total_width_in_mm;
+            total_height_in_mm = new_total_height_in_mm
+        } else {
+            font_size -= Pt(0.25);
+            break;
+        }
+    }
+
+    while dynamic.should_font_shrink_to_fit(
+        font_size,
+        width,
+        height,
+        total_width_in_mm,
+        total_height_in_mm,
+    ) {
+        font_size -= Pt(0.25);
+        (total_width_in_mm, total_height_in_mm) = measure(font_size)?;
+    }
+
+    Ok(font_size)
+}
+
+fn calculate_constraints(
+    spec: &(impl FontSpecTrait + ?Sized),
+    font_size: Pt,
+    line_height: Option<f32>,
+    character_spacing: Pt,
+    width: Mm,
+    dynamic: DynamicFontSize,
+    content: &str,
+) -> Result<(Mm, Mm), Error> {
+    let mut total_width_in_mm: Mm = Mm(0.0);
+    let mut total_height_in_mm: Mm = Mm(0.0);
+    let line_height: f32 = line_height.unwrap_or(1.0);
+    let first_line_text_height: Pt = font_size;
+    let first_line_height_in_mm: Mm = (first_line_text_height * line_height).into();
+    let other_row_height_in_mm: Mm = (font_size * line_height).into();
+    let splitted_paragraphs =
+        spec.split_text_to_size(content, font_size, width.into(), character_spacing)?;
+
+    let needs_width = dynamic.is_fit_vertical() || dynamic.is_fit_horizontal();
+
+    for (line_index, line) in splitted_paragraphs.into_iter().enumerate() {
+        // Calculate width only once if needed
+        if needs_width {
+            // Remove trailing newline if present
+            let line_to_measure = if line.ends_with('\n') {
+                &line[..line.len() - 1]
+            } else {
+                &line
+            };
+
+            let text_width =
+                spec.width_of_text_at_size(line_to_measure, font_size, character_spacing)?;
+
+            total_width_in_mm = max(total_width_in_mm, text_width.into());
+        }
+
+        // Calculate height
+        if line_index == 0 {
+            total_height_in_mm += first_line_height_in_mm;
+        } else {
+            total_height_in_mm += other_row_height_in_mm;
+        }
+    }
+
+    Ok((total_width_in_mm, total_height_in_mm))
+}
+
+/// Breaks `paragraph` into lines no wider than `box_width`, returned as
+/// byte ranges. `measure` gives the width of a slice of the paragraph, so the
+/// same breaking serves text in one font and rich text mixing several.
+pub(crate) fn wrap_paragraph(
+    paragraph: &str,
+    line_break_mode: LineBreakMode,
+    box_width: Pt,
+    measure: impl Fn(Range<usize>) -> Result<Pt, Error>,
+) -> Result<Vec<Range<usize>>, Error> {
+    let segments = match line_break_mode {
+        LineBreakMode::Word => FontSpec::split_text_by_word_segmenter(paragraph),
+        LineBreakMode::Char => FontSpec::split_text_by_grapheme_cluster(paragraph),
+    };
+
+    let mut lines = Vec::new();
+    let mut current_line: Option<Range<usize>> = None;
+    let mut offset = 0;
+
+    for segment in segments {
+        let segment = offset..offset + segment.len();
+        offset = segment.end;
+        if segment.is_empty() {
+            continue;
+        }
+
+        if let Some(line) = current_line.take() {
+            let combined = line.start..segment.end;
+            if measure(combined.clone())? <= box_width {
+                current_line = Some(combined);
+                continue;
+            }
+            lines.push(line);
+        }
+
+        current_line = Some(if measure(segment.clone())? <= box_width {
+            segment
+        } else {
+            push_grapheme_wrapped_lines(paragraph, &mut lines, segment, box_width, &measure)?
+        });
+    }
+
+    lines.extend(current_line);
+    Ok(lines)
+}
+
+/// Wraps a segment wider than the box between grapheme clusters, pushing
+/// every full line and returning the last, still open one.
+fn push_grapheme_wrapped_lines(
+    paragraph: &str,
+    lines: &mut Vec<Range<usize>>,
+    segment: Range<usize>,
+    box_width: Pt,
+    measure: &impl Fn(Range<usize>) -> Result<Pt, Error>,
+) -> Result<Range<usize>, Error> {
+    let mut current_line: Option<Range<usize>> = None;
+    let mut offset = segment.start;
+
+    for cluster in FontSpec::split_text_by_grapheme_cluster(&paragraph[segment.clone()]) {
+        let cluster = offset..offset + cluster.len();
+        offset = cluster.end;
+
+        current_line = Some(match current_line.take() {
+            None => cluster,
+            Some(line) => {
+                let combined = line.start..cluster.end;
+                if measure(combined.clone())? <= box_width {
+                    combined
+                } else {
+                    lines.push(line);
+                    cluster
+                }
+            }
+        });
+    }
+
+    Ok(current_line.unwrap_or(segment))
+}
+
+/// Applies Japanese kinsoku to the lines [`wrap_paragraph`] found. Kinsoku
+/// moves characters to the neighbouring line and empties lines holding only
+/// spaces, so each line comes back as the pieces of `paragraph` it now holds.
+pub(crate) fn kinsoku_line_pieces(
+    paragraph: &str,
+    lines: Vec<Range<usize>>,
+) -> Vec<Vec<Range<usize>>> {
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|range| paragraph[range].to_string())
+        .collect();
+
+    let mut position = 0;
+    FontSpec::apply_japanese_kinsoku(lines)
+        .iter()
+        .map(|line| {
+            let mut pieces: Vec<Range<usize>> = Vec::new();
+            for ch in line.chars() {
+                let Some(offset) = paragraph[position..].find(ch) else {
+                    continue;
+                };
+                let start = position + offset;
+                let end = start + ch.len_utf8();
+                match pieces.last_mut() {
+                    Some(piece) if piece.end == start => piece.end = end,
+                    _ => pieces.push(start..end),
+                }
+                position = end;
+            }
+            pieces
+        })
+        .collect()
+}
+
 fn is_non_rendering_cluster_char(ch: char) -> bool {
     matches!(ch, '\u{200C}' | '\u{200D}')
         || ('\u{FE00}'..='\u{FE0F}').contains(&ch)
         || ('\u{E0100}'..='\u{E01EF}').contains(&ch)
 }
 
+/// Index of the first of `fonts` that has every glyph of `cluster`. When none
+/// has, the cluster becomes tofu in the first font with a tofu glyph.
+fn font_index_for_cluster<'a>(
+    cluster: &str,
+    fonts: impl IntoIterator<Item = &'a ParsedFont> + Clone,
+) -> usize {
+    fonts
+        .clone()
+        .into_iter()
+        .position(|font| cluster_is_supported_by_font(cluster, font))
+        .or_else(|| {
+            fonts
+                .into_iter()
+                .position(|font| fallback_char_for_font(font).is_some())
+        })
+        .unwrap_or(0)
+}
+
 fn fallback_char_for_font(font: &ParsedFont) -> Option<char> {
     FALLBACK_CHARS
         .iter()
@@ -808,9 +1234,10 @@ pub fn filter_end_de(lines: Vec<String>) -> Vec<String> {
 mod tests {
     use super::{
         filter_end_jp, filter_start_jp, sanitize_text_for_font, DynamicFontSize,
-        DynamicFontSizeFit, FontSpec, FontSpecTrait, LineBreakMode,
+        DynamicFontSizeFit, FontChain, FontMap, FontSelector, FontSpec, FontSpecTrait, FontStyle,
+        FontWeight, LineBreakMode,
     };
-    use printpdf::{ParsedFont, Pt};
+    use printpdf::{FontId, ParsedFont, Pt};
     use std::path::PathBuf;
     use std::sync::{Arc, OnceLock};
 
@@ -1110,4 +1537,150 @@ mod tests {
             size.0
         );
     }
+
+    /// Face 0 of the two-face fixture: a 500-unit `A` and nothing else.
+    fn latin_a_font() -> Arc<ParsedFont> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("two-face-test.ttc");
+        let bytes = std::fs::read(path).expect("fixture TTC should be readable");
+        Arc::new(ParsedFont::from_bytes(&bytes, 0, &mut Vec::new()).expect("fixture face"))
+    }
+
+    fn chain() -> FontChain {
+        let mut chain = FontChain::new(FontId::new(), latin_a_font());
+        chain.fonts.push((FontId::new(), test_font()));
+        chain
+    }
+
+    #[test]
+    fn chain_runs_switch_to_the_first_font_with_the_glyph() {
+        let chain = chain();
+        let (latin, japanese) = (&chain.fonts[0].0, &chain.fonts[1].0);
+
+        assert_eq!(
+            chain.runs("AB漢A"),
+            [
+                (latin, "A".to_string()),
+                (japanese, "B漢".to_string()),
+                (latin, "A".to_string())
+            ]
+        );
+        // No font has the emoji, and only the second has a tofu glyph.
+        assert_eq!(chain.runs("😀"), [(japanese, "□".to_string())]);
+    }
+
+    #[test]
+    fn chain_measures_each_cluster_in_its_own_font() {
+        let spec = chain().spec();
+        let width = spec
+            .width_of_text_at_size("AB漢", Pt(10.0), Pt(1.0))
+            .unwrap();
+
+        let expected = Pt(5.0) + hmtx_width_of(&test_font(), "B漢", Pt(10.0)) + Pt(2.0);
+        assert!(
+            (width.0 - expected.0).abs() < 1e-4,
+            "{width:?} vs {expected:?}"
+        );
+    }
+
+    /// A family of three faces, each loaded under its own name.
+    fn family() -> FontMap {
+        let mut fonts = FontMap::default();
+        for (name, weight, style) in [
+            ("Light", 300, FontStyle::Normal),
+            ("Regular", 400, FontStyle::Normal),
+            ("BoldItalic", 700, FontStyle::Italic),
+        ] {
+            fonts.add_font_arc(name.to_string(), FontId::new(), test_font());
+            let weight = FontWeight::new(weight).unwrap();
+            fonts.add_face("Sans".to_string(), weight, style, name.to_string());
+        }
+        fonts
+    }
+
+    fn select(fonts: &FontMap, weight: u16, style: FontStyle) -> (FontId, bool, bool) {
+        let chain = fonts
+            .select(&FontSelector {
+                family: Some("Sans".to_string()),
+                weight: FontWeight::new(weight).unwrap(),
+                style,
+                ..FontSelector::default()
+            })
+            .unwrap();
+        (
+            chain.id().clone(),
+            chain.synthetic_bold(),
+            chain.synthetic_oblique(),
+        )
+    }
+
+    #[test]
+    fn family_faces_match_style_first_then_weight() {
+        let fonts = family();
+        let id = |name: &str| fonts.find(name).unwrap().0.clone();
+
+        assert_eq!(
+            select(&fonts, 400, FontStyle::Normal),
+            (id("Regular"), false, false)
+        );
+        // 500 looks lighter before heavier, 200 lighter before heavier.
+        assert_eq!(
+            select(&fonts, 500, FontStyle::Normal),
+            (id("Regular"), false, false)
+        );
+        assert_eq!(
+            select(&fonts, 200, FontStyle::Normal),
+            (id("Light"), false, false)
+        );
+        // Oblique falls back to the italic face rather than skewing upright.
+        assert_eq!(
+            select(&fonts, 700, FontStyle::Oblique),
+            (id("BoldItalic"), false, false)
+        );
+        assert_eq!(
+            select(&fonts, 400, FontStyle::Italic),
+            (id("BoldItalic"), false, false)
+        );
+    }
+
+    #[test]
+    fn missing_weight_and_style_are_synthesized() {
+        let mut fonts = family();
+        fonts.add_face(
+            "Serif".to_string(),
+            FontWeight::NORMAL,
+            FontStyle::Normal,
+            "Regular".to_string(),
+        );
+        let chain = fonts
+            .select(&FontSelector {
+                family: Some("Serif".to_string()),
+                weight: FontWeight::BOLD,
+                style: FontStyle::Italic,
+                ..FontSelector::default()
+            })
+            .unwrap();
+        assert!(chain.synthetic_bold() && chain.synthetic_oblique());
+
+        // A font named directly counts as a regular upright face.
+        let chain = fonts
+            .select(&FontSelector {
+                weight: FontWeight::BOLD,
+                ..FontSelector::from(super::FontName::from("Light"))
+            })
+            .unwrap();
+        assert!(chain.synthetic_bold() && !chain.synthetic_oblique());
+    }
+
+    #[test]
+    fn font_weight_accepts_numbers_and_keywords() {
+        let parse = |json: &str| serde_json::from_str::<FontWeight>(json).map(FontWeight::get);
+        assert_eq!(parse("600").unwrap(), 600);
+        assert_eq!(parse("\"bold\"").unwrap(), 700);
+        assert_eq!(parse("\"normal\"").unwrap(), 400);
+        assert!(parse("0").is_err());
+        assert!(parse("\"heavy\"").is_err());
+    }
 }
@@ -33,6 +33,11 @@ fn format_font_parse_error(
     }
 }
 
+/// Input groups for one template, one group per template page; each group
+/// expands into as many pages as it has records. This is the `inputs`
+/// argument of [`PDForge::render`].
+pub type TemplateInputs = Vec<Vec<HashMap<&'static str, String>>>;
+
 #[derive(Debug, Clone)]
 pub struct PDForge {
     name: String,
@@ -76,12 +81,168 @@ impl PDForge {
             }),
         }
     }
+
+    /// Renders several templates back to back into a single PDF document.
+    ///
+    /// Each `(template_name, inputs)` section is laid out exactly as
+    /// [`Self::render`] would lay it out on its own, using that template's
+    /// `basePdf`, so one document can mix page sizes — an A4 invoice
+    /// followed by A6 shipping labels, for instance. `table_data` is shared
+    /// by every section and injected by table name.
+    ///
+    /// Static schemas are painted once all sections are laid out, so
+    /// `currentPage` and `totalPages` number the pages of the whole
+    /// document, not of each section.
+    pub fn render_sequence(
+        &self,
+        sections: &[(&str, TemplateInputs)],
+        table_data: Option<HashMap<&'static str, Vec<Vec<String>>>>,
+        static_inputs: Option<HashMap<&'static str, String>>,
+    ) -> Result<Vec<u8>, Error> {
+        if sections.is_empty() {
+            return Err(Error::Whatever {
+                message: "Sections cannot be empty".to_string(),
+                source: None,
+            });
+        }
+
+        let table_data = table_data.unwrap_or_default();
+        let static_inputs = static_inputs.unwrap_or_default();
+
+        let mut doc = PdfDocument::new(&self.name);
+        let font_map = self.font_map.register_fonts_for_document(&mut doc);
+        let laid_out = self.schema_sections(&font_map, sections, &table_data)?;
+
+        schemas::render_sequence(&font_map, &mut doc, laid_out, &static_inputs)
+    }
+
+    /// Renders `sections` like [`Self::render_sequence`] and then imposes the
+    /// finished pages onto sheets: booklet (saddle-stitch) order, 2-up/4-up,
+    /// or single pages padded for duplex printing.
+    ///
+    /// Blank pages added for `with_pad_to_multiple` and
+    /// `with_chapters_on_recto` (each section is a chapter) count towards
+    /// `currentPage` / `totalPages` but get no static schemas.
+    pub fn render_imposed(
+        &self,
+        sections: &[(&str, TemplateInputs)],
+        table_data: Option<HashMap<&'static str, Vec<Vec<String>>>>,
+        static_inputs: Option<HashMap<&'static str, String>>,
+        imposition: &schemas::imposition::PageImposition,
+    ) -> Result<Vec<u8>, Error> {
+        if sections.is_empty() {
+            return Err(Error::Whatever {
+                message: "Sections cannot be empty".to_string(),
+                source: None,
+            });
+        }
+
+        let table_data = table_data.unwrap_or_default();
+        let static_inputs = static_inputs.unwrap_or_default();
+
+        let mut doc = PdfDocument::new(&self.name);
+        let font_map = self.font_map.register_fonts_for_document(&mut doc);
+        let laid_out = self.schema_sections(&font_map, sections, &table_data)?;
+
+        schemas::imposition::render_imposed(
+            &font_map,
+            &mut doc,
+            laid_out,
+            &static_inputs,
+            imposition,
+        )
+    }
+
+    /// Looks up each section's template and builds its schema pages from `inputs`.
+    fn schema_sections(
+        &self,
+        font_map: &font::FontMap,
+        sections: &[(&str, TemplateInputs)],
+        table_data: &HashMap<&'static str, Vec<Vec<String>>>,
+    ) -> Result<Vec<(&schemas::Template, Vec<schemas::SchemaPage>)>, Error> {
+        let mut laid_out = Vec::with_capacity(sections.len());
+        for (template_name, inputs) in sections {
+            if inputs.is_empty() {
+                return Err(Error::Whatever {
+                    message: format!("Inputs cannot be empty for template: {}", template_name),
+                    source: None,
+                });
+            }
+            let template =
+                self.template_map
+                    .get(*template_name)
+                    .ok_or_else(|| Error::Whatever {
+                        message: format!("Template not found: {}", template_name),
+                        source: None,
+                    })?;
+            let schemas = template.schemas_from_inputs(font_map, inputs.clone(), table_data)?;
+            laid_out.push((template, schemas));
+        }
+        Ok(laid_out)
+    }
+
+    /// Renders one label per record and places the labels on sheets.
+    ///
+    /// `template_name` must be a single-page template describing one label;
+    /// its `basePdf` gives the label size. Labels fill the cells of `sheet`
+    /// left to right, top to bottom, starting a new sheet whenever one is
+    /// full. Static schemas see `currentPage` / `totalPages` as the label's
+    /// number and the label count.
+    pub fn render_label_sheet(
+        &self,
+        template_name: &str,
+        records: Vec<HashMap<&'static str, String>>,
+        table_data: Option<HashMap<&'static str, Vec<Vec<String>>>>,
+        static_inputs: Option<HashMap<&'static str, String>>,
+        sheet: &schemas::imposition::LabelSheet,
+    ) -> Result<Vec<u8>, Error> {
+        if records.is_empty() {
+            return Err(Error::Whatever {
+                message: "Records cannot be empty".to_string(),
+                source: None,
+            });
+        }
+
+        let template = self
+            .template_map
+            .get(template_name)
+            .ok_or_else(|| Error::Whatever {
+                message: format!("Template not found: {}", template_name),
+                source: None,
+            })?;
+        if template.schemas.len() != 1 {
+            return Err(Error::InvalidImposition {
+                message: format!(
+                    "label template {} must have exactly one page, found {}",
+                    template_name,
+                    template.schemas.len()
+                ),
+            });
+        }
+
+        let table_data = table_data.unwrap_or_default();
+        let static_inputs = static_inputs.unwrap_or_default();
+
+        let mut doc = PdfDocument::new(&self.name);
+        let font_map = self.font_map.register_fonts_for_document(&mut doc);
+        let labels = template.schemas_from_inputs(&font_map, vec![records], &table_data)?;
+
+        schemas::imposition::render_label_sheet(
+            &font_map,
+            &mut doc,
+            template,
+            labels,
+            &static_inputs,
+            sheet,
+        )
+    }
 }
 
 pub struct PDForgeBuilder {
     name: String,
     font_map: font::FontMap,
     template_map: HashMap<String, schemas::Template>,
+    viewer_preferences: schemas::viewer::ViewerPreferences,
 }
 
 impl PDForgeBuilder {
@@ -90,9 +251,22 @@ impl PDForgeBuilder {
             name,
             font_map: font::FontMap::default(),
             template_map: HashMap::new(),
+            viewer_preferences: schemas::viewer::ViewerPreferences::default(),
         }
     }
 
+    /// Sets default viewer and print preferences for every rendered
+    /// document, such as `print_scaling: Some(PrintScaling::None)` so labels
+    /// print at 100%. A template's `basePdf.viewerPreferences` takes
+    /// precedence field by field.
+    pub fn viewer_preferences(
+        mut self,
+        viewer_preferences: schemas::viewer::ViewerPreferences,
+    ) -> Self {
+        self.viewer_preferences = viewer_preferences;
+        self
+    }
+
     /// Loads a font from a byte slice and registers it under `font_name`.
     ///
     /// Equivalent to `add_font_with_index(font_name, font_bytes, 0)`. This is
@@ -162,6 +336,33 @@ impl PDForgeBuilder {
         self.add_font_with_index(font_name, &font_bytes, font_index)
     }
 
+    /// Sets fonts to draw glyphs with that a schema's own fonts lack, tried
+    /// in order after the ones its `fontName` lists. Each must be registered
+    /// under that name by the time a template is rendered.
+    pub fn font_fallbacks(mut self, font_names: &[&str]) -> Self {
+        self.font_map
+            .set_fallbacks(font_names.iter().map(|name| name.to_string()).collect());
+        self
+    }
+
+    /// Registers the font loaded as `font_name` as the `weight` and `style`
+    /// face of `family`, so schemas can pick it with `fontFamily`,
+    /// `fontWeight` and `fontStyle`. A weight or style the family has no face
+    /// for is drawn from the closest face, with bold and oblique synthesized.
+    /// The font must be registered under `font_name` by the time a template
+    /// is rendered.
+    pub fn font_face(
+        mut self,
+        family: &str,
+        weight: font::FontWeight,
+        style: font::FontStyle,
+        font_name: &str,
+    ) -> Self {
+        self.font_map
+            .add_face(family.to_string(), weight, style, font_name.to_string());
+        self
+    }
+
     pub fn load_template(mut self, template_name: &str, template: &str) -> Result<Self, Error> {
         let template = schemas::Template::new(template)?;
 
@@ -171,7 +372,11 @@ impl PDForgeBuilder {
         Ok(self)
     }
 
-    pub fn build(self) -> PDForge {
+    pub fn build(mut self) -> PDForge {
+        for template in self.template_map.values_mut() {
+            template.inherit_viewer_preferences(&self.viewer_preferences);
+        }
+
         PDForge {
             name: self.name,
             font_map: self.font_map,
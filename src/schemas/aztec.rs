@@ -0,0 +1,568 @@
+//! Aztec code encoder.
+//!
+//! Content made only of digits, or only of capital letters and spaces, is
+//! written in the digit or upper-case mode; anything else goes through
+//! binary shift as UTF-8 bytes. The smallest compact or full-range symbol
+//! that leaves 33% of the data for error correction is chosen.
+
+use super::symbology::{
+    encoding_error, GaloisField, SymbolEncoder, SymbolMatrix, SymbolOptions, Symbology,
+};
+use super::Error;
+
+const MIN_ECC_PERCENT: usize = 33;
+const MAX_LAYERS: usize = 32;
+const UPPER_TO_DIGIT: u32 = 30;
+const BINARY_SHIFT: u32 = 31;
+/// The most bytes one binary shift can carry.
+const MAX_BINARY_SHIFT: usize = 2047 + 31;
+
+pub(crate) struct AztecEncoder;
+
+impl SymbolEncoder for AztecEncoder {
+    fn encode(&self, content: &str, options: &SymbolOptions) -> Result<SymbolMatrix, Error> {
+        options.reject(Symbology::Aztec)?;
+        options.reject_gs1(Symbology::Aztec)?;
+        if content.is_empty() {
+            return Err(encoding_error(Symbology::Aztec, "the content is empty"));
+        }
+        let bits = encode_text(content);
+        let ecc_bits = bits.len() * MIN_ECC_PERCENT / 100 + 11;
+        let layout = choose_layout(&bits, ecc_bits).ok_or_else(|| {
+            encoding_error(
+                Symbology::Aztec,
+                "the content does not fit in the largest symbol, 32 layers",
+            )
+        })?;
+
+        let message = check_words(&layout.stuffed, layout.capacity_bits, layout.word_size);
+        let data_words = layout.stuffed.len() / layout.word_size;
+        let mode_message = if layout.compact {
+            let mut mode = Vec::new();
+            push_bits(&mut mode, layout.layers as u32 - 1, 2);
+            push_bits(&mut mode, data_words as u32 - 1, 6);
+            check_words(&mode, 28, 4)
+        } else {
+            let mut mode = Vec::new();
+            push_bits(&mut mode, layout.layers as u32 - 1, 5);
+            push_bits(&mut mode, data_words as u32 - 1, 11);
+            check_words(&mode, 40, 4)
+        };
+        Ok(draw(layout.compact, layout.layers, &message, &mode_message))
+    }
+}
+
+struct Layout {
+    compact: bool,
+    layers: usize,
+    word_size: usize,
+    capacity_bits: usize,
+    stuffed: Vec<bool>,
+}
+
+fn push_bits(bits: &mut Vec<bool>, value: u32, count: u32) {
+    bits.extend((0..count).rev().map(|bit| value >> bit & 1 == 1));
+}
+
+/// The high-level encoding of `content`, starting in upper-case mode.
+fn encode_text(content: &str) -> Vec<bool> {
+    let mut bits = Vec::new();
+    if content.bytes().all(|byte| byte.is_ascii_digit()) {
+        // 数字だけなら数字モード（4ビット）
+        push_bits(&mut bits, UPPER_TO_DIGIT, 5);
+        for byte in content.bytes() {
+            push_bits(&mut bits, u32::from(byte - b'0') + 2, 4);
+        }
+    } else if content
+        .bytes()
+        .all(|byte| byte.is_ascii_uppercase() || byte == b' ')
+    {
+        for byte in content.bytes() {
+            let code = if byte == b' ' { 1 } else { byte - b'A' + 2 };
+            push_bits(&mut bits, u32::from(code), 5);
+        }
+    } else {
+        for chunk in content.as_bytes().chunks(MAX_BINARY_SHIFT) {
+            push_binary_shift(&mut bits, chunk);
+        }
+    }
+    bits
+}
+
+/// Writes `bytes` behind a binary shift. Up to 31 bytes take a 5-bit
+/// length; 32 to 62 bytes are split in two shifts, and longer runs use the
+/// 11-bit extended length.
+fn push_binary_shift(bits: &mut Vec<bool>, bytes: &[u8]) {
+    let count = bytes.len();
+    for (index, &byte) in bytes.iter().enumerate() {
+        if index == 0 || (index == 31 && count <= 62) {
+            push_bits(bits, BINARY_SHIFT, 5);
+            if count > 62 {
+                push_bits(bits, (count - 31) as u32, 16);
+            } else if index == 0 {
+                push_bits(bits, count.min(31) as u32, 5);
+            } else {
+                push_bits(bits, (count - 31) as u32, 5);
+            }
+        }
+        push_bits(bits, u32::from(byte), 8);
+    }
+}
+
+fn word_size(layers: usize) -> usize {
+    match layers {
+        1..=2 => 6,
+        3..=8 => 8,
+        9..=22 => 10,
+        _ => 12,
+    }
+}
+
+fn total_bits_in_layers(layers: usize, compact: bool) -> usize {
+    (if compact { 88 } else { 112 } + 16 * layers) * layers
+}
+
+/// Picks the smallest symbol holding `bits` and `ecc_bits` of check words.
+fn choose_layout(bits: &[bool], ecc_bits: usize) -> Option<Layout> {
+    let total = bits.len() + ecc_bits;
+    let mut stuffed: Option<(usize, Vec<bool>)> = None;
+    // コンパクト1〜4層、続いてフルサイズ4〜32層の順に試す
+    let candidates = (1..=4)
+        .map(|layers| (true, layers))
+        .chain((4..=MAX_LAYERS).map(|layers| (false, layers)));
+    for (compact, layers) in candidates {
+        let capacity_bits = total_bits_in_layers(layers, compact);
+        if total > capacity_bits {
+            continue;
+        }
+        let word_size = word_size(layers);
+        if stuffed.as_ref().is_none_or(|(size, _)| *size != word_size) {
+            stuffed = Some((word_size, stuff_bits(bits, word_size)));
+        }
+        let (_, words) = stuffed.as_ref()?;
+        let usable = capacity_bits - capacity_bits % word_size;
+        if compact && words.len() > word_size * 64 {
+            continue;
+        }
+        if words.len() + ecc_bits <= usable {
+            return Some(Layout {
+                compact,
+                layers,
+                word_size,
+                capacity_bits,
+                stuffed: words.clone(),
+            });
+        }
+    }
+    None
+}
+
+/// Splits `bits` into words, avoiding words of all zeros or all ones by
+/// stuffing a complementary bit after the first `word_size - 1` bits. The
+/// last word is padded with ones.
+fn stuff_bits(bits: &[bool], word_size: usize) -> Vec<bool> {
+    let mut out = Vec::new();
+    let mask = (1u32 << word_size) - 2;
+    let mut index = 0;
+    while index < bits.len() {
+        let mut word = 0u32;
+        for offset in 0..word_size {
+            if bits.get(index + offset).copied().unwrap_or(true) {
+                word |= 1 << (word_size - 1 - offset);
+            }
+        }
+        if word & mask == mask {
+            push_bits(&mut out, word & mask, word_size as u32);
+            index += word_size - 1;
+        } else if word & mask == 0 {
+            push_bits(&mut out, word | 1, word_size as u32);
+            index += word_size - 1;
+        } else {
+            push_bits(&mut out, word, word_size as u32);
+            index += word_size;
+        }
+    }
+    out
+}
+
+fn galois_field(word_size: usize) -> GaloisField {
+    match word_size {
+        4 => GaloisField::new(0x13, 16),
+        6 => GaloisField::new(0x43, 64),
+        8 => GaloisField::new(0x12d, 256),
+        10 => GaloisField::new(0x409, 1024),
+        _ => GaloisField::new(0x1069, 4096),
+    }
+}
+
+/// Fills `total_bits` with the data words of `bits` and their check words,
+/// after `total_bits % word_size` leading zeros.
+fn check_words(bits: &[bool], total_bits: usize, word_size: usize) -> Vec<bool> {
+    let words: Vec<u16> = bits
+        .chunks(word_size)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .fold(0u16, |word, &bit| word << 1 | u16::from(bit))
+        })
+        .collect();
+    let total_words = total_bits / word_size;
+    let checks = galois_field(word_size).check_words(&words, total_words - words.len());
+
+    let mut out = vec![false; total_bits % word_size];
+    for word in words.into_iter().chain(checks) {
+        push_bits(&mut out, u32::from(word), word_size as u32);
+    }
+    out
+}
+
+/// The side of the symbol, and where each row and column of the symbol
+/// without reference grid lands in the full symbol.
+fn symbol_size(compact: bool, layers: usize) -> (usize, Vec<usize>) {
+    let base_size = if compact { 11 } else { 14 } + layers * 4;
+    if compact {
+        return (base_size, (0..base_size).collect());
+    }
+    // フルサイズでは中心から16モジュールごとに参照グリッドの線が入る
+    let size = base_size + 1 + 2 * ((base_size / 2 - 1) / 15);
+    let (original_center, center) = (base_size / 2, size / 2);
+    let mut map = vec![0; base_size];
+    for i in 0..original_center {
+        let offset = i + i / 15;
+        map[original_center - i - 1] = center - offset - 1;
+        map[original_center + i] = center + offset + 1;
+    }
+    (size, map)
+}
+
+/// The module `(x, y)` of each bit of the layers, outermost layer first.
+fn data_positions(compact: bool, layers: usize) -> Vec<(usize, usize)> {
+    let (_, alignment_map) = symbol_size(compact, layers);
+    let base_size = alignment_map.len();
+    let at = |x: usize, y: usize| (alignment_map[x], alignment_map[y]);
+    let mut positions = vec![(0, 0); total_bits_in_layers(layers, compact)];
+
+    // データは外側の層から時計回りに、2モジュール幅の帯として並べる
+    let mut row_offset = 0;
+    for layer in 0..layers {
+        let row_size = (layers - layer) * 4 + if compact { 9 } else { 12 };
+        let (near, far) = (layer * 2, base_size - 1 - layer * 2);
+        for j in 0..row_size {
+            for k in 0..2 {
+                let index = row_offset + j * 2 + k;
+                positions[index] = at(near + k, near + j);
+                positions[index + row_size * 2] = at(near + j, far - k);
+                positions[index + row_size * 4] = at(far - k, far - j);
+                positions[index + row_size * 6] = at(far - j, near + k);
+            }
+        }
+        row_offset += row_size * 8;
+    }
+    positions
+}
+
+fn draw(compact: bool, layers: usize, message: &[bool], mode_message: &[bool]) -> SymbolMatrix {
+    let (size, alignment_map) = symbol_size(compact, layers);
+    let base_size = alignment_map.len();
+    let mut matrix = SymbolMatrix::new(size, 0);
+    for ((x, y), &dark) in data_positions(compact, layers).into_iter().zip(message) {
+        if dark {
+            matrix.set(x, y, true);
+        }
+    }
+
+    draw_mode_message(&mut matrix, compact, mode_message);
+    let center = size / 2;
+    if compact {
+        draw_bullseye(&mut matrix, center, 5);
+    } else {
+        draw_bullseye(&mut matrix, center, 7);
+        let mut distance = 0;
+        let mut i = 0;
+        while i < base_size / 2 - 1 {
+            for k in ((center & 1)..size).step_by(2) {
+                matrix.set(center - distance, k, true);
+                matrix.set(center + distance, k, true);
+                matrix.set(k, center - distance, true);
+                matrix.set(k, center + distance, true);
+            }
+            i += 15;
+            distance += 16;
+        }
+    }
+    matrix
+}
+
+/// The finder rings around the center and the orientation marks at their
+/// corners.
+fn draw_bullseye(matrix: &mut SymbolMatrix, center: usize, size: usize) {
+    for ring in (0..size).step_by(2) {
+        for j in center - ring..=center + ring {
+            matrix.set(j, center - ring, true);
+            matrix.set(j, center + ring, true);
+            matrix.set(center - ring, j, true);
+            matrix.set(center + ring, j, true);
+        }
+    }
+    matrix.set(center - size, center - size, true);
+    matrix.set(center - size + 1, center - size, true);
+    matrix.set(center - size, center - size + 1, true);
+    matrix.set(center + size, center - size, true);
+    matrix.set(center + size, center - size + 1, true);
+    matrix.set(center + size, center + size - 1, true);
+}
+
+fn draw_mode_message(matrix: &mut SymbolMatrix, compact: bool, mode_message: &[bool]) {
+    let center = matrix.width / 2;
+    if compact {
+        for i in 0..7 {
+            let offset = center - 3 + i;
+            if mode_message[i] {
+                matrix.set(offset, center - 5, true);
+            }
+            if mode_message[i + 7] {
+                matrix.set(center + 5, offset, true);
+            }
+            if mode_message[20 - i] {
+                matrix.set(offset, center + 5, true);
+            }
+            if mode_message[27 - i] {
+                matrix.set(center - 5, offset, true);
+            }
+        }
+    } else {
+        for i in 0..10 {
+            let offset = center - 5 + i + i / 5;
+            if mode_message[i] {
+                matrix.set(offset, center - 7, true);
+            }
+            if mode_message[i + 10] {
+                matrix.set(center + 7, offset, true);
+            }
+            if mode_message[29 - i] {
+                matrix.set(offset, center + 7, true);
+            }
+            if mode_message[39 - i] {
+                matrix.set(center - 7, offset, true);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_bits(text: &str) -> Vec<bool> {
+        text.bytes().map(|byte| byte == b'1').collect()
+    }
+
+    fn render(matrix: &SymbolMatrix) -> Vec<String> {
+        (0..matrix.width)
+            .map(|y| {
+                (0..matrix.width)
+                    .map(|x| if matrix.get(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compact_and_full_range_symbols_match_the_reference_encoder() {
+        // Both drawn by ZXing's Aztec writer at its default 33% error correction.
+        let compact = AztecEncoder
+            .encode("PDFORGE", &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(
+            render(&compact),
+            [
+                "...#.#...#.##.#",
+                "#..#.##..#..##.",
+                "..##.....#..#.#",
+                "#.###########.#",
+                ".###.......###.",
+                ".#.#.#####.#..#",
+                "..##.#...#.#...",
+                "##.#.#.#.#.#.#.",
+                "##.#.#...#.##..",
+                "..##.#####.#.##",
+                "...#.......##..",
+                "#..##########.#",
+                ".#..##.####....",
+                "....####..##.##",
+                "##..#.##.###..#",
+            ]
+        );
+        let full = AztecEncoder
+            .encode(&"PDFORGE ".repeat(15), &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(
+            render(&full),
+            [
+                "#.###...#.#...#..###.#..##...#.#..#.#",
+                "...##.##...#..#.....#...##.##...#...#",
+                "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#",
+                "#.......#..##.#..#.#...#.#...#..##...",
+                ".##..#..#....#...###.##...#..#....#..",
+                ".#.#.#....###.#.....##..#....#.##...#",
+                "..#.#..##.##......##.##.##.##.#..##.#",
+                "##.#.#.##..###.#...#.#...###.#.....#.",
+                "####..#....#......#..#.##.#...##..#.#",
+                "......#######.###...#..#.##...#.##...",
+                "..#......##.#..####...##..#.#.#.###..",
+                "#..####....##..#.......#.#....#....##",
+                ".##..##....######################.###",
+                "#......#.#..#...........#.#....##....",
+                "#.#.#...#...#.#########.#.#.#.#..###.",
+                "...#.#.##..##.#.......#.##.#..##...#.",
+                "..#...#.#####.#.#####.#.#.####.#.##.#",
+                "##.#.#.#.#..#.#.#...#.#.######.##....",
+                "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#",
+                "....##.#....#.#.#...#.#.#...#.#.....#",
+                "..#.#....####.#.#####.#.#....##...##.",
+                ".#......#####.#.......#.#.##.#..##...",
+                "#.#####.#.###.#########.#.........#..",
+                "...##..##...#...........#..#..##...##",
+                "#.#.....###.###############.##...##..",
+                ".#....#.###..##....#.#....#..#...#...",
+                ".###.#...#####....#..#.#.#..#..##.#.#",
+                "....#..#...#######.#..#...#..#.#.#..#",
+                "####.#..##..#...#.###.##.#.##.#...##.",
+                "##.#..##..####..#....#.#.#...#.#....#",
+                "..#..#.##...#....##..#...##.##..###..",
+                ".......##..#.##..#....#.#...##..##...",
+                "#.##..#...##.##...##....#..#...#..###",
+                ".#.#...#.#...##..#..##..#...#.#.#..##",
+                "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#",
+                ".....#..#...##.##....#....#..#...#...",
+                "###..#...#.#...##.#.#.##..#...#.#.##.",
+            ]
+        );
+    }
+
+    #[test]
+    fn text_uses_digit_upper_or_binary_shift_modes() {
+        // D/L, then 1 → 3, 2 → 4.
+        assert_eq!(encode_text("12"), to_bits(concat!("11110", "0011", "0100")));
+        // A → 2, space → 1, Z → 27.
+        assert_eq!(
+            encode_text("A Z"),
+            to_bits(concat!("00010", "00001", "11011"))
+        );
+        let binary = encode_text("a");
+        assert_eq!(binary, to_bits(concat!("11111", "00001", "01100001")));
+        // 40 bytes: a shift of 31, then one of 9.
+        assert_eq!(encode_text(&"a".repeat(40)).len(), 10 + 31 * 8 + 10 + 9 * 8);
+        // 100 bytes: one shift with the extended length.
+        assert_eq!(encode_text(&"a".repeat(100)).len(), 5 + 16 + 100 * 8);
+    }
+
+    #[test]
+    fn stuffing_breaks_words_of_equal_bits() {
+        assert_eq!(
+            stuff_bits(&to_bits("000000"), 6),
+            to_bits(concat!("000001", "011111"))
+        );
+        assert_eq!(
+            stuff_bits(&to_bits("111111"), 6),
+            to_bits(concat!("111110", "111110"))
+        );
+        // Padding to a full word uses ones.
+        assert_eq!(stuff_bits(&to_bits("0101"), 6), to_bits("010111"));
+    }
+
+    #[test]
+    fn symbols_grow_with_the_content() {
+        let small = AztecEncoder
+            .encode("12345", &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(small.width, 15);
+        let full = AztecEncoder
+            .encode(&"x".repeat(200), &SymbolOptions::default())
+            .unwrap();
+        assert!(full.width > 27);
+        assert!(AztecEncoder
+            .encode(&"x".repeat(4000), &SymbolOptions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn the_bullseye_and_orientation_marks_surround_the_center() {
+        let matrix = AztecEncoder
+            .encode("AZTEC", &SymbolOptions::default())
+            .unwrap();
+        let center = matrix.width / 2;
+        // Dark center and rings at even distances, light rings at odd ones.
+        for distance in 0..5 {
+            assert_eq!(
+                matrix.get(center + distance, center),
+                distance % 2 == 0,
+                "{distance}"
+            );
+        }
+        assert!(matrix.get(center - 5, center - 5));
+        assert!(matrix.get(center - 4, center - 5));
+        assert!(matrix.get(center + 5, center + 4));
+        assert!(!matrix.get(center - 5, center + 5));
+    }
+
+    #[test]
+    fn data_bits_fill_the_layers_without_touching_the_core_or_the_grid() {
+        let sizes = (1..=4)
+            .map(|layers| (true, layers))
+            .chain((4..=MAX_LAYERS).map(|layers| (false, layers)));
+        for (compact, layers) in sizes {
+            let (size, _) = symbol_size(compact, layers);
+            let center = size / 2;
+            let core = if compact { 5 } else { 7 };
+            let positions = data_positions(compact, layers);
+            let unique: std::collections::HashSet<_> = positions.iter().collect();
+            assert_eq!(unique.len(), positions.len(), "{compact} {layers}");
+            for &(x, y) in &positions {
+                assert!(x < size && y < size);
+                assert!(
+                    x.abs_diff(center) > core || y.abs_diff(center) > core,
+                    "{compact} {layers}: ({x}, {y}) is in the core"
+                );
+                if !compact {
+                    assert!(
+                        x.abs_diff(center) % 16 != 0 && y.abs_diff(center) % 16 != 0,
+                        "{layers}: ({x}, {y}) is on the reference grid"
+                    );
+                }
+            }
+            // The layers and the core cover the whole symbol.
+            let free = (0..size)
+                .flat_map(|y| (0..size).map(move |x| (x, y)))
+                .filter(|&(x, y)| x.abs_diff(center) > core || y.abs_diff(center) > core)
+                .filter(|&(x, y)| {
+                    compact || (x.abs_diff(center) % 16 != 0 && y.abs_diff(center) % 16 != 0)
+                })
+                .count();
+            assert_eq!(positions.len(), free, "{compact} {layers}");
+        }
+    }
+
+    #[test]
+    fn mode_message_check_words_are_a_reed_solomon_codeword() {
+        // Compact, 1 layer, 3 data words: the check words make the syndromes zero.
+        let mut mode = Vec::new();
+        push_bits(&mut mode, 0, 2);
+        push_bits(&mut mode, 2, 6);
+        let message = check_words(&mode, 28, 4);
+        let words: Vec<u16> = message
+            .chunks(4)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |word, &bit| word << 1 | u16::from(bit))
+            })
+            .collect();
+        assert_eq!(words.len(), 7);
+        assert_eq!(&words[..2], &[0b0000, 0b0010]);
+        let field = galois_field(4);
+        // A whole codeword leaves no remainder.
+        assert_eq!(field.check_words(&words, 5), vec![0; 5]);
+        assert_eq!(field.check_words(&words[..2], 5), words[2..].to_vec());
+    }
+}
@@ -0,0 +1,168 @@
+//! `basePdf.background`: a fill colour and/or an image painted under
+//! everything else on every laid-out page, continuation pages included.
+//!
+//! The image is fitted to the page with the same `objectFit` modes as the
+//! `image` schema. When both are given, the colour is painted first.
+
+use printpdf::{Mm, Op, PdfDocument};
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+
+use super::color::PdfColor;
+use super::image::{Image, ObjectFit};
+use super::pdf_utils::{draw_rectangle, DrawRectangle};
+use super::{Error, HasBaseSchema, InvalidColorSnafu};
+use crate::utils::OpBuffer;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct JsonBackground {
+    #[serde(default)]
+    color: Option<String>,
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    object_fit: Option<ObjectFit>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Background {
+    color: Option<PdfColor>,
+    /// Sized to the page when painted.
+    image: Option<Image>,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidBackground {
+        message: message.to_string(),
+    }
+}
+
+impl Background {
+    pub(crate) fn from_json(json: JsonBackground) -> Result<Background, Error> {
+        if json.color.is_none() && json.image.is_none() {
+            return Err(invalid("set a color, an image or both"));
+        }
+        if json.image.is_none() && json.object_fit.is_some() {
+            return Err(invalid("objectFit only applies to an image"));
+        }
+        let color = match json.color.as_deref() {
+            Some(color) => Some(PdfColor::parse(color).context(InvalidColorSnafu)?),
+            None => None,
+        };
+        let image = match json.image {
+            Some(content) => Some(
+                Image::new(
+                    "background".to_string(),
+                    Mm(0.0),
+                    Mm(0.0),
+                    Mm(0.0),
+                    Mm(0.0),
+                    content,
+                )?
+                .with_object_fit(json.object_fit.unwrap_or_default()),
+            ),
+            None => None,
+        };
+        Ok(Background { color, image })
+    }
+
+    /// Ops painting the background over a page `width` wide whose visible
+    /// part is the top `visible_height` of a `layout_height` tall layout,
+    /// extended by `bleed` on every side.
+    pub(crate) fn render(
+        &self,
+        doc: &mut PdfDocument,
+        width: Mm,
+        layout_height: Mm,
+        visible_height: Mm,
+        bleed: Mm,
+    ) -> Result<Vec<Op>, Error> {
+        let (x, y) = (Mm(0.0) - bleed, Mm(0.0) - bleed);
+        let (width, height) = (width + bleed * 2.0, visible_height + bleed * 2.0);
+        let mut ops = Vec::new();
+        if let Some(color) = &self.color {
+            ops.extend(draw_rectangle(DrawRectangle {
+                x,
+                y,
+                width,
+                height,
+                rotate: None,
+                page_height: layout_height,
+                color: Some(color.clone()),
+                border_width: Some(Mm(0.0)),
+                border_color: Some(color.clone()),
+            }));
+        }
+        if let Some(image) = &self.image {
+            let mut image = image.clone();
+            let base = image.base_mut();
+            (base.x, base.y, base.width, base.height) = (x, y, width, height);
+            let mut buffer = OpBuffer::default();
+            image.render(layout_height, doc, 0, &mut buffer)?;
+            ops.extend(buffer.buffer.into_iter().flatten());
+        }
+        Ok(ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn background(json: serde_json::Value) -> Result<Background, Error> {
+        Background::from_json(serde_json::from_value(json).expect("background json"))
+    }
+
+    #[test]
+    fn a_color_fills_the_visible_page() {
+        let mut doc = PdfDocument::new("background");
+        let ops = background(serde_json::json!({ "color": "#f5f0e1" }))
+            .unwrap()
+            .render(&mut doc, Mm(100.0), Mm(200.0), Mm(50.0), Mm(0.0))
+            .unwrap();
+
+        // The fill starts at the bottom of the visible part of the layout.
+        let origin = ops.iter().find_map(|op| match op {
+            Op::SetTransformationMatrix {
+                matrix: printpdf::CurTransMat::Raw(m),
+            } => Some((m[4], m[5])),
+            _ => None,
+        });
+        let bottom: printpdf::Pt = Mm(150.0).into();
+        assert_eq!(origin, Some((0.0, bottom.0)));
+    }
+
+    #[test]
+    fn a_bleed_extends_the_fill_past_every_edge() {
+        let mut doc = PdfDocument::new("background");
+        let ops = background(serde_json::json!({ "color": "#f5f0e1" }))
+            .unwrap()
+            .render(&mut doc, Mm(100.0), Mm(200.0), Mm(200.0), Mm(3.0))
+            .unwrap();
+
+        let origin = ops.iter().find_map(|op| match op {
+            Op::SetTransformationMatrix {
+                matrix: printpdf::CurTransMat::Raw(m),
+            } => Some((m[4], m[5])),
+            _ => None,
+        });
+        let (x, y) = origin.expect("the fill is positioned");
+        let bleed: printpdf::Pt = Mm(-3.0).into();
+        assert!((x - bleed.0).abs() < 1e-3 && (y - bleed.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn malformed_backgrounds_are_rejected() {
+        for json in [
+            serde_json::json!({}),
+            serde_json::json!({ "color": "#fff", "objectFit": "cover" }),
+            serde_json::json!({ "color": "not-a-color" }),
+        ] {
+            assert!(
+                background(json.clone()).is_err(),
+                "{json} should be rejected"
+            );
+        }
+    }
+}
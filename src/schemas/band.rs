@@ -0,0 +1,141 @@
+//! Header and footer bands.
+//!
+//! A static schema with `"band": "header"` or `"band": "footer"` reserves its
+//! area on every page it is painted on: flowing content (`table`,
+//! `dynamicText`, `spacer`) resumes below the lowest header and breaks above
+//! the highest footer. A band text that needs more room than its `height`
+//! grows to fit — headers downwards, footers upwards so their bottom edge
+//! stays put — and the reserved area grows with it.
+
+use printpdf::Mm;
+use serde::{Deserialize, Serialize};
+
+use super::{Error, HasBaseSchema, Schema, SchemaTrait};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Band {
+    Header,
+    Footer,
+}
+
+impl Band {
+    /// Grows `schema` to its measured height, keeping a footer's bottom edge
+    /// in place.
+    pub(crate) fn fit(self, schema: &mut Schema) -> Result<(), Error> {
+        let declared = schema.base().height;
+        let measured = match schema {
+            Schema::Text(text) => text.get_height()?,
+            _ => declared,
+        };
+        if measured <= declared {
+            return Ok(());
+        }
+        if self == Band::Footer {
+            let y = schema.base().y;
+            schema.set_y(y - (measured - declared));
+        }
+        schema.set_height(measured);
+        Ok(())
+    }
+}
+
+/// The area the bands on one page take: flowing content stays below `top`
+/// and above `bottom`, both measured from the top edge.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct PageBand {
+    pub top: Option<Mm>,
+    pub bottom: Option<Mm>,
+}
+
+impl PageBand {
+    /// The band edges left by fitted band schemas on one page.
+    pub(crate) fn from_schemas<'a>(
+        schemas: impl IntoIterator<Item = (Band, &'a Schema)>,
+    ) -> PageBand {
+        let mut band = PageBand::default();
+        for (kind, schema) in schemas {
+            let bounds = schema.bounds();
+            match kind {
+                Band::Header => {
+                    let bottom = bounds.y + bounds.height;
+                    band.top = Some(band.top.map_or(bottom, |top| Mm(top.0.max(bottom.0))));
+                }
+                Band::Footer => {
+                    band.bottom = Some(
+                        band.bottom
+                            .map_or(bounds.y, |bottom| Mm(bottom.0.min(bounds.y.0))),
+                    );
+                }
+            }
+        }
+        band
+    }
+}
+
+/// Band edges by document page (0-based). Pages past the measured ones take
+/// the largest header and footer seen, so a layout pass that runs longer
+/// than expected still keeps clear of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct BandEdges {
+    pages: Vec<PageBand>,
+    fallback: PageBand,
+}
+
+impl BandEdges {
+    /// `pages[i]` holds the bands of document page `first_page + i`.
+    pub(crate) fn new(first_page: usize, pages: Vec<PageBand>) -> BandEdges {
+        let fallback = PageBand {
+            top: pages
+                .iter()
+                .filter_map(|band| band.top)
+                .reduce(|a, b| Mm(a.0.max(b.0))),
+            bottom: pages
+                .iter()
+                .filter_map(|band| band.bottom)
+                .reduce(|a, b| Mm(a.0.min(b.0))),
+        };
+        let mut all = vec![PageBand::default(); first_page];
+        all.extend(pages);
+        BandEdges {
+            pages: all,
+            fallback,
+        }
+    }
+
+    pub(crate) fn get(&self, page: usize) -> PageBand {
+        self.pages.get(page).copied().unwrap_or(self.fallback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_past_the_measured_ones_keep_clear_of_every_band() {
+        let edges = BandEdges::new(
+            2,
+            vec![
+                PageBand {
+                    top: Some(Mm(20.0)),
+                    bottom: None,
+                },
+                PageBand {
+                    top: Some(Mm(30.0)),
+                    bottom: Some(Mm(280.0)),
+                },
+            ],
+        );
+
+        assert_eq!(edges.get(0), PageBand::default());
+        assert_eq!(edges.get(2).top, Some(Mm(20.0)));
+        assert_eq!(
+            edges.get(7),
+            PageBand {
+                top: Some(Mm(30.0)),
+                bottom: Some(Mm(280.0)),
+            }
+        );
+    }
+}
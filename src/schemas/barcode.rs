@@ -0,0 +1,941 @@
+//! `barcode`: linear barcodes drawn as vector bars.
+//!
+//! Supports Code 128, GS1-128, EAN-13 (JAN), EAN-8, UPC-A, Code 39, ITF and
+//! NW-7 (Codabar). Content is validated and check digits are computed when the
+//! schema is built. The symbol is placed in its box like a QR code, with
+//! `alignment`, `verticalAlignment`, `padding` and `rotate`, and can print
+//! its human-readable text under the bars. Each format's minimum quiet zone
+//! is kept clear on both sides of the bars, inside the symbol width.
+
+use printpdf::{CurTransMat, FontId, Mm, Op, PdfFontHandle, Pt, TextItem, TextMatrix};
+use serde::Deserialize;
+use snafu::{OptionExt, ResultExt};
+
+use super::color::PdfColor;
+use super::gs1::{ElementString, GROUP_SEPARATOR};
+use super::pdf_utils::{
+    calculate_transform_matrix_with_center_pivot, fill_rectangles, sanitize_text_for_font,
+};
+use super::qrcode::SymbolPlacement;
+use super::symbology::dark_runs;
+use super::{
+    Alignment, Error, FontSnafu, Frame, HasBaseSchema, InvalidColorSnafu, JsonPosition,
+    VerticalAlignment,
+};
+use crate::font::{FontMap, FontSpec, FontSpecTrait};
+use crate::schemas::base::BaseSchema;
+use crate::utils::OpBuffer;
+
+const DEFAULT_FONT_SIZE: f32 = 8.0;
+/// Width of a wide element in modules for Code 39, ITF and NW-7.
+const WIDE: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BarcodeFormat {
+    Code128,
+    #[serde(rename = "gs1128", alias = "ean128")]
+    Gs1128,
+    #[serde(alias = "jan", alias = "jan13")]
+    Ean13,
+    #[serde(alias = "jan8")]
+    Ean8,
+    UpcA,
+    Code39,
+    Itf,
+    #[serde(alias = "codabar")]
+    Nw7,
+}
+
+impl BarcodeFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            BarcodeFormat::Code128 => "Code 128",
+            BarcodeFormat::Gs1128 => "GS1-128",
+            BarcodeFormat::Ean13 => "EAN-13",
+            BarcodeFormat::Ean8 => "EAN-8",
+            BarcodeFormat::UpcA => "UPC-A",
+            BarcodeFormat::Code39 => "Code 39",
+            BarcodeFormat::Itf => "ITF",
+            BarcodeFormat::Nw7 => "NW-7",
+        }
+    }
+
+    /// Minimum light margins left and right of the bars, in modules, that
+    /// scanners need to find the start and end of the symbol.
+    pub fn quiet_zone(self) -> (usize, usize) {
+        match self {
+            BarcodeFormat::Ean13 => (11, 7),
+            BarcodeFormat::Ean8 => (7, 7),
+            BarcodeFormat::UpcA => (9, 9),
+            BarcodeFormat::Code128
+            | BarcodeFormat::Gs1128
+            | BarcodeFormat::Code39
+            | BarcodeFormat::Itf
+            | BarcodeFormat::Nw7 => (10, 10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonBarcodeSchema {
+    name: String,
+    content: String,
+    position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
+    width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
+    height: f32,
+    format: BarcodeFormat,
+    rotate: Option<f32>,
+    alignment: Option<Alignment>,
+    vertical_alignment: Option<VerticalAlignment>,
+    padding: Option<Frame>,
+    /// Width of the narrowest bar. Without it the bars and their quiet zones
+    /// fill the box width.
+    #[serde(default, deserialize_with = "super::units::deserialize_optional_mm")]
+    module_width: Option<f32>,
+    /// Appends the optional check digit of Code 39, ITF and NW-7.
+    #[serde(default)]
+    check_digit: bool,
+    #[serde(default)]
+    human_readable: bool,
+    font_name: Option<String>,
+    font_size: Option<f32>,
+    color: Option<String>,
+}
+
+/// Human-readable text printed under the bars.
+#[derive(Debug, Clone)]
+struct Caption {
+    text: String,
+    font_id: FontId,
+    font_size: Pt,
+    width: Mm,
+}
+
+#[derive(Debug, Clone)]
+pub struct Barcode {
+    base: BaseSchema,
+    format: BarcodeFormat,
+    rotate: Option<f32>,
+    placement: SymbolPlacement,
+    module_width: Option<Mm>,
+    color: PdfColor,
+    /// The encoded symbol, `true` for a bar module.
+    modules: Vec<bool>,
+    caption: Option<Caption>,
+}
+
+impl Barcode {
+    pub fn from_json(json: JsonBarcodeSchema, font_map: &FontMap) -> Result<Self, Error> {
+        let format = json.format;
+        let invalid = |message: &str| Error::InvalidBarcode {
+            format: format.name().to_string(),
+            message: message.to_string(),
+        };
+
+        if json.check_digit
+            && !matches!(
+                format,
+                BarcodeFormat::Code39 | BarcodeFormat::Itf | BarcodeFormat::Nw7
+            )
+        {
+            return Err(invalid(
+                "checkDigit only applies to code39, itf and nw7; other formats always carry one",
+            ));
+        }
+        if json.module_width.is_some_and(|width| width <= 0.0) {
+            return Err(invalid("moduleWidth must be positive"));
+        }
+        let (modules, text) = encode(format, &json.content, json.check_digit)?;
+
+        let caption = if json.human_readable {
+            let font_name = json
+                .font_name
+                .as_deref()
+                .ok_or_else(|| invalid("humanReadable needs a fontName"))?;
+            let (font_id, font) = font_map
+                .find(font_name)
+                .whatever_context("Font specified in the barcode is not loaded")?;
+            let font_size = Pt(json.font_size.unwrap_or(DEFAULT_FONT_SIZE));
+            if font_size.0 <= 0.0 {
+                return Err(invalid("fontSize must be positive"));
+            }
+            let text = sanitize_text_for_font(&text, font);
+            let width = FontSpec::new(font.clone())
+                .width_of_text_at_size(&text, font_size, Pt(0.0))
+                .context(FontSnafu)?;
+            Some(Caption {
+                text,
+                font_id: font_id.clone(),
+                font_size,
+                width: width.into(),
+            })
+        } else {
+            if json.font_name.is_some() || json.font_size.is_some() {
+                return Err(invalid("font settings need humanReadable: true"));
+            }
+            None
+        };
+
+        Ok(Barcode {
+            base: BaseSchema::new(
+                json.name,
+                Mm(json.position.x),
+                Mm(json.position.y),
+                Mm(json.width),
+                Mm(json.height),
+            ),
+            format,
+            rotate: json.rotate,
+            placement: SymbolPlacement {
+                alignment: json.alignment.unwrap_or(Alignment::Left),
+                vertical_alignment: json.vertical_alignment.unwrap_or(VerticalAlignment::Top),
+                padding: json.padding,
+            },
+            module_width: json.module_width.map(Mm),
+            color: match json.color.as_deref() {
+                Some(color) => PdfColor::parse(color).context(InvalidColorSnafu)?,
+                None => PdfColor::black(),
+            },
+            modules,
+            caption,
+        })
+    }
+
+    pub fn get_base(&self) -> BaseSchema {
+        self.base.clone()
+    }
+
+    pub fn format(&self) -> BarcodeFormat {
+        self.format
+    }
+
+    pub fn render(
+        &self,
+        parent_height: Mm,
+        page: usize,
+        buffer: &mut OpBuffer,
+    ) -> Result<(), Error> {
+        let invalid = |message: &str| Error::InvalidBarcode {
+            format: self.format.name().to_string(),
+            message: message.to_string(),
+        };
+
+        let (box_width, box_height) = self.placement.inner_size(self.base.width, self.base.height);
+        // 2次元シンボルと同じく、クワイエットゾーンもシンボルの幅に含める
+        let (left_quiet, right_quiet) = self.format.quiet_zone();
+        let module_count = (left_quiet + self.modules.len() + right_quiet) as f32;
+        let (module_width, symbol_width) = match self.module_width {
+            Some(module_width) => (module_width, module_width * module_count),
+            None => (box_width / module_count, box_width),
+        };
+        if symbol_width.0 > box_width.0 + f32::EPSILON || symbol_width.0 <= 0.0 {
+            return Err(invalid("the bars do not fit in the box"));
+        }
+        // クワイエットゾーンを含めた幅に収まらない文字は、収まるまで小さくする
+        let caption = self.caption.as_ref().map(|caption| {
+            let scale = (symbol_width.0 / caption.width.0).min(1.0);
+            (caption, caption.font_size * scale, caption.width * scale)
+        });
+        // 文字の分だけバーを短くする
+        let caption_height = caption.map_or(Mm(0.0), |(_, font_size, _)| Mm::from(font_size) * 1.2);
+        let symbol_height = box_height;
+        let bar_height = symbol_height - caption_height;
+        if bar_height.0 <= 0.0 {
+            return Err(invalid("the box is too short for the bars and their text"));
+        }
+
+        let (x, y) = self.placement.origin(
+            (self.base.x, self.base.y),
+            (box_width, box_height),
+            (symbol_width, symbol_height),
+        );
+        let matrix = calculate_transform_matrix_with_center_pivot(
+            x,
+            parent_height - y - symbol_height,
+            symbol_width,
+            symbol_height,
+            self.rotate,
+        );
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(matrix),
+            },
+        ];
+        ops.extend(self.color.fill_ops());
+        let bottom: Pt = caption_height.into();
+        let top: Pt = symbol_height.into();
+        ops.push(fill_rectangles(dark_runs(&self.modules).map(
+            |(start, width)| {
+                let left: Pt = (module_width * (left_quiet + start) as f32).into();
+                let right: Pt = (module_width * (left_quiet + start + width) as f32).into();
+                (left, bottom, right, top)
+            },
+        )));
+
+        if let Some((caption, font_size, caption_width)) = caption {
+            // バーの下の中央に置き、はみ出す分はクワイエットゾーンに寄せる。
+            // ベースラインはディセンダ分だけ上げる
+            let bars_width = module_width * self.modules.len() as f32;
+            let x = (module_width * left_quiet as f32 + (bars_width - caption_width) / 2.0)
+                .0
+                .clamp(0.0, (symbol_width - caption_width).0.max(0.0));
+            let x: Pt = Mm(x).into();
+            let baseline = font_size * 0.25;
+            ops.push(Op::StartTextSection);
+            ops.extend(self.color.fill_ops());
+            ops.extend([
+                Op::SetFont {
+                    font: PdfFontHandle::External(caption.font_id.clone()),
+                    size: font_size,
+                },
+                Op::SetTextMatrix {
+                    matrix: TextMatrix::Raw([1.0, 0.0, 0.0, 1.0, x.0, baseline.0]),
+                },
+                Op::ShowText {
+                    items: vec![TextItem::Text(caption.text.clone())],
+                },
+                Op::EndTextSection,
+            ]);
+        }
+        ops.push(Op::RestoreGraphicsState);
+
+        buffer.insert(page, ops);
+        Ok(())
+    }
+
+    pub fn set_y(&mut self, y: Mm) {
+        self.base.y = y;
+    }
+
+    pub fn set_height(&mut self, height: Mm) {
+        self.base.height = height;
+    }
+}
+
+impl HasBaseSchema for Barcode {
+    fn base(&self) -> &BaseSchema {
+        &self.base
+    }
+    fn base_mut(&mut self) -> &mut BaseSchema {
+        &mut self.base
+    }
+}
+
+/// Encodes `content`, returning the modules and the human-readable text.
+fn encode(
+    format: BarcodeFormat,
+    content: &str,
+    check_digit: bool,
+) -> Result<(Vec<bool>, String), Error> {
+    let invalid = |message: String| Error::InvalidBarcode {
+        format: format.name().to_string(),
+        message,
+    };
+    if content.is_empty() {
+        return Err(invalid("the content is empty".to_string()));
+    }
+    match format {
+        BarcodeFormat::Code128 => code128(content).map(|modules| (modules, content.to_string())),
+        BarcodeFormat::Gs1128 => {
+            let element_string = ElementString::parse(content)?;
+            let values = code128_symbols(&element_string.encoded(), format, true)?;
+            Ok((code128_modules(&values), element_string.human_readable()))
+        }
+        BarcodeFormat::Ean13 => {
+            let digits = gtin_digits(content, 13).map_err(invalid)?;
+            Ok((ean13(&digits), digits_to_string(&digits)))
+        }
+        BarcodeFormat::Ean8 => {
+            let digits = gtin_digits(content, 8).map_err(invalid)?;
+            Ok((ean8(&digits), digits_to_string(&digits)))
+        }
+        BarcodeFormat::UpcA => {
+            let digits = gtin_digits(content, 12).map_err(invalid)?;
+            // UPC-Aは先頭に0を付けたEAN-13と同じバーになる
+            let ean: Vec<u8> = std::iter::once(0).chain(digits.iter().copied()).collect();
+            Ok((ean13(&ean), digits_to_string(&digits)))
+        }
+        BarcodeFormat::Code39 => code39(content, check_digit).map_err(invalid),
+        BarcodeFormat::Itf => itf(content, check_digit).map_err(invalid),
+        BarcodeFormat::Nw7 => nw7(content, check_digit).map_err(invalid),
+    }
+}
+
+/// Appends runs of alternating bars and spaces, starting with a bar.
+fn push_elements(modules: &mut Vec<bool>, widths: impl IntoIterator<Item = usize>) {
+    for (index, width) in widths.into_iter().enumerate() {
+        modules.extend(std::iter::repeat_n(index % 2 == 0, width));
+    }
+}
+
+/// Widths of the elements encoded by `bits`, most significant first, where a
+/// set bit is a wide element.
+fn wide_narrow(bits: u16, count: u32) -> impl Iterator<Item = usize> {
+    (0..count)
+        .rev()
+        .map(move |bit| if bits >> bit & 1 == 1 { WIDE } else { 1 })
+}
+
+fn digits_to_string(digits: &[u8]) -> String {
+    digits
+        .iter()
+        .map(|digit| char::from(b'0' + digit))
+        .collect()
+}
+
+/// GS1 mod-10 check digit: weights 3 and 1 alternate from the rightmost digit.
+pub(crate) fn gs1_check_digit(digits: &[u8]) -> u8 {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| u32::from(digit) * if index % 2 == 0 { 3 } else { 1 })
+        .sum();
+    ((10 - sum % 10) % 10) as u8
+}
+
+/// The digits of an EAN/UPC number `length` digits long, check digit
+/// included. One digit short computes the check digit; a full-length number
+/// must carry the correct one.
+fn gtin_digits(content: &str, length: usize) -> Result<Vec<u8>, String> {
+    if !content.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(format!("{content:?} must contain only digits"));
+    }
+    let mut digits: Vec<u8> = content.bytes().map(|byte| byte - b'0').collect();
+    if digits.len() == length - 1 {
+        digits.push(gs1_check_digit(&digits));
+    } else if digits.len() == length {
+        let expected = gs1_check_digit(&digits[..length - 1]);
+        if digits[length - 1] != expected {
+            return Err(format!(
+                "{content:?} has check digit {}, expected {expected}",
+                digits[length - 1]
+            ));
+        }
+    } else {
+        return Err(format!(
+            "{content:?} must have {} digits, or {length} with the check digit",
+            length - 1
+        ));
+    }
+    Ok(digits)
+}
+
+// EAN/UPCの左側奇数パリティ（L）の符号。右側（R）はその反転、偶数パリティ（G）はRの逆順
+const EAN_L: [u8; 10] = [
+    0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011,
+    0b0110111, 0b0001011,
+];
+
+// EAN-13の先頭桁ごとの左側6桁のパリティ（1がG）
+const EAN13_PARITY: [u8; 10] = [
+    0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110,
+    0b011010,
+];
+
+fn push_bits(modules: &mut Vec<bool>, bits: u8, count: u32) {
+    modules.extend((0..count).rev().map(|bit| bits >> bit & 1 == 1));
+}
+
+fn ean_digit(modules: &mut Vec<bool>, digit: u8, set: char) {
+    let l = EAN_L[usize::from(digit)];
+    let r = !l & 0x7f;
+    let code = match set {
+        'L' => l,
+        'R' => r,
+        _ => r.reverse_bits() >> 1,
+    };
+    push_bits(modules, code, 7);
+}
+
+fn ean13(digits: &[u8]) -> Vec<bool> {
+    let mut modules = Vec::with_capacity(95);
+    push_bits(&mut modules, 0b101, 3);
+    let parity = EAN13_PARITY[usize::from(digits[0])];
+    for (index, &digit) in digits[1..7].iter().enumerate() {
+        let set = if parity >> (5 - index) & 1 == 1 {
+            'G'
+        } else {
+            'L'
+        };
+        ean_digit(&mut modules, digit, set);
+    }
+    push_bits(&mut modules, 0b01010, 5);
+    for &digit in &digits[7..] {
+        ean_digit(&mut modules, digit, 'R');
+    }
+    push_bits(&mut modules, 0b101, 3);
+    modules
+}
+
+fn ean8(digits: &[u8]) -> Vec<bool> {
+    let mut modules = Vec::with_capacity(67);
+    push_bits(&mut modules, 0b101, 3);
+    for &digit in &digits[..4] {
+        ean_digit(&mut modules, digit, 'L');
+    }
+    push_bits(&mut modules, 0b01010, 5);
+    for &digit in &digits[4..] {
+        ean_digit(&mut modules, digit, 'R');
+    }
+    push_bits(&mut modules, 0b101, 3);
+    modules
+}
+
+// Code 128のシンボル値0〜105のバーとスペースの幅（各11モジュール）
+const CODE128_PATTERNS: [&str; 106] = [
+    "212222", "222122", "222221", "121223", "121322", "131222", "122213", "122312", "132212",
+    "221213", "221312", "231212", "112232", "122132", "122231", "113222", "123122", "123221",
+    "223211", "221132", "221231", "213212", "223112", "312131", "311222", "321122", "321221",
+    "312212", "322112", "322211", "212123", "212321", "232121", "111323", "131123", "131321",
+    "112313", "132113", "132311", "211313", "231113", "231311", "112133", "112331", "132131",
+    "113123", "113321", "133121", "313121", "211331", "231131", "213113", "213311", "213131",
+    "311123", "311321", "331121", "312113", "312311", "332111", "314111", "221411", "431111",
+    "111224", "111422", "121124", "121421", "141122", "141221", "112214", "112412", "122114",
+    "122411", "142112", "142211", "241211", "221114", "413111", "241112", "134111", "111242",
+    "121142", "121241", "114212", "124112", "124211", "411212", "421112", "421211", "212141",
+    "214121", "412121", "111143", "111341", "131141", "114113", "114311", "411113", "411311",
+    "113141", "114131", "311141", "411131", "211412", "211214", "211232",
+];
+const CODE128_STOP: &str = "2331112";
+const CODE128_START_A: u8 = 103;
+const CODE128_START_B: u8 = 104;
+const CODE128_START_C: u8 = 105;
+const CODE128_CODE_C: u8 = 99;
+const CODE128_CODE_B: u8 = 100;
+const CODE128_CODE_A: u8 = 101;
+const CODE128_FNC1: u8 = 102;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodeSet {
+    A,
+    B,
+    C,
+}
+
+fn digit_run(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count()
+}
+
+/// Code 128 symbol values for `content`, start code and check symbol
+/// included. Runs of digits use code set C; control characters use set A
+/// and everything else set B.
+pub(crate) fn code128_values(content: &str) -> Result<Vec<u8>, Error> {
+    code128_symbols(content, BarcodeFormat::Code128, false)
+}
+
+/// [`code128_values`], or with `gs1` the GS1-128 values: FNC1 after the
+/// start code and in place of every group separator.
+fn code128_symbols(content: &str, format: BarcodeFormat, gs1: bool) -> Result<Vec<u8>, Error> {
+    let bytes = content.as_bytes();
+    if let Some(byte) = bytes.iter().find(|byte| !byte.is_ascii()) {
+        return Err(Error::InvalidBarcode {
+            format: format.name().to_string(),
+            message: format!("{content:?} contains the non-ASCII byte {byte:#04x}"),
+        });
+    }
+    let text_set = |byte: u8| if byte < 32 { CodeSet::A } else { CodeSet::B };
+
+    let leading_digits = digit_run(bytes);
+    let mut set = if leading_digits >= 4
+        || (leading_digits == bytes.len() && leading_digits.is_multiple_of(2))
+    {
+        CodeSet::C
+    } else {
+        text_set(bytes[0])
+    };
+    let mut values = vec![match set {
+        CodeSet::A => CODE128_START_A,
+        CodeSet::B => CODE128_START_B,
+        CodeSet::C => CODE128_START_C,
+    }];
+    if gs1 {
+        values.push(CODE128_FNC1);
+    }
+
+    let mut index = 0;
+    while index < bytes.len() {
+        // FNC1はどのコードセットでも使える
+        if gs1 && char::from(bytes[index]) == GROUP_SEPARATOR {
+            values.push(CODE128_FNC1);
+            index += 1;
+            continue;
+        }
+        let run = digit_run(&bytes[index..]);
+        if set == CodeSet::C {
+            if run >= 2 {
+                values.push((bytes[index] - b'0') * 10 + (bytes[index + 1] - b'0'));
+                index += 2;
+                continue;
+            }
+            set = text_set(bytes[index]);
+            values.push(if set == CodeSet::A {
+                CODE128_CODE_A
+            } else {
+                CODE128_CODE_B
+            });
+        }
+        // 4桁以上で終わる数字列、または6桁以上の数字列はセットCに切り替える
+        let reaches_end = index + run == bytes.len();
+        if (reaches_end && run >= 4) || run >= 6 {
+            if run % 2 == 1 {
+                values.push(bytes[index] - 32);
+                index += 1;
+            }
+            values.push(CODE128_CODE_C);
+            set = CodeSet::C;
+            continue;
+        }
+        let byte = bytes[index];
+        match (set, byte) {
+            (CodeSet::B, 0..=31) => {
+                values.push(CODE128_CODE_A);
+                set = CodeSet::A;
+            }
+            (CodeSet::A, 96..=127) => {
+                values.push(CODE128_CODE_B);
+                set = CodeSet::B;
+            }
+            _ => {}
+        }
+        values.push(match (set, byte) {
+            (CodeSet::A, 0..=31) => byte + 64,
+            _ => byte - 32,
+        });
+        index += 1;
+    }
+
+    let checksum = values
+        .iter()
+        .enumerate()
+        .map(|(position, &value)| position.max(1) * usize::from(value))
+        .sum::<usize>()
+        % 103;
+    values.push(checksum as u8);
+    Ok(values)
+}
+
+/// Modules for a sequence of Code 128 symbol values, stop pattern added.
+pub(crate) fn code128_modules(values: &[u8]) -> Vec<bool> {
+    let mut modules = Vec::with_capacity(values.len() * 11 + 13);
+    for pattern in values
+        .iter()
+        .map(|&value| CODE128_PATTERNS[usize::from(value)])
+        .chain(std::iter::once(CODE128_STOP))
+    {
+        push_elements(
+            &mut modules,
+            pattern.bytes().map(|width| usize::from(width - b'0')),
+        );
+    }
+    modules
+}
+
+fn code128(content: &str) -> Result<Vec<bool>, Error> {
+    Ok(code128_modules(&code128_values(content)?))
+}
+
+const CODE39_CHARS: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ-. $/+%";
+// 9要素（バー5・スペース4）のうち太い要素を1とする
+const CODE39_PATTERNS: [u16; 43] = [
+    0x034, 0x121, 0x061, 0x160, 0x031, 0x130, 0x070, 0x025, 0x124, 0x064, 0x109, 0x049, 0x148,
+    0x019, 0x118, 0x058, 0x00D, 0x10C, 0x04C, 0x01C, 0x103, 0x043, 0x142, 0x013, 0x112, 0x052,
+    0x007, 0x106, 0x046, 0x016, 0x181, 0x0C1, 0x1C0, 0x091, 0x190, 0x0D0, 0x085, 0x184, 0x0C4,
+    0x0A8, 0x0A2, 0x08A, 0x02A,
+];
+const CODE39_START_STOP: u16 = 0x094;
+
+fn code39(content: &str, check_digit: bool) -> Result<(Vec<bool>, String), String> {
+    let mut values = content
+        .chars()
+        .map(|c| {
+            CODE39_CHARS.find(c).ok_or_else(|| {
+                format!("{c:?} cannot be encoded; use 0-9, A-Z and - . space $ / + %")
+            })
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+    let mut text = content.to_string();
+    if check_digit {
+        let check = values.iter().sum::<usize>() % 43;
+        values.push(check);
+        text.push(CODE39_CHARS.as_bytes()[check] as char);
+    }
+
+    let mut modules = Vec::new();
+    let patterns = std::iter::once(CODE39_START_STOP)
+        .chain(values.iter().map(|&value| CODE39_PATTERNS[value]))
+        .chain(std::iter::once(CODE39_START_STOP));
+    for (index, pattern) in patterns.enumerate() {
+        if index > 0 {
+            modules.push(false);
+        }
+        push_elements(&mut modules, wide_narrow(pattern, 9));
+    }
+    Ok((modules, text))
+}
+
+// ITFの数字ごとの5要素の太細（バーとスペースで共通）
+const ITF_PATTERNS: [u16; 10] = [
+    0b00110, 0b10001, 0b01001, 0b11000, 0b00101, 0b10100, 0b01100, 0b00011, 0b10010, 0b01010,
+];
+
+fn itf(content: &str, check_digit: bool) -> Result<(Vec<bool>, String), String> {
+    if !content.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(format!("{content:?} must contain only digits"));
+    }
+    let mut digits: Vec<u8> = content.bytes().map(|byte| byte - b'0').collect();
+    if check_digit {
+        digits.push(gs1_check_digit(&digits));
+    }
+    if digits.len() % 2 == 1 {
+        return Err(if check_digit {
+            format!("{content:?} needs an odd number of digits to add a check digit")
+        } else {
+            format!("{content:?} needs an even number of digits")
+        });
+    }
+
+    let mut modules = Vec::new();
+    push_elements(&mut modules, [1, 1, 1, 1]);
+    for pair in digits.chunks(2) {
+        let bars: Vec<usize> = wide_narrow(ITF_PATTERNS[usize::from(pair[0])], 5).collect();
+        let spaces: Vec<usize> = wide_narrow(ITF_PATTERNS[usize::from(pair[1])], 5).collect();
+        push_elements(
+            &mut modules,
+            bars.into_iter()
+                .zip(spaces)
+                .flat_map(|(bar, space)| [bar, space]),
+        );
+    }
+    push_elements(&mut modules, [WIDE, 1, 1]);
+    Ok((modules, digits_to_string(&digits)))
+}
+
+const NW7_CHARS: &str = "0123456789-$:/.+ABCD";
+// 7要素（バー4・スペース3）のうち太い要素を1とする
+const NW7_PATTERNS: [u16; 20] = [
+    0x003, 0x006, 0x009, 0x060, 0x012, 0x042, 0x021, 0x024, 0x030, 0x048, 0x00C, 0x018, 0x045,
+    0x051, 0x054, 0x015, 0x01A, 0x029, 0x00B, 0x00E,
+];
+
+fn nw7(content: &str, check_digit: bool) -> Result<(Vec<bool>, String), String> {
+    let upper = content.to_ascii_uppercase();
+    let is_guard = |c: char| ('A'..='D').contains(&c);
+    let first = upper.chars().next().is_some_and(is_guard);
+    let last = upper.len() > 1 && upper.chars().last().is_some_and(is_guard);
+    // スタート・ストップ文字は両端とも指定するか、両方省略してAとする
+    let (start, data, stop) = match (first, last) {
+        (true, true) => (
+            &upper[..1],
+            &upper[1..upper.len() - 1],
+            &upper[upper.len() - 1..],
+        ),
+        (false, false) => ("A", upper.as_str(), "A"),
+        _ => return Err("give both a start and a stop character (A-D), or neither".to_string()),
+    };
+    if data.is_empty() {
+        return Err("there is no data between the start and stop characters".to_string());
+    }
+    let mut values = data
+        .chars()
+        .map(|c| {
+            NW7_CHARS[..16]
+                .find(c)
+                .ok_or_else(|| format!("{c:?} cannot be encoded; use 0-9 and - $ : / . +"))
+        })
+        .collect::<Result<Vec<usize>, _>>()?;
+    let start = NW7_CHARS.find(start).unwrap_or(16);
+    let stop = NW7_CHARS.find(stop).unwrap_or(16);
+    if check_digit {
+        // モジュラス16。スタート・ストップ文字も合計に含める
+        let sum = start + stop + values.iter().sum::<usize>();
+        values.push((16 - sum % 16) % 16);
+    }
+
+    let mut modules = Vec::new();
+    let all: Vec<usize> = std::iter::once(start)
+        .chain(values.iter().copied())
+        .chain(std::iter::once(stop))
+        .collect();
+    for (index, &value) in all.iter().enumerate() {
+        if index > 0 {
+            modules.push(false);
+        }
+        push_elements(&mut modules, wide_narrow(NW7_PATTERNS[value], 7));
+    }
+    let text = all
+        .iter()
+        .map(|&value| NW7_CHARS.as_bytes()[value] as char)
+        .collect();
+    Ok((modules, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn widths(modules: &[bool]) -> Vec<usize> {
+        let mut widths = Vec::new();
+        let mut previous = None;
+        for &module in modules {
+            if previous == Some(module) {
+                *widths.last_mut().unwrap() += 1;
+            } else {
+                widths.push(1);
+                previous = Some(module);
+            }
+        }
+        widths
+    }
+
+    #[test]
+    fn code128_patterns_are_eleven_modules_with_even_bars() {
+        for (value, pattern) in CODE128_PATTERNS.iter().enumerate() {
+            let widths: Vec<u32> = pattern.bytes().map(|w| u32::from(w - b'0')).collect();
+            assert_eq!(widths.iter().sum::<u32>(), 11, "value {value}");
+            assert_eq!((widths[0] + widths[2] + widths[4]) % 2, 0, "value {value}");
+        }
+    }
+
+    #[test]
+    fn code128_computes_its_check_symbol() {
+        // Start B, P J J 1 2 3 C, check symbol 55.
+        let values = code128_values("PJJ123C").unwrap();
+        assert_eq!(values.first(), Some(&CODE128_START_B));
+        assert_eq!(values.last(), Some(&55));
+    }
+
+    #[test]
+    fn code128_packs_digit_runs_into_code_set_c() {
+        assert_eq!(
+            code128_values("123456").unwrap(),
+            vec![
+                CODE128_START_C,
+                12,
+                34,
+                56,
+                ((105 + 12 + 34 * 2 + 56 * 3) % 103) as u8
+            ]
+        );
+        // An odd run keeps its first digit in set B before switching.
+        let values = code128_values("AB12345").unwrap();
+        assert_eq!(
+            &values[..6],
+            &[CODE128_START_B, 33, 34, 17, CODE128_CODE_C, 23]
+        );
+        assert!(code128_values("naïve").is_err());
+    }
+
+    #[test]
+    fn gs1_128_marks_the_start_and_field_ends_with_fnc1() {
+        let content = "(01)09501101530003(10)AB1(17)250101";
+        let (modules, text) = encode(BarcodeFormat::Gs1128, content, false).unwrap();
+        assert_eq!(text, content);
+
+        // FNC1 after the start code and after the variable-length lot, none
+        // after the fixed-length GTIN; digits stay in code set C.
+        let values = code128_symbols(
+            "010950110153000310AB1\u{1d}17250101",
+            BarcodeFormat::Gs1128,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            &values[..values.len() - 1],
+            &[
+                CODE128_START_C,
+                CODE128_FNC1,
+                1,
+                9,
+                50,
+                11,
+                1,
+                53,
+                0,
+                3,
+                10,
+                CODE128_CODE_B,
+                33,
+                34,
+                17,
+                CODE128_FNC1,
+                CODE128_CODE_C,
+                17,
+                25,
+                1,
+                1
+            ]
+        );
+        assert_eq!(modules, code128_modules(&values));
+
+        assert!(matches!(
+            encode(BarcodeFormat::Gs1128, "(01)09501101530004", false),
+            Err(Error::InvalidGs1 { .. })
+        ));
+    }
+
+    #[test]
+    fn ean13_checks_and_computes_the_check_digit() {
+        assert_eq!(gtin_digits("400638133393", 13).unwrap()[12], 1);
+        assert!(gtin_digits("4006381333931", 13).is_ok());
+        assert!(gtin_digits("4006381333932", 13).is_err());
+        assert!(gtin_digits("40063813339", 13).is_err());
+        assert!(gtin_digits("40063813339a", 13).is_err());
+
+        let (modules, text) = encode(BarcodeFormat::Ean13, "400638133393", false).unwrap();
+        assert_eq!(modules.len(), 95);
+        assert_eq!(text, "4006381333931");
+        // Left guard, then 0 in odd parity (first digit 4 → LGLLGG).
+        assert_eq!(widths(&modules[..10]), vec![1, 1, 1, 3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn ean8_and_upc_a_use_their_own_lengths() {
+        let (modules, text) = encode(BarcodeFormat::Ean8, "9638507", false).unwrap();
+        assert_eq!((modules.len(), text.as_str()), (67, "96385074"));
+        let (modules, text) = encode(BarcodeFormat::UpcA, "03600029145", false).unwrap();
+        assert_eq!((modules.len(), text.as_str()), (95, "036000291452"));
+    }
+
+    #[test]
+    fn code39_patterns_have_three_wide_elements() {
+        for pattern in CODE39_PATTERNS.iter().chain([&CODE39_START_STOP]) {
+            assert_eq!(pattern.count_ones(), 3, "{pattern:#05x}");
+        }
+        let (modules, text) = encode(BarcodeFormat::Code39, "CODE39", true).unwrap();
+        // Start, six characters, check character and stop, one gap between each.
+        assert_eq!(modules.len(), 9 * (6 + 3 * WIDE) + 8);
+        assert_eq!(text, "CODE39W");
+        assert!(encode(BarcodeFormat::Code39, "lower", false).is_err());
+    }
+
+    #[test]
+    fn itf_interleaves_pairs_of_digits() {
+        let (modules, text) = encode(BarcodeFormat::Itf, "1234567", true).unwrap();
+        assert_eq!(text, "12345670");
+        // Start, four pairs of 2 × (3 narrow + 2 wide), stop.
+        assert_eq!(modules.len(), 4 + 4 * 2 * (3 + 2 * WIDE) + WIDE + 2);
+        assert!(encode(BarcodeFormat::Itf, "123", false).is_err());
+        assert!(encode(BarcodeFormat::Itf, "1234", true).is_err());
+    }
+
+    #[test]
+    fn nw7_wraps_data_in_start_and_stop_characters() {
+        let (_, text) = encode(BarcodeFormat::Nw7, "1234", false).unwrap();
+        assert_eq!(text, "A1234A");
+        let (_, text) = encode(BarcodeFormat::Nw7, "b1234d", true).unwrap();
+        // B(17) + D(19) + 1 + 2 + 3 + 4 = 46, 16 - 46 % 16 = 2.
+        assert_eq!(text, "B12342D");
+        assert!(encode(BarcodeFormat::Nw7, "A1234", false).is_err());
+        assert!(encode(BarcodeFormat::Nw7, "12A4", false).is_err());
+    }
+}
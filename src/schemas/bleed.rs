@@ -0,0 +1,404 @@
+//! `basePdf.bleed` and `basePdf.printerMarks`: output for commercial printing.
+//!
+//! Template coordinates stay relative to the trimmed page. The emitted page
+//! grows by the bleed on every side (and by a slug for the printer's marks,
+//! when any are drawn), the drawn ops are moved in by the same amount, and
+//! the trim and bleed areas are recorded as `/TrimBox`, `/BleedBox` and
+//! `/ArtBox` after saving.
+
+use lopdf::{Document, Object};
+use printpdf::{
+    Cmyk, Color, CurTransMat, Line, LinePoint, Mm, Op, PaintMode, Point, Polygon, PolygonRing, Pt,
+    WindingOrder,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{rotation, Error};
+
+/// Closest a mark comes to the trim, when the bleed is narrower.
+const MARK_OFFSET: Mm = Mm(3.0);
+/// Length of a crop mark, and the size of registration targets and colour
+/// bar patches.
+const MARK_LENGTH: Mm = Mm(5.0);
+/// Blank space left between the marks and the edge of the media.
+const SLUG_GAP: Mm = Mm(2.0);
+const MARK_LINE_WIDTH: Pt = Pt(0.25);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct PrinterMarks {
+    #[serde(default)]
+    pub crop: bool,
+    #[serde(default)]
+    pub registration: bool,
+    #[serde(default)]
+    pub color_bar: bool,
+}
+
+impl PrinterMarks {
+    fn any(&self) -> bool {
+        self.crop || self.registration || self.color_bar
+    }
+}
+
+/// Bleed and printer's marks of a page. The default adds neither.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bleed {
+    pub size: Mm,
+    pub marks: PrinterMarks,
+}
+
+/// Page boxes in points, as `[left, bottom, right, top]` in the emitted page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PageBoxes {
+    trim: [f32; 4],
+    bleed: [f32; 4],
+}
+
+impl Bleed {
+    pub(crate) fn is_none(&self) -> bool {
+        self.size.0 <= 0.0 && !self.marks.any()
+    }
+
+    fn mark_offset(&self) -> Mm {
+        Mm(self.size.0.max(MARK_OFFSET.0))
+    }
+
+    /// Distance from the trim to the edge of the emitted page.
+    fn margin(&self) -> Mm {
+        if self.marks.any() {
+            self.mark_offset() + MARK_LENGTH + SLUG_GAP
+        } else {
+            self.size
+        }
+    }
+
+    /// Moves the ops of a trimmed `width` × `height` page into the enlarged
+    /// page and draws the printer's marks around it. Returns the ops and the
+    /// enlarged page size.
+    pub(crate) fn extend(&self, width: Mm, height: Mm, ops: Vec<Op>) -> (Vec<Op>, Mm, Mm) {
+        if self.is_none() {
+            return (ops, width, height);
+        }
+        let margin: Pt = self.margin().into();
+        let mut extended = Vec::with_capacity(ops.len() + 4);
+        extended.push(Op::SaveGraphicsState);
+        extended.push(Op::SetTransformationMatrix {
+            matrix: CurTransMat::Translate(margin, margin),
+        });
+        extended.extend(ops);
+        extended.extend(self.marks(width, height));
+        extended.push(Op::RestoreGraphicsState);
+        let grow = self.margin() * 2.0;
+        (extended, width + grow, height + grow)
+    }
+
+    /// Trim and bleed boxes of a trimmed `width` × `height` page, after
+    /// `rotation` is applied to its content.
+    pub(crate) fn page_boxes(
+        &self,
+        width: Mm,
+        height: Mm,
+        rotation: rotation::Rotation,
+    ) -> Option<PageBoxes> {
+        if self.is_none() {
+            return None;
+        }
+        let margin: Pt = self.margin().into();
+        let bleed: Pt = self.size.into();
+        let (width, height): (Pt, Pt) = (width.into(), height.into());
+        let trim = [margin.0, margin.0, margin.0 + width.0, margin.0 + height.0];
+        let boxes = PageBoxes {
+            trim,
+            bleed: [
+                trim[0] - bleed.0,
+                trim[1] - bleed.0,
+                trim[2] + bleed.0,
+                trim[3] + bleed.0,
+            ],
+        };
+
+        let media = (width.0 + margin.0 * 2.0, height.0 + margin.0 * 2.0);
+        let [a, b, c, d, e, f] = rotation.matrix(media.0, media.1);
+        let rotate = |[left, bottom, right, top]: [f32; 4]| {
+            let (x0, y0) = (a * left + c * bottom + e, b * left + d * bottom + f);
+            let (x1, y1) = (a * right + c * top + e, b * right + d * top + f);
+            [x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)]
+        };
+        Some(PageBoxes {
+            trim: rotate(boxes.trim),
+            bleed: rotate(boxes.bleed),
+        })
+    }
+
+    // マークはトリム基準の座標で、仕上がり線の外側にだけ描く
+    fn marks(&self, width: Mm, height: Mm) -> Vec<Op> {
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetOutlineThickness {
+                pt: MARK_LINE_WIDTH,
+            },
+            Op::SetOutlineColor {
+                col: registration_color(),
+            },
+        ];
+        let offset = self.mark_offset();
+        let outer = offset + MARK_LENGTH;
+
+        if self.marks.crop {
+            for (x, outward_x) in [(Mm(0.0), -1.0), (width, 1.0)] {
+                for (y, outward_y) in [(Mm(0.0), -1.0), (height, 1.0)] {
+                    ops.push(line(
+                        (x + offset * outward_x, y),
+                        (x + outer * outward_x, y),
+                    ));
+                    ops.push(line(
+                        (x, y + offset * outward_y),
+                        (x, y + outer * outward_y),
+                    ));
+                }
+            }
+        }
+
+        if self.marks.registration {
+            let distance = offset + MARK_LENGTH / 2.0;
+            for center in [
+                (Mm(0.0) - distance, height / 2.0),
+                (width + distance, height / 2.0),
+                (width / 2.0, Mm(0.0) - distance),
+            ] {
+                ops.extend(registration_target(center));
+            }
+        }
+
+        if self.marks.color_bar {
+            // 上辺に並べる。角のトンボと重ならないよう両端を空ける
+            let y = height + offset;
+            let mut x = MARK_LENGTH * 2.0;
+            for patch in color_bar_patches() {
+                if x + MARK_LENGTH > width - MARK_LENGTH * 2.0 {
+                    break;
+                }
+                ops.push(Op::SetFillColor { col: patch });
+                ops.push(square(x, y, MARK_LENGTH));
+                x += MARK_LENGTH;
+            }
+        }
+
+        ops.push(Op::RestoreGraphicsState);
+        ops
+    }
+}
+
+/// The colour that prints on every separation.
+fn registration_color() -> Color {
+    cmyk(1.0, 1.0, 1.0, 1.0)
+}
+
+fn cmyk(c: f32, m: f32, y: f32, k: f32) -> Color {
+    Color::Cmyk(Cmyk {
+        c,
+        m,
+        y,
+        k,
+        icc_profile: None,
+    })
+}
+
+/// Process solids, overprints and a 50% black tint.
+fn color_bar_patches() -> Vec<Color> {
+    vec![
+        cmyk(1.0, 0.0, 0.0, 0.0),
+        cmyk(0.0, 1.0, 0.0, 0.0),
+        cmyk(0.0, 0.0, 1.0, 0.0),
+        cmyk(0.0, 0.0, 0.0, 1.0),
+        cmyk(1.0, 1.0, 0.0, 0.0),
+        cmyk(1.0, 0.0, 1.0, 0.0),
+        cmyk(0.0, 1.0, 1.0, 0.0),
+        cmyk(0.0, 0.0, 0.0, 0.5),
+    ]
+}
+
+fn point((x, y): (Mm, Mm), bezier: bool) -> LinePoint {
+    LinePoint {
+        p: Point {
+            x: x.into(),
+            y: y.into(),
+        },
+        bezier,
+    }
+}
+
+fn line(from: (Mm, Mm), to: (Mm, Mm)) -> Op {
+    Op::DrawLine {
+        line: Line {
+            points: vec![point(from, false), point(to, false)],
+            is_closed: false,
+        },
+    }
+}
+
+fn square(x: Mm, y: Mm, size: Mm) -> Op {
+    Op::DrawPolygon {
+        polygon: Polygon {
+            rings: vec![PolygonRing {
+                points: vec![
+                    point((x, y), false),
+                    point((x + size, y), false),
+                    point((x + size, y + size), false),
+                    point((x, y + size), false),
+                ],
+            }],
+            mode: PaintMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        },
+    }
+}
+
+/// A circle with a crosshair through it.
+fn registration_target((cx, cy): (Mm, Mm)) -> Vec<Op> {
+    let arm = MARK_LENGTH / 2.0;
+    let radius = arm * 0.6;
+    // 4本の3次ベジェ曲線で円を近似する
+    let k = radius * 0.552_284_8;
+    let circle = vec![
+        point((cx + radius, cy), false),
+        point((cx + radius, cy + k), true),
+        point((cx + k, cy + radius), true),
+        point((cx, cy + radius), false),
+        point((cx - k, cy + radius), true),
+        point((cx - radius, cy + k), true),
+        point((cx - radius, cy), false),
+        point((cx - radius, cy - k), true),
+        point((cx - k, cy - radius), true),
+        point((cx, cy - radius), false),
+        point((cx + k, cy - radius), true),
+        point((cx + radius, cy - k), true),
+        point((cx + radius, cy), false),
+    ];
+    vec![
+        Op::DrawLine {
+            line: Line {
+                points: circle,
+                is_closed: true,
+            },
+        },
+        line((cx - arm, cy), (cx + arm, cy)),
+        line((cx, cy - arm), (cx, cy + arm)),
+    ]
+}
+
+/// Writes `/TrimBox`, `/BleedBox` and `/ArtBox` on the saved pages, one
+/// entry per page. Returns the bytes unchanged when no page has any.
+pub(crate) fn apply_page_boxes(
+    pdf: Vec<u8>,
+    boxes: &[Option<PageBoxes>],
+) -> Result<Vec<u8>, Error> {
+    if boxes.iter().all(Option::is_none) {
+        return Ok(pdf);
+    }
+
+    fn pdf_error(e: impl std::fmt::Display) -> Error {
+        Error::Whatever {
+            message: format!("Failed to set page boxes: {e}"),
+            source: None,
+        }
+    }
+    let to_array = |rect: [f32; 4]| -> Vec<Object> { rect.into_iter().map(Object::from).collect() };
+
+    let mut document = Document::load_mem(&pdf).map_err(pdf_error)?;
+    let page_ids: Vec<_> = document.get_pages().into_values().collect();
+    for (page_id, page_boxes) in page_ids.into_iter().zip(boxes) {
+        let Some(page_boxes) = page_boxes else {
+            continue;
+        };
+        let page = document
+            .get_object_mut(page_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?;
+        page.set("TrimBox", to_array(page_boxes.trim));
+        page.set("BleedBox", to_array(page_boxes.bleed));
+        page.set("ArtBox", to_array(page_boxes.trim));
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mm(value: f32) -> f32 {
+        Pt::from(Mm(value)).0
+    }
+
+    fn assert_rect(actual: [f32; 4], expected: [f32; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 0.01, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn a_bare_bleed_grows_the_page_by_the_bleed() {
+        let bleed = Bleed {
+            size: Mm(3.0),
+            marks: PrinterMarks::default(),
+        };
+        let (_, width, height) = bleed.extend(Mm(100.0), Mm(50.0), Vec::new());
+        assert_eq!((width, height), (Mm(106.0), Mm(56.0)));
+
+        let boxes = bleed
+            .page_boxes(Mm(100.0), Mm(50.0), rotation::Rotation::None)
+            .unwrap();
+        assert_rect(boxes.trim, [mm(3.0), mm(3.0), mm(103.0), mm(53.0)]);
+        assert_rect(boxes.bleed, [0.0, 0.0, mm(106.0), mm(56.0)]);
+    }
+
+    #[test]
+    fn marks_add_a_slug_outside_the_bleed() {
+        let bleed = Bleed {
+            size: Mm(3.0),
+            marks: PrinterMarks {
+                crop: true,
+                ..PrinterMarks::default()
+            },
+        };
+        // 3mm offset + 5mm marks + 2mm gap on each side.
+        let (_, width, _) = bleed.extend(Mm(100.0), Mm(50.0), Vec::new());
+        assert_eq!(width, Mm(120.0));
+        let boxes = bleed
+            .page_boxes(Mm(100.0), Mm(50.0), rotation::Rotation::None)
+            .unwrap();
+        assert_rect(boxes.bleed, [mm(7.0), mm(7.0), mm(113.0), mm(63.0)]);
+    }
+
+    #[test]
+    fn boxes_turn_with_content_rotation() {
+        let bleed = Bleed {
+            size: Mm(3.0),
+            marks: PrinterMarks::default(),
+        };
+        let boxes = bleed
+            .page_boxes(Mm(100.0), Mm(50.0), rotation::Rotation::Clockwise90)
+            .unwrap();
+        assert_rect(boxes.trim, [mm(3.0), mm(3.0), mm(53.0), mm(103.0)]);
+    }
+
+    #[test]
+    fn no_bleed_leaves_the_page_alone() {
+        let bleed = Bleed::default();
+        assert!(bleed.is_none());
+        assert_eq!(
+            bleed
+                .extend(Mm(100.0), Mm(50.0), vec![Op::SaveGraphicsState])
+                .0
+                .len(),
+            1
+        );
+        assert!(bleed
+            .page_boxes(Mm(100.0), Mm(50.0), rotation::Rotation::None)
+            .is_none());
+    }
+}
@@ -0,0 +1,507 @@
+//! Print colours: CSS colours plus `cmyk(…)`, `gray(…)` and `spot(…)`.
+//!
+//! CSS colours are written as DeviceRGB (`rg` / `RG`), `cmyk(…)` as
+//! DeviceCMYK (`k` / `K`) and `gray(…)` as DeviceGray (`g` / `G`), so pure K
+//! black text stays on the black plate. `spot(Name, tint)` selects a
+//! Separation colour space named after an entry of `basePdf.spotColors`; the
+//! colour space itself, with its CMYK alternate, is added to each page's
+//! resources after saving.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use lopdf::{dictionary, Document, Object};
+use printpdf::{Cmyk, Color, DictItem, Greyscale, Op, Rgb};
+use snafu::{ResultExt, Snafu};
+
+use super::Error;
+
+/// Prefix of the page resource names given to spot colour spaces, so they
+/// never clash with the device colour space names.
+const RESOURCE_PREFIX: &str = "CS_";
+
+#[derive(Debug, Snafu)]
+pub enum ParseColorError {
+    #[snafu(display("{source}"))]
+    Css {
+        source: csscolorparser::ParseColorError,
+    },
+
+    #[snafu(display("Invalid color {value:?}: {message}"))]
+    Malformed { value: String, message: String },
+}
+
+/// A colour as it is written to the PDF.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PdfColor {
+    Rgb {
+        r: f32,
+        g: f32,
+        b: f32,
+    },
+    Cmyk {
+        c: f32,
+        m: f32,
+        y: f32,
+        k: f32,
+    },
+    /// DeviceGray level: 0 is black, 1 is white.
+    Gray(f32),
+    /// A tint of a spot colour defined in `basePdf.spotColors`.
+    Spot {
+        name: String,
+        tint: f32,
+    },
+}
+
+impl PdfColor {
+    pub fn black() -> Self {
+        PdfColor::Rgb {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+        }
+    }
+
+    pub fn white() -> Self {
+        PdfColor::Rgb {
+            r: 1.0,
+            g: 1.0,
+            b: 1.0,
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, ParseColorError> {
+        let trimmed = value.trim();
+        let Some((function, arguments)) = trimmed
+            .strip_suffix(')')
+            .and_then(|rest| rest.split_once('('))
+        else {
+            return csscolorparser::parse(trimmed)
+                .map(PdfColor::from)
+                .context(CssSnafu);
+        };
+        let malformed = |message: &str| ParseColorError::Malformed {
+            value: value.to_string(),
+            message: message.to_string(),
+        };
+
+        match function.trim().to_ascii_lowercase().as_str() {
+            "cmyk" | "device-cmyk" => {
+                let components = arguments
+                    .split(',')
+                    .map(|part| {
+                        parse_component(part).ok_or_else(|| malformed("expected 0–1 or 0%–100%"))
+                    })
+                    .collect::<Result<Vec<f32>, _>>()?;
+                let [c, m, y, k] = components[..] else {
+                    return Err(malformed("cmyk() takes four components"));
+                };
+                Ok(PdfColor::Cmyk { c, m, y, k })
+            }
+            "gray" | "grey" => parse_component(arguments)
+                .map(PdfColor::Gray)
+                .ok_or_else(|| malformed("gray() takes one level, 0–1 or 0%–100%")),
+            "spot" => {
+                // 引用符で囲めば名前にカンマを含められる
+                let arguments = arguments.trim();
+                let (name, tint) = match arguments.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let (name, rest) = arguments[1..]
+                            .split_once(quote)
+                            .ok_or_else(|| malformed("the spot color name is not closed"))?;
+                        match rest.trim() {
+                            "" => (name, None),
+                            rest => match rest.strip_prefix(',') {
+                                Some(tint) => (name, Some(tint)),
+                                None => return Err(malformed("expected a tint after the name")),
+                            },
+                        }
+                    }
+                    _ => match arguments.split_once(',') {
+                        Some((name, tint)) => (name.trim(), Some(tint)),
+                        None => (arguments, None),
+                    },
+                };
+                let tint = match tint {
+                    Some(tint) => parse_component(tint)
+                        .ok_or_else(|| malformed("the spot tint must be 0–1 or 0%–100%"))?,
+                    None => 1.0,
+                };
+                if name.is_empty() {
+                    return Err(malformed("spot() needs the name of a spot color"));
+                }
+                Ok(PdfColor::Spot {
+                    name: name.to_string(),
+                    tint,
+                })
+            }
+            _ => csscolorparser::parse(trimmed)
+                .map(PdfColor::from)
+                .context(CssSnafu),
+        }
+    }
+
+    /// The colour in DeviceCMYK, for colours that have a process equivalent.
+    fn to_cmyk(&self) -> Option<[f32; 4]> {
+        match *self {
+            PdfColor::Rgb { r, g, b } => {
+                let k = 1.0 - r.max(g).max(b);
+                if k >= 1.0 {
+                    return Some([0.0, 0.0, 0.0, 1.0]);
+                }
+                let ink = |channel: f32| (1.0 - channel - k) / (1.0 - k);
+                Some([ink(r), ink(g), ink(b), k])
+            }
+            PdfColor::Cmyk { c, m, y, k } => Some([c, m, y, k]),
+            PdfColor::Gray(level) => Some([0.0, 0.0, 0.0, 1.0 - level]),
+            PdfColor::Spot { .. } => None,
+        }
+    }
+
+    fn device_color(&self) -> Option<Color> {
+        match *self {
+            PdfColor::Rgb { r, g, b } => Some(Color::Rgb(Rgb {
+                r,
+                g,
+                b,
+                icc_profile: None,
+            })),
+            PdfColor::Cmyk { c, m, y, k } => Some(Color::Cmyk(Cmyk {
+                c,
+                m,
+                y,
+                k,
+                icc_profile: None,
+            })),
+            PdfColor::Gray(percent) => Some(Color::Greyscale(Greyscale {
+                percent,
+                icc_profile: None,
+            })),
+            PdfColor::Spot { .. } => None,
+        }
+    }
+
+    /// Ops selecting this colour for filling (text included).
+    pub fn fill_ops(&self) -> Vec<Op> {
+        match (self.device_color(), self) {
+            (Some(col), _) => vec![Op::SetFillColor { col }],
+            (None, PdfColor::Spot { name, tint }) => vec![
+                Op::SetColorSpaceFill {
+                    id: resource_name(name),
+                },
+                Op::Unknown {
+                    key: "scn".to_string(),
+                    value: vec![DictItem::Real(*tint)],
+                },
+            ],
+            (None, _) => unreachable!("only spot colors lack a device color"),
+        }
+    }
+
+    /// Ops selecting this colour for stroking.
+    pub fn stroke_ops(&self) -> Vec<Op> {
+        match (self.device_color(), self) {
+            (Some(col), _) => vec![Op::SetOutlineColor { col }],
+            (None, PdfColor::Spot { name, tint }) => vec![
+                Op::SetColorSpaceStroke {
+                    id: resource_name(name),
+                },
+                Op::Unknown {
+                    key: "SCN".to_string(),
+                    value: vec![DictItem::Real(*tint)],
+                },
+            ],
+            (None, _) => unreachable!("only spot colors lack a device color"),
+        }
+    }
+}
+
+impl From<csscolorparser::Color> for PdfColor {
+    fn from(color: csscolorparser::Color) -> Self {
+        PdfColor::Rgb {
+            r: color.r,
+            g: color.g,
+            b: color.b,
+        }
+    }
+}
+
+impl FromStr for PdfColor {
+    type Err = ParseColorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        PdfColor::parse(value)
+    }
+}
+
+// 0〜1の数値または0%〜100%の百分率を読む
+fn parse_component(value: &str) -> Option<f32> {
+    let value = value.trim();
+    let component = match value.strip_suffix('%') {
+        Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+        None => value.parse::<f32>().ok()?,
+    };
+    (0.0..=1.0).contains(&component).then_some(component)
+}
+
+fn resource_name(spot: &str) -> String {
+    format!("{RESOURCE_PREFIX}{spot}")
+}
+
+/// `basePdf.spotColors`: spot colour names and the process colour each one
+/// is approximated with on devices that lack the ink.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpotColors(BTreeMap<String, [f32; 4]>);
+
+impl SpotColors {
+    pub(crate) fn from_json(json: BTreeMap<String, String>) -> Result<SpotColors, Error> {
+        json.into_iter()
+            .map(|(name, alternate)| {
+                let alternate = PdfColor::parse(&alternate)
+                    .context(super::InvalidColorSnafu)?
+                    .to_cmyk()
+                    .ok_or_else(|| Error::InvalidSpotColor {
+                        message: format!("{name:?} must be approximated by a process color"),
+                    })?;
+                if name.trim().is_empty() {
+                    return Err(Error::InvalidSpotColor {
+                        message: "spot color names must not be empty".to_string(),
+                    });
+                }
+                Ok((name, alternate))
+            })
+            .collect::<Result<_, _>>()
+            .map(SpotColors)
+    }
+
+    /// The spot colours `ops` select, which must all be defined here.
+    pub(crate) fn used_in(&self, ops: &[Op]) -> Result<Vec<Separation>, Error> {
+        let mut used: BTreeMap<&str, Separation> = BTreeMap::new();
+        for op in ops {
+            let (Op::SetColorSpaceFill { id } | Op::SetColorSpaceStroke { id }) = op else {
+                continue;
+            };
+            let Some(name) = id.strip_prefix(RESOURCE_PREFIX) else {
+                continue;
+            };
+            if used.contains_key(name) {
+                continue;
+            }
+            let alternate = self.0.get(name).ok_or_else(|| Error::InvalidSpotColor {
+                message: format!("{name:?} is not defined in basePdf.spotColors"),
+            })?;
+            used.insert(
+                name,
+                Separation {
+                    name: name.to_string(),
+                    alternate: *alternate,
+                },
+            );
+        }
+        Ok(used.into_values().collect())
+    }
+}
+
+/// A spot colour selected on a page, ready to be added to its resources.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Separation {
+    name: String,
+    alternate: [f32; 4],
+}
+
+impl Separation {
+    // [/Separation /名前 /DeviceCMYK 濃度0〜1をCMYKへ線形に写す関数]
+    fn color_space(&self) -> Object {
+        let real =
+            |values: [f32; 4]| -> Vec<Object> { values.into_iter().map(Object::Real).collect() };
+        Object::Array(vec![
+            Object::Name(b"Separation".to_vec()),
+            Object::Name(self.name.as_bytes().to_vec()),
+            Object::Name(b"DeviceCMYK".to_vec()),
+            Object::Dictionary(dictionary! {
+                "FunctionType" => 2,
+                "Domain" => vec![Object::Real(0.0), Object::Real(1.0)],
+                "C0" => real([0.0; 4]),
+                "C1" => real(self.alternate),
+                "N" => 1,
+            }),
+        ])
+    }
+}
+
+/// The distinct spot colours selected across several pages.
+pub(crate) fn merge(pages: Vec<Vec<Separation>>) -> Vec<Separation> {
+    let mut merged: BTreeMap<String, Separation> = BTreeMap::new();
+    for separation in pages.into_iter().flatten() {
+        merged.entry(separation.name.clone()).or_insert(separation);
+    }
+    merged.into_values().collect()
+}
+
+/// Adds the Separation colour spaces each page selects to its resources.
+pub(crate) fn apply_separations(pdf: Vec<u8>, pages: &[Vec<Separation>]) -> Result<Vec<u8>, Error> {
+    if pages.iter().all(Vec::is_empty) {
+        return Ok(pdf);
+    }
+
+    fn pdf_error(e: impl std::fmt::Display) -> Error {
+        Error::Whatever {
+            message: format!("Failed to add spot colors: {e}"),
+            source: None,
+        }
+    }
+
+    let mut document = Document::load_mem(&pdf).map_err(pdf_error)?;
+    let page_ids: Vec<_> = document.get_pages().into_values().collect();
+    for (page_id, separations) in page_ids.into_iter().zip(pages) {
+        if separations.is_empty() {
+            continue;
+        }
+        let resources = match document
+            .get_dictionary(page_id)
+            .and_then(|page| page.get(b"Resources"))
+            .map_err(pdf_error)?
+        {
+            Object::Reference(id) => *id,
+            _ => {
+                return Err(pdf_error("page resources are not an indirect object"));
+            }
+        };
+        let color_spaces = separations
+            .iter()
+            .map(|separation| (resource_name(&separation.name), separation.color_space()))
+            .collect::<lopdf::Dictionary>();
+        document
+            .get_object_mut(resources)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("ColorSpace", color_spaces);
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_colors_parse_from_percentages_or_fractions() {
+        assert_eq!(
+            PdfColor::parse("cmyk(0%, 100%, 50%, 0%)").unwrap(),
+            PdfColor::Cmyk {
+                c: 0.0,
+                m: 1.0,
+                y: 0.5,
+                k: 0.0
+            }
+        );
+        assert_eq!(
+            PdfColor::parse("CMYK(0, 0, 0, 1)").unwrap(),
+            PdfColor::Cmyk {
+                c: 0.0,
+                m: 0.0,
+                y: 0.0,
+                k: 1.0
+            }
+        );
+        assert_eq!(PdfColor::parse("gray(25%)").unwrap(), PdfColor::Gray(0.25));
+        assert_eq!(
+            PdfColor::parse("#ff0000").unwrap(),
+            PdfColor::Rgb {
+                r: 1.0,
+                g: 0.0,
+                b: 0.0
+            }
+        );
+        assert_eq!(
+            PdfColor::parse("rgb(0, 0, 255)").unwrap(),
+            PdfColor::Rgb {
+                r: 0.0,
+                g: 0.0,
+                b: 1.0
+            }
+        );
+    }
+
+    #[test]
+    fn spot_colors_take_an_optional_tint() {
+        assert_eq!(
+            PdfColor::parse("spot(PANTONE 185 C)").unwrap(),
+            PdfColor::Spot {
+                name: "PANTONE 185 C".to_string(),
+                tint: 1.0
+            }
+        );
+        assert_eq!(
+            PdfColor::parse("spot(\"Gold, metallic\", 40%)").unwrap(),
+            PdfColor::Spot {
+                name: "Gold, metallic".to_string(),
+                tint: 0.4
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_colors_are_rejected() {
+        for value in [
+            "cmyk(0, 91, 76, 0)",
+            "cmyk(0%, 0%, 0%)",
+            "gray(120%)",
+            "spot()",
+            "spot(Gold, lots)",
+            "not-a-color",
+        ] {
+            assert!(
+                PdfColor::parse(value).is_err(),
+                "{value} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn each_color_space_uses_its_own_operators() {
+        let fill = |value: &str| PdfColor::parse(value).unwrap().fill_ops();
+        assert!(matches!(
+            fill("cmyk(0, 0, 0, 1)")[..],
+            [Op::SetFillColor {
+                col: Color::Cmyk(_)
+            }]
+        ));
+        assert!(matches!(
+            fill("gray(0)")[..],
+            [Op::SetFillColor {
+                col: Color::Greyscale(_)
+            }]
+        ));
+        assert!(matches!(
+            &PdfColor::parse("spot(Gold, 50%)").unwrap().stroke_ops()[..],
+            [Op::SetColorSpaceStroke { id }, Op::Unknown { key, .. }] if id == "CS_Gold" && key == "SCN"
+        ));
+    }
+
+    #[test]
+    fn only_defined_spot_colors_may_be_used() {
+        let spots = SpotColors::from_json(BTreeMap::from([(
+            "Gold".to_string(),
+            "cmyk(0%, 20%, 60%, 20%)".to_string(),
+        )]))
+        .unwrap();
+
+        let gold = PdfColor::parse("spot(Gold)").unwrap().fill_ops();
+        let used = spots.used_in(&[gold.clone(), gold].concat()).unwrap();
+        assert_eq!(used.len(), 1);
+
+        let silver = PdfColor::parse("spot(Silver)").unwrap().fill_ops();
+        assert!(spots.used_in(&silver).is_err());
+    }
+
+    #[test]
+    fn a_spot_color_cannot_be_approximated_by_another_spot_color() {
+        let json = BTreeMap::from([("Gold".to_string(), "spot(Silver)".to_string())]);
+        assert!(SpotColors::from_json(json).is_err());
+    }
+}
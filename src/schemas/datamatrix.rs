@@ -0,0 +1,550 @@
+//! Data Matrix ECC 200 encoder.
+//!
+//! Content is encoded in ASCII encodation (digit pairs packed into one
+//! codeword) into the smallest square symbol that holds it. Text outside
+//! Latin-1 is written as UTF-8 behind an ECI 26 designator. GS1 element
+//! strings start with FNC1, which also ends each variable-length field.
+
+use super::gs1::GROUP_SEPARATOR;
+use super::symbology::{
+    encoding_error, GaloisField, SymbolEncoder, SymbolMatrix, SymbolOptions, Symbology,
+};
+use super::Error;
+
+const PAD: u8 = 129;
+const FNC1: u8 = 232;
+const UPPER_SHIFT: u8 = 235;
+const ECI: u8 = 241;
+/// ECI 000026 (UTF-8), written as the value plus one.
+const ECI_UTF8: u8 = 27;
+
+struct SymbolSize {
+    /// Side of the whole symbol in modules.
+    size: usize,
+    /// Side of one data region, without its finder and clock patterns.
+    region: usize,
+    data_codewords: usize,
+    ecc_codewords: usize,
+    blocks: usize,
+}
+
+const fn symbol(
+    size: usize,
+    region: usize,
+    data_codewords: usize,
+    ecc_codewords: usize,
+    blocks: usize,
+) -> SymbolSize {
+    SymbolSize {
+        size,
+        region,
+        data_codewords,
+        ecc_codewords,
+        blocks,
+    }
+}
+
+// 正方形シンボルの一覧（ISO/IEC 16022 表7）
+const SYMBOL_SIZES: [SymbolSize; 24] = [
+    symbol(10, 8, 3, 5, 1),
+    symbol(12, 10, 5, 7, 1),
+    symbol(14, 12, 8, 10, 1),
+    symbol(16, 14, 12, 12, 1),
+    symbol(18, 16, 18, 14, 1),
+    symbol(20, 18, 22, 18, 1),
+    symbol(22, 20, 30, 20, 1),
+    symbol(24, 22, 36, 24, 1),
+    symbol(26, 24, 44, 28, 1),
+    symbol(32, 14, 62, 36, 1),
+    symbol(36, 16, 86, 42, 1),
+    symbol(40, 18, 114, 48, 1),
+    symbol(44, 20, 144, 56, 1),
+    symbol(48, 22, 174, 68, 1),
+    symbol(52, 24, 204, 84, 2),
+    symbol(64, 14, 280, 112, 2),
+    symbol(72, 16, 368, 144, 4),
+    symbol(80, 18, 456, 192, 4),
+    symbol(88, 20, 576, 224, 4),
+    symbol(96, 22, 696, 272, 4),
+    symbol(104, 24, 816, 336, 6),
+    symbol(120, 18, 1050, 408, 6),
+    symbol(132, 20, 1304, 496, 8),
+    symbol(144, 22, 1558, 620, 10),
+];
+
+pub(crate) struct DataMatrixEncoder;
+
+impl SymbolEncoder for DataMatrixEncoder {
+    fn encode(&self, content: &str, options: &SymbolOptions) -> Result<SymbolMatrix, Error> {
+        options.reject(Symbology::DataMatrix)?;
+        let mut codewords = if options.gs1 {
+            encode_gs1(content)
+        } else {
+            encode_ascii(content)
+        };
+        let symbol = SYMBOL_SIZES
+            .iter()
+            .find(|symbol| symbol.data_codewords >= codewords.len())
+            .ok_or_else(|| {
+                encoding_error(
+                    Symbology::DataMatrix,
+                    format!(
+                        "the content needs {} codewords, more than the 144 × 144 symbol holds",
+                        codewords.len()
+                    ),
+                )
+            })?;
+        pad(&mut codewords, symbol.data_codewords);
+        let codewords = add_check_words(&codewords, symbol);
+
+        let regions = symbol.size / (symbol.region + 2) * symbol.region;
+        let mapping = place(&codewords, regions);
+        Ok(draw(&mapping, regions, symbol))
+    }
+}
+
+/// Codewords for `content` in ASCII encodation.
+fn encode_ascii(content: &str) -> Vec<u8> {
+    let latin1: Option<Vec<u8>> = content
+        .chars()
+        .map(|c| u8::try_from(u32::from(c)).ok())
+        .collect();
+    let (mut codewords, bytes) = match latin1 {
+        Some(bytes) => (Vec::new(), bytes),
+        None => (vec![ECI, ECI_UTF8], content.as_bytes().to_vec()),
+    };
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        match bytes.get(index + 1) {
+            // 数字2桁は1コードワードにまとめる
+            Some(next) if byte.is_ascii_digit() && next.is_ascii_digit() => {
+                codewords.push(130 + (byte - b'0') * 10 + (next - b'0'));
+                index += 2;
+                continue;
+            }
+            _ => {}
+        }
+        if byte < 128 {
+            codewords.push(byte + 1);
+        } else {
+            codewords.extend([UPPER_SHIFT, byte - 127]);
+        }
+        index += 1;
+    }
+    codewords
+}
+
+/// Codewords for a GS1 element string: FNC1 first and in place of every
+/// group separator, the fields between in ASCII encodation.
+fn encode_gs1(content: &str) -> Vec<u8> {
+    let mut codewords = Vec::new();
+    for field in content.split(GROUP_SEPARATOR) {
+        codewords.push(FNC1);
+        codewords.extend(encode_ascii(field));
+    }
+    codewords
+}
+
+/// Fills the data capacity with the pad codeword, scrambled after the first.
+fn pad(codewords: &mut Vec<u8>, capacity: usize) {
+    if codewords.len() < capacity {
+        codewords.push(PAD);
+    }
+    while codewords.len() < capacity {
+        let position = codewords.len() + 1;
+        let value = usize::from(PAD) + (149 * position) % 253 + 1;
+        codewords.push(if value > 254 { value - 254 } else { value } as u8);
+    }
+}
+
+/// Appends the Reed–Solomon check codewords, interleaving the blocks of the
+/// larger symbols.
+fn add_check_words(data: &[u8], symbol: &SymbolSize) -> Vec<u8> {
+    let field = GaloisField::new(0x12d, 256);
+    let ecc_per_block = symbol.ecc_codewords / symbol.blocks;
+    let mut codewords = data.to_vec();
+    codewords.resize(data.len() + symbol.ecc_codewords, 0);
+    for block in 0..symbol.blocks {
+        let block_data: Vec<u16> = data
+            .iter()
+            .skip(block)
+            .step_by(symbol.blocks)
+            .map(|&codeword| u16::from(codeword))
+            .collect();
+        for (index, check) in field
+            .check_words(&block_data, ecc_per_block)
+            .into_iter()
+            .enumerate()
+        {
+            codewords[data.len() + block + index * symbol.blocks] = check as u8;
+        }
+    }
+    codewords
+}
+
+/// Places the codewords in a `side` × `side` mapping matrix of the data
+/// regions joined together (ISO/IEC 16022 Annex F).
+fn place(codewords: &[u8], side: usize) -> Vec<Option<bool>> {
+    let mut placement = Placement {
+        rows: side as isize,
+        cols: side as isize,
+        bits: vec![None; side * side],
+        codewords,
+    };
+    let (rows, cols) = (placement.rows, placement.cols);
+    let mut position = 0;
+    let (mut row, mut col) = (4isize, 0isize);
+    loop {
+        if row == rows && col == 0 {
+            placement.corner(position, 1);
+            position += 1;
+        }
+        if row == rows - 2 && col == 0 && cols % 4 != 0 {
+            placement.corner(position, 2);
+            position += 1;
+        }
+        if row == rows - 2 && col == 0 && cols % 8 == 4 {
+            placement.corner(position, 3);
+            position += 1;
+        }
+        if row == rows + 4 && col == 2 && cols % 8 == 0 {
+            placement.corner(position, 4);
+            position += 1;
+        }
+        // 右上へ斜めに走査
+        loop {
+            if row < rows && col >= 0 && placement.is_free(row, col) {
+                placement.utah(row, col, position);
+                position += 1;
+            }
+            row -= 2;
+            col += 2;
+            if row < 0 || col >= cols {
+                break;
+            }
+        }
+        row += 1;
+        col += 3;
+        // 左下へ斜めに走査
+        loop {
+            if row >= 0 && col < cols && placement.is_free(row, col) {
+                placement.utah(row, col, position);
+                position += 1;
+            }
+            row += 2;
+            col -= 2;
+            if row >= rows || col < 0 {
+                break;
+            }
+        }
+        row += 3;
+        col += 1;
+        if row >= rows && col >= cols {
+            break;
+        }
+    }
+    // 右下隅が残った場合は固定パターンで埋める
+    if placement.is_free(rows - 1, cols - 1) {
+        placement.set(rows - 1, cols - 1, true);
+        placement.set(rows - 2, cols - 2, true);
+    }
+    placement.bits
+}
+
+struct Placement<'a> {
+    rows: isize,
+    cols: isize,
+    bits: Vec<Option<bool>>,
+    codewords: &'a [u8],
+}
+
+impl Placement<'_> {
+    fn index(&self, row: isize, col: isize) -> usize {
+        (row * self.cols + col) as usize
+    }
+
+    fn is_free(&self, row: isize, col: isize) -> bool {
+        self.bits[self.index(row, col)].is_none()
+    }
+
+    fn set(&mut self, row: isize, col: isize, dark: bool) {
+        let index = self.index(row, col);
+        self.bits[index] = Some(dark);
+    }
+
+    /// Places bit `bit` (1 is the most significant) of codeword `position`,
+    /// wrapping positions outside the matrix.
+    fn module(&mut self, mut row: isize, mut col: isize, position: usize, bit: u32) {
+        if row < 0 {
+            row += self.rows;
+            col += 4 - (self.rows + 4) % 8;
+        }
+        if col < 0 {
+            col += self.cols;
+            row += 4 - (self.cols + 4) % 8;
+        }
+        let codeword = self.codewords.get(position).copied().unwrap_or(0);
+        self.set(row, col, codeword >> (8 - bit) & 1 == 1);
+    }
+
+    /// The standard L-shaped placement of one codeword ending at (`row`, `col`).
+    fn utah(&mut self, row: isize, col: isize, position: usize) {
+        for (bit, (dr, dc)) in [
+            (-2, -2),
+            (-2, -1),
+            (-1, -2),
+            (-1, -1),
+            (-1, 0),
+            (0, -2),
+            (0, -1),
+            (0, 0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            self.module(row + dr, col + dc, position, bit as u32 + 1);
+        }
+    }
+
+    /// The four special shapes that wrap around the corners.
+    fn corner(&mut self, position: usize, shape: u8) {
+        let (r, c) = (self.rows, self.cols);
+        let modules: [(isize, isize); 8] = match shape {
+            1 => [
+                (r - 1, 0),
+                (r - 1, 1),
+                (r - 1, 2),
+                (0, c - 2),
+                (0, c - 1),
+                (1, c - 1),
+                (2, c - 1),
+                (3, c - 1),
+            ],
+            2 => [
+                (r - 3, 0),
+                (r - 2, 0),
+                (r - 1, 0),
+                (0, c - 4),
+                (0, c - 3),
+                (0, c - 2),
+                (0, c - 1),
+                (1, c - 1),
+            ],
+            3 => [
+                (r - 3, 0),
+                (r - 2, 0),
+                (r - 1, 0),
+                (0, c - 2),
+                (0, c - 1),
+                (1, c - 1),
+                (2, c - 1),
+                (3, c - 1),
+            ],
+            _ => [
+                (r - 1, 0),
+                (r - 1, c - 1),
+                (0, c - 3),
+                (0, c - 2),
+                (0, c - 1),
+                (1, c - 3),
+                (1, c - 2),
+                (1, c - 1),
+            ],
+        };
+        for (bit, (row, col)) in modules.into_iter().enumerate() {
+            self.module(row, col, position, bit as u32 + 1);
+        }
+    }
+}
+
+/// Splits the mapping matrix into data regions and surrounds each with its
+/// solid finder pattern (left and bottom) and clock track (top and right).
+fn draw(mapping: &[Option<bool>], side: usize, symbol: &SymbolSize) -> SymbolMatrix {
+    let region = symbol.region;
+    let mut matrix = SymbolMatrix::new(symbol.size, 1);
+    let to_symbol = |index: usize| index / region * (region + 2) + 1 + index % region;
+    for y in 0..symbol.size {
+        for x in 0..symbol.size {
+            let (region_x, region_y) = (x % (region + 2), y % (region + 2));
+            let dark = if region_x == 0 || region_y == region + 1 {
+                true
+            } else if region_y == 0 {
+                x % 2 == 0
+            } else if region_x == region + 1 {
+                y % 2 == 1
+            } else {
+                continue;
+            };
+            matrix.set(x, y, dark);
+        }
+    }
+    for row in 0..side {
+        for col in 0..side {
+            let dark = mapping[row * side + col].unwrap_or(false);
+            matrix.set(to_symbol(col), to_symbol(row), dark);
+        }
+    }
+    matrix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(matrix: &SymbolMatrix) -> Vec<String> {
+        (0..matrix.width)
+            .map(|y| {
+                (0..matrix.width)
+                    .map(|x| if matrix.get(x, y) { '#' } else { '.' })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ascii_encodation_packs_digit_pairs() {
+        assert_eq!(encode_ascii("123456"), vec![142, 164, 186]);
+        assert_eq!(encode_ascii("A1b"), vec![66, 50, 99]);
+        assert_eq!(encode_ascii("é"), vec![UPPER_SHIFT, 0xe9 - 127]);
+        assert_eq!(
+            encode_ascii("日"),
+            vec![ECI, ECI_UTF8, 235, 103, 235, 24, 235, 38]
+        );
+    }
+
+    #[test]
+    fn gs1_fields_are_led_by_fnc1() {
+        assert_eq!(
+            encode_gs1("0112\u{1d}10A"),
+            vec![FNC1, 131, 142, FNC1, 140, 66]
+        );
+    }
+
+    #[test]
+    fn pad_codewords_are_scrambled_after_the_first() {
+        let mut codewords = vec![66];
+        pad(&mut codewords, 4);
+        // 129, then 129 + (149 × 3 mod 253) + 1 = 324 − 254 = 70, and so on.
+        assert_eq!(codewords, vec![66, 129, 70, 220]);
+    }
+
+    #[test]
+    fn every_module_of_the_mapping_matrix_is_used() {
+        for symbol in &SYMBOL_SIZES {
+            let side = symbol.size / (symbol.region + 2) * symbol.region;
+            let codewords = vec![0xff; symbol.data_codewords + symbol.ecc_codewords];
+            let mapping = place(&codewords, side);
+            // Eight modules per codeword; an unused 2 × 2 corner gets two
+            // dark modules and leaves the other two light.
+            let dark = mapping.iter().filter(|bit| **bit == Some(true)).count();
+            let unset = mapping.iter().filter(|bit| bit.is_none()).count();
+            assert!(
+                (dark, unset) == (codewords.len() * 8, 0)
+                    || (dark, unset) == (codewords.len() * 8 + 2, 2),
+                "{}",
+                symbol.size
+            );
+        }
+    }
+
+    #[test]
+    fn a_ten_by_ten_symbol_has_its_finder_and_clock_patterns() {
+        let matrix = DataMatrixEncoder
+            .encode("123456", &SymbolOptions::default())
+            .unwrap();
+        let rows = render(&matrix);
+        assert_eq!(matrix.width, 10);
+        assert_eq!(rows[0], "#.#.#.#.#.");
+        assert_eq!(rows[9], "##########");
+        assert!(rows.iter().all(|row| row.starts_with('#')));
+        let right: String = rows.iter().map(|row| &row[9..]).collect();
+        assert_eq!(right, ".#.#.#.#.#");
+    }
+
+    #[test]
+    fn symbols_match_the_reference_encoder() {
+        // "123456" in a 10 × 10 symbol, as drawn by ZXing's Data Matrix writer.
+        let matrix = DataMatrixEncoder
+            .encode("123456", &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(
+            render(&matrix),
+            [
+                "#.#.#.#.#.",
+                "##..#.##.#",
+                "##.....#..",
+                "##...###.#",
+                "##....#...",
+                "#.....####",
+                "###.##....",
+                "####.##..#",
+                "#..###.#..",
+                "##########",
+            ]
+        );
+        // 100 digits in a 32 × 32 symbol of four data regions.
+        let matrix = DataMatrixEncoder
+            .encode(&"0123456789".repeat(10), &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(
+            render(&matrix),
+            [
+                "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+                "##.###...#.#.#.#####.###....#..#",
+                "#.#####.##.##...###.#####.#.....",
+                "#..#.###.###...###...#.###...###",
+                "#.#..##.###..##.#...#..###.####.",
+                "##...#...#.######.##......#..#.#",
+                "#..##...#...#.#.###..##.#..####.",
+                "#####.###.###.####.##.##..#.##.#",
+                "#.#.#######...#.#...####..##....",
+                "###.##...#.#.####.####.....#..##",
+                "#...###.##.####.####..####.#.##.",
+                "##.#.###.###...##.##.###.#...#.#",
+                "###..##.#####.#.#..###..#.#.#...",
+                "##...#...#.###.#######..#....#.#",
+                "#..##...#..####.###.#..####.....",
+                "################################",
+                "#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.#.",
+                "#####.##.......##..###########.#",
+                "#.#.###..###....##.###..###.##..",
+                "###.##.###..#.####.##.#....#.###",
+                "#...#...#....#..###..###.##.###.",
+                "##.##.###..###.##..#..##.#.....#",
+                "#######.#####.#.##.##..#.###.#..",
+                "##...##.##...######.#.#.#.###..#",
+                "###.#...##.#.#..#..###.##.#.#...",
+                "####.#.##...########.#.##...##.#",
+                "####.###..#.###.#..###.###..##..",
+                "##.####.##.#.#.##.#..##...#..###",
+                "#.###.#....####.#####..#......#.",
+                "#..#.#.#.###.#######..#..#.....#",
+                "##...#.#.###..#.#...####.#.###..",
+                "################################",
+            ]
+        );
+    }
+
+    #[test]
+    fn larger_content_picks_a_larger_symbol() {
+        assert_eq!(
+            DataMatrixEncoder
+                .encode("ABCDEFGHIJ", &SymbolOptions::default())
+                .unwrap()
+                .width,
+            16
+        );
+        assert_eq!(
+            DataMatrixEncoder
+                .encode(&"A".repeat(100), &SymbolOptions::default())
+                .unwrap()
+                .width,
+            40
+        );
+        assert!(DataMatrixEncoder
+            .encode(&"A".repeat(1600), &SymbolOptions::default())
+            .is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
-use crate::font::{FontMap, FontSpec, FontSpecTrait, LineBreakMode};
+use crate::font::{
+    FontChain, FontMap, FontName, FontSelector, FontSpecTrait, FontStyle, FontWeight, LineBreakMode,
+};
 use crate::schemas::base::BaseSchema;
 use crate::schemas::{Error, FontSnafu, HasBaseSchema, JsonPosition};
 use crate::utils::OpBuffer;
@@ -8,6 +10,8 @@ use printpdf::*;
 use serde::Deserialize;
 use snafu::prelude::*;
 
+use super::color::PdfColor;
+use super::vertical::{VerticalFontSpec, WritingMode};
 use super::BasePdf;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -15,14 +19,21 @@ use super::BasePdf;
 pub struct JsonDynamicTextSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     content: String,
-    font_name: String,
+    font_name: Option<FontName>,
+    font_family: Option<String>,
+    font_weight: Option<FontWeight>,
+    font_style: Option<FontStyle>,
     character_spacing: Option<f32>,
     line_height: Option<f32>,
     font_size: Option<f32>,
     line_break_mode: Option<LineBreakMode>,
+    writing_mode: Option<WritingMode>,
+    tate_chu_yoko: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -32,10 +43,11 @@ pub struct DynamicText {
     character_spacing: Pt,
     line_height: Option<f32>,
     font_size: Pt,
-    font_id: FontId,
     font_spec: Arc<dyn FontSpecTrait>,
-    font: Arc<ParsedFont>,
+    fonts: FontChain,
     line_break_mode: Option<LineBreakMode>,
+    /// Column layout, present when the text is written vertically.
+    vertical: Option<VerticalFontSpec>,
 }
 
 impl DynamicText {
@@ -44,15 +56,14 @@ impl DynamicText {
         y: Mm,
         width: Mm,
         height: Mm,
-        font_name: String,
+        font_name: impl Into<FontName>,
         font_size: Pt,
         content: String,
         font_map: &FontMap,
     ) -> Result<Self, Error> {
-        let (font_id, font) = font_map
-            .find(&font_name)
+        let fonts = font_map
+            .find_chain(&font_name.into())
             .whatever_context("Font specified in the schema is not loaded")?;
-        let font_spec = FontSpec::new(font.clone());
         let base = BaseSchema::new(String::from("cell"), x, y, width, height);
 
         Ok(Self {
@@ -61,18 +72,24 @@ impl DynamicText {
             character_spacing: Pt(0.0),
             line_height: None,
             font_size,
-            font_id: font_id.clone(),
-            font_spec: Arc::new(font_spec.clone()),
-            font: font.clone(),
+            font_spec: Arc::new(fonts.spec()),
+            fonts,
             line_break_mode: None,
+            vertical: None,
         })
     }
 
     pub fn from_json(json: JsonDynamicTextSchema, font_map: &FontMap) -> Result<Self, Error> {
-        let (font_id, font) = font_map
-            .find(&json.font_name)
+        let selector = FontSelector {
+            name: json.font_name,
+            family: json.font_family,
+            weight: json.font_weight.unwrap_or_default(),
+            style: json.font_style.unwrap_or_default(),
+        };
+        ensure_whatever!(!selector.is_empty(), "fontName or fontFamily is required");
+        let fonts = font_map
+            .select(&selector)
             .whatever_context("Font specified in the schema is not loaded")?;
-        let font_spec = FontSpec::new(font.clone());
 
         let base = BaseSchema::new(
             json.name,
@@ -89,18 +106,28 @@ impl DynamicText {
             None => Pt(12.0),
         };
         let line_break_mode = json.line_break_mode;
+        let vertical = (json.writing_mode == Some(WritingMode::VerticalRl)).then(|| {
+            VerticalFontSpec::new(fonts.clone(), json.tate_chu_yoko.unwrap_or(0))
+                .with_line_break_mode(line_break_mode.unwrap_or_default())
+        });
+        let font_spec: Arc<dyn FontSpecTrait> = match &vertical {
+            Some(vertical) => Arc::new(vertical.clone()),
+            None => Arc::new(
+                fonts
+                    .spec()
+                    .with_line_break_mode(line_break_mode.unwrap_or_default()),
+            ),
+        };
         let text = Self {
             base,
             content: json.content,
             character_spacing,
             line_height,
             font_size,
-            font_id: font_id.clone(),
-            font_spec: Arc::new(
-                font_spec.with_line_break_mode(line_break_mode.unwrap_or_default()),
-            ),
-            font: font.clone(),
+            font_spec,
+            fonts,
             line_break_mode,
+            vertical,
         };
 
         Ok(text)
@@ -117,14 +144,15 @@ impl DynamicText {
         current_top_mm: Option<Mm>,
         buffer: &mut OpBuffer,
     ) -> Result<(usize, Option<Mm>), Error> {
-        let top_margin_in_mm = base_pdf.padding.top;
-        let bottom_margin_in_mm = base_pdf.padding.bottom;
+        if let Some(vertical) = &self.vertical {
+            return self.render_vertical(vertical, base_pdf, current_page, current_top_mm, buffer);
+        }
         let page_height_in_mm = base_pdf.height;
 
         let mut page_counter = 0;
         let y_top_mm: Mm = current_top_mm.unwrap_or(self.base.y);
         let mut y_line_mm: Mm = y_top_mm;
-        let y_bottom_mm = base_pdf.height - bottom_margin_in_mm;
+        let mut y_bottom_mm = base_pdf.flow_bottom(current_page);
         let line_height_in_mm: Mm = Pt(self.line_height.unwrap_or(1.0) * self.font_size.0).into();
         let character_spacing = self.character_spacing;
 
@@ -149,7 +177,8 @@ impl DynamicText {
 
             if y_line_mm > y_bottom_mm {
                 page_counter += 1;
-                y_line_mm = top_margin_in_mm;
+                y_line_mm = base_pdf.flow_top(current_page + page_counter);
+                y_bottom_mm = base_pdf.flow_bottom(current_page + page_counter);
             }
 
             if let Some(page) = pages.get_mut(page_counter) {
@@ -167,13 +196,15 @@ impl DynamicText {
 
         for (page_index, page) in pages.into_iter().enumerate() {
             let mut ops: Vec<Op> = Vec::new();
+            // 見開きの余白に合わせてページごとに左右へずらす
+            let x = self.base.x + base_pdf.horizontal_shift(current_page + page_index);
 
             for (index, line) in page.into_iter().enumerate() {
                 let y_position =
                     page_height_in_mm - y_start - line_height_in_mm * (index + 1) as f32;
 
                 let matrix = super::pdf_utils::calculate_transform_matrix_with_center_pivot(
-                    self.base.x,
+                    x,
                     self.base.y,
                     self.base.width,
                     self.base.height,
@@ -182,30 +213,103 @@ impl DynamicText {
 
                 let line_ops = super::pdf_utils::create_text_ops_with_font(
                     matrix,
-                    &self.font_id,
+                    self.fonts.id(),
                     self.font_size,
-                    self.base.x,
+                    x,
                     y_position,
                     None,
                     None,
                     character_spacing,
                     &line,
                     self.line_height,
-                    &csscolorparser::parse("#000000").map_err(|e| Error::ColorParsing {
-                        source: e,
-                        message: "Failed to parse default black color".to_string(),
-                    })?, // デフォルトの黒色
-                    Some(&self.font),
+                    &PdfColor::black(), // デフォルトの黒色
+                    Some(&self.fonts),
                 );
 
                 ops.extend_from_slice(&line_ops);
             }
             buffer.insert(current_page + page_index, ops);
-            y_start = top_margin_in_mm;
+            y_start = base_pdf.flow_top(current_page + page_index + 1);
         }
         Ok((current_page + pages_increased, Some(next_y_line_mm)))
     }
 
+    // 縦書き：列を右から左へ並べ、ボックスの幅を使い切ったら次のページへ送る
+    fn render_vertical(
+        &self,
+        vertical: &VerticalFontSpec,
+        base_pdf: &BasePdf,
+        current_page: usize,
+        current_top_mm: Option<Mm>,
+        buffer: &mut OpBuffer,
+    ) -> Result<(usize, Option<Mm>), Error> {
+        let mut y_start: Mm = current_top_mm.unwrap_or(self.base.y);
+
+        let columns: Vec<String> = self
+            .font_spec
+            .split_text_to_size(
+                &self.content,
+                self.font_size,
+                self.base.height.into(),
+                self.character_spacing,
+            )
+            .context(FontSnafu)?;
+
+        if columns.is_empty() {
+            return Ok((current_page, Some(y_start)));
+        }
+
+        // ボックスが下端（フッターや下余白）に収まらなければ次のページの先頭から始める
+        let mut current_page = current_page;
+        if y_start + self.base.height > base_pdf.flow_bottom(current_page)
+            && y_start > base_pdf.flow_top(current_page)
+        {
+            current_page += 1;
+            y_start = base_pdf.flow_top(current_page);
+        }
+
+        let column_width: Mm = Pt(self.line_height.unwrap_or(1.0) * self.font_size.0).into();
+        let columns_per_page = ((self.base.width.0 / column_width.0).floor() as usize).max(1);
+        let pages: Vec<&[String]> = columns.chunks(columns_per_page).collect();
+
+        for (page_index, page) in pages.iter().enumerate() {
+            let page_number = current_page + page_index;
+            if page_index > 0 {
+                y_start = base_pdf.flow_top(page_number);
+            }
+            let x = self.base.x + base_pdf.horizontal_shift(page_number);
+            let matrix = super::pdf_utils::calculate_transform_matrix_with_center_pivot(
+                x,
+                base_pdf.height - y_start - self.base.height,
+                self.base.width,
+                self.base.height,
+                None,
+            );
+
+            let mut ops: Vec<Op> = Vec::new();
+            for (index, column) in page.iter().enumerate() {
+                let column_ops = vertical
+                    .column_ops(
+                        matrix,
+                        self.font_size,
+                        self.base.width - column_width * (index as f32 + 0.5),
+                        self.base.height,
+                        self.character_spacing,
+                        column,
+                        &PdfColor::black(),
+                    )
+                    .context(FontSnafu)?;
+                ops.extend(column_ops);
+            }
+            buffer.insert(page_number, ops);
+        }
+
+        Ok((
+            current_page + pages.len() - 1,
+            Some(y_start + self.base.height),
+        ))
+    }
+
     pub fn set_y(&mut self, y: Mm) {
         self.base.y = y;
     }
@@ -228,9 +332,15 @@ impl DynamicText {
 
     pub fn set_line_break_mode(&mut self, line_break_mode: Option<LineBreakMode>) {
         self.line_break_mode = line_break_mode;
-        self.font_spec = Arc::new(
-            FontSpec::new(self.font.clone()).with_line_break_mode(self.resolved_line_break_mode()),
-        );
+        let line_break_mode = self.resolved_line_break_mode();
+        self.font_spec = match self.vertical.take() {
+            Some(vertical) => {
+                let vertical = vertical.with_line_break_mode(line_break_mode);
+                self.vertical = Some(vertical.clone());
+                Arc::new(vertical)
+            }
+            None => Arc::new(self.fonts.spec().with_line_break_mode(line_break_mode)),
+        };
     }
 }
 
@@ -335,17 +445,16 @@ mod tests {
         }))
         .unwrap();
         let mut text = DynamicText::from_json(json, &font_map).unwrap();
-        let base_pdf = BasePdf {
-            width: Mm(100.0),
-            height: Mm(100.0),
-            padding: Frame {
+        let base_pdf = BasePdf::new(
+            Mm(100.0),
+            Mm(100.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(0.0),
                 bottom: Mm(0.0),
                 left: Mm(0.0),
             },
-            static_schema: vec![],
-        };
+        );
         let mut buffer = OpBuffer::default();
 
         text.render(&base_pdf, 0, None, &mut buffer).unwrap();
@@ -387,17 +496,16 @@ mod tests {
         }))
         .unwrap();
         let mut text = DynamicText::from_json(json, &font_map).unwrap();
-        let base_pdf = BasePdf {
-            width: Mm(100.0),
-            height: Mm(100.0),
-            padding: Frame {
+        let base_pdf = BasePdf::new(
+            Mm(100.0),
+            Mm(100.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(0.0),
                 bottom: Mm(0.0),
                 left: Mm(0.0),
             },
-            static_schema: vec![],
-        };
+        );
         let mut buffer = OpBuffer::default();
 
         let result = text.render(&base_pdf, 0, None, &mut buffer);
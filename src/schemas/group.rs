@@ -9,7 +9,9 @@ use serde::Deserialize;
 pub struct JsonGroupSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     rotate: Option<f32>,
     schemas: Vec<crate::schemas::JsonSchema>,
@@ -50,6 +52,9 @@ impl Group {
                     Schema::Table(crate::schemas::table::Table::from_json(schema, font_map)?)
                 }
                 JsonSchema::QrCode(schema) => schema.into(),
+                JsonSchema::Barcode(schema) => Schema::Barcode(
+                    crate::schemas::barcode::Barcode::from_json(schema, font_map)?,
+                ),
                 JsonSchema::Image(schema) => schema.try_into()?,
                 JsonSchema::Svg(schema) => schema.try_into()?,
                 JsonSchema::Rectangle(schema) => schema.try_into()?,
@@ -0,0 +1,459 @@
+//! GS1 element strings such as `(01)09501101530003(17)250101(10)ABC123`.
+//!
+//! [`ElementString::parse`] reads the bracketed Application Identifier (AI)
+//! syntax and checks every value against the format of its AI: length,
+//! character set, check digit and date. The parsed string is then written
+//! out as the data carriers need it: AI and value pairs separated by FNC1
+//! where a variable-length value ends, the human-readable interpretation, or
+//! a GS1 Digital Link URI.
+
+use serde::Deserialize;
+
+use super::barcode::gs1_check_digit;
+use super::Error;
+
+/// The group separator that stands for FNC1 in [`ElementString::encoded`].
+pub const GROUP_SEPARATOR: char = '\u{1d}';
+
+/// The domain of GS1 Digital Link URIs when the schema names none.
+pub const DEFAULT_DIGITAL_LINK_DOMAIN: &str = "https://id.gs1.org";
+
+/// AIs that can start a Digital Link path, each with the key qualifiers that
+/// may follow it, in path order.
+const PRIMARY_KEYS: [(&str, &[&str]); 15] = [
+    ("00", &[]),
+    ("01", &["22", "10", "21"]),
+    ("253", &[]),
+    ("255", &[]),
+    ("401", &[]),
+    ("402", &[]),
+    ("414", &["254"]),
+    ("417", &[]),
+    ("8003", &[]),
+    ("8004", &[]),
+    ("8006", &["22", "10", "21"]),
+    ("8010", &["8011"]),
+    ("8013", &[]),
+    ("8017", &[]),
+    ("8018", &[]),
+];
+
+/// How the `qrCode` schema carries a GS1 element string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Gs1Syntax {
+    /// The AI and value pairs with FNC1, for GS1 QR Code and GS1 DataMatrix.
+    ElementString,
+    /// A GS1 Digital Link URI, readable by any phone camera.
+    DigitalLink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub ai: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementString {
+    elements: Vec<Element>,
+}
+
+impl ElementString {
+    /// Parses `(AI)value` pairs. A `(` starts the next AI only when two to
+    /// four digits and `)` follow it, so values may contain brackets.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let mut rest = match ai_at(input) {
+            Some(_) => input,
+            None => {
+                return Err(invalid(format!(
+                    "{input:?} must start with an AI in brackets such as (01)"
+                )))
+            }
+        };
+
+        let mut elements: Vec<Element> = Vec::new();
+        while let Some(ai) = ai_at(rest) {
+            rest = &rest[ai.len() + 2..];
+            let end = rest
+                .char_indices()
+                .find(|&(index, _)| ai_at(&rest[index..]).is_some())
+                .map_or(rest.len(), |(index, _)| index);
+            let value = &rest[..end];
+            rest = &rest[end..];
+
+            let format =
+                ai_format(ai).ok_or_else(|| invalid(format!("({ai}) is not a known AI")))?;
+            validate(ai, format, value)?;
+            if elements.iter().any(|element| element.ai == ai) {
+                return Err(invalid(format!("({ai}) appears more than once")));
+            }
+            elements.push(Element {
+                ai: ai.to_string(),
+                value: value.to_string(),
+            });
+        }
+        Ok(ElementString { elements })
+    }
+
+    pub fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+
+    /// The data to encode: each AI followed by its value, with
+    /// [`GROUP_SEPARATOR`] after a value whose length the AI does not fix.
+    pub fn encoded(&self) -> String {
+        let mut data = String::new();
+        for (index, element) in self.elements.iter().enumerate() {
+            data.push_str(&element.ai);
+            data.push_str(&element.value);
+            if index + 1 < self.elements.len() && !has_predefined_length(&element.ai) {
+                data.push(GROUP_SEPARATOR);
+            }
+        }
+        data
+    }
+
+    /// The human-readable interpretation: every AI in brackets before its
+    /// value, as printed under a GS1 barcode.
+    pub fn human_readable(&self) -> String {
+        self.elements
+            .iter()
+            .map(|element| format!("({}){}", element.ai, element.value))
+            .collect()
+    }
+
+    /// A GS1 Digital Link URI on `domain`: the primary key and its
+    /// qualifiers in the path, every other element as a query parameter.
+    pub fn digital_link(&self, domain: &str) -> Result<String, Error> {
+        if !(domain.starts_with("https://") || domain.starts_with("http://")) {
+            return Err(invalid(format!(
+                "the Digital Link domain {domain:?} must start with https:// or http://"
+            )));
+        }
+        let keys: Vec<&(&str, &[&str])> = PRIMARY_KEYS
+            .iter()
+            .filter(|(key, _)| self.get(key).is_some())
+            .collect();
+        let (key, qualifiers) = match keys.as_slice() {
+            [key] => **key,
+            [] => {
+                return Err(invalid(
+                    "a Digital Link needs a primary key such as (01) or (00)".to_string(),
+                ))
+            }
+            _ => {
+                return Err(invalid(
+                    "a Digital Link takes only one primary key".to_string(),
+                ))
+            }
+        };
+
+        let mut uri = domain.trim_end_matches('/').to_string();
+        let mut path_ais = vec![key];
+        path_ais.extend(qualifiers.iter().filter(|ai| self.get(ai).is_some()));
+        for ai in &path_ais {
+            uri.push_str(&format!("/{ai}/{}", percent_encode(self.get(ai).unwrap())));
+        }
+        let query: Vec<String> = self
+            .elements
+            .iter()
+            .filter(|element| !path_ais.contains(&element.ai.as_str()))
+            .map(|element| format!("{}={}", element.ai, percent_encode(&element.value)))
+            .collect();
+        if !query.is_empty() {
+            uri.push('?');
+            uri.push_str(&query.join("&"));
+        }
+        Ok(uri)
+    }
+
+    fn get(&self, ai: &str) -> Option<&str> {
+        self.elements
+            .iter()
+            .find(|element| element.ai == ai)
+            .map(|element| element.value.as_str())
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidGs1 { message }
+}
+
+/// The AI in brackets at the start of `text`.
+fn ai_at(text: &str) -> Option<&str> {
+    let digits = text.strip_prefix('(')?;
+    let length = digits.bytes().take_while(u8::is_ascii_digit).count();
+    ((2..=4).contains(&length) && digits[length..].starts_with(')')).then(|| &digits[..length])
+}
+
+/// The format of the value of `ai`, written as in the GS1 General
+/// Specifications: `N` digits or `X` characters, a fixed length or `..` a
+/// maximum, `c` for a trailing check digit and `d` for a YYMMDD date.
+/// Parts joined with `+` follow one another; a part ending in `?` may be
+/// left out.
+fn ai_format(ai: &str) -> Option<&'static str> {
+    let number: u32 = ai.parse().ok()?;
+    let format = match (ai.len(), number) {
+        (2, 0) => "N18c",
+        (2, 1 | 2) => "N14c",
+        (2, 10 | 21 | 22) => "X..20",
+        (2, 11..=13 | 15..=17) => "N6d",
+        (2, 20) => "N2",
+        (2, 30 | 37) => "N..8",
+        (2, 90) => "X..30",
+        (2, 91..=99) => "X..90",
+        (3, 235) => "X..28",
+        (3, 240 | 241 | 250 | 251 | 400 | 401 | 403) => "X..30",
+        (3, 242) => "N..6",
+        (3, 243 | 254 | 420) => "X..20",
+        (3, 253) => "N13c+X..17?",
+        (3, 255) => "N13c+N..12?",
+        (3, 402) => "N17c",
+        (3, 410..=417) => "N13c",
+        (3, 421) => "N3+X..9",
+        (3, 422 | 424 | 426) => "N3",
+        (3, 423 | 425) => "N3+N..12",
+        // 4桁目は小数点の位置
+        (4, 3100..=3169 | 3200..=3699) => "N6",
+        (4, 3900..=3909 | 3920..=3929) => "N..15",
+        (4, 3910..=3919 | 3930..=3939) => "N3+N..15",
+        (4, 7003) => "N10",
+        (4, 7006) => "N6d",
+        (4, 8003) => "N14c+X..16?",
+        (4, 8004) => "X..30",
+        (4, 8005) => "N6",
+        (4, 8006) => "N14c+N4",
+        (4, 8007) => "X..34",
+        (4, 8008) => "N8+N..4?",
+        (4, 8010) => "X..30",
+        (4, 8011) => "N..12",
+        (4, 8013 | 8020) => "X..25",
+        (4, 8017 | 8018) => "N18c",
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// AIs whose value length is fixed by their first two digits, so no FNC1
+/// is needed after them.
+fn has_predefined_length(ai: &str) -> bool {
+    matches!(
+        &ai[..2],
+        "00" | "01"
+            | "02"
+            | "03"
+            | "04"
+            | "11"
+            | "12"
+            | "13"
+            | "14"
+            | "15"
+            | "16"
+            | "17"
+            | "18"
+            | "19"
+            | "20"
+            | "31"
+            | "32"
+            | "33"
+            | "34"
+            | "35"
+            | "36"
+            | "41"
+    )
+}
+
+/// One part of an AI format such as `N13c` or `X..20`.
+struct Part {
+    numeric: bool,
+    variable: bool,
+    optional: bool,
+    length: usize,
+    check: Option<char>,
+}
+
+impl Part {
+    fn parse(part: &str) -> Part {
+        let (kind, spec) = part.split_at(1);
+        let (optional, spec) = match spec.strip_suffix('?') {
+            Some(spec) => (true, spec),
+            None => (false, spec),
+        };
+        let (variable, spec) = match spec.strip_prefix("..") {
+            Some(spec) => (true, spec),
+            None => (false, spec),
+        };
+        let digits = spec.bytes().take_while(u8::is_ascii_digit).count();
+        Part {
+            numeric: kind == "N",
+            variable,
+            optional,
+            length: spec[..digits].parse().unwrap(),
+            check: spec[digits..].chars().next(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let unit = if self.numeric { "digits" } else { "characters" };
+        if self.variable {
+            format!("up to {} {unit}", self.length)
+        } else {
+            format!("{} {unit}", self.length)
+        }
+    }
+}
+
+fn validate(ai: &str, format: &str, value: &str) -> Result<(), Error> {
+    // 英数字の値はGS1で使える82文字に限る
+    if let Some(c) = value.chars().find(|&c| !is_cset82(c)) {
+        return Err(invalid(format!(
+            "({ai}) contains {c:?}, which GS1 does not allow"
+        )));
+    }
+    let parts: Vec<Part> = format.split('+').map(Part::parse).collect();
+    let wrong_length = || {
+        let expected: Vec<String> = parts.iter().map(Part::describe).collect();
+        invalid(format!(
+            "({ai}) must be {}, got {value:?}",
+            expected.join(" followed by ")
+        ))
+    };
+
+    let mut rest = value;
+    for part in &parts {
+        let field = if part.variable {
+            if (rest.is_empty() && !part.optional) || rest.len() > part.length {
+                return Err(wrong_length());
+            }
+            rest
+        } else {
+            rest.get(..part.length).ok_or_else(wrong_length)?
+        };
+        rest = &rest[field.len()..];
+
+        if part.numeric && !field.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(wrong_length());
+        }
+        match part.check {
+            Some('c') => {
+                let digits: Vec<u8> = field.bytes().map(|byte| byte - b'0').collect();
+                let (last, body) = digits.split_last().unwrap();
+                let expected = gs1_check_digit(body);
+                if *last != expected {
+                    return Err(invalid(format!(
+                        "({ai}) {value} has check digit {last}, expected {expected}"
+                    )));
+                }
+            }
+            Some('d') if !is_date(field) => {
+                return Err(invalid(format!("({ai}) {value} is not a YYMMDD date")));
+            }
+            _ => {}
+        }
+    }
+    if !rest.is_empty() {
+        return Err(wrong_length());
+    }
+    Ok(())
+}
+
+fn is_cset82(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!\"%&'()*+,-./:;<=>?_".contains(c)
+}
+
+/// YYMMDD, where a day of 00 stands for the last day of the month.
+fn is_date(field: &str) -> bool {
+    let month: u32 = field[2..4].parse().unwrap();
+    let day: u32 = field[4..6].parse().unwrap();
+    // 年が分からないので2月は29日まで認める
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 => 29,
+        _ => return false,
+    };
+    day <= days
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bracketed_element_strings() {
+        let string = ElementString::parse("(01)09501101530003(17)250101(10)AB(1)C").unwrap();
+        let ais: Vec<&str> = string.elements().iter().map(|e| e.ai.as_str()).collect();
+        assert_eq!(ais, ["01", "17", "10"]);
+        // A bracket that does not hold an AI stays in the value.
+        assert_eq!(string.elements()[2].value, "AB(1)C");
+
+        assert!(ElementString::parse("0109501101530003").is_err());
+        assert!(ElementString::parse("(01)09501101530003(01)09501101530003").is_err());
+        assert!(ElementString::parse("(999)1").is_err());
+    }
+
+    #[test]
+    fn values_are_checked_against_their_ai_format() {
+        // Wrong check digit, length, character set and date.
+        for input in [
+            "(01)09501101530004",
+            "(01)0950110153000",
+            "(10)ABCDEFGHIJKLMNOPQRSTU",
+            "(10)AB#C",
+            "(17)251301",
+            "(17)250231",
+            "(3103)12.345",
+            "(00)",
+        ] {
+            let error = ElementString::parse(input).unwrap_err();
+            assert!(
+                matches!(error, Error::InvalidGs1 { .. }),
+                "{input}: {error}"
+            );
+        }
+        // Day 00 means the end of the month; February 29 has no year to check.
+        assert!(ElementString::parse("(17)250200(11)240229").is_ok());
+        assert!(ElementString::parse("(3103)001250(421)392ABC").is_ok());
+    }
+
+    #[test]
+    fn fnc1_follows_only_variable_length_values() {
+        let string = ElementString::parse("(01)09501101530003(10)ABC(17)250101(21)S1").unwrap();
+        assert_eq!(string.encoded(), "010950110153000310ABC\u{1d}1725010121S1");
+        assert_eq!(
+            string.human_readable(),
+            "(01)09501101530003(10)ABC(17)250101(21)S1"
+        );
+    }
+
+    #[test]
+    fn digital_links_put_keys_in_the_path_and_attributes_in_the_query() {
+        let string = ElementString::parse("(01)09501101530003(17)250101(21)S/1(10)ABC").unwrap();
+        assert_eq!(
+            string.digital_link(DEFAULT_DIGITAL_LINK_DOMAIN).unwrap(),
+            "https://id.gs1.org/01/09501101530003/10/ABC/21/S%2F1?17=250101"
+        );
+        assert_eq!(
+            string.digital_link("https://example.com/").unwrap(),
+            "https://example.com/01/09501101530003/10/ABC/21/S%2F1?17=250101"
+        );
+
+        let no_key = ElementString::parse("(10)ABC").unwrap();
+        assert!(no_key.digital_link(DEFAULT_DIGITAL_LINK_DOMAIN).is_err());
+        assert!(string.digital_link("example.com").is_err());
+    }
+}
@@ -6,11 +6,11 @@ use crate::utils::OpBuffer;
 use base64::{engine::general_purpose, Engine as _};
 use image::{DynamicImage, ImageFormat};
 use printpdf::{Mm, Op, PdfDocument, Px, RawImage, XObjectTransform};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use snafu::{whatever, ResultExt};
 use std::io::Cursor;
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum ObjectFit {
     Fill,
@@ -31,7 +31,9 @@ impl Default for ObjectFit {
 pub struct JsonImageSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     content: String,
     #[serde(default)]
@@ -68,7 +70,7 @@ impl TryFrom<JsonImageSchema> for Schema {
     }
 }
 impl Image {
-    fn decode_base64_to_image_buffer(content: &str) -> Result<DynamicImage, Error> {
+    pub(crate) fn decode_base64_to_image_buffer(content: &str) -> Result<DynamicImage, Error> {
         let parts: Vec<&str> = content.split(',').collect();
         if parts.len() != 2 {
             whatever!("Invalid image content");
@@ -121,18 +123,7 @@ impl Image {
         page: usize,
         buffer: &mut OpBuffer,
     ) -> Result<(), Error> {
-        let rgb_image = self.content.to_rgb8();
-
-        let mut buf = Cursor::new(Vec::new());
-        rgb_image
-            .write_to(&mut buf, ImageFormat::Jpeg)
-            .map_err(|_| Error::ImageEncoding {
-                message: "Failed to encode image to JPEG".to_string(),
-            })?;
-
-        let mut warnings = Vec::new();
-        let image = RawImage::decode_from_bytes(&buf.get_ref(), &mut warnings)
-            .whatever_context("Failed to decode image bytes for PDF")?;
+        let image = Self::to_raw_image(&self.content)?;
 
         let image_x_object_id = doc.add_image(&image);
         let transform = self.calculate_object_fit_transform(parent_height, &image);
@@ -151,6 +142,22 @@ impl Image {
         Ok(())
     }
 
+    /// Encodes a decoded image the way every image schema embeds it.
+    pub(crate) fn to_raw_image(content: &DynamicImage) -> Result<RawImage, Error> {
+        let rgb_image = content.to_rgb8();
+
+        let mut buf = Cursor::new(Vec::new());
+        rgb_image
+            .write_to(&mut buf, ImageFormat::Jpeg)
+            .map_err(|_| Error::ImageEncoding {
+                message: "Failed to encode image to JPEG".to_string(),
+            })?;
+
+        let mut warnings = Vec::new();
+        RawImage::decode_from_bytes(buf.get_ref(), &mut warnings)
+            .whatever_context("Failed to decode image bytes for PDF")
+    }
+
     fn calculate_object_fit_transform(
         &self,
         parent_height: Mm,
@@ -0,0 +1,828 @@
+//! Placing laid-out pages onto larger sheets.
+//!
+//! A label template describes a single label. [`LabelSheet`] describes the
+//! grid of labels on the stock it is printed on, and `render_label_sheet`
+//! places one rendered label per cell, filling sheets in reading order.
+//!
+//! [`PageImposition`] works on a finished document instead: every page is
+//! turned into a Form XObject and placed on sheets in booklet or N-up order,
+//! after blank pages have been added for padding and recto chapter starts.
+
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use printpdf::{
+    Color, CurTransMat, Line, LinePoint, Mm, Op, PaintMode, PdfDocument, PdfPage, Point, Polygon,
+    PolygonRing, Pt, Rgb, WindingOrder,
+};
+use std::collections::HashMap;
+
+use super::{
+    color, lay_out_sequence, paper, rotation, save_laid_out_pages, save_pages, viewer,
+    viewer_preferences, Error, LaidOutPages, SchemaPage, Template,
+};
+use crate::font::FontMap;
+
+/// Gap between a cut line and the start of its crop mark.
+const CROP_MARK_OFFSET: Mm = Mm(1.0);
+/// Length of a crop mark.
+const CROP_MARK_LENGTH: Mm = Mm(4.0);
+
+/// A sheet of labels arranged in a `rows` × `columns` grid.
+///
+/// The label size is taken from the label template's `basePdf`. Cells are
+/// `pitch` apart (top-left to top-left); without an explicit pitch the labels
+/// butt against each other. Without explicit margins the grid is centred on
+/// the sheet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelSheet {
+    sheet_width: Mm,
+    sheet_height: Mm,
+    rows: usize,
+    columns: usize,
+    pitch: Option<(Mm, Mm)>,
+    margins: Option<(Mm, Mm)>,
+    start_offset: usize,
+    crop_marks: bool,
+}
+
+impl LabelSheet {
+    pub fn new(sheet_width: Mm, sheet_height: Mm, rows: usize, columns: usize) -> Self {
+        LabelSheet {
+            sheet_width,
+            sheet_height,
+            rows,
+            columns,
+            pitch: None,
+            margins: None,
+            start_offset: 0,
+            crop_marks: false,
+        }
+    }
+
+    /// Uses a named paper size (see `basePdf.size`) for the sheet.
+    pub fn with_paper_size(name: &str, rows: usize, columns: usize) -> Result<Self, Error> {
+        let (width, height) = paper::resolve_page_size(Some(name), None, None, None)?;
+        Ok(LabelSheet::new(Mm(width), Mm(height), rows, columns))
+    }
+
+    /// Distance between the top-left corners of neighbouring cells.
+    pub fn with_pitch(mut self, horizontal: Mm, vertical: Mm) -> Self {
+        self.pitch = Some((horizontal, vertical));
+        self
+    }
+
+    /// Distance from the sheet's top and left edges to the first cell.
+    pub fn with_margins(mut self, top: Mm, left: Mm) -> Self {
+        self.margins = Some((top, left));
+        self
+    }
+
+    /// Skips the first `used` cells of the first sheet, for feeding a
+    /// partially used sheet back into the printer.
+    pub fn with_start_offset(mut self, used: usize) -> Self {
+        self.start_offset = used;
+        self
+    }
+
+    /// Draws crop marks in the sheet margins in line with every cell edge.
+    pub fn with_crop_marks(mut self, crop_marks: bool) -> Self {
+        self.crop_marks = crop_marks;
+        self
+    }
+
+    fn cells_per_sheet(&self) -> usize {
+        self.rows * self.columns
+    }
+
+    /// Resolves pitch and margins for labels of the given size.
+    fn grid(&self, label_width: Mm, label_height: Mm) -> Result<Grid, Error> {
+        let invalid = |message: String| Error::InvalidImposition { message };
+
+        if self.rows == 0 || self.columns == 0 {
+            return Err(invalid(format!(
+                "a label sheet needs at least one row and one column, got {}x{}",
+                self.rows, self.columns
+            )));
+        }
+        if self.start_offset >= self.cells_per_sheet() {
+            return Err(invalid(format!(
+                "start offset {} leaves no free cell on a sheet of {} labels",
+                self.start_offset,
+                self.cells_per_sheet()
+            )));
+        }
+
+        let (pitch_x, pitch_y) = self.pitch.unwrap_or((label_width, label_height));
+        if pitch_x < label_width || pitch_y < label_height {
+            return Err(invalid(format!(
+                "pitch {}x{}mm is smaller than the {}x{}mm label",
+                pitch_x.0, pitch_y.0, label_width.0, label_height.0
+            )));
+        }
+
+        let grid_width = pitch_x * (self.columns - 1) as f32 + label_width;
+        let grid_height = pitch_y * (self.rows - 1) as f32 + label_height;
+        let (top, left) = self.margins.unwrap_or((
+            (self.sheet_height - grid_height) / 2.0,
+            (self.sheet_width - grid_width) / 2.0,
+        ));
+        // 丸め誤差で弾かないよう0.01mmの余裕を持たせる
+        let tolerance = Mm(0.01);
+        if top.0 < -tolerance.0
+            || left.0 < -tolerance.0
+            || left + grid_width > self.sheet_width + tolerance
+            || top + grid_height > self.sheet_height + tolerance
+        {
+            return Err(invalid(format!(
+                "a {}x{} grid of {}x{}mm labels does not fit on a {}x{}mm sheet",
+                self.columns,
+                self.rows,
+                label_width.0,
+                label_height.0,
+                self.sheet_width.0,
+                self.sheet_height.0
+            )));
+        }
+
+        Ok(Grid {
+            top,
+            left,
+            pitch_x,
+            pitch_y,
+            label_width,
+            label_height,
+        })
+    }
+}
+
+/// Cell geometry of a [`LabelSheet`], measured from the sheet's top-left.
+#[derive(Debug, Clone, Copy)]
+struct Grid {
+    top: Mm,
+    left: Mm,
+    pitch_x: Mm,
+    pitch_y: Mm,
+    label_width: Mm,
+    label_height: Mm,
+}
+
+impl Grid {
+    fn cell_origin(&self, row: usize, column: usize) -> (Mm, Mm) {
+        (
+            self.left + self.pitch_x * column as f32,
+            self.top + self.pitch_y * row as f32,
+        )
+    }
+}
+
+/// Lays out one label per record and places them on label sheets.
+///
+/// Static schemas see `currentPage` / `totalPages` as the label's index and
+/// the number of labels.
+pub(crate) fn render_label_sheet(
+    font_map: &FontMap,
+    doc: &mut PdfDocument,
+    template: &Template,
+    labels: Vec<SchemaPage>,
+    static_inputs: &HashMap<&'static str, String>,
+    sheet: &LabelSheet,
+) -> Result<Vec<u8>, Error> {
+    let laid_out = lay_out_sequence(
+        font_map,
+        doc,
+        vec![(template, labels)],
+        static_inputs,
+        &BlankPages::default(),
+    )?
+    .into_iter()
+    .next()
+    .unwrap_or_default();
+    // どのシートにどのラベルが載るかによらず、使われた特色はすべてのシートに定義する
+    let separations = color::merge(laid_out.separations()?);
+    let pages = impose_labels(sheet, laid_out)?;
+    let sheets = vec![separations; pages.len()];
+    let pdf = color::apply_separations(save_pages(doc, pages), &sheets)?;
+    viewer::apply(pdf, &viewer_preferences([template]))
+}
+
+fn impose_labels(sheet: &LabelSheet, labels: LaidOutPages) -> Result<Vec<PdfPage>, Error> {
+    let Some(first) = labels.base_pdfs.first() else {
+        return Ok(Vec::new());
+    };
+    if labels.base_pdfs.iter().any(|base_pdf| base_pdf.auto_height) {
+        return Err(Error::InvalidImposition {
+            message: "label templates need a fixed height, not \"auto\"".to_string(),
+        });
+    }
+    if labels
+        .base_pdfs
+        .iter()
+        .any(|base_pdf| base_pdf.rotate != rotation::Rotation::None)
+    {
+        return Err(Error::InvalidImposition {
+            message: "label templates cannot be rotated; rotate the sheet instead".to_string(),
+        });
+    }
+    if labels
+        .base_pdfs
+        .iter()
+        .any(|base_pdf| !base_pdf.bleed.is_none())
+    {
+        return Err(Error::InvalidImposition {
+            message: "label templates cannot have a bleed or printer's marks".to_string(),
+        });
+    }
+    let grid = sheet.grid(first.width, first.height)?;
+
+    let per_sheet = sheet.cells_per_sheet();
+    let cell_count = sheet.start_offset + labels.page_count();
+    let sheet_count = cell_count.div_ceil(per_sheet);
+    let mut sheets: Vec<Vec<Op>> = vec![Vec::new(); sheet_count];
+
+    for (index, (ops, base_pdf)) in labels
+        .buffer
+        .buffer
+        .into_iter()
+        .zip(&labels.base_pdfs)
+        .enumerate()
+    {
+        let position = sheet.start_offset + index;
+        let cell = position % per_sheet;
+        let (x, y) = grid.cell_origin(cell / sheet.columns, cell % sheet.columns);
+        // ラベルの描画はラベル自身の左下原点なので、セル左下へ移動してから
+        // セルの外にはみ出さないようクリップする
+        let bottom = sheet.sheet_height - y - base_pdf.height;
+        let sheet_ops = &mut sheets[position / per_sheet];
+        sheet_ops.push(Op::SaveGraphicsState);
+        sheet_ops.push(Op::SetTransformationMatrix {
+            matrix: CurTransMat::Translate(x.into(), bottom.into()),
+        });
+        sheet_ops.push(clip_rect(base_pdf.width, base_pdf.height));
+        sheet_ops.extend(ops);
+        sheet_ops.push(Op::RestoreGraphicsState);
+    }
+
+    Ok(sheets
+        .into_iter()
+        .map(|mut ops| {
+            if sheet.crop_marks {
+                ops.extend(crop_marks(sheet, &grid));
+            }
+            PdfPage::new(sheet.sheet_width, sheet.sheet_height, ops)
+        })
+        .collect())
+}
+
+/// How pages are arranged on each sheet by [`PageImposition`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImpositionLayout {
+    /// One page per sheet. Only padding is applied, and pages are scaled onto
+    /// the sheet if a sheet size is set.
+    Single,
+    /// `columns` × `rows` pages per sheet in reading order.
+    NUp { columns: usize, rows: usize },
+    /// Saddle-stitch booklet: two pages side by side, ordered so the folded
+    /// stack of duplex sheets reads in order (4-1, 2-3 for four pages). The
+    /// page count is padded to a multiple of four.
+    Booklet,
+}
+
+impl ImpositionLayout {
+    fn grid(&self) -> (usize, usize) {
+        match *self {
+            ImpositionLayout::Single => (1, 1),
+            ImpositionLayout::NUp { columns, rows } => (columns, rows),
+            ImpositionLayout::Booklet => (2, 1),
+        }
+    }
+}
+
+/// Post-layout imposition of a whole document for
+/// [`PDForge::render_imposed`](crate::PDForge::render_imposed).
+///
+/// Without a sheet size the sheet is the grid of first-page-sized cells, so
+/// A5 pages in a booklet land on an A4 landscape sheet. Pages that do not
+/// match the cell size are scaled down uniformly and centred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageImposition {
+    layout: ImpositionLayout,
+    sheet: Option<(Mm, Mm)>,
+    pad_to_multiple: usize,
+    chapters_on_recto: bool,
+}
+
+impl PageImposition {
+    pub fn new(layout: ImpositionLayout) -> Self {
+        PageImposition {
+            layout,
+            sheet: None,
+            pad_to_multiple: 1,
+            chapters_on_recto: false,
+        }
+    }
+
+    pub fn booklet() -> Self {
+        PageImposition::new(ImpositionLayout::Booklet)
+    }
+
+    pub fn two_up() -> Self {
+        PageImposition::new(ImpositionLayout::NUp {
+            columns: 2,
+            rows: 1,
+        })
+    }
+
+    pub fn four_up() -> Self {
+        PageImposition::new(ImpositionLayout::NUp {
+            columns: 2,
+            rows: 2,
+        })
+    }
+
+    pub fn with_sheet_size(mut self, width: Mm, height: Mm) -> Self {
+        self.sheet = Some((width, height));
+        self
+    }
+
+    /// Uses a named paper size (see `basePdf.size`) for the sheet, turned
+    /// landscape when the layout has more columns than rows.
+    pub fn with_paper_size(self, name: &str) -> Result<Self, Error> {
+        let (columns, rows) = self.layout.grid();
+        let orientation = if columns > rows {
+            paper::Orientation::Landscape
+        } else {
+            paper::Orientation::Portrait
+        };
+        let (width, height) = paper::resolve_page_size(Some(name), None, None, Some(orientation))?;
+        Ok(self.with_sheet_size(Mm(width), Mm(height)))
+    }
+
+    /// Appends blank pages until the page count is a multiple of `multiple`.
+    pub fn with_pad_to_multiple(mut self, multiple: usize) -> Self {
+        self.pad_to_multiple = multiple;
+        self
+    }
+
+    /// Inserts a blank page before a section that would otherwise start on a
+    /// verso (even) page. Each `render_imposed` section is a chapter.
+    pub fn with_chapters_on_recto(mut self, chapters_on_recto: bool) -> Self {
+        self.chapters_on_recto = chapters_on_recto;
+        self
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let (columns, rows) = self.layout.grid();
+        let message = if columns == 0 || rows == 0 {
+            format!("an N-up layout needs at least one column and row, got {columns}x{rows}")
+        } else if self.pad_to_multiple == 0 {
+            "pages cannot be padded to a multiple of 0".to_string()
+        } else if self
+            .sheet
+            .is_some_and(|(width, height)| width.0 <= 0.0 || height.0 <= 0.0)
+        {
+            "the sheet size must be positive".to_string()
+        } else {
+            return Ok(());
+        };
+        Err(Error::InvalidImposition { message })
+    }
+
+    pub(crate) fn blank_pages(&self) -> BlankPages {
+        let multiple = match self.layout {
+            ImpositionLayout::Booklet => lcm(self.pad_to_multiple, 4),
+            _ => self.pad_to_multiple,
+        };
+        BlankPages {
+            chapters_on_recto: self.chapters_on_recto,
+            multiple,
+        }
+    }
+
+    /// Source page indices for each side of each sheet; `None` is an empty
+    /// cell.
+    fn sheet_sides(&self, page_count: usize) -> Vec<Vec<Option<usize>>> {
+        match self.layout {
+            ImpositionLayout::Booklet => {
+                let last = page_count.saturating_sub(1);
+                (0..page_count / 4)
+                    .flat_map(|sheet| {
+                        let (outer, inner) = (2 * sheet, 2 * sheet + 1);
+                        [
+                            vec![Some(last - outer), Some(outer)],
+                            vec![Some(inner), Some(last - inner)],
+                        ]
+                    })
+                    .collect()
+            }
+            _ => {
+                let (columns, rows) = self.layout.grid();
+                let pages: Vec<Option<usize>> = (0..page_count).map(Some).collect();
+                pages
+                    .chunks(columns * rows)
+                    .map(|chunk| {
+                        let mut side = chunk.to_vec();
+                        side.resize(columns * rows, None);
+                        side
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+fn lcm(a: usize, b: usize) -> usize {
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    a / gcd(a, b) * b
+}
+
+/// Blank pages added while laying out a sequence, before static schemas are
+/// numbered, so `currentPage` / `totalPages` count them.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BlankPages {
+    chapters_on_recto: bool,
+    multiple: usize,
+}
+
+impl BlankPages {
+    pub(crate) fn leading_blanks(&self, section: usize, pages_before: usize) -> usize {
+        usize::from(self.chapters_on_recto && section > 0 && pages_before % 2 == 1)
+    }
+
+    pub(crate) fn trailing_blanks(&self, page_count: usize) -> usize {
+        if self.multiple <= 1 {
+            0
+        } else {
+            (self.multiple - page_count % self.multiple) % self.multiple
+        }
+    }
+}
+
+/// Renders the sections like `render_sequence` and imposes the result.
+pub(crate) fn render_imposed(
+    font_map: &FontMap,
+    doc: &mut PdfDocument,
+    sections: Vec<(&Template, Vec<SchemaPage>)>,
+    static_inputs: &HashMap<&'static str, String>,
+    imposition: &PageImposition,
+) -> Result<Vec<u8>, Error> {
+    imposition.validate()?;
+    let preferences = viewer_preferences(sections.iter().map(|(template, _)| *template));
+    let laid_out = lay_out_sequence(
+        font_map,
+        doc,
+        sections,
+        static_inputs,
+        &imposition.blank_pages(),
+    )?;
+    let pdf = save_laid_out_pages(doc, laid_out)?;
+
+    let pdf = if imposition.layout == ImpositionLayout::Single && imposition.sheet.is_none() {
+        pdf
+    } else {
+        impose_document(&pdf, imposition)?
+    };
+    viewer::apply(pdf, &preferences)
+}
+
+/// A source page turned into a Form XObject.
+struct FormPage {
+    id: ObjectId,
+    origin: (f32, f32),
+    /// BBox size before `/Rotate` is applied.
+    bbox_size: (f32, f32),
+    /// Size as displayed, after `/Rotate`.
+    size: (f32, f32),
+    rotation: rotation::Rotation,
+}
+
+impl FormPage {
+    /// `cm` operands drawing the form upright (honouring the source page's
+    /// `/Rotate`), scaled by `scale` with its bottom-left corner at `(x, y)`.
+    fn placement(&self, scale: f32, x: f32, y: f32) -> [f32; 6] {
+        let [a, b, c, d, e, f] = self.rotation.matrix(self.bbox_size.0, self.bbox_size.1);
+        let (origin_x, origin_y) = self.origin;
+        [
+            a * scale,
+            b * scale,
+            c * scale,
+            d * scale,
+            (e - a * origin_x - c * origin_y) * scale + x,
+            (f - b * origin_x - d * origin_y) * scale + y,
+        ]
+    }
+}
+
+fn impose_document(pdf: &[u8], imposition: &PageImposition) -> Result<Vec<u8>, Error> {
+    fn pdf_error(e: impl std::fmt::Display) -> Error {
+        Error::InvalidImposition {
+            message: format!("failed to rework the rendered document: {e}"),
+        }
+    }
+    let mut document = Document::load_mem(pdf).map_err(pdf_error)?;
+
+    let page_ids: Vec<ObjectId> = document.get_pages().into_values().collect();
+    let mut forms = Vec::with_capacity(page_ids.len());
+    for page_id in page_ids {
+        let page = document.get_dictionary(page_id).map_err(pdf_error)?;
+        // 裁ち落としのあるページは仕上がりサイズ（TrimBox）で配置する
+        let media_box: Vec<f32> = page
+            .get(b"TrimBox")
+            .or_else(|_| page.get(b"MediaBox"))
+            .and_then(Object::as_array)
+            .map_err(pdf_error)?
+            .iter()
+            .map(|value| value.as_float().unwrap_or(0.0))
+            .collect();
+        let [left, bottom, right, top] = media_box[..] else {
+            return Err(Error::InvalidImposition {
+                message: format!("page has a malformed MediaBox: {media_box:?}"),
+            });
+        };
+        let rotation = page
+            .get(b"Rotate")
+            .and_then(Object::as_i64)
+            .ok()
+            .and_then(rotation::Rotation::from_degrees)
+            .unwrap_or_default();
+        let resources = page
+            .get(b"Resources")
+            .cloned()
+            .unwrap_or_else(|_| Object::Dictionary(lopdf::Dictionary::new()));
+        let content = document.get_page_content(page_id);
+
+        let form = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![left.into(), bottom.into(), right.into(), top.into()],
+                "Resources" => resources,
+            },
+            content,
+        );
+        let bbox_size = (right - left, top - bottom);
+        forms.push(FormPage {
+            id: document.add_object(form),
+            origin: (left, bottom),
+            bbox_size,
+            size: rotation.rotated_size(bbox_size.0, bbox_size.1),
+            rotation,
+        });
+    }
+
+    let (columns, rows) = imposition.layout.grid();
+    let (sheet_width, sheet_height): (Pt, Pt) = match imposition.sheet {
+        Some((width, height)) => (width.into(), height.into()),
+        None => {
+            let (width, height) = forms.first().map(|form| form.size).unwrap_or_default();
+            (Pt(width * columns as f32), Pt(height * rows as f32))
+        }
+    };
+    let cell = (sheet_width.0 / columns as f32, sheet_height.0 / rows as f32);
+
+    let pages_id = document
+        .catalog()
+        .and_then(|catalog| catalog.get(b"Pages"))
+        .and_then(Object::as_reference)
+        .map_err(pdf_error)?;
+    let mut kids = Vec::new();
+    for side in imposition.sheet_sides(forms.len()) {
+        let mut content = String::new();
+        let mut xobjects = lopdf::Dictionary::new();
+        for (slot, source) in side.into_iter().enumerate() {
+            let Some(form) = source.map(|index| &forms[index]) else {
+                continue;
+            };
+            // セルに収まるよう等倍率で縮小し、セルの中央に置く
+            let scale = (cell.0 / form.size.0).min(cell.1 / form.size.1).min(1.0);
+            let (column, row) = (slot % columns, slot / columns);
+            let x = cell.0 * column as f32 + (cell.0 - form.size.0 * scale) / 2.0;
+            let y =
+                sheet_height.0 - cell.1 * (row + 1) as f32 + (cell.1 - form.size.1 * scale) / 2.0;
+            let name = format!("P{slot}");
+            let [a, b, c, d, e, f] = form.placement(scale, x, y);
+            content.push_str(&format!("q {a} {b} {c} {d} {e} {f} cm /{name} Do Q\n"));
+            xobjects.set(name, Object::Reference(form.id));
+        }
+
+        let contents = document.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let sheet = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "MediaBox" => vec![0.into(), 0.into(), sheet_width.0.into(), sheet_height.0.into()],
+            "Contents" => contents,
+            "Resources" => dictionary! { "XObject" => xobjects },
+        });
+        kids.push(Object::Reference(sheet));
+    }
+
+    let pages = document
+        .get_object_mut(pages_id)
+        .and_then(Object::as_dict_mut)
+        .map_err(pdf_error)?;
+    pages.set("Count", kids.len() as i64);
+    pages.set("Kids", kids);
+    document.prune_objects();
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+fn point(x: Mm, y: Mm) -> LinePoint {
+    LinePoint {
+        p: Point {
+            x: x.into(),
+            y: y.into(),
+        },
+        bezier: false,
+    }
+}
+
+fn clip_rect(width: Mm, height: Mm) -> Op {
+    Op::DrawPolygon {
+        polygon: Polygon {
+            rings: vec![PolygonRing {
+                points: vec![
+                    point(Mm(0.0), Mm(0.0)),
+                    point(width, Mm(0.0)),
+                    point(width, height),
+                    point(Mm(0.0), height),
+                ],
+            }],
+            mode: PaintMode::Clip,
+            winding_order: WindingOrder::NonZero,
+        },
+    }
+}
+
+/// Crop marks outside the grid, in line with every cell edge, so they never
+/// print onto a label even when the cells butt against each other.
+fn crop_marks(sheet: &LabelSheet, grid: &Grid) -> Vec<Op> {
+    let page_height = sheet.sheet_height;
+    let grid_top = grid.top;
+    let grid_bottom = grid.top + grid.pitch_y * (sheet.rows - 1) as f32 + grid.label_height;
+    let grid_left = grid.left;
+    let grid_right = grid.left + grid.pitch_x * (sheet.columns - 1) as f32 + grid.label_width;
+
+    let mut cut_xs = Vec::with_capacity(sheet.columns * 2);
+    for column in 0..sheet.columns {
+        let (x, _) = grid.cell_origin(0, column);
+        cut_xs.extend([x, x + grid.label_width]);
+    }
+    let mut cut_ys = Vec::with_capacity(sheet.rows * 2);
+    for row in 0..sheet.rows {
+        let (_, y) = grid.cell_origin(row, 0);
+        cut_ys.extend([y, y + grid.label_height]);
+    }
+    cut_xs.dedup_by(|a, b| (a.0 - b.0).abs() < 0.01);
+    cut_ys.dedup_by(|a, b| (a.0 - b.0).abs() < 0.01);
+
+    let mut ops = vec![
+        Op::SaveGraphicsState,
+        Op::SetOutlineColor {
+            col: Color::Rgb(Rgb {
+                r: 0.0,
+                g: 0.0,
+                b: 0.0,
+                icc_profile: None,
+            }),
+        },
+        Op::SetOutlineThickness { pt: Pt(0.25) },
+    ];
+    let mut mark = |from: (Mm, Mm), to: (Mm, Mm)| {
+        ops.push(Op::DrawLine {
+            line: Line {
+                points: vec![
+                    point(from.0, page_height - from.1),
+                    point(to.0, page_height - to.1),
+                ],
+                is_closed: false,
+            },
+        });
+    };
+    for x in cut_xs {
+        let above = grid_top - CROP_MARK_OFFSET;
+        let below = grid_bottom + CROP_MARK_OFFSET;
+        mark((x, above), (x, above - CROP_MARK_LENGTH));
+        mark((x, below), (x, below + CROP_MARK_LENGTH));
+    }
+    for y in cut_ys {
+        let before = grid_left - CROP_MARK_OFFSET;
+        let after = grid_right + CROP_MARK_OFFSET;
+        mark((before, y), (before - CROP_MARK_LENGTH, y));
+        mark((after, y), (after + CROP_MARK_LENGTH, y));
+    }
+    ops.push(Op::RestoreGraphicsState);
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_is_centred_without_margins() {
+        let grid = LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2)
+            .grid(Mm(90.0), Mm(50.0))
+            .unwrap();
+
+        assert_eq!(grid.left, Mm(15.0));
+        assert_eq!(grid.top, Mm(23.5));
+        assert_eq!(grid.cell_origin(4, 1), (Mm(105.0), Mm(223.5)));
+    }
+
+    #[test]
+    fn pitch_and_margins_place_cells() {
+        let grid = LabelSheet::new(Mm(210.0), Mm(297.0), 8, 3)
+            .with_pitch(Mm(70.0), Mm(36.0))
+            .with_margins(Mm(4.5), Mm(0.0))
+            .grid(Mm(66.0), Mm(33.9))
+            .unwrap();
+
+        assert_eq!(grid.cell_origin(0, 0), (Mm(0.0), Mm(4.5)));
+        assert_eq!(grid.cell_origin(1, 2), (Mm(140.0), Mm(40.5)));
+    }
+
+    #[test]
+    fn booklet_sides_fold_into_reading_order() {
+        let sides = PageImposition::booklet().sheet_sides(8);
+
+        let pages: Vec<Vec<usize>> = sides
+            .into_iter()
+            .map(|side| side.into_iter().map(|page| page.unwrap() + 1).collect())
+            .collect();
+        assert_eq!(pages, vec![vec![8, 1], vec![2, 7], vec![6, 3], vec![4, 5]]);
+    }
+
+    #[test]
+    fn n_up_sides_fill_in_reading_order_and_leave_empty_cells() {
+        let sides = PageImposition::four_up().sheet_sides(5);
+
+        assert_eq!(
+            sides,
+            vec![
+                vec![Some(0), Some(1), Some(2), Some(3)],
+                vec![Some(4), None, None, None]
+            ]
+        );
+    }
+
+    #[test]
+    fn blank_pages_pad_and_push_chapters_to_recto() {
+        let blanks = PageImposition::booklet()
+            .with_pad_to_multiple(8)
+            .with_chapters_on_recto(true)
+            .blank_pages();
+
+        assert_eq!(blanks.leading_blanks(0, 0), 0);
+        assert_eq!(blanks.leading_blanks(1, 3), 1);
+        assert_eq!(blanks.leading_blanks(1, 4), 0);
+        assert_eq!(blanks.trailing_blanks(5), 3);
+        assert_eq!(blanks.trailing_blanks(16), 0);
+        assert_eq!(PageImposition::two_up().blank_pages().trailing_blanks(3), 0);
+    }
+
+    #[test]
+    fn rotated_pages_are_placed_upright_in_their_cell() {
+        let form = FormPage {
+            id: (1, 0),
+            origin: (0.0, 0.0),
+            bbox_size: (100.0, 50.0),
+            size: (50.0, 100.0),
+            rotation: rotation::Rotation::Clockwise90,
+        };
+
+        let [a, b, c, d, e, f] = form.placement(1.0, 10.0, 20.0);
+        let corner = |x: f32, y: f32| (a * x + c * y + e, b * x + d * y + f);
+
+        assert_eq!(corner(0.0, 50.0), (60.0, 120.0));
+        assert_eq!(corner(100.0, 0.0), (10.0, 20.0));
+    }
+
+    #[test]
+    fn impossible_sheets_are_rejected() {
+        let label = (Mm(90.0), Mm(50.0));
+        let cases = [
+            LabelSheet::new(Mm(210.0), Mm(297.0), 0, 2),
+            LabelSheet::new(Mm(210.0), Mm(297.0), 5, 3),
+            LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2).with_start_offset(10),
+            LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2).with_pitch(Mm(80.0), Mm(50.0)),
+            LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2).with_margins(Mm(60.0), Mm(0.0)),
+        ];
+
+        for sheet in cases {
+            assert!(
+                matches!(
+                    sheet.grid(label.0, label.1),
+                    Err(Error::InvalidImposition { .. })
+                ),
+                "{sheet:?} should be rejected"
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use super::color::PdfColor;
 use super::InvalidColorSnafu;
 use crate::schemas::pdf_utils::{
     calculate_transform_matrix_with_center_pivot, wrap_ops_with_opacity,
@@ -5,8 +6,8 @@ use crate::schemas::pdf_utils::{
 use crate::schemas::{base::BaseSchema, Error, HasBaseSchema, JsonPosition, Schema};
 use crate::utils::OpBuffer;
 use printpdf::{
-    Color, CurTransMat, LinePoint, Mm, Op, PaintMode, PdfDocument, Point, Polygon, PolygonRing, Pt,
-    Rgb, WindingOrder,
+    CurTransMat, LinePoint, Mm, Op, PaintMode, PdfDocument, Point, Polygon, PolygonRing, Pt,
+    WindingOrder,
 };
 use serde::Deserialize;
 use snafu::prelude::*;
@@ -34,7 +35,9 @@ use snafu::prelude::*;
 pub struct JsonLineSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(default, deserialize_with = "super::units::deserialize_optional_mm")]
     height: Option<f32>,
     rotate: Option<f32>,
     opacity: Option<f32>,
@@ -48,7 +51,7 @@ pub struct Line {
     rotate: Option<f32>,
     opacity: Option<f32>,
     border_width: Pt,
-    color: csscolorparser::Color,
+    color: PdfColor,
 }
 
 impl TryFrom<JsonLineSchema> for Schema {
@@ -63,7 +66,7 @@ impl TryFrom<JsonLineSchema> for Schema {
             Mm(json.height.unwrap_or(0.0)), // 線の場合heightは0でOK
         );
 
-        let color = csscolorparser::parse(&json.color).context(InvalidColorSnafu)?;
+        let color = PdfColor::parse(&json.color).context(InvalidColorSnafu)?;
         Ok(Schema::Line(Line {
             base,
             rotate: json.rotate,
@@ -92,12 +95,7 @@ impl Line {
             width: self.base.width,
             rotate: self.rotate,
             page_height: parent_height,
-            color: Color::Rgb(Rgb {
-                r: self.color.r,
-                g: self.color.g,
-                b: self.color.b,
-                icc_profile: None,
-            }),
+            color: self.color.clone(),
             border_width: Mm(self.border_width.0),
         });
         let ops = wrap_ops_with_opacity(doc, self.opacity, ops);
@@ -143,7 +141,7 @@ pub struct DrawLine {
     pub width: Mm,
     pub rotate: Option<f32>,
     pub page_height: Mm,
-    pub color: Color,
+    pub color: PdfColor,
     pub border_width: Mm,
 }
 
@@ -183,10 +181,9 @@ pub fn draw_line(props: DrawLine) -> Vec<Op> {
         winding_order: WindingOrder::NonZero,
     };
 
-    vec![
-        Op::SaveGraphicsState,
-        matrix_op,
-        Op::SetOutlineColor { col: props.color },
+    let mut ops = vec![Op::SaveGraphicsState, matrix_op];
+    ops.extend(props.color.stroke_ops());
+    ops.extend([
         Op::SetOutlineThickness {
             pt: props.border_width.into(),
         },
@@ -194,5 +191,6 @@ pub fn draw_line(props: DrawLine) -> Vec<Op> {
             polygon: polygon.clone(),
         },
         Op::RestoreGraphicsState,
-    ]
+    ]);
+    ops
 }
@@ -1,23 +1,45 @@
+pub mod aztec;
+pub mod background;
+pub mod band;
+pub mod barcode;
 pub mod base;
+pub mod bleed;
+pub mod color;
+pub mod datamatrix;
 pub mod dynamic_text;
 pub mod group;
+pub mod gs1;
 pub mod image;
+pub mod imposition;
 pub mod line;
+pub mod page_selector;
+pub mod paper;
+pub mod pdf417;
 pub mod pdf_utils;
+pub mod qr_style;
 pub mod qrcode;
 pub mod rect;
+pub mod rich_text;
+pub mod rotation;
+pub mod ruby;
 pub mod spacer;
 pub mod svg;
+pub mod symbology;
 pub mod table;
 pub mod text;
+pub mod units;
+pub mod vertical;
+pub mod viewer;
+pub mod watermark;
 
 use crate::font::{self, FontMap};
 use crate::utils::OpBuffer;
 use base::BaseSchema;
-use printpdf::{Mm, PdfDocument, PdfPage, PdfSaveOptions};
+use printpdf::{CurTransMat, Mm, Op, PdfDocument, PdfPage, PdfSaveOptions, Pt};
 use serde::{Deserialize, Serialize};
 use snafu::prelude::*;
 use std::collections::HashMap;
+use std::sync::Arc;
 use time;
 
 // Snafu context for error handling
@@ -41,13 +63,45 @@ pub enum Error {
     },
 
     #[snafu(display("Font error"))]
-    FontError { source: font::Error },
+    FontError {
+        source: font::Error,
+    },
 
     #[snafu(display("Invalid BasePDF"))]
     InvalidBasePdf,
 
+    #[snafu(display("Unknown paper size {name:?}"))]
+    UnknownPaperSize {
+        name: String,
+    },
+
+    #[snafu(display("Invalid page setup: {message}"))]
+    InvalidPageSetup {
+        message: String,
+    },
+
+    #[snafu(display("Invalid imposition: {message}"))]
+    InvalidImposition {
+        message: String,
+    },
+
+    #[snafu(display("Invalid watermark: {message}"))]
+    InvalidWatermark {
+        message: String,
+    },
+
+    #[snafu(display("Invalid background: {message}"))]
+    InvalidBackground {
+        message: String,
+    },
+
+    #[snafu(display("Invalid spot color: {message}"))]
+    InvalidSpotColor {
+        message: String,
+    },
+
     InvalidColor {
-        source: csscolorparser::ParseColorError,
+        source: color::ParseColorError,
     },
 
     #[snafu(display("Failed to convert {schema_type} schema"))]
@@ -63,7 +117,9 @@ pub enum Error {
     },
 
     #[snafu(display("Spacer height must be non-negative, got {height}"))]
-    InvalidSpacerHeight { height: f32 },
+    InvalidSpacerHeight {
+        height: f32,
+    },
 
     #[snafu(display("Font file I/O error: {message}"))]
     FontFileIo {
@@ -72,19 +128,56 @@ pub enum Error {
     },
 
     #[snafu(display("Font parsing error: {message}"))]
-    FontParsing { message: String },
+    FontParsing {
+        message: String,
+    },
 
     #[snafu(display("Template loading error: {message}"))]
-    TemplateLoading { message: String },
+    TemplateLoading {
+        message: String,
+    },
 
     #[snafu(display("Image decoding error: {message}"))]
-    ImageDecoding { message: String },
+    ImageDecoding {
+        message: String,
+    },
 
     #[snafu(display("Image encoding error: {message}"))]
-    ImageEncoding { message: String },
+    ImageEncoding {
+        message: String,
+    },
+
+    #[snafu(display("Invalid {format} barcode: {message}"))]
+    InvalidBarcode {
+        format: String,
+        message: String,
+    },
+
+    #[snafu(display("Cannot encode the {symbology}: {message}"))]
+    SymbolEncoding {
+        symbology: String,
+        message: String,
+    },
+
+    #[snafu(display("Invalid GS1 element string: {message}"))]
+    InvalidGs1 {
+        message: String,
+    },
+
+    #[snafu(display("Invalid rich text: {message}"))]
+    RichText {
+        message: String,
+    },
+
+    #[snafu(display("Invalid ruby: {message}"))]
+    Ruby {
+        message: String,
+    },
 
     #[snafu(display("QR code generation error: {message}"))]
-    QrCodeGeneration { message: String },
+    QrCodeGeneration {
+        message: String,
+    },
 
     #[snafu(display("Color parsing error: {message}"))]
     ColorParsing {
@@ -109,6 +202,7 @@ enum JsonSchema {
     DynamicText(dynamic_text::JsonDynamicTextSchema),
     Table(table::JsonTableSchema),
     QrCode(qrcode::JsonQrCodeSchema),
+    Barcode(barcode::JsonBarcodeSchema),
     Image(image::JsonImageSchema),
     Svg(svg::JsonSvgSchema),
     Rectangle(rect::JsonRectSchema),
@@ -119,11 +213,123 @@ enum JsonSchema {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 struct JsonBasePdf {
-    width: f32,
-    height: f32,
-    padding: Vec<f32>,
+    #[serde(default)]
+    size: Option<String>,
+    #[serde(default)]
+    orientation: Option<paper::Orientation>,
+    #[serde(default, deserialize_with = "units::deserialize_optional_mm")]
+    width: Option<f32>,
+    #[serde(default, deserialize_with = "paper::deserialize_optional_page_height")]
+    height: Option<paper::PageHeight>,
+    #[serde(
+        rename = "maxHeight",
+        default,
+        deserialize_with = "units::deserialize_optional_mm"
+    )]
+    max_height: Option<f32>,
+    padding: JsonPadding,
+    #[serde(rename = "firstPagePadding", default)]
+    first_page_padding: Option<JsonPadding>,
+    #[serde(default)]
+    rotate: rotation::Rotation,
+    #[serde(rename = "rotateMode", default)]
+    rotate_mode: rotation::RotateMode,
     #[serde(rename = "staticSchema", default)]
     static_schema: Vec<serde_json::Value>,
+    #[serde(rename = "viewerPreferences", default)]
+    viewer_preferences: viewer::ViewerPreferences,
+    #[serde(default)]
+    watermark: Option<watermark::JsonWatermark>,
+    #[serde(default)]
+    background: Option<background::JsonBackground>,
+    #[serde(default, deserialize_with = "units::deserialize_optional_mm")]
+    bleed: Option<f32>,
+    #[serde(rename = "printerMarks", default)]
+    printer_marks: bleed::PrinterMarks,
+    #[serde(rename = "spotColors", default)]
+    spot_colors: std::collections::BTreeMap<String, String>,
+}
+
+/// `basePdf.padding`: `[top, right, bottom, left]`, or `top` / `bottom` with
+/// `inside` / `outside` margins that change sides between odd and even pages.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum JsonPadding {
+    Sides(#[serde(deserialize_with = "units::deserialize_mm_vec")] Vec<f32>),
+    Mirrored(JsonMirroredPadding),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct JsonMirroredPadding {
+    #[serde(deserialize_with = "units::deserialize_mm")]
+    top: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
+    bottom: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
+    inside: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
+    outside: f32,
+}
+
+impl JsonPadding {
+    // 見開きの余白は奇数ページ（右ページ）の向きで持つ。左が内側、右が外側
+    fn resolve(self) -> Result<(Frame, bool), Error> {
+        match self {
+            JsonPadding::Sides(sides) => Ok((sides.try_into()?, false)),
+            JsonPadding::Mirrored(padding) => Ok((
+                Frame {
+                    top: Mm(padding.top),
+                    right: Mm(padding.outside),
+                    bottom: Mm(padding.bottom),
+                    left: Mm(padding.inside),
+                },
+                true,
+            )),
+        }
+    }
+}
+
+/// Per-page override of `basePdf`. Any field left out keeps the template's
+/// value.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct JsonPageBasePdf {
+    #[serde(default)]
+    size: Option<String>,
+    #[serde(default)]
+    orientation: Option<paper::Orientation>,
+    #[serde(default, deserialize_with = "units::deserialize_optional_mm")]
+    width: Option<f32>,
+    #[serde(default, deserialize_with = "paper::deserialize_optional_page_height")]
+    height: Option<paper::PageHeight>,
+    #[serde(
+        rename = "maxHeight",
+        default,
+        deserialize_with = "units::deserialize_optional_mm"
+    )]
+    max_height: Option<f32>,
+    #[serde(default)]
+    padding: Option<JsonPadding>,
+    #[serde(rename = "firstPagePadding", default)]
+    first_page_padding: Option<JsonPadding>,
+    #[serde(default)]
+    rotate: Option<rotation::Rotation>,
+    #[serde(rename = "rotateMode", default)]
+    rotate_mode: Option<rotation::RotateMode>,
+    #[serde(default)]
+    watermark: Option<watermark::JsonWatermark>,
+    #[serde(default)]
+    background: Option<background::JsonBackground>,
+}
+
+/// A `schemas` entry written as an object instead of a bare array, so the
+/// page can carry its own `basePdf` override.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonPage {
+    #[serde(default)]
+    base_pdf: Option<JsonPageBasePdf>,
+    schemas: serde_json::Value,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -184,6 +390,7 @@ pub enum Schema {
     DynamicText(dynamic_text::DynamicText),
     Table(table::Table),
     QrCode(qrcode::QrCode),
+    Barcode(barcode::Barcode),
     Image(image::Image),
     Svg(svg::Svg),
     Rect(rect::Rect),
@@ -199,6 +406,7 @@ impl Schema {
             Schema::DynamicText(_) => "DynamicText",
             Schema::Table(_) => "Table",
             Schema::QrCode(_) => "QrCode",
+            Schema::Barcode(_) => "Barcode",
             Schema::Image(_) => "Image",
             Schema::Svg(_) => "Svg",
             Schema::Rect(_) => "Rectangle",
@@ -214,6 +422,7 @@ impl Schema {
             Schema::DynamicText(text) => text.get_base(),
             Schema::Table(table) => table.get_base(),
             Schema::QrCode(qr_code) => qr_code.get_base(),
+            Schema::Barcode(barcode) => barcode.get_base(),
             Schema::Image(image) => image.get_base(),
             Schema::Svg(svg) => svg.get_base(),
             Schema::Rect(rect) => rect.get_base(),
@@ -229,6 +438,7 @@ impl Schema {
             Schema::DynamicText(text) => text.get_base(),
             Schema::Table(table) => table.get_base(),
             Schema::QrCode(qr_code) => qr_code.get_base(),
+            Schema::Barcode(barcode) => barcode.get_base(),
             Schema::Image(image) => image.get_base(),
             Schema::Svg(svg) => svg.get_base(),
             Schema::Rect(rect) => rect.get_base(),
@@ -246,6 +456,7 @@ impl HasBaseSchema for Schema {
             Schema::DynamicText(s) => s.base(),
             Schema::Table(s) => s.base(),
             Schema::QrCode(s) => s.base(),
+            Schema::Barcode(s) => s.base(),
             Schema::Image(s) => s.base(),
             Schema::Svg(s) => s.base(),
             Schema::Rect(s) => s.base(),
@@ -261,6 +472,7 @@ impl HasBaseSchema for Schema {
             Schema::DynamicText(s) => s.base_mut(),
             Schema::Table(s) => s.base_mut(),
             Schema::QrCode(s) => s.base_mut(),
+            Schema::Barcode(s) => s.base_mut(),
             Schema::Image(s) => s.base_mut(),
             Schema::Svg(s) => s.base_mut(),
             Schema::Rect(s) => s.base_mut(),
@@ -289,6 +501,10 @@ impl SchemaTrait for Schema {
                 qr_code.render(parent_height, doc, page, buffer)?;
                 Ok(())
             }
+            Schema::Barcode(barcode) => {
+                barcode.render(parent_height, page, buffer)?;
+                Ok(())
+            }
             Schema::Image(image) => {
                 image.render(parent_height, doc, page, buffer)?;
                 Ok(())
@@ -306,47 +522,44 @@ impl SchemaTrait for Schema {
                 Ok(())
             }
             Schema::DynamicText(mut obj) => {
-                let base_pdf = BasePdf {
-                    width: parent_width,
-                    height: parent_height,
-                    padding: Frame {
+                let base_pdf = BasePdf::new(
+                    parent_width,
+                    parent_height,
+                    Frame {
                         top: Mm(0.0),
                         right: Mm(0.0),
                         bottom: Mm(0.0),
                         left: Mm(0.0),
                     },
-                    static_schema: vec![],
-                };
+                );
                 obj.render(&base_pdf, page, None, buffer)?;
                 Ok(())
             }
             Schema::Table(mut obj) => {
-                let base_pdf = BasePdf {
-                    width: parent_width,
-                    height: parent_height,
-                    padding: Frame {
+                let base_pdf = BasePdf::new(
+                    parent_width,
+                    parent_height,
+                    Frame {
                         top: Mm(0.0),
                         right: Mm(0.0),
                         bottom: Mm(0.0),
                         left: Mm(0.0),
                     },
-                    static_schema: vec![],
-                };
+                );
                 obj.render(&base_pdf, doc, page, None, buffer)?;
                 Ok(())
             }
             Schema::Group(mut obj) => {
-                let base_pdf = BasePdf {
-                    width: parent_width,
-                    height: parent_height,
-                    padding: Frame {
+                let base_pdf = BasePdf::new(
+                    parent_width,
+                    parent_height,
+                    Frame {
                         top: Mm(0.0),
                         right: Mm(0.0),
                         bottom: Mm(0.0),
                         left: Mm(0.0),
                     },
-                    static_schema: vec![],
-                };
+                );
                 obj.render(&base_pdf, doc, page, buffer)?;
                 Ok(())
             }
@@ -361,6 +574,7 @@ impl SchemaTrait for Schema {
             Schema::QrCode(qr_code) => {
                 qr_code.set_y(y);
             }
+            Schema::Barcode(barcode) => barcode.set_y(y),
             Schema::Group(group) => {
                 group.set_y(y);
             }
@@ -382,6 +596,7 @@ impl SchemaTrait for Schema {
             Schema::QrCode(qr_code) => {
                 qr_code.set_height(height);
             }
+            Schema::Barcode(barcode) => barcode.set_height(height),
             Schema::Group(group) => {
                 group.set_height(height);
             }
@@ -400,25 +615,427 @@ impl SchemaTrait for Schema {
 #[derive(Debug, Clone)]
 pub struct BasePdf {
     pub width: Mm,
+    /// Layout height. For an auto-height page this is `maxHeight` (or the PDF
+    /// page limit) and the emitted page is shrunk to its content.
     pub height: Mm,
+    /// Padding of odd pages. With `mirror_margins` its left side is the
+    /// inside margin and its right side the outside margin.
     pub padding: Frame,
+    /// Padding of the document's first page, when it differs from `padding`.
+    pub first_page_padding: Option<Frame>,
+    /// Swap the left and right padding on even pages, for bound duplex output.
+    pub mirror_margins: bool,
     pub static_schema: Vec<Schema>,
+    pub auto_height: bool,
+    pub rotate: rotation::Rotation,
+    pub rotate_mode: rotation::RotateMode,
+    pub watermark: Option<Arc<watermark::Watermark>>,
+    pub background: Option<Arc<background::Background>>,
+    /// Bleed and printer's marks added around the page when it is emitted.
+    pub bleed: bleed::Bleed,
+    /// Spot colours `spot(…)` colours on the page may refer to.
+    pub spot_colors: Arc<color::SpotColors>,
+    /// Document page number (0-based) of page 0 of the buffer this page is
+    /// laid out in, so per-page padding can be resolved from buffer indices.
+    pub(crate) page_offset: usize,
+    /// Buffer page of the template page this page's schemas are declared on.
+    /// Flowing content keeps its `position.x` there and moves with the
+    /// mirrored margins on the pages it continues onto.
+    pub(crate) origin_page: usize,
+    /// Header and footer band edges by document page.
+    pub(crate) bands: Arc<band::BandEdges>,
+}
+
+impl BasePdf {
+    pub fn new(width: Mm, height: Mm, padding: Frame) -> Self {
+        BasePdf {
+            width,
+            height,
+            padding,
+            first_page_padding: None,
+            mirror_margins: false,
+            static_schema: Vec::new(),
+            auto_height: false,
+            rotate: rotation::Rotation::None,
+            rotate_mode: rotation::RotateMode::Page,
+            watermark: None,
+            background: None,
+            bleed: bleed::Bleed::default(),
+            spot_colors: Arc::default(),
+            page_offset: 0,
+            origin_page: 0,
+            bands: Arc::default(),
+        }
+    }
+
+    /// Whether buffer page `page` is an even (left-hand) document page.
+    fn is_verso(&self, page: usize) -> bool {
+        (self.page_offset + page) % 2 == 1
+    }
+
+    /// Padding of buffer page `page`, with the inside and outside margins on
+    /// the correct sides.
+    pub(crate) fn padding_on(&self, page: usize) -> Frame {
+        let padding = match &self.first_page_padding {
+            Some(first) if self.page_offset + page == 0 => first,
+            _ => &self.padding,
+        };
+        if self.mirror_margins && self.is_verso(page) {
+            Frame {
+                left: padding.right,
+                right: padding.left,
+                ..padding.clone()
+            }
+        } else {
+            padding.clone()
+        }
+    }
+
+    /// How far flowing content on buffer page `page` moves horizontally
+    /// relative to the page it is declared on, so it keeps its place inside
+    /// the mirrored margins.
+    pub(crate) fn horizontal_shift(&self, page: usize) -> Mm {
+        self.padding_on(page).left - self.padding_on(self.origin_page).left
+    }
+
+    /// Where flowing content resumes at the top of buffer page `page`: the
+    /// top padding or the header band, whichever is lower.
+    pub(crate) fn flow_top(&self, page: usize) -> Mm {
+        let top = self.padding_on(page).top;
+        match self.bands.get(self.page_offset + page).top {
+            Some(header) if header > top => header,
+            _ => top,
+        }
+    }
+
+    /// The lowest point flowing content may reach on buffer page `page`: the
+    /// bottom padding or the footer band, whichever is higher.
+    pub(crate) fn flow_bottom(&self, page: usize) -> Mm {
+        let bottom = self.height - self.padding_on(page).bottom;
+        match self.bands.get(self.page_offset + page).bottom {
+            Some(footer) if footer < bottom => footer,
+            _ => bottom,
+        }
+    }
+
+    fn from_dimensions(dimensions: paper::PageDimensions, padding: Frame) -> Self {
+        BasePdf {
+            auto_height: dimensions.auto_height,
+            ..BasePdf::new(Mm(dimensions.width), Mm(dimensions.height), padding)
+        }
+    }
+
+    // ページ単位の上書き設定を適用したBasePdfを返す
+    // sizeを指定した場合はテンプレートの寸法ではなく用紙サイズから求める
+    fn with_page_override(&self, page: JsonPageBasePdf) -> Result<BasePdf, Error> {
+        let dimensions = match page.size.as_deref() {
+            Some(size) => paper::resolve_dimensions(
+                Some(size),
+                page.width,
+                page.height,
+                page.max_height,
+                page.orientation,
+            )?,
+            // heightを省略した場合は自動高さの設定もテンプレートから引き継ぐ
+            None => match page.height {
+                None if self.auto_height => paper::resolve_dimensions(
+                    None,
+                    Some(page.width.unwrap_or(self.width.0)),
+                    Some(paper::PageHeight::Auto),
+                    Some(page.max_height.unwrap_or(self.height.0)),
+                    page.orientation,
+                )?,
+                height => paper::resolve_dimensions(
+                    None,
+                    Some(page.width.unwrap_or(self.width.0)),
+                    Some(height.unwrap_or(paper::PageHeight::Length(self.height.0))),
+                    page.max_height,
+                    page.orientation,
+                )?,
+            },
+        };
+        let (padding, mirror_margins) = match page.padding {
+            Some(padding) => padding.resolve()?,
+            None => (self.padding.clone(), self.mirror_margins),
+        };
+        let first_page_padding = match page.first_page_padding {
+            Some(padding) => Some(padding.resolve()?.0),
+            None => self.first_page_padding.clone(),
+        };
+        let watermark = match page.watermark {
+            Some(watermark) => Some(Arc::new(watermark::Watermark::from_json(watermark)?)),
+            None => self.watermark.clone(),
+        };
+        let background = match page.background {
+            Some(background) => Some(Arc::new(background::Background::from_json(background)?)),
+            None => self.background.clone(),
+        };
+        Ok(BasePdf {
+            first_page_padding,
+            mirror_margins,
+            static_schema: self.static_schema.clone(),
+            rotate: page.rotate.unwrap_or(self.rotate),
+            rotate_mode: page.rotate_mode.unwrap_or(self.rotate_mode),
+            watermark,
+            background,
+            bleed: self.bleed,
+            spot_colors: self.spot_colors.clone(),
+            ..BasePdf::from_dimensions(dimensions, padding)
+        })
+    }
+}
+
+/// One output page's schemas together with the `BasePdf` it is laid out on.
+#[derive(Debug, Clone)]
+pub(crate) struct SchemaPage {
+    base_pdf: BasePdf,
+    schemas: Vec<Schema>,
+}
+
+/// The result of laying out a template's pages: the drawn ops per output page
+/// and, for each of those pages, the `BasePdf` that sizes it. Continuation
+/// pages created by an overflowing table or dynamic text inherit the
+/// `BasePdf` of the page the overflow started on.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LaidOutPages {
+    buffer: OpBuffer,
+    base_pdfs: Vec<BasePdf>,
+    extents: PageExtents,
+}
+
+impl LaidOutPages {
+    fn page_count(&self) -> usize {
+        self.buffer.page_count()
+    }
+
+    /// Inserts empty pages before and after the laid-out pages. They take the
+    /// size of the first and last page respectively.
+    fn add_blank_pages(&mut self, leading: usize, trailing: usize) {
+        let (Some(first), Some(last)) = (self.base_pdfs.first(), self.base_pdfs.last()) else {
+            return;
+        };
+        let (first, last) = (first.clone(), last.clone());
+
+        // 先頭に挿入した分だけバッファ上の番号がずれるので、文書上のページ番号を保つ
+        for base_pdf in &mut self.base_pdfs {
+            base_pdf.page_offset = base_pdf.page_offset.saturating_sub(leading);
+        }
+
+        self.buffer
+            .buffer
+            .splice(0..0, std::iter::repeat_n(Vec::new(), leading));
+        self.base_pdfs
+            .splice(0..0, std::iter::repeat_n(first, leading));
+        self.extents
+            .0
+            .splice(0..0, std::iter::repeat_n(Mm(0.0), leading));
+
+        self.buffer
+            .buffer
+            .extend(std::iter::repeat_n(Vec::new(), trailing));
+        self.base_pdfs.extend(std::iter::repeat_n(last, trailing));
+    }
+
+    /// Height of emitted page `page_idx`: the layout height, or for an
+    /// auto-height page its lowest drawn extent plus bottom padding.
+    fn page_height(&self, page_idx: usize) -> Mm {
+        let base_pdf = &self.base_pdfs[page_idx];
+        if base_pdf.auto_height {
+            let content_height = self.extents.get(page_idx) + base_pdf.padding_on(page_idx).bottom;
+            Mm(content_height.0.min(base_pdf.height.0))
+        } else {
+            base_pdf.height
+        }
+    }
+
+    /// Paints the watermark of page `page_idx`, if it has one, behind or
+    /// above what is already drawn there.
+    fn paint_watermark(
+        &mut self,
+        font_map: &FontMap,
+        doc: &mut PdfDocument,
+        page_idx: usize,
+    ) -> Result<(), Error> {
+        let base_pdf = &self.base_pdfs[page_idx];
+        let Some(watermark) = base_pdf.watermark.clone() else {
+            return Ok(());
+        };
+        let ops = watermark.render(
+            font_map,
+            doc,
+            base_pdf.width,
+            base_pdf.height,
+            self.page_height(page_idx),
+        )?;
+        let page = &mut self.buffer.buffer[page_idx];
+        match watermark.z_order {
+            watermark::ZOrder::Behind => {
+                page.splice(0..0, ops);
+            }
+            watermark::ZOrder::Above => page.extend(ops),
+        }
+        Ok(())
+    }
+
+    /// Paints the background of page `page_idx`, if it has one, under
+    /// everything already drawn there.
+    fn paint_background(&mut self, doc: &mut PdfDocument, page_idx: usize) -> Result<(), Error> {
+        let base_pdf = &self.base_pdfs[page_idx];
+        let Some(background) = base_pdf.background.clone() else {
+            return Ok(());
+        };
+        let ops = background.render(
+            doc,
+            base_pdf.width,
+            base_pdf.height,
+            self.page_height(page_idx),
+            base_pdf.bleed.size,
+        )?;
+        self.buffer.buffer[page_idx].splice(0..0, ops);
+        Ok(())
+    }
+
+    /// Turns the laid-out ops into printpdf pages, shrinking every
+    /// auto-height page to its lowest drawn extent plus bottom padding,
+    /// adding the bleed and printer's marks and applying
+    /// `rotateMode: "content"` rotation.
+    fn into_pdf_pages(self) -> Vec<PdfPage> {
+        let heights: Vec<Mm> = (0..self.base_pdfs.len())
+            .map(|page_idx| self.page_height(page_idx))
+            .collect();
+        self.buffer
+            .buffer
+            .into_iter()
+            .zip(self.base_pdfs)
+            .zip(heights)
+            .map(|((ops, base_pdf), height)| {
+                let (ops, height) = if base_pdf.auto_height {
+                    // 描画はレイアウト高さ基準の座標なので、縮めた分だけ下にずらす
+                    let shift: Pt = (base_pdf.height - height).into();
+                    let mut shifted = Vec::with_capacity(ops.len() + 3);
+                    shifted.push(Op::SaveGraphicsState);
+                    shifted.push(Op::SetTransformationMatrix {
+                        matrix: CurTransMat::Translate(Pt(0.0), Pt(-shift.0)),
+                    });
+                    shifted.extend(ops);
+                    shifted.push(Op::RestoreGraphicsState);
+                    (shifted, height)
+                } else {
+                    (ops, base_pdf.height)
+                };
+
+                let (ops, width, height) = base_pdf.bleed.extend(base_pdf.width, height, ops);
+                if base_pdf.rotate_mode == rotation::RotateMode::Content {
+                    let (ops, width, height) =
+                        rotation::rotate_content(base_pdf.rotate, width, height, ops);
+                    PdfPage::new(width, height, ops)
+                } else {
+                    PdfPage::new(width, height, ops)
+                }
+            })
+            .collect()
+    }
+
+    /// Trim and bleed boxes for each page; pages without a bleed or marks
+    /// get none.
+    fn page_boxes(&self) -> Vec<Option<bleed::PageBoxes>> {
+        self.base_pdfs
+            .iter()
+            .enumerate()
+            .map(|(page_idx, base_pdf)| {
+                let rotation = match base_pdf.rotate_mode {
+                    rotation::RotateMode::Page => rotation::Rotation::None,
+                    rotation::RotateMode::Content => base_pdf.rotate,
+                };
+                base_pdf
+                    .bleed
+                    .page_boxes(base_pdf.width, self.page_height(page_idx), rotation)
+            })
+            .collect()
+    }
+
+    /// The spot colours drawn on each page. Fails if a page uses one its
+    /// `basePdf.spotColors` does not define.
+    fn separations(&self) -> Result<Vec<Vec<color::Separation>>, Error> {
+        self.buffer
+            .buffer
+            .iter()
+            .zip(&self.base_pdfs)
+            .map(|(ops, base_pdf)| base_pdf.spot_colors.used_in(ops))
+            .collect()
+    }
+
+    /// `/Rotate` for each page; pages rotated in content mode get none.
+    fn page_rotations(&self) -> Vec<rotation::Rotation> {
+        self.base_pdfs
+            .iter()
+            .map(|base_pdf| match base_pdf.rotate_mode {
+                rotation::RotateMode::Page => base_pdf.rotate,
+                rotation::RotateMode::Content => rotation::Rotation::None,
+            })
+            .collect()
+    }
+}
+
+/// The lowest point, measured from the top edge, that anything was drawn at
+/// on each page. Only auto-height pages use it.
+#[derive(Debug, Clone, Default)]
+struct PageExtents(Vec<Mm>);
+
+impl PageExtents {
+    fn get(&self, page: usize) -> Mm {
+        self.0.get(page).copied().unwrap_or(Mm(0.0))
+    }
+
+    fn record(&mut self, page: usize, bottom: Mm) {
+        if self.0.len() <= page {
+            self.0.resize(page + 1, Mm(0.0));
+        }
+        if bottom > self.0[page] {
+            self.0[page] = bottom;
+        }
+    }
+
+    fn record_schema(&mut self, page: usize, schema: &Schema) {
+        let bounds = schema.bounds();
+        self.record(page, bounds.y + bounds.height);
+    }
+
+    /// Records a flowing schema that started on `start_page` and ended at
+    /// `end`. Every page it overflowed from was filled to the bottom padding.
+    fn record_flow(&mut self, base_pdf: &BasePdf, start_page: usize, end: (usize, Option<Mm>)) {
+        let (end_page, end_y) = end;
+        for page in start_page..end_page {
+            self.record(page, base_pdf.flow_bottom(page));
+        }
+        if let Some(y) = end_y {
+            self.record(end_page, y);
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JsonFrame {
+    #[serde(deserialize_with = "units::deserialize_mm")]
     top: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     right: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     bottom: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     left: f32,
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Frame {
+    #[serde(deserialize_with = "units::deserialize_length")]
     top: Mm,
+    #[serde(deserialize_with = "units::deserialize_length")]
     right: Mm,
+    #[serde(deserialize_with = "units::deserialize_length")]
     bottom: Mm,
+    #[serde(deserialize_with = "units::deserialize_length")]
     left: Mm,
 }
 
@@ -455,11 +1072,67 @@ impl TryFrom<Vec<f32>> for Frame {
     }
 }
 
+/// A `staticSchema` entry. The JSON is rendered per page with the page
+/// context; `pages` decides which pages it is painted on.
+#[derive(Debug, Clone)]
+struct StaticSchema {
+    pages: page_selector::PageSelector,
+    band: Option<band::Band>,
+    /// Mirror the x position on even pages.
+    mirror: bool,
+    json: serde_json::Value,
+}
+
+impl StaticSchema {
+    // `pages`・`band`・`mirror`はスキーマ本体の項目ではないので取り除いてから保持する
+    fn from_json(mut json: serde_json::Value) -> Result<StaticSchema, Error> {
+        let pages = match json
+            .as_object_mut()
+            .and_then(|object| object.remove("pages"))
+        {
+            Some(pages) => serde_json::from_value(pages).context(TemplateDeserializeSnafu {
+                message: "Failed to parse staticSchema pages",
+            })?,
+            None => page_selector::PageSelector::All,
+        };
+        let band = match json
+            .as_object_mut()
+            .and_then(|object| object.remove("band"))
+        {
+            Some(band) => Some(
+                serde_json::from_value(band).context(TemplateDeserializeSnafu {
+                    message: "Failed to parse staticSchema band",
+                })?,
+            ),
+            None => None,
+        };
+        let mirror = match json
+            .as_object_mut()
+            .and_then(|object| object.remove("mirror"))
+        {
+            Some(mirror) => serde_json::from_value(mirror).context(TemplateDeserializeSnafu {
+                message: "Failed to parse staticSchema mirror",
+            })?,
+            None => false,
+        };
+        Ok(StaticSchema {
+            pages,
+            band,
+            mirror,
+            json,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Template {
     pub schemas: Vec<serde_json::Value>,
-    base_pdf: BasePdf,
-    static_schema_json: Vec<serde_json::Value>,
+    page_base_pdfs: Vec<BasePdf>,
+    static_schemas: Vec<StaticSchema>,
+    /// The template's own `basePdf.viewerPreferences`.
+    viewer_preferences: viewer::ViewerPreferences,
+    /// The forge-wide defaults for fields no template sets.
+    viewer_defaults: viewer::ViewerPreferences,
 }
 
 impl Template {
@@ -473,21 +1146,87 @@ impl Template {
         // Parse static schemas if they exist
         let static_schemas = Self::parse_static_schemas(&json.base_pdf.static_schema)?;
 
+        let dimensions = paper::resolve_dimensions(
+            json.base_pdf.size.as_deref(),
+            json.base_pdf.width,
+            json.base_pdf.height,
+            json.base_pdf.max_height,
+            json.base_pdf.orientation,
+        )?;
+
+        let (padding, mirror_margins) = json.base_pdf.padding.resolve()?;
+        let bleed = json.base_pdf.bleed.unwrap_or(0.0);
+        if bleed < 0.0 {
+            return Err(Error::InvalidPageSetup {
+                message: format!("bleed must not be negative, got {bleed}"),
+            });
+        }
         let base_pdf = BasePdf {
-            width: Mm(json.base_pdf.width),
-            height: Mm(json.base_pdf.height),
-            padding: json.base_pdf.padding.try_into()?,
+            first_page_padding: match json.base_pdf.first_page_padding {
+                Some(padding) => Some(padding.resolve()?.0),
+                None => None,
+            },
+            mirror_margins,
             static_schema: static_schemas,
+            rotate: json.base_pdf.rotate,
+            rotate_mode: json.base_pdf.rotate_mode,
+            watermark: match json.base_pdf.watermark {
+                Some(watermark) => Some(Arc::new(watermark::Watermark::from_json(watermark)?)),
+                None => None,
+            },
+            background: match json.base_pdf.background {
+                Some(background) => Some(Arc::new(background::Background::from_json(background)?)),
+                None => None,
+            },
+            bleed: bleed::Bleed {
+                size: Mm(bleed),
+                marks: json.base_pdf.printer_marks,
+            },
+            spot_colors: Arc::new(color::SpotColors::from_json(json.base_pdf.spot_colors)?),
+            ..BasePdf::from_dimensions(dimensions, padding)
         };
 
+        // ページが {"basePdf": ..., "schemas": [...]} 形式の場合は上書き設定を取り出す
+        let mut schemas = Vec::with_capacity(json.schemas.len());
+        let mut page_base_pdfs = Vec::with_capacity(json.schemas.len());
+        for page in json.schemas {
+            if page.is_object() {
+                let page: JsonPage =
+                    serde_json::from_value(page).context(TemplateDeserializeSnafu {
+                        message: "Failed to parse page with basePdf override",
+                    })?;
+                page_base_pdfs.push(match page.base_pdf {
+                    Some(page_base_pdf) => base_pdf.with_page_override(page_base_pdf)?,
+                    None => base_pdf.clone(),
+                });
+                schemas.push(page.schemas);
+            } else {
+                page_base_pdfs.push(base_pdf.clone());
+                schemas.push(page);
+            }
+        }
+
         let template = Template {
-            schemas: json.schemas,
-            base_pdf,
-            static_schema_json: json.base_pdf.static_schema.clone(),
+            schemas,
+            page_base_pdfs,
+            static_schemas: json
+                .base_pdf
+                .static_schema
+                .into_iter()
+                .map(StaticSchema::from_json)
+                .collect::<Result<_, _>>()?,
+            viewer_preferences: json.base_pdf.viewer_preferences,
+            viewer_defaults: viewer::ViewerPreferences::default(),
         };
         Ok(template)
     }
 
+    /// Sets the forge-wide defaults for the viewer preferences this template
+    /// leaves unset.
+    pub(crate) fn inherit_viewer_preferences(&mut self, defaults: &viewer::ViewerPreferences) {
+        self.viewer_defaults = defaults.clone();
+    }
+
     // Parse static schemas from JSON (placeholder - actual parsing happens during rendering)
     fn parse_static_schemas(
         _static_schema_json: &[serde_json::Value],
@@ -529,21 +1268,39 @@ impl Template {
     }
 
     // Render static schemas with special variables and custom inputs
+    // `bands_only`ならheader/footerのバンドだけを返す。バンドは内容に合わせて伸ばしてある
     fn render_static_schemas_for_page(
         &self,
         font_map: &FontMap,
         current_page: usize,
         total_pages: usize,
         static_inputs: &HashMap<&'static str, String>,
-    ) -> Result<Vec<Schema>, Error> {
-        if self.static_schema_json.is_empty() {
+        bands_only: bool,
+    ) -> Result<Vec<(&StaticSchema, Schema)>, Error> {
+        if self.static_schemas.is_empty() {
             return Ok(Vec::new());
         }
 
         // Create context with special variables and custom inputs
         let context = Self::create_special_context(current_page, total_pages, static_inputs);
 
-        let json_schemas = Self::render_schema_json_values(&self.static_schema_json, &context)?;
+        // `pages`で対象外のページに当たるstatic schemaは描画しない
+        let mut entries = Vec::with_capacity(self.static_schemas.len());
+        let mut selected = Vec::with_capacity(self.static_schemas.len());
+        for static_schema in &self.static_schemas {
+            if bands_only && static_schema.band.is_none() {
+                continue;
+            }
+            if static_schema
+                .pages
+                .matches(current_page + 1, total_pages, &context)?
+            {
+                entries.push(static_schema);
+                selected.push(static_schema.json.clone());
+            }
+        }
+
+        let json_schemas = Self::render_schema_json_values(&selected, &context)?;
 
         // Convert JSON schemas to Schema objects
         let schemas: Result<Vec<Schema>, Error> = json_schemas
@@ -556,6 +1313,9 @@ impl Template {
                     )),
                     JsonSchema::Table(json) => Ok(Schema::Table(Table::from_json(json, font_map)?)),
                     JsonSchema::QrCode(json) => Ok(json.into()),
+                    JsonSchema::Barcode(json) => Ok(Schema::Barcode(barcode::Barcode::from_json(
+                        json, font_map,
+                    )?)),
                     JsonSchema::Image(json) => {
                         Ok(json.try_into().map_err(|e| Error::SchemaConversion {
                             schema_type: "Image".to_string(),
@@ -591,7 +1351,51 @@ impl Template {
             })
             .collect();
 
-        schemas
+        entries
+            .into_iter()
+            .zip(schemas?)
+            .map(|(entry, mut schema)| {
+                if let Some(band) = entry.band {
+                    band.fit(&mut schema)?;
+                }
+                Ok((entry, schema))
+            })
+            .collect()
+    }
+
+    fn has_bands(&self) -> bool {
+        self.static_schemas
+            .iter()
+            .any(|static_schema| static_schema.band.is_some())
+    }
+
+    // 文書の`first_page`から`page_count`ページ分のバンドの範囲を測る
+    fn band_edges(
+        &self,
+        font_map: &FontMap,
+        first_page: usize,
+        page_count: usize,
+        total_pages: usize,
+        static_inputs: &HashMap<&'static str, String>,
+    ) -> Result<band::BandEdges, Error> {
+        if !self.has_bands() {
+            return Ok(band::BandEdges::default());
+        }
+        let pages = (first_page..first_page + page_count)
+            .map(|page| {
+                let bands = self.render_static_schemas_for_page(
+                    font_map,
+                    page,
+                    total_pages,
+                    static_inputs,
+                    true,
+                )?;
+                Ok(band::PageBand::from_schemas(bands.iter().filter_map(
+                    |(entry, schema)| entry.band.map(|band| (band, schema)),
+                )))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(band::BandEdges::new(first_page, pages))
     }
 
     fn render_json_value_strings(
@@ -670,10 +1474,10 @@ impl Template {
         font_map: &FontMap,
         static_inputs: HashMap<&'static str, String>,
     ) -> Result<Vec<u8>, Error> {
-        let mut schemas: Vec<Vec<Schema>> = Vec::new();
+        let mut schemas: Vec<SchemaPage> = Vec::new();
 
         // 各ページのschemaを直接変換（テンプレート処理なし）
-        for page_schema in &self.schemas {
+        for (page_schema, base_pdf) in self.schemas.iter().zip(&self.page_base_pdfs) {
             let json_schemas: Vec<JsonSchema> = serde_json::from_value(page_schema.clone())
                 .map_err(|e| Error::TemplateDeserialize {
                     source: e,
@@ -694,6 +1498,9 @@ impl Template {
                             Ok(Schema::Table(Table::from_json(json, font_map)?))
                         }
                         JsonSchema::QrCode(json) => Ok(json.into()),
+                        JsonSchema::Barcode(json) => Ok(Schema::Barcode(
+                            barcode::Barcode::from_json(json, font_map)?,
+                        )),
                         JsonSchema::Image(json) => {
                             Ok(json.try_into().map_err(|e| Error::SchemaConversion {
                                 schema_type: "Image".to_string(),
@@ -726,7 +1533,10 @@ impl Template {
                 })
                 .collect::<Result<Vec<Schema>, Error>>()?;
 
-            schemas.push(converted);
+            schemas.push(SchemaPage {
+                base_pdf: base_pdf.clone(),
+                schemas: converted,
+            });
         }
 
         self.render_schemas_with_static_inputs(font_map, doc, schemas, static_inputs)
@@ -741,6 +1551,18 @@ impl Template {
         table_data: HashMap<&'static str, Vec<Vec<String>>>,
         static_inputs: HashMap<&'static str, String>,
     ) -> Result<Vec<u8>, Error> {
+        let schemas = self.schemas_from_inputs(font_map, inputs, &table_data)?;
+
+        self.render_schemas_with_static_inputs(font_map, doc, schemas, static_inputs)
+    }
+
+    // 入力データからページごとのSchemaを生成する
+    pub(crate) fn schemas_from_inputs(
+        &self,
+        font_map: &FontMap,
+        inputs: Vec<Vec<HashMap<&'static str, String>>>,
+        table_data: &HashMap<&'static str, Vec<Vec<String>>>,
+    ) -> Result<Vec<SchemaPage>, Error> {
         if inputs.len() != self.schemas.len() {
             return Err(Error::Whatever {
                 message: "Input length does not match page length".to_string(),
@@ -748,7 +1570,7 @@ impl Template {
             });
         }
 
-        let mut schemas: Vec<Vec<Schema>> = Vec::new();
+        let mut schemas: Vec<SchemaPage> = Vec::new();
 
         for (index, group) in inputs.iter().enumerate() {
             let mut json_schemas: Vec<Vec<JsonSchema>> = Vec::new();
@@ -801,6 +1623,9 @@ impl Template {
                                     Ok(Schema::Table(Table::from_json(json, font_map)?))
                                 }
                                 JsonSchema::QrCode(json) => Ok(json.into()),
+                                JsonSchema::Barcode(json) => Ok(Schema::Barcode(
+                                    barcode::Barcode::from_json(json, font_map)?,
+                                )),
                                 JsonSchema::Image(json) => {
                                     Ok(json.try_into().map_err(|e| Error::SchemaConversion {
                                         schema_type: "Image".to_string(),
@@ -837,10 +1662,13 @@ impl Template {
                 })
                 .collect::<Result<Vec<Vec<Schema>>, Error>>()?;
 
-            schemas.extend(converted);
+            schemas.extend(converted.into_iter().map(|page| SchemaPage {
+                base_pdf: self.page_base_pdfs[index].clone(),
+                schemas: page,
+            }));
         }
 
-        self.render_schemas_with_static_inputs(font_map, doc, schemas, static_inputs)
+        Ok(schemas)
     }
 
     // static inputs対応の共通レンダリング処理
@@ -848,144 +1676,374 @@ impl Template {
         &self,
         font_map: &FontMap,
         doc: &mut PdfDocument,
-        schemas: Vec<Vec<Schema>>,
+        schemas: Vec<SchemaPage>,
         static_inputs: HashMap<&'static str, String>,
     ) -> Result<Vec<u8>, Error> {
-        let mut buffer = OpBuffer::default();
+        render_sequence(font_map, doc, vec![(self, schemas)], &static_inputs)
+    }
+
+    // ページ本文（static schema以外）をレイアウトする
+    // `page_offset`はバッファ先頭ページの文書全体でのページ番号（0始まり）
+    fn layout_schemas(
+        &self,
+        doc: &mut PdfDocument,
+        pages: Vec<SchemaPage>,
+        page_offset: usize,
+        bands: Arc<band::BandEdges>,
+    ) -> Result<LaidOutPages, Error> {
+        let mut laid_out = LaidOutPages::default();
+        let LaidOutPages {
+            buffer,
+            base_pdfs,
+            extents,
+        } = &mut laid_out;
         // First render all page content to determine actual page count
-        for (page_index, page) in schemas.iter().enumerate() {
+        for (page_index, page) in pages.iter().enumerate() {
+            let base_pdf = &BasePdf {
+                page_offset,
+                origin_page: page_index,
+                bands: bands.clone(),
+                ..page.base_pdf.clone()
+            };
+            // テンプレート上のページは自身のbasePdfを持つ。前ページのはみ出しで
+            // 既に作られていても上書きする
+            if base_pdfs.len() <= page_index {
+                base_pdfs.resize(page_index + 1, base_pdf.clone());
+            }
+            base_pdfs[page_index] = base_pdf.clone();
+
             let mut flow_cursor = FlowCursor::new(page_index);
-            for schema in page {
+            for schema in &page.schemas {
                 match schema {
                     Schema::Text(obj) => {
                         let mut obj = obj.clone();
-                        obj.render(self.base_pdf.height, page_index, &mut buffer)?;
+                        obj.render(base_pdf.height, page_index, buffer)?;
                     }
                     Schema::DynamicText(obj) => {
                         let mut obj = obj.clone();
-                        (flow_cursor.page, flow_cursor.y) = obj.render(
-                            &self.base_pdf,
-                            flow_cursor.page,
-                            flow_cursor.y,
-                            &mut buffer,
-                        )?;
+                        let start_page = flow_cursor.page;
+                        (flow_cursor.page, flow_cursor.y) =
+                            obj.render(base_pdf, flow_cursor.page, flow_cursor.y, buffer)?;
+                        extents.record_flow(
+                            base_pdf,
+                            start_page,
+                            (flow_cursor.page, flow_cursor.y),
+                        );
+                        continue;
                     }
                     Schema::Table(obj) => {
                         let mut obj = obj.clone();
-                        (flow_cursor.page, flow_cursor.y) = obj.render(
-                            &self.base_pdf,
-                            doc,
-                            flow_cursor.page,
-                            flow_cursor.y,
-                            &mut buffer,
-                        )?;
+                        let start_page = flow_cursor.page;
+                        (flow_cursor.page, flow_cursor.y) =
+                            obj.render(base_pdf, doc, flow_cursor.page, flow_cursor.y, buffer)?;
+                        extents.record_flow(
+                            base_pdf,
+                            start_page,
+                            (flow_cursor.page, flow_cursor.y),
+                        );
+                        continue;
                     }
                     Schema::QrCode(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_index, &mut buffer)?;
-                    }
-                    Schema::Image(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_index, &mut buffer)?
-                    }
-                    Schema::Svg(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_index, &mut buffer)?
-                    }
-                    Schema::Rect(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_index, &mut buffer)?
-                    }
-                    Schema::Line(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_index, &mut buffer)?
+                        obj.render(base_pdf.height, doc, page_index, buffer)?;
                     }
+                    Schema::Barcode(obj) => obj.render(base_pdf.height, page_index, buffer)?,
+                    Schema::Image(obj) => obj.render(base_pdf.height, doc, page_index, buffer)?,
+                    Schema::Svg(obj) => obj.render(base_pdf.height, doc, page_index, buffer)?,
+                    Schema::Rect(obj) => obj.render(base_pdf.height, doc, page_index, buffer)?,
+                    Schema::Line(obj) => obj.render(base_pdf.height, doc, page_index, buffer)?,
                     Schema::Group(obj) => {
                         let mut obj = obj.clone();
-                        obj.render(&self.base_pdf, doc, page_index, &mut buffer)?;
+                        obj.render(base_pdf, doc, page_index, buffer)?;
+                    }
+                    Schema::Spacer(obj) => {
+                        obj.advance(base_pdf, &mut flow_cursor);
+                        if let Some(y) = flow_cursor.y {
+                            extents.record(flow_cursor.page, y);
+                        }
+                        continue;
                     }
-                    Schema::Spacer(obj) => obj.advance(&self.base_pdf, &mut flow_cursor),
                 }
+                extents.record_schema(page_index, schema);
+            }
+
+            // はみ出しで増えたページはこのページのbasePdfを引き継ぐ
+            while base_pdfs.len() < buffer.page_count() {
+                base_pdfs.push(base_pdf.clone());
             }
         }
 
-        // Now we know the actual number of pages, add static schemas to each page
-        let actual_page_count = buffer.buffer.len();
+        // 何も描画されなかった末尾ページの設定は捨てる
+        base_pdfs.truncate(buffer.page_count());
 
-        // Add static schemas to each page
-        for page_idx in 0..actual_page_count {
-            let static_schemas = self.render_static_schemas_for_page(
-                font_map,
-                page_idx,
-                actual_page_count,
-                &static_inputs,
-            )?;
-            for static_schema in static_schemas {
-                match static_schema {
-                    Schema::Text(mut obj) => {
-                        obj.render(self.base_pdf.height, page_idx, &mut buffer)?;
-                    }
-                    Schema::DynamicText(mut obj) => {
-                        // For static schemas, we render directly to the specific page
-                        let temp_current_page = page_idx;
-                        let temp_y = None;
-                        let _ =
-                            obj.render(&self.base_pdf, temp_current_page, temp_y, &mut buffer)?;
-                    }
-                    Schema::Table(mut obj) => {
-                        // For static schemas, we render directly to the specific page
-                        // let temp_current_page = page_idx;
-                        let temp_y = None;
-                        let _ = obj.render(&self.base_pdf, doc, page_idx, temp_y, &mut buffer)?;
-                    }
-                    Schema::QrCode(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_idx, &mut buffer)?;
-                    }
-                    Schema::Image(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_idx, &mut buffer)?
-                    }
-                    Schema::Svg(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_idx, &mut buffer)?
-                    }
-                    Schema::Rect(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_idx, &mut buffer)?
-                    }
-                    Schema::Line(obj) => {
-                        obj.render(self.base_pdf.height, doc, page_idx, &mut buffer)?
-                    }
-                    Schema::Group(mut obj) => {
-                        obj.render(&self.base_pdf, doc, page_idx, &mut buffer)?;
-                    }
-                    Schema::Spacer(_) => {
-                        return Err(Error::UnsupportedSchema {
-                            context: "staticSchema".to_string(),
-                            schema_type: "Spacer".to_string(),
-                        });
-                    }
+        Ok(laid_out)
+    }
+
+    // 1ページ分のstatic schemaを描画する。`current_page`と`total_pages`は文書全体での値
+    #[allow(clippy::too_many_arguments)]
+    fn render_static_schemas_on_page(
+        &self,
+        font_map: &FontMap,
+        doc: &mut PdfDocument,
+        buffer: &mut OpBuffer,
+        extents: &mut PageExtents,
+        base_pdf: &BasePdf,
+        page_idx: usize,
+        current_page: usize,
+        total_pages: usize,
+        static_inputs: &HashMap<&'static str, String>,
+    ) -> Result<(), Error> {
+        let static_schemas = self.render_static_schemas_for_page(
+            font_map,
+            current_page,
+            total_pages,
+            static_inputs,
+            false,
+        )?;
+        for (entry, mut static_schema) in static_schemas {
+            // 偶数ページでは左右を反転した位置に置く
+            if entry.mirror && current_page % 2 == 1 {
+                let base = static_schema.base_mut();
+                base.x = base_pdf.width - base.x - base.width;
+            }
+            extents.record_schema(page_idx, &static_schema);
+            match static_schema {
+                Schema::Text(mut obj) => {
+                    obj.render(base_pdf.height, page_idx, buffer)?;
+                }
+                Schema::DynamicText(mut obj) => {
+                    // For static schemas, we render directly to the specific page
+                    let temp_current_page = page_idx;
+                    let temp_y = None;
+                    let _ = obj.render(base_pdf, temp_current_page, temp_y, buffer)?;
+                }
+                Schema::Table(mut obj) => {
+                    // For static schemas, we render directly to the specific page
+                    // let temp_current_page = page_idx;
+                    let temp_y = None;
+                    let _ = obj.render(base_pdf, doc, page_idx, temp_y, buffer)?;
+                }
+                Schema::QrCode(obj) => {
+                    obj.render(base_pdf.height, doc, page_idx, buffer)?;
+                }
+                Schema::Barcode(obj) => obj.render(base_pdf.height, page_idx, buffer)?,
+                Schema::Image(obj) => obj.render(base_pdf.height, doc, page_idx, buffer)?,
+                Schema::Svg(obj) => obj.render(base_pdf.height, doc, page_idx, buffer)?,
+                Schema::Rect(obj) => obj.render(base_pdf.height, doc, page_idx, buffer)?,
+                Schema::Line(obj) => obj.render(base_pdf.height, doc, page_idx, buffer)?,
+                Schema::Group(mut obj) => {
+                    obj.render(base_pdf, doc, page_idx, buffer)?;
+                }
+                Schema::Spacer(_) => {
+                    return Err(Error::UnsupportedSchema {
+                        context: "staticSchema".to_string(),
+                        schema_type: "Spacer".to_string(),
+                    });
                 }
             }
         }
+        Ok(())
+    }
+}
+
+/// Lays out each `(template, schema pages)` section in order and saves them as
+/// one document.
+///
+/// Every section is laid out against its own template's `BasePdf`, so page
+/// sizes may change between sections. Static schemas are painted after all
+/// sections are laid out, so `currentPage` / `totalPages` count across the
+/// whole document rather than restarting per section.
+pub(crate) fn render_sequence(
+    font_map: &FontMap,
+    doc: &mut PdfDocument,
+    sections: Vec<(&Template, Vec<SchemaPage>)>,
+    static_inputs: &HashMap<&'static str, String>,
+) -> Result<Vec<u8>, Error> {
+    let preferences = viewer_preferences(sections.iter().map(|(template, _)| *template));
+    let laid_out = lay_out_sequence(
+        font_map,
+        doc,
+        sections,
+        static_inputs,
+        &imposition::BlankPages::default(),
+    )?;
+    let pdf = save_laid_out_pages(doc, laid_out)?;
+
+    viewer::apply(pdf, &preferences)
+}
+
+/// Viewer preferences of a document made of pages of `templates`: each field
+/// comes from the first template that sets it, and the forge-wide defaults
+/// fill the fields none of them sets.
+pub(crate) fn viewer_preferences<'a>(
+    templates: impl IntoIterator<Item = &'a Template>,
+) -> viewer::ViewerPreferences {
+    let (preferences, defaults) = templates.into_iter().fold(
+        Default::default(),
+        |(preferences, defaults): (viewer::ViewerPreferences, viewer::ViewerPreferences),
+         template| {
+            (
+                preferences.or(&template.viewer_preferences),
+                defaults.or(&template.viewer_defaults),
+            )
+        },
+    );
+    preferences.or(&defaults)
+}
 
-        let mut pages: Vec<PdfPage> = Vec::new();
+// ページを保存し、rotateMode "page" の回転を /Rotate として、裁ち落としを各ボックスとして、
+// 特色をSeparation色空間として書き込む
+fn save_laid_out_pages(
+    doc: &mut PdfDocument,
+    laid_out: Vec<LaidOutPages>,
+) -> Result<Vec<u8>, Error> {
+    let rotations: Vec<rotation::Rotation> = laid_out
+        .iter()
+        .flat_map(LaidOutPages::page_rotations)
+        .collect();
+    let boxes: Vec<Option<bleed::PageBoxes>> =
+        laid_out.iter().flat_map(LaidOutPages::page_boxes).collect();
+    let mut separations = Vec::new();
+    for section in &laid_out {
+        separations.extend(section.separations()?);
+    }
+    let pages = laid_out
+        .into_iter()
+        .flat_map(LaidOutPages::into_pdf_pages)
+        .collect();
+
+    let pdf = rotation::apply_page_rotation(save_pages(doc, pages), &rotations)?;
+    let pdf = bleed::apply_page_boxes(pdf, &boxes)?;
+    color::apply_separations(pdf, &separations)
+}
 
-        for ops in buffer.buffer {
-            let page = PdfPage::new(self.base_pdf.width, self.base_pdf.height, ops);
-            pages.push(page)
+/// Layout passes allowed while header/footer bands settle on a page count.
+const MAX_BAND_PASSES: usize = 4;
+
+/// One layout pass over all sections: each section's pages with the leading
+/// blank pages to insert before them, and the page count including those.
+type SectionsLaidOut<'a> = (Vec<(&'a Template, LaidOutPages, usize)>, usize);
+
+// 全セクションをレイアウトし、文書全体の通し番号でstatic schemaを描画する
+// `padding`で挿入する白紙ページも通し番号に含めるが、static schemaは描画しない
+fn lay_out_sequence(
+    font_map: &FontMap,
+    doc: &mut PdfDocument,
+    sections: Vec<(&Template, Vec<SchemaPage>)>,
+    static_inputs: &HashMap<&'static str, String>,
+    padding: &imposition::BlankPages,
+) -> Result<Vec<LaidOutPages>, Error> {
+    // header/footerのバンドはtotalPagesによって高さが変わりうるので、
+    // 総ページ数が推定と一致するまでレイアウトをやり直す
+    let pristine = sections
+        .iter()
+        .any(|(template, _)| template.has_bands())
+        .then(|| doc.clone());
+    let mut total_guess: usize = sections.iter().map(|(_, pages)| pages.len()).sum();
+    let mut sections = sections;
+    let mut pass = 1;
+    let (sections_laid_out, page_count) = loop {
+        let final_pass = pristine.is_none() || pass == MAX_BAND_PASSES;
+        let input = if final_pass {
+            std::mem::take(&mut sections)
+        } else {
+            sections.clone()
+        };
+        let laid_out = lay_out_sections(font_map, doc, input, static_inputs, padding, total_guess)?;
+        let total = laid_out.1 + padding.trailing_blanks(laid_out.1);
+        if final_pass || total == total_guess {
+            break laid_out;
+        }
+        if let Some(pristine) = &pristine {
+            *doc = pristine.clone();
+        }
+        total_guess = total;
+        pass += 1;
+    };
+    let trailing_blanks = padding.trailing_blanks(page_count);
+    let total_pages = page_count + trailing_blanks;
+    let mut current_page = 0;
+
+    let section_count = sections_laid_out.len();
+    let mut result = Vec::with_capacity(section_count);
+    for (index, (template, mut laid_out, leading_blanks)) in
+        sections_laid_out.into_iter().enumerate()
+    {
+        current_page += leading_blanks;
+        for page_idx in 0..laid_out.page_count() {
+            template.render_static_schemas_on_page(
+                font_map,
+                doc,
+                &mut laid_out.buffer,
+                &mut laid_out.extents,
+                &laid_out.base_pdfs[page_idx],
+                page_idx,
+                current_page,
+                total_pages,
+                static_inputs,
+            )?;
+            laid_out.paint_watermark(font_map, doc, page_idx)?;
+            laid_out.paint_background(doc, page_idx)?;
+            current_page += 1;
         }
+        let trailing = if index + 1 == section_count {
+            trailing_blanks
+        } else {
+            0
+        };
+        laid_out.add_blank_pages(leading_blanks, trailing);
+        result.push(laid_out);
+    }
 
-        let mut warn = Vec::new();
-        let bytes = doc.with_pages(pages).save(
-            &PdfSaveOptions {
-                optimize: false,
-                subset_fonts: true,
-                secure: false,
-                image_optimization: None,
-            },
-            &mut warn,
-        );
+    Ok(result)
+}
 
-        Ok(bytes)
+// 各セクションのページ本文をレイアウトする。バンドは総ページ数を`total_pages`と仮定して測る
+fn lay_out_sections<'a>(
+    font_map: &FontMap,
+    doc: &mut PdfDocument,
+    sections: Vec<(&'a Template, Vec<SchemaPage>)>,
+    static_inputs: &HashMap<&'static str, String>,
+    padding: &imposition::BlankPages,
+    total_pages: usize,
+) -> Result<SectionsLaidOut<'a>, Error> {
+    // 白紙ページの数は前のセクションまでのページ数で決まるので、レイアウトしながら求める
+    let mut sections_laid_out = Vec::with_capacity(sections.len());
+    let mut page_count = 0;
+    for (index, (template, schemas)) in sections.into_iter().enumerate() {
+        let blanks = padding.leading_blanks(index, page_count);
+        let first_page = page_count + blanks;
+        let bands = template.band_edges(
+            font_map,
+            first_page,
+            total_pages.saturating_sub(first_page).max(schemas.len()),
+            total_pages,
+            static_inputs,
+        )?;
+        let laid_out = template.layout_schemas(doc, schemas, first_page, Arc::new(bands))?;
+        page_count = first_page + laid_out.page_count();
+        sections_laid_out.push((template, laid_out, blanks));
     }
+    Ok((sections_laid_out, page_count))
+}
+
+fn save_pages(doc: &mut PdfDocument, pages: Vec<PdfPage>) -> Vec<u8> {
+    let mut warn = Vec::new();
+    doc.with_pages(pages).save(
+        &PdfSaveOptions {
+            optimize: false,
+            subset_fonts: true,
+            secure: false,
+            image_optimization: None,
+        },
+        &mut warn,
+    )
 }
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct JsonPosition {
+    #[serde(deserialize_with = "units::deserialize_mm")]
     x: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     y: f32,
 }
 
@@ -1009,9 +2067,13 @@ pub enum VerticalAlignment {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JsonBoundingBox {
+    #[serde(deserialize_with = "units::deserialize_mm")]
     pub x: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     pub y: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     pub width: f32,
+    #[serde(deserialize_with = "units::deserialize_mm")]
     pub height: f32,
 }
 
@@ -0,0 +1,195 @@
+//! The `pages` selector of a static schema.
+//!
+//! A static schema without `pages` is painted on every page. Otherwise it is
+//! painted only on the pages the selector picks: `"first"`, `"last"`,
+//! `"odd"`, `"even"`, a list of 1-based page ranges such as `"2-"`, `"-3"` or
+//! `"1,3,5-7"`, or a Tera boolean expression such as
+//! `"currentPage < totalPages"`.
+
+use serde::{Deserialize, Deserializer};
+
+use super::Error;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum PageSelector {
+    #[default]
+    All,
+    First,
+    Last,
+    Odd,
+    Even,
+    /// Inclusive 1-based ranges; `None` leaves that end open.
+    Ranges(Vec<(Option<usize>, Option<usize>)>),
+    /// A Tera expression evaluated with the static schema context.
+    Expression(String),
+}
+
+impl PageSelector {
+    pub fn parse(raw: &str) -> Result<PageSelector, String> {
+        let trimmed = raw.trim();
+        match trimmed {
+            "" => return Err("empty pages selector".to_string()),
+            "all" => return Ok(PageSelector::All),
+            "first" => return Ok(PageSelector::First),
+            "last" => return Ok(PageSelector::Last),
+            "odd" => return Ok(PageSelector::Odd),
+            "even" => return Ok(PageSelector::Even),
+            _ => {}
+        }
+
+        let is_range_list = trimmed
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | ',' | ' '));
+        if is_range_list {
+            return trimmed
+                .split(',')
+                .map(|range| parse_range(range.trim()))
+                .collect::<Result<Vec<_>, _>>()
+                .map(PageSelector::Ranges)
+                .map_err(|e| format!("invalid pages {raw:?}: {e}"));
+        }
+
+        // 式は読み込み時に構文だけ確認し、評価は描画時に行う
+        tera::Tera::default()
+            .add_raw_template("pages", &Self::expression_template(trimmed))
+            .map_err(|e| format!("invalid pages expression {raw:?}: {e}"))?;
+        Ok(PageSelector::Expression(trimmed.to_string()))
+    }
+
+    fn expression_template(expression: &str) -> String {
+        format!("{{% if {expression} %}}true{{% endif %}}")
+    }
+
+    /// Whether the 1-based `current_page` of `total_pages` is selected.
+    /// `context` is the static schema context the expression is evaluated in.
+    pub fn matches(
+        &self,
+        current_page: usize,
+        total_pages: usize,
+        context: &tera::Context,
+    ) -> Result<bool, Error> {
+        Ok(match self {
+            PageSelector::All => true,
+            PageSelector::First => current_page == 1,
+            PageSelector::Last => current_page == total_pages,
+            PageSelector::Odd => !current_page.is_multiple_of(2),
+            PageSelector::Even => current_page.is_multiple_of(2),
+            PageSelector::Ranges(ranges) => ranges.iter().any(|(start, end)| {
+                start.is_none_or(|start| start <= current_page)
+                    && end.is_none_or(|end| current_page <= end)
+            }),
+            PageSelector::Expression(expression) => {
+                let rendered =
+                    tera::Tera::one_off(&Self::expression_template(expression), context, false)
+                        .map_err(|e| Error::Whatever {
+                            message: format!("Failed to evaluate pages expression {expression:?}"),
+                            source: Some(Box::new(e)),
+                        })?;
+                rendered == "true"
+            }
+        })
+    }
+}
+
+fn parse_range(range: &str) -> Result<(Option<usize>, Option<usize>), String> {
+    let page = |number: &str| -> Result<Option<usize>, String> {
+        let number = number.trim();
+        if number.is_empty() {
+            return Ok(None);
+        }
+        match number.parse::<usize>() {
+            Ok(0) => Err("pages are numbered from 1".to_string()),
+            Ok(page) => Ok(Some(page)),
+            Err(_) => Err(format!("{number:?} is not a page number")),
+        }
+    };
+
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (page(start)?, page(end)?),
+        None => {
+            let page = page(range)?;
+            (page, page)
+        }
+    };
+    match (start, end) {
+        (None, None) => Err(format!("{range:?} is not a page range")),
+        (Some(start), Some(end)) if start > end => {
+            Err(format!("range {range:?} ends before it starts"))
+        }
+        range => Ok(range),
+    }
+}
+
+impl<'de> Deserialize<'de> for PageSelector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Page(usize),
+            Selector(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Page(0) => Err(serde::de::Error::custom("pages are numbered from 1")),
+            Raw::Page(page) => Ok(PageSelector::Ranges(vec![(Some(page), Some(page))])),
+            Raw::Selector(raw) => PageSelector::parse(&raw).map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected(selector: &str, total_pages: usize) -> Vec<usize> {
+        let selector = PageSelector::parse(selector).unwrap();
+        (1..=total_pages)
+            .filter(|&page| {
+                let mut context = tera::Context::new();
+                context.insert("currentPage", &page);
+                context.insert("totalPages", &total_pages);
+                selector.matches(page, total_pages, &context).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keywords_pick_their_pages() {
+        assert_eq!(selected("first", 4), vec![1]);
+        assert_eq!(selected("last", 4), vec![4]);
+        assert_eq!(selected("odd", 4), vec![1, 3]);
+        assert_eq!(selected("even", 4), vec![2, 4]);
+    }
+
+    #[test]
+    fn ranges_may_be_open_and_listed() {
+        assert_eq!(selected("2-", 4), vec![2, 3, 4]);
+        assert_eq!(selected("-2", 4), vec![1, 2]);
+        assert_eq!(selected("1, 3-4", 5), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn expressions_see_the_page_numbers() {
+        assert_eq!(selected("currentPage < totalPages", 3), vec![1, 2]);
+        assert_eq!(selected("currentPage % 3 == 0", 6), vec![3, 6]);
+    }
+
+    #[test]
+    fn malformed_selectors_are_rejected() {
+        for selector in ["0", "3-1", "-", "1--2", "currentPage <", ""] {
+            assert!(
+                PageSelector::parse(selector).is_err(),
+                "{selector:?} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn a_bare_number_selects_one_page() {
+        let selector: PageSelector = serde_json::from_value(serde_json::json!(2)).unwrap();
+        assert_eq!(selector, PageSelector::Ranges(vec![(Some(2), Some(2))]));
+    }
+}
@@ -0,0 +1,424 @@
+//! Named paper sizes and page orientation for `basePdf`.
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::units;
+use super::Error;
+
+/// Layout height of an auto-height page when no `maxHeight` is given: 200in,
+/// the largest page dimension PDF viewers are required to support. Content
+/// shorter than this never paginates.
+pub const AUTO_HEIGHT_LIMIT_MM: f32 = 5080.0;
+
+/// `basePdf.height`: either a length or `"auto"`, which sizes each page to
+/// its content (for continuous receipt rolls).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageHeight {
+    Length(f32),
+    Auto,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonPageHeight {
+    Number(f32),
+    Text(String),
+}
+
+/// Deserializes an optional `basePdf.height`. Use together with
+/// `#[serde(default)]`.
+pub(crate) fn deserialize_optional_page_height<'de, D>(
+    deserializer: D,
+) -> Result<Option<PageHeight>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let height = match Option::<JsonPageHeight>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(JsonPageHeight::Number(value)) => PageHeight::Length(value),
+        Some(JsonPageHeight::Text(raw)) if raw.trim().eq_ignore_ascii_case("auto") => {
+            PageHeight::Auto
+        }
+        Some(JsonPageHeight::Text(raw)) => {
+            PageHeight::Length(units::parse_length_mm(&raw).ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "invalid height {raw:?}; expected \"auto\" or a length such as \"297mm\""
+                ))
+            })?)
+        }
+    };
+    Ok(Some(height))
+}
+
+/// Page orientation. `portrait` makes the page at least as tall as it is
+/// wide, `landscape` at least as wide as it is tall; the two dimensions are
+/// swapped when needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+impl Orientation {
+    /// Orders `(width, height)` to match the orientation.
+    pub fn apply(self, width: f32, height: f32) -> (f32, f32) {
+        let (short, long) = if width <= height {
+            (width, height)
+        } else {
+            (height, width)
+        };
+        match self {
+            Orientation::Portrait => (short, long),
+            Orientation::Landscape => (long, short),
+        }
+    }
+}
+
+/// Dimensions of a named paper size in millimetres, in portrait orientation.
+///
+/// Continuous rolls (thermal receipt paper) have no fixed length, so their
+/// `height` is `None` and the template has to give one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaperSize {
+    pub width: f32,
+    pub height: Option<f32>,
+}
+
+/// Looks up a paper size by name.
+///
+/// Names are case-insensitive, and spaces, `-` and `_` are ignored, so
+/// `"JIS B5"`, `"jis-b5"` and `"JISB5"` are the same size. Supported:
+///
+/// - ISO 216 `A0`–`A10`, `B0`–`B10`, and ISO 269 envelopes `C4`–`C6`, `DL`
+/// - JIS P 0138 `JIS-B0`–`JIS-B10`
+/// - US `Letter`, `Legal`, `Tabloid` / `Ledger`, `Executive`, `Statement`
+/// - Japanese postcard `Postcard` (`Hagaki`)
+/// - Label stock `Label-4x6`, `Label-4x3`, `Label-2x1`, `Label-62x100`,
+///   `Label-62x29`
+/// - Continuous thermal rolls `Receipt-58mm` and `Receipt-80mm` (width only)
+pub fn lookup(name: &str) -> Option<PaperSize> {
+    let key: String = name
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    let fixed = |width: f32, height: f32| {
+        Some(PaperSize {
+            width,
+            height: Some(height),
+        })
+    };
+    let roll = |width: f32| {
+        Some(PaperSize {
+            width,
+            height: None,
+        })
+    };
+
+    if let Some(n) = key.strip_prefix("jisb") {
+        return series_size(JIS_B_SERIES, n);
+    }
+    if let Some(n) = key.strip_prefix('a') {
+        if let Some(size) = series_size(ISO_A_SERIES, n) {
+            return Some(size);
+        }
+    }
+    if let Some(n) = key.strip_prefix('b') {
+        if let Some(size) = series_size(ISO_B_SERIES, n) {
+            return Some(size);
+        }
+    }
+
+    match key.as_str() {
+        "c4" => fixed(229.0, 324.0),
+        "c5" => fixed(162.0, 229.0),
+        "c6" => fixed(114.0, 162.0),
+        "dl" => fixed(110.0, 220.0),
+        "letter" => fixed(215.9, 279.4),
+        "legal" => fixed(215.9, 355.6),
+        "tabloid" | "ledger" => fixed(279.4, 431.8),
+        "executive" => fixed(184.15, 266.7),
+        "statement" => fixed(139.7, 215.9),
+        "postcard" | "hagaki" => fixed(100.0, 148.0),
+        "label4x6" => fixed(101.6, 152.4),
+        "label4x3" => fixed(101.6, 76.2),
+        "label2x1" => fixed(50.8, 25.4),
+        "label62x100" => fixed(62.0, 100.0),
+        "label62x29" => fixed(62.0, 29.0),
+        "receipt58mm" | "receipt58" => roll(58.0),
+        "receipt80mm" | "receipt80" => roll(80.0),
+        _ => None,
+    }
+}
+
+/// ISO 216 A series, A0–A10.
+const ISO_A_SERIES: &[(f32, f32)] = &[
+    (841.0, 1189.0),
+    (594.0, 841.0),
+    (420.0, 594.0),
+    (297.0, 420.0),
+    (210.0, 297.0),
+    (148.0, 210.0),
+    (105.0, 148.0),
+    (74.0, 105.0),
+    (52.0, 74.0),
+    (37.0, 52.0),
+    (26.0, 37.0),
+];
+
+/// ISO 216 B series, B0–B10.
+const ISO_B_SERIES: &[(f32, f32)] = &[
+    (1000.0, 1414.0),
+    (707.0, 1000.0),
+    (500.0, 707.0),
+    (353.0, 500.0),
+    (250.0, 353.0),
+    (176.0, 250.0),
+    (125.0, 176.0),
+    (88.0, 125.0),
+    (62.0, 88.0),
+    (44.0, 62.0),
+    (31.0, 44.0),
+];
+
+/// JIS P 0138 B series, B0–B10. Slightly larger than ISO B.
+const JIS_B_SERIES: &[(f32, f32)] = &[
+    (1030.0, 1456.0),
+    (728.0, 1030.0),
+    (515.0, 728.0),
+    (364.0, 515.0),
+    (257.0, 364.0),
+    (182.0, 257.0),
+    (128.0, 182.0),
+    (91.0, 128.0),
+    (64.0, 91.0),
+    (45.0, 64.0),
+    (32.0, 45.0),
+];
+
+fn series_size(series: &[(f32, f32)], index: &str) -> Option<PaperSize> {
+    // Only plain decimal digits: `"a+4"` or `"a04"` are not sizes.
+    if index.is_empty() || index.len() > 2 || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if index.len() == 2 && index.starts_with('0') {
+        return None;
+    }
+    let (width, height) = *series.get(index.parse::<usize>().ok()?)?;
+    Some(PaperSize {
+        width,
+        height: Some(height),
+    })
+}
+
+/// Page dimensions resolved from a `basePdf`, in millimetres.
+///
+/// For an auto-height page `height` is the layout height — `maxHeight`, or
+/// [`AUTO_HEIGHT_LIMIT_MM`] without one — and the emitted page is shrunk to
+/// its content afterwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PageDimensions {
+    pub width: f32,
+    pub height: f32,
+    pub auto_height: bool,
+}
+
+/// Resolves `size`, `width`, `height`, `maxHeight` and `orientation` into
+/// page dimensions. See [`resolve_page_size`] for how a named size combines
+/// with explicit dimensions.
+pub(crate) fn resolve_dimensions(
+    size: Option<&str>,
+    width: Option<f32>,
+    height: Option<PageHeight>,
+    max_height: Option<f32>,
+    orientation: Option<Orientation>,
+) -> Result<PageDimensions, Error> {
+    match height {
+        Some(PageHeight::Auto) => {
+            if orientation == Some(Orientation::Landscape) {
+                return Err(Error::InvalidPageSetup {
+                    message: "`height: \"auto\"` cannot be combined with a landscape orientation"
+                        .to_string(),
+                });
+            }
+            let layout_height = max_height.unwrap_or(AUTO_HEIGHT_LIMIT_MM);
+            if layout_height <= 0.0 {
+                return Err(Error::InvalidPageSetup {
+                    message: format!("maxHeight must be positive, got {layout_height}"),
+                });
+            }
+            let (width, height) = resolve_page_size(size, width, Some(layout_height), None)?;
+            Ok(PageDimensions {
+                width,
+                height,
+                auto_height: true,
+            })
+        }
+        height => {
+            if max_height.is_some() {
+                return Err(Error::InvalidPageSetup {
+                    message: "maxHeight only applies when height is \"auto\"".to_string(),
+                });
+            }
+            let height = height.map(|height| match height {
+                PageHeight::Length(value) => value,
+                PageHeight::Auto => unreachable!(),
+            });
+            let (width, height) = resolve_page_size(size, width, height, orientation)?;
+            Ok(PageDimensions {
+                width,
+                height,
+                auto_height: false,
+            })
+        }
+    }
+}
+
+/// Resolves the page dimensions of a `basePdf`, in millimetres.
+///
+/// `size` supplies both dimensions; an explicit `width` or `height` replaces
+/// the corresponding one (which is how a receipt roll gets its length).
+/// Without a `size`, `width` and `height` are both required. `orientation`
+/// is applied last.
+pub(crate) fn resolve_page_size(
+    size: Option<&str>,
+    width: Option<f32>,
+    height: Option<f32>,
+    orientation: Option<Orientation>,
+) -> Result<(f32, f32), Error> {
+    let paper = match size {
+        Some(name) => Some(lookup(name).ok_or_else(|| Error::UnknownPaperSize {
+            name: name.to_string(),
+        })?),
+        None => None,
+    };
+
+    let width =
+        width
+            .or(paper.map(|paper| paper.width))
+            .ok_or_else(|| Error::InvalidPageSetup {
+                message: "basePdf needs either `size` or `width`".to_string(),
+            })?;
+    let height = height
+        .or(paper.and_then(|paper| paper.height))
+        .ok_or_else(|| Error::InvalidPageSetup {
+            message: match size {
+                Some(name) => format!("paper size {name:?} is a continuous roll; give `height`"),
+                None => "basePdf needs either `size` or `height`".to_string(),
+            },
+        })?;
+
+    Ok(match orientation {
+        Some(orientation) => orientation.apply(width, height),
+        None => (width, height),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso_and_jis_series_are_distinct() {
+        assert_eq!(lookup("A4").unwrap().height, Some(297.0));
+        assert_eq!(lookup("B5").unwrap().width, 176.0);
+        assert_eq!(lookup("JIS-B5").unwrap().width, 182.0);
+        assert_eq!(lookup("jis b4").unwrap().height, Some(364.0));
+    }
+
+    #[test]
+    fn names_ignore_case_and_separators() {
+        assert_eq!(lookup("letter"), lookup("LETTER"));
+        assert_eq!(lookup("receipt_80mm"), lookup("Receipt-80mm"));
+        assert_eq!(lookup("Label 4x6"), lookup("label-4x6"));
+    }
+
+    #[test]
+    fn unknown_and_malformed_names_are_rejected() {
+        for name in ["", "A11", "A04", "A-1x", "B+1", "Foolscap", "JIS-B"] {
+            assert_eq!(lookup(name), None, "{name:?} should not be a size");
+        }
+    }
+
+    #[test]
+    fn orientation_swaps_dimensions_only_when_needed() {
+        assert_eq!(Orientation::Landscape.apply(210.0, 297.0), (297.0, 210.0));
+        assert_eq!(Orientation::Landscape.apply(297.0, 210.0), (297.0, 210.0));
+        assert_eq!(Orientation::Portrait.apply(297.0, 210.0), (210.0, 297.0));
+    }
+
+    #[test]
+    fn explicit_dimensions_override_the_named_size() {
+        let (width, height) =
+            resolve_page_size(Some("Receipt-80mm"), None, Some(200.0), None).unwrap();
+        assert_eq!((width, height), (80.0, 200.0));
+
+        let (width, height) =
+            resolve_page_size(Some("A4"), None, None, Some(Orientation::Landscape)).unwrap();
+        assert_eq!((width, height), (297.0, 210.0));
+    }
+
+    #[test]
+    fn auto_height_lays_out_against_max_height_or_the_pdf_limit() {
+        let auto = resolve_dimensions(
+            Some("Receipt-58mm"),
+            None,
+            Some(PageHeight::Auto),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            auto,
+            PageDimensions {
+                width: 58.0,
+                height: AUTO_HEIGHT_LIMIT_MM,
+                auto_height: true
+            }
+        );
+
+        let capped =
+            resolve_dimensions(None, Some(80.0), Some(PageHeight::Auto), Some(300.0), None)
+                .unwrap();
+        assert_eq!(capped.height, 300.0);
+        assert!(capped.auto_height);
+    }
+
+    #[test]
+    fn max_height_and_landscape_are_rejected_where_they_make_no_sense() {
+        let fixed = resolve_dimensions(
+            None,
+            Some(80.0),
+            Some(PageHeight::Length(200.0)),
+            Some(300.0),
+            None,
+        )
+        .unwrap_err();
+        assert!(fixed.to_string().contains("maxHeight"));
+
+        let landscape = resolve_dimensions(
+            Some("Receipt-80mm"),
+            None,
+            Some(PageHeight::Auto),
+            None,
+            Some(Orientation::Landscape),
+        )
+        .unwrap_err();
+        assert!(landscape.to_string().contains("landscape"));
+    }
+
+    #[test]
+    fn a_roll_without_height_or_an_unknown_size_is_an_error() {
+        let roll = resolve_page_size(Some("Receipt-58mm"), None, None, None).unwrap_err();
+        assert!(roll.to_string().contains("continuous roll"));
+
+        let unknown = resolve_page_size(Some("A42"), None, None, None).unwrap_err();
+        assert!(unknown.to_string().contains("A42"));
+
+        let missing = resolve_page_size(None, Some(100.0), None, None).unwrap_err();
+        assert!(missing.to_string().contains("height"));
+    }
+}
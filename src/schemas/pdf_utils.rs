@@ -1,3 +1,4 @@
+use super::color::PdfColor;
 use super::Frame;
 use crate::font;
 use printpdf::*;
@@ -67,6 +68,71 @@ pub fn calculate_transform_matrix_with_center_pivot(
     ]
 }
 
+/// Fills rectangles given as `(left, bottom, right, top)` in one path, so
+/// touching rectangles print without hairline seams between them.
+pub fn fill_rectangles(rectangles: impl IntoIterator<Item = (Pt, Pt, Pt, Pt)>) -> Op {
+    let rings = rectangles
+        .into_iter()
+        .map(|(left, bottom, right, top)| PolygonRing {
+            points: [(left, bottom), (right, bottom), (right, top), (left, top)]
+                .into_iter()
+                .map(|(x, y)| LinePoint {
+                    p: Point { x, y },
+                    bezier: false,
+                })
+                .collect(),
+        })
+        .collect();
+    Op::DrawPolygon {
+        polygon: Polygon {
+            rings,
+            mode: PaintMode::Fill,
+            winding_order: WindingOrder::NonZero,
+        },
+    }
+}
+
+/// A rectangle whose corners are rounded with cubic Bézier arcs of `radius`;
+/// a radius of half the side of a square gives a circle.
+pub fn rounded_rectangle_ring(left: Pt, bottom: Pt, right: Pt, top: Pt, radius: Pt) -> PolygonRing {
+    // 円弧を3次ベジェ曲線で近似するときの制御点の係数
+    const KAPPA: f32 = 0.552_284_8;
+    let r = radius.0;
+    let k = r * (1.0 - KAPPA);
+    let (left, bottom, right, top) = (left.0, bottom.0, right.0, top.0);
+    let corner =
+        |points: [(f32, f32); 3]| [(points[0], true), (points[1], true), (points[2], false)];
+    let mut points = vec![((left + r, bottom), false), ((right - r, bottom), false)];
+    points.extend(corner([
+        (right - k, bottom),
+        (right, bottom + k),
+        (right, bottom + r),
+    ]));
+    points.push(((right, top - r), false));
+    points.extend(corner([
+        (right, top - k),
+        (right - k, top),
+        (right - r, top),
+    ]));
+    points.push(((left + r, top), false));
+    points.extend(corner([(left + k, top), (left, top - k), (left, top - r)]));
+    points.push(((left, bottom + r), false));
+    points.extend(corner([
+        (left, bottom + k),
+        (left + k, bottom),
+        (left + r, bottom),
+    ]));
+    PolygonRing {
+        points: points
+            .into_iter()
+            .map(|((x, y), bezier)| LinePoint {
+                p: Point { x: Pt(x), y: Pt(y) },
+                bezier,
+            })
+            .collect(),
+    }
+}
+
 pub fn create_text_ops(
     bounding_matrix: [f32; 6],
     font_id: &FontId,
@@ -78,7 +144,7 @@ pub fn create_text_ops(
     character_spacing: Pt,
     line: &str,
     line_height: Option<f32>,
-    font_color: &csscolorparser::Color,
+    font_color: &PdfColor,
 ) -> Vec<Op> {
     create_text_ops_with_font(
         bounding_matrix,
@@ -96,6 +162,9 @@ pub fn create_text_ops(
     )
 }
 
+/// Draws `line` in `font_id`, or, given `fonts`, in runs of the chain's
+/// fonts: each cluster in the first font that has it, tofu only where none
+/// has. A chain's synthetic bold and oblique are applied here.
 pub fn create_text_ops_with_font(
     bounding_matrix: [f32; 6],
     font_id: &FontId,
@@ -107,19 +176,25 @@ pub fn create_text_ops_with_font(
     character_spacing: Pt,
     line: &str,
     line_height: Option<f32>,
-    font_color: &csscolorparser::Color,
-    font: Option<&ParsedFont>,
+    font_color: &PdfColor,
+    fonts: Option<&font::FontChain>,
 ) -> Vec<Op> {
-    let matrix_values = calculate_transform_matrix(x_line, y, scale_x, scale_y);
+    let mut matrix_values = calculate_transform_matrix(x_line, y, scale_x, scale_y);
+    let bold = fonts.is_some_and(|fonts| fonts.synthetic_bold());
+    if fonts.is_some_and(|fonts| fonts.synthetic_oblique()) {
+        matrix_values[2] = matrix_values[3] * font::OBLIQUE_SKEW;
+    }
     let matrix = TextMatrix::Raw(matrix_values);
 
-    // Sanitize text if font is provided, otherwise use original text
-    let sanitized_line = match font {
-        Some(font_ref) => sanitize_text_for_font(line, font_ref),
-        None => line.to_string(),
+    // フォントチェーンがあれば、グリフを持つフォントごとのランに分けて描画する
+    let runs = match fonts {
+        Some(fonts) => fonts.runs(line),
+        None => vec![(font_id, line.to_string())],
     };
+    let mut runs = runs.into_iter();
+    let (font_id, first_run) = runs.next().unwrap_or((font_id, String::new()));
 
-    vec![
+    let mut ops = vec![
         Op::SaveGraphicsState,
         Op::SetTransformationMatrix {
             matrix: CurTransMat::Raw(bounding_matrix),
@@ -128,14 +203,21 @@ pub fn create_text_ops_with_font(
         Op::SetLineHeight {
             lh: font_size * line_height.unwrap_or(1.0),
         },
-        Op::SetFillColor {
-            col: Color::Rgb(Rgb {
-                r: font_color.r,
-                g: font_color.g,
-                b: font_color.b,
-                icc_profile: None,
-            }),
-        },
+    ];
+    ops.extend(font_color.fill_ops());
+    // 太字の字形がなければアウトラインを重ねて擬似的に太くする
+    if bold {
+        ops.extend(font_color.stroke_ops());
+        ops.extend([
+            Op::SetOutlineThickness {
+                pt: font_size * font::BOLD_STROKE,
+            },
+            Op::SetTextRenderingMode {
+                mode: TextRenderingMode::FillStroke,
+            },
+        ]);
+    }
+    ops.extend([
         Op::SetFont {
             font: PdfFontHandle::External(font_id.clone()),
             size: font_size,
@@ -145,11 +227,23 @@ pub fn create_text_ops_with_font(
             multiplier: character_spacing.0,
         },
         Op::ShowText {
-            items: vec![TextItem::Text(sanitized_line)],
+            items: vec![TextItem::Text(first_run)],
         },
-        Op::EndTextSection,
-        Op::RestoreGraphicsState,
-    ]
+    ]);
+    // 後続のランは前のランの終わりから続けて描画される
+    for (font_id, run) in runs {
+        ops.extend([
+            Op::SetFont {
+                font: PdfFontHandle::External(font_id.clone()),
+                size: font_size,
+            },
+            Op::ShowText {
+                items: vec![TextItem::Text(run)],
+            },
+        ]);
+    }
+    ops.extend([Op::EndTextSection, Op::RestoreGraphicsState]);
+    ops
 }
 
 #[derive(Debug, Clone)]
@@ -160,9 +254,9 @@ pub struct DrawRectangle {
     pub height: Mm,
     pub rotate: Option<f32>,
     pub page_height: Mm,
-    pub color: Option<Color>,
+    pub color: Option<PdfColor>,
     pub border_width: Option<Mm>,
-    pub border_color: Option<Color>,
+    pub border_color: Option<PdfColor>,
 }
 
 pub fn draw_rectangle(props: DrawRectangle) -> Vec<Op> {
@@ -186,19 +280,9 @@ pub fn draw_rectangle(props: DrawRectangle) -> Vec<Op> {
         PaintMode::FillStroke
     };
 
-    let color = props.color.unwrap_or(Color::Rgb(Rgb {
-        r: 1.0,
-        g: 1.0,
-        b: 1.0,
-        icc_profile: None,
-    }));
+    let color = props.color.unwrap_or_else(PdfColor::white);
 
-    let border_color = props.border_color.unwrap_or(Color::Rgb(Rgb {
-        r: 0.0,
-        g: 0.0,
-        b: 0.0,
-        icc_profile: None,
-    }));
+    let border_color = props.border_color.unwrap_or_else(PdfColor::black);
 
     let border_width = props.border_width.unwrap_or(Mm(0.1));
 
@@ -232,11 +316,10 @@ pub fn draw_rectangle(props: DrawRectangle) -> Vec<Op> {
         winding_order: WindingOrder::NonZero,
     };
 
-    vec![
-        Op::SaveGraphicsState,
-        matrixe,
-        Op::SetOutlineColor { col: border_color },
-        Op::SetFillColor { col: color },
+    let mut ops = vec![Op::SaveGraphicsState, matrixe];
+    ops.extend(border_color.stroke_ops());
+    ops.extend(color.fill_ops());
+    ops.extend([
         Op::SetOutlineThickness {
             pt: border_width.into(),
         },
@@ -244,7 +327,8 @@ pub fn draw_rectangle(props: DrawRectangle) -> Vec<Op> {
             polygon: polygon.clone(),
         },
         Op::RestoreGraphicsState,
-    ]
+    ]);
+    ops
 }
 
 /// Wrap `ops` so the element draws at the given `alpha` (0.0 = fully
@@ -287,8 +371,8 @@ pub(crate) struct DrawCell {
     pub height: Mm,
     pub rotate: Option<f32>,
     pub page_height: Mm,
-    pub fill: Color,
-    pub border_color: Color,
+    pub fill: PdfColor,
+    pub border_color: PdfColor,
     pub border_width: Frame,
 }
 
@@ -340,10 +424,8 @@ pub(crate) fn draw_table_cell(props: DrawCell) -> Vec<Op> {
         } else {
             PaintMode::Fill
         };
-        ops.push(Op::SetOutlineColor {
-            col: props.border_color,
-        });
-        ops.push(Op::SetFillColor { col: props.fill });
+        ops.extend(props.border_color.stroke_ops());
+        ops.extend(props.fill.fill_ops());
         ops.push(Op::SetOutlineThickness { pt: width_pt });
         ops.push(Op::DrawPolygon {
             polygon: Polygon {
@@ -356,7 +438,7 @@ pub(crate) fn draw_table_cell(props: DrawCell) -> Vec<Op> {
         });
     } else {
         // Fill first, then stroke each edge with its own thickness.
-        ops.push(Op::SetFillColor { col: props.fill });
+        ops.extend(props.fill.fill_ops());
         ops.push(Op::DrawPolygon {
             polygon: Polygon {
                 rings: vec![PolygonRing {
@@ -366,9 +448,7 @@ pub(crate) fn draw_table_cell(props: DrawCell) -> Vec<Op> {
                 winding_order: WindingOrder::NonZero,
             },
         });
-        ops.push(Op::SetOutlineColor {
-            col: props.border_color,
-        });
+        ops.extend(props.border_color.stroke_ops());
         let edges = [
             (Point { x: x1, y: y2 }, Point { x: x2, y: y2 }, bw.top),
             (Point { x: x2, y: y1 }, Point { x: x2, y: y2 }, bw.right),
@@ -415,18 +495,8 @@ mod tests {
             height: Mm(8.0),
             rotate: None,
             page_height: Mm(297.0),
-            fill: Color::Rgb(Rgb {
-                r: 1.0,
-                g: 1.0,
-                b: 1.0,
-                icc_profile: None,
-            }),
-            border_color: Color::Rgb(Rgb {
-                r: 0.0,
-                g: 0.0,
-                b: 0.0,
-                icc_profile: None,
-            }),
+            fill: PdfColor::white(),
+            border_color: PdfColor::black(),
             border_width,
         }
     }
@@ -455,7 +525,7 @@ mod tests {
             .collect()
     }
 
-    fn sample_rectangle(color: Option<Color>, border_color: Option<Color>) -> DrawRectangle {
+    fn sample_rectangle(color: Option<PdfColor>, border_color: Option<PdfColor>) -> DrawRectangle {
         DrawRectangle {
             x: Mm(10.0),
             y: Mm(10.0),
@@ -478,12 +548,11 @@ mod tests {
 
     #[test]
     fn rectangle_with_matching_fill_and_border_uses_fill_mode() {
-        let color = Color::Rgb(Rgb {
+        let color = PdfColor::Rgb {
             r: 0.5,
             g: 0.5,
             b: 0.5,
-            icc_profile: None,
-        });
+        };
         let ops = draw_rectangle(sample_rectangle(Some(color.clone()), Some(color)));
 
         assert_eq!(polygon_modes(&ops), vec![PaintMode::Fill]);
@@ -0,0 +1,430 @@
+//! Logos and module styles for QR codes drawn by the `qrCode` schema.
+//!
+//! The finder patterns can be drawn apart from the other modules, in their
+//! own shape and color, and a logo clears a square of modules in the middle
+//! of the symbol. The clear zone is checked against the codewords it
+//! destroys, so that at least half of the error correction is left for
+//! damage to the printed code.
+
+use std::collections::BTreeSet;
+
+use image::DynamicImage;
+use printpdf::{Op, PdfDocument, Polygon, PolygonRing, Pt, WindingOrder, XObjectTransform};
+use serde::Deserialize;
+
+use super::base::XOBJECT_DPI;
+use super::image::Image;
+use super::pdf_utils::{fill_rectangles, rounded_rectangle_ring};
+use super::symbology::{dark_runs, encoding_error, SymbolMatrix, Symbology};
+use super::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ModuleStyle {
+    #[default]
+    Square,
+    Rounded,
+    Dot,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FinderStyle {
+    #[default]
+    Square,
+    Rounded,
+    Circle,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonQrLogo {
+    image: String,
+    size: Option<f32>,
+    margin: Option<u32>,
+}
+
+/// An image drawn over the middle of a QR code.
+#[derive(Debug, Clone)]
+pub(crate) struct Logo {
+    /// Data URL of the image.
+    image: String,
+    /// Side of the image as a fraction of the symbol; the largest the error
+    /// correction can spare when `None`.
+    size: Option<f32>,
+    /// Light modules between the image and the remaining modules.
+    margin: usize,
+}
+
+impl From<JsonQrLogo> for Logo {
+    fn from(json: JsonQrLogo) -> Self {
+        Logo {
+            image: json.image,
+            size: json.size,
+            margin: json.margin.unwrap_or(1) as usize,
+        }
+    }
+}
+
+/// Styling drawn on top of a plain QR code.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct QrStyle {
+    pub(crate) module_style: ModuleStyle,
+    pub(crate) finder_style: FinderStyle,
+    pub(crate) finder_color: Option<String>,
+    pub(crate) logo: Option<Logo>,
+}
+
+impl QrStyle {
+    /// Fails for symbologies other than QR codes, whose finder patterns and
+    /// codeword layout the styling relies on.
+    pub(crate) fn check_symbology(&self, symbology: Symbology) -> Result<(), Error> {
+        let styled = self.module_style != ModuleStyle::Square
+            || self.separate_finders()
+            || self.logo.is_some();
+        if styled && symbology != Symbology::Qr {
+            return Err(encoding_error(
+                symbology,
+                "moduleStyle, finderStyle, finderColor and logo only apply to qr",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether the finder patterns are drawn on their own instead of with
+    /// the other modules.
+    pub(crate) fn separate_finders(&self) -> bool {
+        self.finder_style != FinderStyle::Square || self.finder_color.is_some()
+    }
+}
+
+/// The square of modules cleared for a logo, `size` modules wide with its
+/// top-left module at (`start`, `start`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClearZone {
+    pub(crate) start: usize,
+    pub(crate) size: usize,
+}
+
+impl ClearZone {
+    fn centered(width: usize, size: usize) -> Self {
+        ClearZone {
+            start: (width - size) / 2,
+            size,
+        }
+    }
+
+    pub(crate) fn contains(&self, x: usize, y: usize) -> bool {
+        (self.start..self.start + self.size).contains(&x)
+            && (self.start..self.start + self.size).contains(&y)
+    }
+
+    /// Checks that clearing the zone leaves the symbol readable: it must not
+    /// touch the patterns a reader locates the symbol by, and may destroy at
+    /// most half of the codewords the error correction can repair.
+    fn check(&self, symbol: &SymbolMatrix) -> Result<(), String> {
+        let map = symbol
+            .codewords
+            .as_ref()
+            .expect("QR codes carry their codeword map");
+        let width = symbol.width;
+        let modules = || {
+            (self.start..self.start + self.size)
+                .flat_map(move |y| (self.start..self.start + self.size).map(move |x| (x, y)))
+        };
+        if modules().any(|(x, y)| is_locator(width, x, y)) {
+            return Err(format!(
+                "a {0} × {0} logo clear zone would cover the finder, timing or format patterns",
+                self.size
+            ));
+        }
+        let destroyed: BTreeSet<usize> = modules()
+            .filter_map(|(x, y)| map.index[y * width + x])
+            .collect();
+        let budget = map.correctable / 2;
+        if destroyed.len() > budget {
+            return Err(format!(
+                "a {0} × {0} logo clear zone destroys {1} codewords, but only {budget} can be spared at this error correction level",
+                self.size,
+                destroyed.len()
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Finder patterns with their separators and format information, and the
+/// timing patterns.
+fn is_locator(width: usize, x: usize, y: usize) -> bool {
+    x == 6 || y == 6 || (x < 9 && y < 9) || (x < 9 && y + 8 >= width) || (x + 8 >= width && y < 9)
+}
+
+/// Whether the module belongs to one of the three 7 × 7 finder patterns.
+pub(crate) fn is_finder(width: usize, x: usize, y: usize) -> bool {
+    (x < 7 || x + 7 >= width) && (y < 7 || (x < 7 && y + 7 >= width))
+}
+
+impl Logo {
+    /// The zone cleared for the logo in `symbol`: the logo's `size` plus its
+    /// margin, or the largest zone the error correction can spare.
+    pub(crate) fn clear_zone(&self, symbol: &SymbolMatrix) -> Result<ClearZone, Error> {
+        let width = symbol.width;
+        let error = |message: String| encoding_error(Symbology::Qr, message);
+        match self.size {
+            Some(size) => {
+                if !(size > 0.0 && size < 1.0) {
+                    return Err(error("logo size must be between 0 and 1".to_string()));
+                }
+                // 中央に置けるよう、シンボルと同じ偶奇のモジュール数にする
+                let mut modules = (size * width as f32).ceil() as usize + 2 * self.margin;
+                if modules % 2 != width % 2 {
+                    modules += 1;
+                }
+                if modules > width {
+                    return Err(error("the logo is larger than the symbol".to_string()));
+                }
+                let zone = ClearZone::centered(width, modules);
+                zone.check(symbol).map_err(error)?;
+                Ok(zone)
+            }
+            None => (2 * self.margin + 1..=width)
+                .rev()
+                .step_by(2)
+                .map(|modules| ClearZone::centered(width, modules))
+                .find(|zone| zone.check(symbol).is_ok())
+                .ok_or_else(|| {
+                    error(
+                        "there is no room for the logo; use a higher errorCorrectionLevel or version"
+                            .to_string(),
+                    )
+                }),
+        }
+    }
+
+    /// Ops drawing the logo centred in `zone`, inside its margin. The top-left
+    /// module of the symbol has its top-left corner at `origin`.
+    pub(crate) fn ops(
+        &self,
+        doc: &mut PdfDocument,
+        zone: ClearZone,
+        module: Pt,
+        origin: (Pt, Pt),
+    ) -> Result<Vec<Op>, Error> {
+        let content = flatten_onto_white(Image::decode_base64_to_image_buffer(&self.image)?);
+        let image = Image::to_raw_image(&content)?;
+
+        // 余白を除いた正方形に縦横比を保って収める
+        let side = module * (zone.size - 2 * self.margin) as f32;
+        let (width, height) = (image.width as f32, image.height as f32);
+        let pt_per_pixel = side.0 / width.max(height);
+        let left = origin.0
+            + module * (zone.start + self.margin) as f32
+            + Pt((side.0 - width * pt_per_pixel) / 2.0);
+        let bottom = origin.1 - module * (zone.start + self.margin) as f32 - side
+            + Pt((side.0 - height * pt_per_pixel) / 2.0);
+        let scale = pt_per_pixel / (72.0 / XOBJECT_DPI);
+
+        let id = doc.add_image(&image);
+        Ok(vec![Op::UseXobject {
+            id,
+            transform: XObjectTransform {
+                translate_x: Some(left),
+                translate_y: Some(bottom),
+                rotate: None,
+                scale_x: Some(scale),
+                scale_y: Some(scale),
+                dpi: Some(XOBJECT_DPI),
+                no_auto_scale: false,
+            },
+        }])
+    }
+}
+
+// 画像は JPEG として埋め込まれるため、透過部分は白で塗りつぶす
+fn flatten_onto_white(image: DynamicImage) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return image;
+    }
+    let rgba = image.to_rgba8();
+    DynamicImage::ImageRgb8(image::RgbImage::from_fn(
+        rgba.width(),
+        rgba.height(),
+        |x, y| {
+            let [r, g, b, a] = rgba.get_pixel(x, y).0;
+            let blend =
+                |c: u8| ((u16::from(c) * u16::from(a) + 255 * (255 - u16::from(a))) / 255) as u8;
+            image::Rgb([blend(r), blend(g), blend(b)])
+        },
+    ))
+}
+
+/// The dark modules of `modules`, a `width` wide grid, drawn in `style`.
+/// Each module is `module.0` wide and `module.1` high; the top-left module
+/// has its top-left corner at `origin`.
+pub(crate) fn modules_op(
+    style: ModuleStyle,
+    modules: &[bool],
+    width: usize,
+    (module, row_height): (Pt, Pt),
+    origin: (Pt, Pt),
+) -> Op {
+    let runs = modules
+        .chunks(width)
+        .enumerate()
+        .flat_map(move |(row, modules)| {
+            let top = origin.1 - row_height * row as f32;
+            dark_runs(modules).map(move |(start, length)| {
+                let left = origin.0 + module * start as f32;
+                (left, top - row_height, left + module * length as f32, top)
+            })
+        });
+    // 行ごとに連続する暗モジュールを1つの図形にまとめる
+    match style {
+        ModuleStyle::Square => fill_rectangles(runs),
+        ModuleStyle::Rounded => fill_rings(
+            runs.map(|(left, bottom, right, top)| {
+                rounded_rectangle_ring(left, bottom, right, top, module / 3.0)
+            })
+            .collect(),
+            WindingOrder::NonZero,
+        ),
+        ModuleStyle::Dot => fill_rings(
+            runs.flat_map(|(left, bottom, right, top)| {
+                let count = ((right - left).0 / module.0).round() as usize;
+                (0..count).map(move |index| {
+                    let left = left + module * index as f32;
+                    rounded_rectangle_ring(left, bottom, left + module, top, module / 2.0)
+                })
+            })
+            .collect(),
+            WindingOrder::NonZero,
+        ),
+    }
+}
+
+/// The three finder patterns of a `width` wide QR code drawn in `style`.
+/// The top-left module has its top-left corner at `origin`.
+pub(crate) fn finders_op(style: FinderStyle, width: usize, module: Pt, origin: (Pt, Pt)) -> Op {
+    // 外枠・内側の白・中央の黒の角丸半径（モジュール単位）
+    let radii: [f32; 3] = match style {
+        FinderStyle::Square => [0.0; 3],
+        FinderStyle::Rounded => [2.0, 1.0, 1.0],
+        FinderStyle::Circle => [3.5, 2.5, 1.5],
+    };
+    let far = width - 7;
+    let rings = [(0, 0), (far, 0), (0, far)]
+        .into_iter()
+        .flat_map(|(column, row)| {
+            [(0, 7), (1, 5), (2, 3)]
+                .into_iter()
+                .zip(radii)
+                .map(move |((inset, size), radius)| {
+                    let left = origin.0 + module * (column + inset) as f32;
+                    let top = origin.1 - module * (row + inset) as f32;
+                    let side = module * size as f32;
+                    rounded_rectangle_ring(left, top - side, left + side, top, module * radius)
+                })
+        })
+        .collect();
+    // 偶奇規則で外枠の内側をくり抜く
+    fill_rings(rings, WindingOrder::EvenOdd)
+}
+
+fn fill_rings(rings: Vec<PolygonRing>, winding_order: WindingOrder) -> Op {
+    Op::DrawPolygon {
+        polygon: Polygon {
+            rings,
+            mode: printpdf::PaintMode::Fill,
+            winding_order,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schemas::symbology::SymbolOptions;
+
+    fn qr(version: u8, level: &str) -> SymbolMatrix {
+        let options = SymbolOptions {
+            error_correction: Some(serde_json::from_value(serde_json::json!(level)).unwrap()),
+            min_version: Some(version),
+            gs1: false,
+        };
+        Symbology::Qr.encoder().encode("PDFORGE", &options).unwrap()
+    }
+
+    fn logo(size: Option<f32>) -> Logo {
+        Logo {
+            image: String::new(),
+            size,
+            margin: 1,
+        }
+    }
+
+    #[test]
+    fn finder_patterns_sit_in_three_corners() {
+        let width = 21;
+        let finders = (0..width)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| is_finder(width, x, y))
+            .count();
+        assert_eq!(finders, 3 * 49);
+        assert!(is_finder(width, 20, 0));
+        assert!(is_finder(width, 0, 20));
+        assert!(!is_finder(width, 20, 20));
+    }
+
+    #[test]
+    fn automatic_clear_zone_is_the_largest_within_budget() {
+        let symbol = qr(5, "H");
+        let zone = logo(None).clear_zone(&symbol).unwrap();
+        assert!(zone.check(&symbol).is_ok());
+        // The next larger zone would destroy too many codewords.
+        let larger = ClearZone::centered(symbol.width, zone.size + 2);
+        assert!(larger.check(&symbol).is_err());
+        assert_eq!(zone.start * 2 + zone.size, symbol.width);
+    }
+
+    #[test]
+    fn higher_error_correction_spares_a_larger_zone() {
+        let low = logo(None).clear_zone(&qr(5, "L")).unwrap();
+        let high = logo(None).clear_zone(&qr(5, "H")).unwrap();
+        assert!(high.size > low.size, "{high:?} <= {low:?}");
+    }
+
+    #[test]
+    fn explicit_sizes_beyond_the_budget_are_rejected() {
+        let symbol = qr(5, "H");
+        // 37 modules: 20% is 8 modules plus a margin each side, rounded up to odd.
+        assert_eq!(
+            logo(Some(0.2)).clear_zone(&symbol).unwrap(),
+            ClearZone {
+                start: 13,
+                size: 11
+            }
+        );
+        assert!(logo(Some(0.6)).clear_zone(&symbol).is_err());
+        assert!(logo(Some(0.0)).clear_zone(&symbol).is_err());
+        assert!(logo(Some(1.5)).clear_zone(&symbol).is_err());
+    }
+
+    #[test]
+    fn zones_touching_the_timing_patterns_are_rejected() {
+        let symbol = qr(1, "H");
+        // Version 1 is 21 modules wide; a 9-module zone starts on the timing row.
+        let error = ClearZone::centered(21, 9).check(&symbol).unwrap_err();
+        assert!(error.contains("timing"), "{error}");
+    }
+
+    #[test]
+    fn styles_only_apply_to_qr_codes() {
+        let style = QrStyle {
+            module_style: ModuleStyle::Dot,
+            ..QrStyle::default()
+        };
+        assert!(style.check_symbology(Symbology::Qr).is_ok());
+        assert!(style.check_symbology(Symbology::DataMatrix).is_err());
+        assert!(QrStyle::default().check_symbology(Symbology::Aztec).is_ok());
+    }
+}
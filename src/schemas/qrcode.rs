@@ -1,12 +1,12 @@
-use super::{Alignment, BoundingBox, Frame, VerticalAlignment};
-use crate::schemas::{
-    base::{rotation_for_xobject, BaseSchema},
-    Error, HasBaseSchema, JsonPosition, Schema,
-};
+use super::color::PdfColor;
+use super::gs1::{ElementString, Gs1Syntax, DEFAULT_DIGITAL_LINK_DOMAIN};
+use super::pdf_utils::{calculate_transform_matrix_with_center_pivot, fill_rectangles};
+use super::qr_style::{self, FinderStyle, JsonQrLogo, ModuleStyle, QrStyle};
+use super::symbology::{ErrorCorrectionLevel, SymbolOptions, Symbology};
+use super::{Alignment, BoundingBox, Frame, InvalidColorSnafu, VerticalAlignment};
+use crate::schemas::{base::BaseSchema, Error, HasBaseSchema, JsonPosition, Schema};
 use crate::utils::OpBuffer;
-use image::codecs::png::PngEncoder;
-use image::{ExtendedColorType, ImageEncoder, Luma};
-use printpdf::{Mm, Op, PdfDocument, Px, RawImage};
+use printpdf::{CurTransMat, Mm, Op, PdfDocument, Pt};
 use serde::Deserialize;
 use snafu::ResultExt;
 
@@ -16,23 +16,93 @@ pub struct JsonQrCodeSchema {
     name: String,
     content: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     rotate: Option<f32>,
     alignment: Option<Alignment>,
     vertical_alignment: Option<VerticalAlignment>,
     padding: Option<Frame>,
+    #[serde(default)]
+    symbology: Symbology,
+    error_correction_level: Option<ErrorCorrectionLevel>,
+    version: Option<u8>,
+    quiet_zone: Option<u32>,
+    color: Option<String>,
+    background_color: Option<String>,
+    module_style: Option<ModuleStyle>,
+    finder_style: Option<FinderStyle>,
+    finder_color: Option<String>,
+    logo: Option<JsonQrLogo>,
+    gs1: Option<Gs1Syntax>,
+    digital_link_domain: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct QrCode {
     base: BaseSchema,
     content: String,
+    symbology: Symbology,
+    options: SymbolOptions,
+    quiet_zone: Option<u32>,
+    color: Option<String>,
+    background_color: Option<String>,
+    style: QrStyle,
+    gs1: Option<Gs1Syntax>,
+    digital_link_domain: Option<String>,
     rotate: Option<f32>,
-    alignment: Alignment,
-    vertical_alignment: VerticalAlignment,
+    placement: SymbolPlacement,
     bounding_box: Option<BoundingBox>,
-    padding: Option<Frame>,
+}
+
+/// How a machine-readable symbol sits in its schema's box: the box is shrunk
+/// by `padding` and the symbol is aligned inside what is left. Shared by the
+/// QR code and barcode schemas.
+#[derive(Debug, Clone)]
+pub(crate) struct SymbolPlacement {
+    pub(crate) alignment: Alignment,
+    pub(crate) vertical_alignment: VerticalAlignment,
+    pub(crate) padding: Option<Frame>,
+}
+
+impl SymbolPlacement {
+    // パディングを考慮したボックスの有効サイズを計算
+    pub(crate) fn inner_size(&self, width: Mm, height: Mm) -> (Mm, Mm) {
+        let padding = self.padding.as_ref();
+        (
+            width - padding.map_or(Mm(0.0), |p| p.left + p.right),
+            height - padding.map_or(Mm(0.0), |p| p.top + p.bottom),
+        )
+    }
+
+    /// Top-left corner of a `symbol_width` × `symbol_height` symbol placed in
+    /// the box at (`x`, `y`) whose inner size is `inner_width` × `inner_height`.
+    pub(crate) fn origin(
+        &self,
+        (x, y): (Mm, Mm),
+        (inner_width, inner_height): (Mm, Mm),
+        (symbol_width, symbol_height): (Mm, Mm),
+    ) -> (Mm, Mm) {
+        // 水平方向の配置オフセット
+        let x_offset = match self.alignment {
+            Alignment::Left => Mm(0.0),
+            Alignment::Center => (inner_width - symbol_width) / 2.0,
+            Alignment::Right => inner_width - symbol_width,
+            Alignment::Justify => Mm(0.0), // 通常、QRコードには適用されない
+        };
+        // 垂直方向の配置オフセット
+        let y_offset = match self.vertical_alignment {
+            VerticalAlignment::Top => Mm(0.0),
+            VerticalAlignment::Middle => (inner_height - symbol_height) / 2.0,
+            VerticalAlignment::Bottom => inner_height - symbol_height,
+        };
+        let padding = self.padding.as_ref();
+        (
+            x + padding.map_or(Mm(0.0), |p| p.left) + x_offset,
+            y + padding.map_or(Mm(0.0), |p| p.top) + y_offset,
+        )
+    }
 }
 
 impl From<JsonQrCodeSchema> for Schema {
@@ -51,11 +121,30 @@ impl From<JsonQrCodeSchema> for Schema {
         Schema::QrCode(QrCode {
             base,
             content: json.content,
+            symbology: json.symbology,
+            options: SymbolOptions {
+                error_correction: json.error_correction_level,
+                min_version: json.version,
+                gs1: false,
+            },
+            quiet_zone: json.quiet_zone,
+            color: json.color,
+            background_color: json.background_color,
+            style: QrStyle {
+                module_style: json.module_style.unwrap_or_default(),
+                finder_style: json.finder_style.unwrap_or_default(),
+                finder_color: json.finder_color,
+                logo: json.logo.map(Into::into),
+            },
+            gs1: json.gs1,
+            digital_link_domain: json.digital_link_domain,
             rotate: json.rotate,
-            alignment,
-            vertical_alignment,
+            placement: SymbolPlacement {
+                alignment,
+                vertical_alignment,
+                padding: json.padding,
+            },
             bounding_box: None,
-            padding: json.padding,
         })
     }
 }
@@ -65,11 +154,21 @@ impl QrCode {
         Self {
             base,
             content,
+            symbology: Symbology::Qr,
+            options: SymbolOptions::default(),
+            quiet_zone: None,
+            color: None,
+            background_color: None,
+            style: QrStyle::default(),
+            gs1: None,
+            digital_link_domain: None,
             rotate: None,
-            alignment: Alignment::Center,
-            vertical_alignment: VerticalAlignment::Middle,
+            placement: SymbolPlacement {
+                alignment: Alignment::Center,
+                vertical_alignment: VerticalAlignment::Middle,
+                padding: None,
+            },
             bounding_box: None,
-            padding: None,
         }
     }
 
@@ -86,50 +185,48 @@ impl QrCode {
         }
     }
 
-    // パディングを考慮したボックスの有効サイズを計算
-    fn get_effective_box_size(&self) -> (Mm, Mm) {
-        match self.bounding_box.clone() {
-            Some(bounding_box) => {
-                let box_width = bounding_box.width
-                    - self.padding.as_ref().map_or(Mm(0.0), |p| p.left + p.right);
-
-                let box_height = bounding_box.height
-                    - self.padding.as_ref().map_or(Mm(0.0), |p| p.top + p.bottom);
+    // 表のセルに置かれた場合はセルの範囲を配置の基準にする
+    fn placement_box(&self) -> BoundingBox {
+        self.bounding_box.clone().unwrap_or(BoundingBox {
+            x: self.base.x,
+            y: self.base.y,
+            width: self.base.width,
+            height: self.base.height,
+        })
+    }
 
-                (box_width, box_height)
+    /// The data to encode and the options to encode it with: the content as
+    /// given, or the GS1 element string it holds written out for `gs1`.
+    fn symbol_data(&self) -> Result<(String, SymbolOptions), Error> {
+        let Some(gs1) = self.gs1 else {
+            if self.digital_link_domain.is_some() {
+                return Err(Error::InvalidGs1 {
+                    message: "digitalLinkDomain needs gs1: digitalLink".to_string(),
+                });
             }
-
-            None => {
-                let box_width =
-                    self.base.width - self.padding.as_ref().map_or(Mm(0.0), |p| p.left + p.right);
-
-                let box_height =
-                    self.base.height - self.padding.as_ref().map_or(Mm(0.0), |p| p.top + p.bottom);
-
-                (box_width, box_height)
+            return Ok((self.content.clone(), self.options));
+        };
+        let element_string = ElementString::parse(&self.content)?;
+        match gs1 {
+            Gs1Syntax::ElementString => Ok((
+                element_string.encoded(),
+                SymbolOptions {
+                    gs1: true,
+                    ..self.options
+                },
+            )),
+            Gs1Syntax::DigitalLink => {
+                let domain = self
+                    .digital_link_domain
+                    .as_deref()
+                    .unwrap_or(DEFAULT_DIGITAL_LINK_DOMAIN);
+                Ok((element_string.digital_link(domain)?, self.options))
             }
         }
     }
 
-    // 垂直方向の配置オフセットを計算
-    fn calculate_vertical_offset(&self, box_height: Mm, qr_height: Mm) -> Mm {
-        match self.vertical_alignment {
-            VerticalAlignment::Top => Mm(0.0),
-            VerticalAlignment::Middle => (box_height - qr_height) / 2.0,
-            VerticalAlignment::Bottom => box_height - qr_height,
-        }
-    }
-
-    // 水平方向の配置オフセットを計算
-    fn calculate_horizontal_offset(&self, box_width: Mm, qr_width: Mm) -> Mm {
-        match self.alignment {
-            Alignment::Left => Mm(0.0),
-            Alignment::Center => (box_width - qr_width) / 2.0,
-            Alignment::Right => box_width - qr_width,
-            Alignment::Justify => Mm(0.0), // 通常、QRコードには適用されない
-        }
-    }
-
+    /// Draws the modules as vector shapes, so the symbol stays sharp at any
+    /// size and on any printer.
     pub fn render(
         &self,
         parent_height: Mm,
@@ -137,77 +234,108 @@ impl QrCode {
         page: usize,
         buffer: &mut OpBuffer,
     ) -> Result<(), Error> {
-        let code =
-            qrcode::QrCode::new(&self.content).whatever_context("Failed to create a QR code")?;
-        let luma: image::ImageBuffer<Luma<u8>, Vec<u8>> = code.render::<Luma<u8>>().build();
-        let w = luma.width();
-        let h = luma.height();
-
-        let qrcode_width = Px(w as usize);
-
-        let mut buf: Vec<u8> = Vec::new();
-        let encoder: PngEncoder<&mut Vec<u8>> = PngEncoder::new(&mut buf);
-        encoder
-            .write_image(&luma.into_raw(), w, h, ExtendedColorType::L8)
-            .map_err(|_| Error::ImageEncoding {
-                message: "Failed to encode QR code to PNG".to_string(),
-            })?;
-
-        let mut warnings = Vec::new();
-        let image = RawImage::decode_from_bytes(&buf, &mut warnings)
-            .whatever_context("Failed to decode QR code")?;
-
-        let image_x_object_id = doc.add_image(&image);
-
-        // QRコードの実際のサイズを計算
-        // QRコードの縦横比は常に1:1。指定サイズと有効ボックスの両方に
-        // 収まる正方形とし、回転時も内容物のサイズを変えない。
-        let (box_width, box_height) = self.get_effective_box_size();
-        let qr_side = self
-            .base
-            .width
-            .min(self.base.height)
-            .min(box_width)
-            .min(box_height);
-        let qr_width = qr_side;
-        let qr_height = qr_side;
-
-        // 配置オフセットを計算
-        let x_offset = self.calculate_horizontal_offset(box_width, qr_width);
-        let y_offset = self.calculate_vertical_offset(box_height, qr_height);
-
-        // パディングとオフセットを考慮した位置を計算
-        let x = self
-            .bounding_box
-            .clone()
-            .map(|bounding_box| bounding_box.x)
-            .unwrap_or(self.base.x)
-            + self.padding.as_ref().map_or(Mm(0.0), |p| p.left)
-            + x_offset;
-        let y = self
-            .bounding_box
-            .clone()
-            .map(|bounding_box| bounding_box.y)
-            .unwrap_or(self.base.y)
-            + self.padding.as_ref().map_or(Mm(0.0), |p| p.top)
-            + y_offset;
+        self.style.check_symbology(self.symbology)?;
+        let (content, options) = self.symbol_data()?;
+        let symbol = self.symbology.encoder().encode(&content, &options)?;
+        let quiet_zone = self.quiet_zone.unwrap_or(symbol.quiet_zone) as usize;
+        let color = match self.color.as_deref() {
+            Some(color) => PdfColor::parse(color).context(InvalidColorSnafu)?,
+            None => PdfColor::black(),
+        };
+        // "transparent" のときは背景を塗らない
+        let background_color = match self.background_color.as_deref() {
+            Some(color) if color.eq_ignore_ascii_case("transparent") => None,
+            Some(color) => Some(PdfColor::parse(color).context(InvalidColorSnafu)?),
+            None => Some(PdfColor::white()),
+        };
+        let finder_color = match self.style.finder_color.as_deref() {
+            Some(color) => PdfColor::parse(color).context(InvalidColorSnafu)?,
+            None => color.clone(),
+        };
+        let clear_zone = match &self.style.logo {
+            Some(logo) => Some(logo.clear_zone(&symbol)?),
+            None => None,
+        };
+
+        // シンボルの実際のサイズを計算
+        // モジュールの縦横比を保ったまま、指定サイズと有効ボックスの両方に
+        // 収まる大きさとし、回転時も内容物のサイズを変えない。
+        // PDF417 以外は正方形になる。
+        let placement_box = self.placement_box();
+        let (box_width, box_height) = self
+            .placement
+            .inner_size(placement_box.width, placement_box.height);
+        let (columns, rows) = symbol.extent(quiet_zone);
+        let scale = (self.base.width.min(box_width) / columns as f32)
+            .min(self.base.height.min(box_height) / rows as f32);
+        let (symbol_width, symbol_height) = (scale * columns as f32, scale * rows as f32);
+
+        // パディングと配置を考慮した位置を計算
+        let (x, y) = self.placement.origin(
+            (placement_box.x, placement_box.y),
+            (box_width, box_height),
+            (symbol_width, symbol_height),
+        );
 
-        // 新しい基本スキーマを一時的に作成して、正しい位置に変換行列を取得
-        let temp_base = BaseSchema::new(self.base.name.clone(), x, y, qr_width, qr_height);
+        // シンボルの中心を軸に回転させる
+        let matrix = calculate_transform_matrix_with_center_pivot(
+            x,
+            parent_height - y - symbol_height,
+            symbol_width,
+            symbol_height,
+            self.rotate,
+        );
 
-        let mut transform = temp_base.get_matrix(parent_height, Some(qrcode_width));
-        transform.rotate = self.rotate.map(|angle_deg| {
-            rotation_for_xobject(angle_deg, Px(w as usize), Px(h as usize), &transform)
-        });
+        let (width, height): (Pt, Pt) = (symbol_width.into(), symbol_height.into());
+        let module = width / columns as f32;
 
-        let ops = vec![
+        let mut ops = vec![
             Op::SaveGraphicsState,
-            Op::UseXobject {
-                id: image_x_object_id,
-                transform,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(matrix),
             },
-            Op::RestoreGraphicsState,
         ];
+        if let Some(background_color) = background_color {
+            ops.extend(background_color.fill_ops());
+            ops.push(fill_rectangles([(Pt(0.0), Pt(0.0), width, height)]));
+        }
+        // ロゴの領域と、別に描くファインダパターンのモジュールは除く
+        let separate_finders = self.style.separate_finders();
+        let modules: Vec<bool> = symbol
+            .modules
+            .iter()
+            .enumerate()
+            .map(|(index, &dark)| {
+                let (x, y) = (index % symbol.width, index / symbol.width);
+                dark && !clear_zone.is_some_and(|zone| zone.contains(x, y))
+                    && !(separate_finders && qr_style::is_finder(symbol.width, x, y))
+            })
+            .collect();
+        let origin = (
+            module * quiet_zone as f32,
+            height - module * quiet_zone as f32,
+        );
+        ops.extend(color.fill_ops());
+        ops.push(qr_style::modules_op(
+            self.style.module_style,
+            &modules,
+            symbol.width,
+            (module, module * symbol.row_height as f32),
+            origin,
+        ));
+        if separate_finders {
+            ops.extend(finder_color.fill_ops());
+            ops.push(qr_style::finders_op(
+                self.style.finder_style,
+                symbol.width,
+                module,
+                origin,
+            ));
+        }
+        if let (Some(logo), Some(zone)) = (&self.style.logo, clear_zone) {
+            ops.extend(logo.ops(doc, zone, module, origin)?);
+        }
+        ops.push(Op::RestoreGraphicsState);
 
         buffer.insert(page, ops);
 
@@ -1,3 +1,4 @@
+use super::color::PdfColor;
 use super::InvalidColorSnafu;
 use crate::schemas::pdf_utils::{draw_rectangle, wrap_ops_with_opacity, DrawRectangle};
 use crate::schemas::{base::BaseSchema, Error, HasBaseSchema, JsonPosition, Schema};
@@ -34,7 +35,9 @@ use snafu::prelude::*;
 pub struct JsonRectSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     rotate: Option<f32>,
     opacity: Option<f32>,
@@ -49,8 +52,8 @@ pub struct Rect {
     rotate: Option<f32>,
     opacity: Option<f32>,
     border_width: Pt,
-    border_color: csscolorparser::Color,
-    color: csscolorparser::Color,
+    border_color: PdfColor,
+    color: PdfColor,
 }
 
 impl TryFrom<JsonRectSchema> for Schema {
@@ -65,8 +68,8 @@ impl TryFrom<JsonRectSchema> for Schema {
             Mm(json.height),
         );
 
-        let border_color = csscolorparser::parse(&json.border_color).context(InvalidColorSnafu)?;
-        let color = csscolorparser::parse(&json.color).context(InvalidColorSnafu)?;
+        let border_color = PdfColor::parse(&json.border_color).context(InvalidColorSnafu)?;
+        let color = PdfColor::parse(&json.color).context(InvalidColorSnafu)?;
         Ok(Schema::Rect(Rect {
             base,
             rotate: json.rotate,
@@ -97,19 +100,9 @@ impl Rect {
             height: self.base.height,
             rotate: self.rotate,
             page_height: parent_height,
-            color: Some(Color::Rgb(Rgb {
-                r: self.color.r,
-                g: self.color.g,
-                b: self.color.b,
-                icc_profile: None,
-            })),
+            color: Some(self.color.clone()),
             border_width: Some(Mm(self.border_width.0)),
-            border_color: Some(Color::Rgb(Rgb {
-                r: self.border_color.r,
-                g: self.border_color.g,
-                b: self.border_color.b,
-                icc_profile: None,
-            })),
+            border_color: Some(self.border_color.clone()),
         };
 
         let ops = draw_rectangle(rect);
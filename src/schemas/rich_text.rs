@@ -0,0 +1,571 @@
+//! Rich text for the `text` schema: inline markup that changes the font,
+//! size, color, weight and baseline of parts of the content.
+//!
+//! ```text
+//! Total: <b><color=#cc0000>¥100,000</color></b> <size=70%>(tax included)</size>
+//! ```
+//!
+//! The tags are `<b>`, `<i>`, `<sup>`, `<sub>`, `<color=…>`, `<font=…>` and
+//! `<size=…>` (points, or a percentage of the enclosing size), each closed by
+//! its own `</…>`. A `<` that does not start one of them is plain text, and
+//! `\<` writes a `<` that would.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use printpdf::{CurTransMat, Mm, Op, PdfFontHandle, Pt, TextItem, TextMatrix, TextRenderingMode};
+use snafu::{OptionExt, ResultExt};
+
+use super::color::PdfColor;
+use super::{Error, InvalidColorSnafu, RichTextSnafu};
+use crate::font::{
+    self, kinsoku_line_pieces, wrap_paragraph, FontChain, FontMap, FontName, FontSelector,
+    FontSpecTrait, FontStyle, FontWeight, LineBreakMode, BOLD_STROKE, OBLIQUE_SKEW,
+};
+
+/// Size of superscripts and subscripts relative to the enclosing text.
+const SCRIPT_SCALE: f32 = 0.6;
+
+/// How a span of rich text is drawn.
+#[derive(Debug, Clone)]
+pub(crate) struct SpanStyle {
+    pub(crate) fonts: FontChain,
+    /// Size the glyphs are drawn at.
+    pub(crate) size: Pt,
+    /// Size the line is measured with; a superscript keeps its parent's.
+    pub(crate) line_size: Pt,
+    /// Baseline shift, upwards.
+    pub(crate) rise: Pt,
+    pub(crate) color: PdfColor,
+    pub(crate) bold: bool,
+    pub(crate) italic: bool,
+}
+
+impl SpanStyle {
+    pub(crate) fn new(fonts: FontChain, size: Pt, color: PdfColor) -> Self {
+        SpanStyle {
+            bold: fonts.synthetic_bold(),
+            italic: fonts.synthetic_oblique(),
+            fonts,
+            size,
+            line_size: size,
+            rise: Pt(0.0),
+            color,
+        }
+    }
+
+    /// Switches to the fonts `selector` picks, drawing bold and italic
+    /// synthetically where they have no face of their own.
+    fn select(&mut self, selector: FontSelector, fonts: &FontMap) -> Option<()> {
+        self.fonts = fonts.select(&selector)?;
+        self.bold = self.fonts.synthetic_bold();
+        self.italic = self.fonts.synthetic_oblique();
+        Some(())
+    }
+
+    fn width_of(&self, text: &str, character_spacing: Pt) -> Result<Pt, font::Error> {
+        self.fonts
+            .spec()
+            .width_of_text_at_size(text, self.size, character_spacing)
+    }
+}
+
+/// The content with its markup removed, and the style of every part of it.
+#[derive(Debug, Clone)]
+pub(crate) struct RichText {
+    text: String,
+    /// Consecutive byte ranges of `text`, together covering all of it.
+    spans: Vec<(Range<usize>, Arc<SpanStyle>)>,
+    /// The style outside any tag, which sizes lines left empty.
+    base: Arc<SpanStyle>,
+}
+
+/// One wrapped line: runs of text in a single style, left to right.
+#[derive(Debug, Clone)]
+pub(crate) struct RichLine {
+    pub(crate) runs: Vec<(String, Arc<SpanStyle>)>,
+    /// The largest line size among the runs.
+    pub(crate) size: Pt,
+}
+
+enum Tag<'a> {
+    Open(&'a str, Option<&'a str>),
+    Close(&'a str),
+}
+
+impl<'a> Tag<'a> {
+    /// The tag at the start of `text` and its length, if `text` starts with
+    /// one this module knows.
+    fn parse(text: &'a str) -> Option<(Tag<'a>, usize)> {
+        let end = text.find('>')?;
+        let inner = text.strip_prefix('<')?.get(..end - 1)?;
+        let tag = match inner.strip_prefix('/') {
+            Some(name) => Tag::Close(name),
+            None => match inner.split_once('=') {
+                Some((name, value)) => Tag::Open(name, Some(value)),
+                None => Tag::Open(inner, None),
+            },
+        };
+        let known = match tag {
+            Tag::Open(name, None) => matches!(name, "b" | "i" | "sup" | "sub"),
+            Tag::Close(name) => {
+                matches!(name, "b" | "i" | "sup" | "sub" | "color" | "font" | "size")
+            }
+            Tag::Open(name, Some(value)) => {
+                matches!(name, "color" | "font" | "size") && !value.is_empty()
+            }
+        };
+        known.then_some((tag, end + 1))
+    }
+}
+
+impl RichText {
+    /// Strips the markup from `content`, resolving every tag against `base`
+    /// and the fonts `<font>` may name.
+    pub(crate) fn parse(content: &str, base: SpanStyle, fonts: &FontMap) -> Result<Self, Error> {
+        let base = Arc::new(base);
+        let mut text = String::new();
+        let mut spans = Vec::new();
+        let mut stack: Vec<(&str, Arc<SpanStyle>)> = vec![("", base.clone())];
+        let mut span_start = 0;
+        let mut rest = content;
+
+        while let Some(ch) = rest.chars().next() {
+            if let Some(escaped) = rest.strip_prefix("\\<") {
+                text.push('<');
+                rest = escaped;
+                continue;
+            }
+            let Some((tag, length)) = (ch == '<').then(|| Tag::parse(rest)).flatten() else {
+                text.push(ch);
+                rest = &rest[ch.len_utf8()..];
+                continue;
+            };
+            rest = &rest[length..];
+
+            let (_, style) = stack.last().unwrap();
+            if text.len() > span_start {
+                spans.push((span_start..text.len(), style.clone()));
+                span_start = text.len();
+            }
+            match tag {
+                Tag::Open(name, value) => {
+                    let style = open(name, value, style, fonts)?;
+                    stack.push((name, Arc::new(style)));
+                }
+                Tag::Close(name) => match stack.pop() {
+                    Some((open, _)) if open == name => {}
+                    Some(("", _)) | None => {
+                        return RichTextSnafu {
+                            message: format!("</{name}> has no opening tag"),
+                        }
+                        .fail()
+                    }
+                    Some((open, _)) => {
+                        return RichTextSnafu {
+                            message: format!("</{name}> does not close <{open}>"),
+                        }
+                        .fail()
+                    }
+                },
+            }
+        }
+
+        if let [_, .., (name, _)] = stack.as_slice() {
+            return RichTextSnafu {
+                message: format!("<{name}> is not closed"),
+            }
+            .fail();
+        }
+        if text.len() > span_start {
+            spans.push((span_start..text.len(), base.clone()));
+        }
+        Ok(RichText { text, spans, base })
+    }
+
+    /// Wraps every paragraph to `box_width` and applies kinsoku, measuring
+    /// each run in its own font and size.
+    pub(crate) fn lines(
+        &self,
+        line_break_mode: LineBreakMode,
+        box_width: Pt,
+        character_spacing: Pt,
+    ) -> Result<Vec<RichLine>, font::Error> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for paragraph in self.text.split('\n') {
+            let offset = start;
+            start += paragraph.len() + 1;
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+
+            let wrapped = wrap_paragraph(paragraph, line_break_mode, box_width, |range| {
+                self.width_of(offset + range.start..offset + range.end, character_spacing)
+            })?;
+            for pieces in kinsoku_line_pieces(paragraph, wrapped) {
+                let pieces = pieces
+                    .into_iter()
+                    .map(|piece| offset + piece.start..offset + piece.end);
+                lines.push(self.line(pieces));
+            }
+        }
+        Ok(lines)
+    }
+
+    /// The parts of `range` in each style.
+    fn runs(&self, range: Range<usize>) -> impl Iterator<Item = (&str, &Arc<SpanStyle>)> + '_ {
+        self.spans.iter().filter_map(move |(span, style)| {
+            let start = span.start.max(range.start);
+            let end = span.end.min(range.end);
+            (start < end).then(|| (&self.text[start..end], style))
+        })
+    }
+
+    fn width_of(&self, range: Range<usize>, character_spacing: Pt) -> Result<Pt, font::Error> {
+        let mut width = 0.0;
+        let mut runs = 0usize;
+        for (text, style) in self.runs(range) {
+            width += style.width_of(text, character_spacing)?.0;
+            runs += 1;
+        }
+        Ok(Pt(
+            width + character_spacing.0 * runs.saturating_sub(1) as f32
+        ))
+    }
+
+    fn line(&self, pieces: impl Iterator<Item = Range<usize>>) -> RichLine {
+        let mut runs: Vec<(String, Arc<SpanStyle>)> = Vec::new();
+        for piece in pieces {
+            for (text, style) in self.runs(piece) {
+                match runs.last_mut() {
+                    Some((run, run_style)) if Arc::ptr_eq(run_style, style) => run.push_str(text),
+                    _ => runs.push((text.to_string(), style.clone())),
+                }
+            }
+        }
+        let size = runs
+            .iter()
+            .map(|(_, style)| style.line_size)
+            .reduce(|a, b| if b > a { b } else { a })
+            .unwrap_or(self.base.line_size);
+        RichLine { runs, size }
+    }
+}
+
+/// The style a tag opened inside `parent` gives.
+fn open(
+    name: &str,
+    value: Option<&str>,
+    parent: &SpanStyle,
+    fonts: &FontMap,
+) -> Result<SpanStyle, Error> {
+    let mut style = parent.clone();
+    let value = value.unwrap_or_default();
+    match name {
+        // ファミリーに太字・斜体の字形があればそれを使い、なければ擬似的に描く
+        "b" | "i" => {
+            let mut selector = parent.fonts.selector().clone();
+            if name == "b" {
+                selector.weight = FontWeight::BOLD;
+            } else {
+                selector.style = FontStyle::Italic;
+            }
+            style.select(selector, fonts).context(RichTextSnafu {
+                message: format!("<{name}> has no font to draw with"),
+            })?;
+        }
+        "sup" | "sub" => {
+            style.size = parent.size * SCRIPT_SCALE;
+            let shift = if name == "sup" { 0.33 } else { -0.15 };
+            style.rise = Pt(parent.rise.0 + parent.size.0 * shift);
+        }
+        "color" => style.color = PdfColor::parse(value).context(InvalidColorSnafu)?,
+        "font" => {
+            let selector = FontSelector {
+                name: Some(FontName::from(value)),
+                family: None,
+                ..parent.fonts.selector().clone()
+            };
+            style.select(selector, fonts).context(RichTextSnafu {
+                message: format!("<font={value}> names a font that is not loaded"),
+            })?;
+        }
+        _ => {
+            let size = match value.strip_suffix('%') {
+                Some(percent) => percent
+                    .parse::<f32>()
+                    .ok()
+                    .map(|percent| parent.size.0 * percent / 100.0),
+                None => value.parse::<f32>().ok(),
+            };
+            let size = size
+                .filter(|size| size.is_finite() && *size > 0.0)
+                .context(RichTextSnafu {
+                    message: format!("<size={value}> must be a positive size in points or %"),
+                })?;
+            style.size = Pt(size);
+            style.line_size = Pt(size);
+        }
+    }
+    Ok(style)
+}
+
+impl RichLine {
+    pub(crate) fn text(&self) -> String {
+        self.runs.iter().map(|(text, _)| text.as_str()).collect()
+    }
+
+    pub(crate) fn width(&self, character_spacing: Pt) -> Result<Pt, font::Error> {
+        let mut width = character_spacing.0 * self.runs.len().saturating_sub(1) as f32;
+        for (text, style) in &self.runs {
+            width += style.width_of(text, character_spacing)?.0;
+        }
+        Ok(Pt(width))
+    }
+
+    /// One text section drawing the runs from `x` along the baseline `y`,
+    /// each in its own font, size, color and weight.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn ops(
+        &self,
+        bounding_matrix: [f32; 6],
+        x: Mm,
+        y: Mm,
+        scale_x: Option<f32>,
+        scale_y: Option<f32>,
+        character_spacing: Pt,
+    ) -> Result<Vec<Op>, font::Error> {
+        let scale_x = scale_x.unwrap_or(1.0);
+        let scale_y = scale_y.unwrap_or(1.0);
+        let Pt(mut x) = x.into();
+        let Pt(y) = y.into();
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(bounding_matrix),
+            },
+            Op::StartTextSection,
+        ];
+        let mut bold = false;
+        for (text, style) in &self.runs {
+            ops.extend(style.color.fill_ops());
+            // 太字はアウトラインを重ねて擬似的に太くする
+            if style.bold {
+                ops.extend(style.color.stroke_ops());
+                ops.push(Op::SetOutlineThickness {
+                    pt: style.size * BOLD_STROKE,
+                });
+            }
+            if style.bold != bold {
+                bold = style.bold;
+                ops.push(Op::SetTextRenderingMode {
+                    mode: if bold {
+                        TextRenderingMode::FillStroke
+                    } else {
+                        TextRenderingMode::Fill
+                    },
+                });
+            }
+            let skew = if style.italic { OBLIQUE_SKEW } else { 0.0 };
+            for (index, (font_id, run)) in style.fonts.runs(text).into_iter().enumerate() {
+                ops.push(Op::SetFont {
+                    font: PdfFontHandle::External(font_id.clone()),
+                    size: style.size,
+                });
+                // フォールバックのランは前のランの終わりから続けて描画される
+                if index == 0 {
+                    ops.extend([
+                        Op::SetTextMatrix {
+                            matrix: TextMatrix::Raw([
+                                scale_x,
+                                0.0,
+                                scale_y * skew,
+                                scale_y,
+                                x,
+                                y + style.rise.0 * scale_y,
+                            ]),
+                        },
+                        Op::SetCharacterSpacing {
+                            multiplier: character_spacing.0,
+                        },
+                    ]);
+                }
+                ops.push(Op::ShowText {
+                    items: vec![TextItem::Text(run)],
+                });
+            }
+            x += (style.width_of(text, character_spacing)?.0 + character_spacing.0) * scale_x;
+        }
+        ops.extend([Op::EndTextSection, Op::RestoreGraphicsState]);
+        Ok(ops)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::{ParsedFont, PdfDocument};
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+
+    fn test_font() -> Arc<ParsedFont> {
+        static FONT: OnceLock<Arc<ParsedFont>> = OnceLock::new();
+        FONT.get_or_init(|| {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join("fonts")
+                .join("NotoSansJP-Regular.ttf");
+            let bytes = std::fs::read(path).expect("test font should be readable");
+            Arc::new(ParsedFont::from_bytes(&bytes, 0, &mut Vec::new()).expect("test font"))
+        })
+        .clone()
+    }
+
+    fn fonts() -> FontMap {
+        let mut doc = PdfDocument::new("test");
+        let font = test_font();
+        let mut fonts = FontMap::default();
+        fonts.add_font("Sans".to_string(), doc.add_font(&font), &font);
+        fonts.add_font("Other".to_string(), doc.add_font(&font), &font);
+        fonts
+    }
+
+    fn parse(content: &str) -> Result<RichText, Error> {
+        let fonts = fonts();
+        let base = SpanStyle::new(
+            fonts.find_chain(&"Sans".into()).unwrap(),
+            Pt(10.0),
+            PdfColor::black(),
+        );
+        RichText::parse(content, base, &fonts)
+    }
+
+    fn span_texts(rich: &RichText) -> Vec<&str> {
+        rich.spans
+            .iter()
+            .map(|(range, _)| &rich.text[range.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn tags_split_the_content_into_styled_spans() {
+        let rich = parse("Total: <b><color=#ff0000>100</color></b> <size=150%>yen</size>").unwrap();
+        assert_eq!(rich.text, "Total: 100 yen");
+        assert_eq!(span_texts(&rich), ["Total: ", "100", " ", "yen"]);
+
+        let styles: Vec<&SpanStyle> = rich.spans.iter().map(|(_, style)| &**style).collect();
+        assert!(!styles[0].bold && styles[1].bold);
+        assert_eq!(styles[1].color, PdfColor::parse("#ff0000").unwrap());
+        assert_eq!((styles[3].size, styles[3].line_size), (Pt(15.0), Pt(15.0)));
+    }
+
+    #[test]
+    fn scripts_shrink_and_shift_without_growing_the_line() {
+        let rich = parse("x<sup>2</sup>H<sub>2</sub>").unwrap();
+        let sup = &rich.spans[1].1;
+        let sub = &rich.spans[3].1;
+        assert_eq!((sup.size, sup.line_size), (Pt(6.0), Pt(10.0)));
+        assert!(sup.rise > Pt(0.0) && sub.rise < Pt(0.0));
+    }
+
+    #[test]
+    fn unknown_tags_and_escapes_stay_text() {
+        let rich = parse("a < b, <u>x</u>, \\<b> and <font=Other>c</font>").unwrap();
+        assert_eq!(rich.text, "a < b, <u>x</u>, <b> and c");
+        assert!(!Arc::ptr_eq(&rich.spans[0].1, &rich.spans[1].1));
+    }
+
+    #[test]
+    fn bold_uses_the_family_bold_face_when_there_is_one() {
+        let mut fonts = fonts();
+        fonts.add_face(
+            "Family".to_string(),
+            FontWeight::NORMAL,
+            FontStyle::Normal,
+            "Sans".to_string(),
+        );
+        fonts.add_face(
+            "Family".to_string(),
+            FontWeight::BOLD,
+            FontStyle::Normal,
+            "Other".to_string(),
+        );
+        let selector = FontSelector {
+            family: Some("Family".to_string()),
+            ..FontSelector::default()
+        };
+        let base = SpanStyle::new(
+            fonts.select(&selector).unwrap(),
+            Pt(10.0),
+            PdfColor::black(),
+        );
+        let rich = RichText::parse("a<b>b</b><i>c</i>", base, &fonts).unwrap();
+
+        let (bold, italic) = (&rich.spans[1].1, &rich.spans[2].1);
+        assert_eq!(bold.fonts.id(), &fonts.find("Other").unwrap().0);
+        assert!(!bold.bold);
+        // The family has no italic face, so the regular one is skewed.
+        assert_eq!(italic.fonts.id(), &fonts.find("Sans").unwrap().0);
+        assert!(italic.italic);
+    }
+
+    #[test]
+    fn malformed_markup_is_rejected() {
+        for content in [
+            "<b>open",
+            "close</b>",
+            "<b><i>x</b></i>",
+            "<font=Missing>x</font>",
+            "<size=-2>x</size>",
+            "<size=big>x</size>",
+        ] {
+            assert!(
+                matches!(parse(content), Err(Error::RichText { .. })),
+                "{content}"
+            );
+        }
+        assert!(matches!(
+            parse("<color=nope>x</color>"),
+            Err(Error::InvalidColor { .. })
+        ));
+    }
+
+    #[test]
+    fn lines_measure_each_run_at_its_own_size() {
+        let plain = parse("aaaa aaaa").unwrap();
+        let large = parse("aaaa <size=20>aaaa</size>").unwrap();
+        let width = plain.width_of(0..9, Pt(0.0)).unwrap();
+        let larger = large.width_of(0..9, Pt(0.0)).unwrap();
+        assert!(larger > width, "{larger:?} <= {width:?}");
+
+        // A box that holds the plain text on one line breaks the larger one,
+        // and the line holding the large run takes its size.
+        let lines = large.lines(LineBreakMode::Word, width, Pt(0.0)).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].text(), "aaaa ");
+        assert_eq!((lines[0].size, lines[1].size), (Pt(10.0), Pt(20.0)));
+        assert_eq!(
+            plain
+                .lines(LineBreakMode::Word, width, Pt(0.0))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn kinsoku_applies_across_runs() {
+        let rich = parse("あいう<color=#ff0000>。</color>えお").unwrap();
+        let width = rich.width_of(0..9, Pt(0.0)).unwrap();
+        let lines = rich.lines(LineBreakMode::Char, width, Pt(0.0)).unwrap();
+
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|line| !line.text().starts_with('。')));
+        let (_, style) = lines
+            .iter()
+            .flat_map(|line| &line.runs)
+            .find(|(text, _)| text == "。")
+            .unwrap();
+        assert_eq!(style.color, PdfColor::parse("#ff0000").unwrap());
+    }
+}
@@ -0,0 +1,204 @@
+//! `basePdf.rotate`: emitting upright designs in printer orientation.
+//!
+//! With `rotateMode: "page"` (the default) the page keeps its designed
+//! MediaBox and gets a `/Rotate` entry, written after saving. With
+//! `rotateMode: "content"` every op is transformed instead and the page's
+//! width and height are swapped for quarter turns, for printer drivers that
+//! ignore `/Rotate`.
+
+use lopdf::{Document, Object};
+use printpdf::{CurTransMat, Mm, Op, Pt};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::Error;
+
+/// Clockwise page rotation in quarter turns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum Rotation {
+    #[default]
+    None,
+    Clockwise90,
+    Clockwise180,
+    Clockwise270,
+}
+
+impl Rotation {
+    /// Parses a multiple of 90 degrees; negative angles turn anticlockwise.
+    pub fn from_degrees(degrees: i64) -> Option<Rotation> {
+        if degrees % 90 != 0 {
+            return None;
+        }
+        Some(match degrees.rem_euclid(360) {
+            0 => Rotation::None,
+            90 => Rotation::Clockwise90,
+            180 => Rotation::Clockwise180,
+            _ => Rotation::Clockwise270,
+        })
+    }
+
+    pub fn degrees(self) -> i64 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 90,
+            Rotation::Clockwise180 => 180,
+            Rotation::Clockwise270 => 270,
+        }
+    }
+
+    fn is_quarter_turn(self) -> bool {
+        matches!(self, Rotation::Clockwise90 | Rotation::Clockwise270)
+    }
+
+    /// Size of a `width` × `height` page after rotation.
+    pub(crate) fn rotated_size<T>(self, width: T, height: T) -> (T, T) {
+        if self.is_quarter_turn() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Matrix mapping a `width` × `height` page (in points) onto the rotated
+    /// page, keeping the result in the positive quadrant.
+    pub(crate) fn matrix(self, width: f32, height: f32) -> [f32; 6] {
+        match self {
+            Rotation::None => [1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            Rotation::Clockwise90 => [0.0, -1.0, 1.0, 0.0, 0.0, width],
+            Rotation::Clockwise180 => [-1.0, 0.0, 0.0, -1.0, width, height],
+            Rotation::Clockwise270 => [0.0, 1.0, -1.0, 0.0, height, 0.0],
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Rotation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let degrees = i64::deserialize(deserializer)?;
+        Rotation::from_degrees(degrees).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid rotate {degrees}; expected 0, 90, 180 or 270"
+            ))
+        })
+    }
+}
+
+/// How `basePdf.rotate` is applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RotateMode {
+    /// Set the page's `/Rotate` entry.
+    #[default]
+    Page,
+    /// Transform the content and swap the page size.
+    Content,
+}
+
+/// Rotates the ops of a `width` × `height` page. Returns the ops and the
+/// rotated page size.
+pub(crate) fn rotate_content(
+    rotation: Rotation,
+    width: Mm,
+    height: Mm,
+    ops: Vec<Op>,
+) -> (Vec<Op>, Mm, Mm) {
+    if rotation == Rotation::None {
+        return (ops, width, height);
+    }
+    let (width_pt, height_pt): (Pt, Pt) = (width.into(), height.into());
+    let mut rotated = Vec::with_capacity(ops.len() + 3);
+    rotated.push(Op::SaveGraphicsState);
+    rotated.push(Op::SetTransformationMatrix {
+        matrix: CurTransMat::Raw(rotation.matrix(width_pt.0, height_pt.0)),
+    });
+    rotated.extend(ops);
+    rotated.push(Op::RestoreGraphicsState);
+    let (width, height) = rotation.rotated_size(width, height);
+    (rotated, width, height)
+}
+
+/// Sets `/Rotate` on the saved pages, one entry per page. Returns the bytes
+/// unchanged when no page is rotated.
+pub(crate) fn apply_page_rotation(pdf: Vec<u8>, rotations: &[Rotation]) -> Result<Vec<u8>, Error> {
+    if rotations.iter().all(|rotation| *rotation == Rotation::None) {
+        return Ok(pdf);
+    }
+
+    fn pdf_error(e: impl std::fmt::Display) -> Error {
+        Error::Whatever {
+            message: format!("Failed to rotate pages: {e}"),
+            source: None,
+        }
+    }
+
+    let mut document = Document::load_mem(&pdf).map_err(pdf_error)?;
+    let page_ids: Vec<_> = document.get_pages().into_values().collect();
+    for (page_id, rotation) in page_ids.into_iter().zip(rotations) {
+        if *rotation == Rotation::None {
+            continue;
+        }
+        document
+            .get_object_mut(page_id)
+            .and_then(Object::as_dict_mut)
+            .map_err(pdf_error)?
+            .set("Rotate", rotation.degrees());
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(matrix: [f32; 6], x: f32, y: f32) -> (f32, f32) {
+        (
+            matrix[0] * x + matrix[2] * y + matrix[4],
+            matrix[1] * x + matrix[3] * y + matrix[5],
+        )
+    }
+
+    #[test]
+    fn degrees_normalise_to_quarter_turns() {
+        assert_eq!(Rotation::from_degrees(0), Some(Rotation::None));
+        assert_eq!(Rotation::from_degrees(450), Some(Rotation::Clockwise90));
+        assert_eq!(Rotation::from_degrees(-90), Some(Rotation::Clockwise270));
+        assert_eq!(Rotation::from_degrees(45), None);
+    }
+
+    #[test]
+    fn the_designed_top_left_corner_turns_clockwise() {
+        // Top-left corner of a 100 × 50 landscape page.
+        let (width, height) = (100.0, 50.0);
+        let top_left = (0.0, height);
+
+        assert_eq!(
+            apply(
+                Rotation::Clockwise90.matrix(width, height),
+                top_left.0,
+                top_left.1
+            ),
+            (50.0, 100.0),
+            "top-left becomes top-right of the 50 × 100 page"
+        );
+        assert_eq!(
+            apply(
+                Rotation::Clockwise180.matrix(width, height),
+                top_left.0,
+                top_left.1
+            ),
+            (100.0, 0.0)
+        );
+        assert_eq!(
+            apply(
+                Rotation::Clockwise270.matrix(width, height),
+                top_left.0,
+                top_left.1
+            ),
+            (0.0, 0.0)
+        );
+    }
+}
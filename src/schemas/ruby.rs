@@ -0,0 +1,454 @@
+//! Ruby (furigana) for the `text` schema: small readings set above the base
+//! text, or to its right in vertical writing.
+//!
+//! ```text
+//! {田中|たなか}{太郎|た|ろう}
+//! ```
+//!
+//! `{base|ruby}` is group ruby: the reading spans the whole base, which never
+//! breaks across lines. `{base|r1|r2|…}` is mono-ruby, one reading per
+//! character of the base, each centred on its own character; a reading may be
+//! left empty. A `{` that does not start such markup is plain text, and `\{`
+//! writes a `{` that would.
+
+use std::ops::Range;
+
+use printpdf::Pt;
+
+use super::{Error, RubySnafu};
+use crate::font::{
+    self, kinsoku_line_pieces, wrap_paragraph, FontSpec, FontSpecTrait, LineBreakMode,
+};
+
+/// Ruby size relative to the base text when the template does not set one.
+pub(crate) const DEFAULT_RUBY_SIZE: f32 = 0.5;
+/// Share of the em box below the baseline, where horizontal ruby starts to
+/// stick out above the base glyphs.
+const EM_DESCENT: f32 = 0.12;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Ruby {
+    text: String,
+    /// Belongs to a single character rather than to a group.
+    mono: bool,
+}
+
+/// The content with its markup removed, and the reading of each annotated
+/// part of it.
+#[derive(Debug, Clone)]
+pub(crate) struct RubyText {
+    text: String,
+    /// Byte ranges of `text` in order, each with its reading.
+    annotations: Vec<(Range<usize>, Ruby)>,
+}
+
+/// One wrapped line: base text in pieces, each annotated one with its ruby.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RubyLine {
+    pieces: Vec<(String, Option<Ruby>)>,
+}
+
+/// The font and sizes base text and ruby are measured with.
+pub(crate) struct RubyMetrics<'a> {
+    pub(crate) spec: &'a dyn FontSpecTrait,
+    pub(crate) size: Pt,
+    pub(crate) ruby_size: Pt,
+}
+
+/// Text placed along a line, `offset` from its start.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Run<'a> {
+    pub(crate) text: &'a str,
+    pub(crate) offset: Pt,
+    pub(crate) character_spacing: Pt,
+}
+
+/// A piece of a laid out line: its base text and the ruby over it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Placed<'a> {
+    pub(crate) base: Run<'a>,
+    pub(crate) ruby: Option<Run<'a>>,
+}
+
+/// Room a line holding ruby needs beyond its line height: whatever the ruby
+/// does not find in the half-leading on its side of the base text.
+pub(crate) fn extra_line_space(
+    font_size: Pt,
+    ruby_size: Pt,
+    line_height: f32,
+    vertical: bool,
+) -> Pt {
+    let mut room = font_size.0 * (line_height - 1.0) / 2.0;
+    if !vertical {
+        room += font_size.0 * EM_DESCENT;
+    }
+    Pt((ruby_size.0 - room).max(0.0))
+}
+
+/// How far the baseline of horizontal ruby sits above that of its base, so
+/// that their em boxes touch.
+pub(crate) fn ruby_rise(font_size: Pt, ruby_size: Pt) -> Pt {
+    Pt(font_size.0 * (1.0 - EM_DESCENT) + ruby_size.0 * EM_DESCENT)
+}
+
+/// The `{base|ruby…}` markup at the start of `text`: its base, readings and
+/// length.
+fn markup(text: &str) -> Option<(&str, Vec<&str>, usize)> {
+    let end = text.find('}')?;
+    let inner = &text[1..end];
+    if inner.contains(['{', '\n']) {
+        return None;
+    }
+    let mut parts = inner.split('|');
+    let base = parts.next()?;
+    let readings: Vec<&str> = parts.collect();
+    let valid = !base.is_empty() && readings.iter().any(|reading| !reading.is_empty());
+    valid.then_some((base, readings, end + 1))
+}
+
+fn cluster_count(text: &str) -> usize {
+    FontSpec::split_text_by_grapheme_cluster(text).len()
+}
+
+impl RubyText {
+    /// Strips the ruby markup from `content`.
+    pub(crate) fn parse(content: &str) -> Result<Self, Error> {
+        let mut text = String::new();
+        let mut annotations = Vec::new();
+        let mut rest = content;
+
+        while let Some(ch) = rest.chars().next() {
+            if let Some(escaped) = rest.strip_prefix("\\{") {
+                text.push('{');
+                rest = escaped;
+                continue;
+            }
+            let Some((base, readings, length)) = (ch == '{').then(|| markup(rest)).flatten() else {
+                text.push(ch);
+                rest = &rest[ch.len_utf8()..];
+                continue;
+            };
+            rest = &rest[length..];
+
+            let clusters = FontSpec::split_text_by_grapheme_cluster(base);
+            match readings[..] {
+                [reading] => annotations.push((
+                    text.len()..text.len() + base.len(),
+                    Ruby {
+                        text: reading.to_string(),
+                        mono: clusters.len() == 1,
+                    },
+                )),
+                _ if readings.len() == clusters.len() => {
+                    let mut start = text.len();
+                    for (cluster, reading) in clusters.iter().zip(readings) {
+                        if !reading.is_empty() {
+                            annotations.push((
+                                start..start + cluster.len(),
+                                Ruby {
+                                    text: reading.to_string(),
+                                    mono: true,
+                                },
+                            ));
+                        }
+                        start += cluster.len();
+                    }
+                }
+                _ => {
+                    return RubySnafu {
+                        message: format!(
+                            "{{{base}|…}} has {} readings for {} characters",
+                            readings.len(),
+                            clusters.len()
+                        ),
+                    }
+                    .fail()
+                }
+            }
+            text.push_str(base);
+        }
+
+        Ok(RubyText { text, annotations })
+    }
+
+    /// Wraps every paragraph to `box_length` and applies kinsoku, measuring
+    /// annotated text as wide as the wider of its base and ruby. Lines never
+    /// break inside group ruby.
+    pub(crate) fn lines(
+        &self,
+        metrics: &RubyMetrics,
+        line_break_mode: LineBreakMode,
+        box_length: Pt,
+        character_spacing: Pt,
+    ) -> Result<Vec<RubyLine>, font::Error> {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for paragraph in self.text.split('\n') {
+            let offset = start;
+            start += paragraph.len() + 1;
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+
+            let wrapped = wrap_paragraph(paragraph, line_break_mode, box_length, |range| {
+                self.line(std::iter::once(offset + range.start..offset + range.end))
+                    .length(metrics, character_spacing)
+            })?;
+            let wrapped = self.keep_groups_whole(wrapped, offset);
+            for pieces in kinsoku_line_pieces(paragraph, wrapped) {
+                let pieces = pieces
+                    .into_iter()
+                    .map(|piece| offset + piece.start..offset + piece.end);
+                lines.push(self.line(pieces));
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Moves every line break that falls inside group ruby to before the
+    /// group, or past it when the group starts its line.
+    fn keep_groups_whole(&self, lines: Vec<Range<usize>>, offset: usize) -> Vec<Range<usize>> {
+        let mut kept: Vec<Range<usize>> = Vec::new();
+        for line in lines {
+            let Some(previous) = kept.last_mut() else {
+                kept.push(line);
+                continue;
+            };
+            let boundary = offset + line.start;
+            let group = self
+                .annotations
+                .iter()
+                .map(|(range, _)| range)
+                .find(|range| range.start < boundary && boundary < range.end);
+            match group {
+                Some(group) if group.start > offset + previous.start => {
+                    previous.end = group.start - offset;
+                    kept.push(group.start - offset..line.end);
+                }
+                Some(group) => {
+                    let end = (group.end - offset).min(line.end);
+                    previous.end = end;
+                    if end < line.end {
+                        kept.push(end..line.end);
+                    }
+                }
+                None => kept.push(line),
+            }
+        }
+        kept
+    }
+
+    /// The line holding `pieces` of the text. Annotations a piece only holds
+    /// part of are left out.
+    fn line(&self, pieces: impl Iterator<Item = Range<usize>>) -> RubyLine {
+        let mut line = RubyLine::default();
+        for piece in pieces {
+            let mut position = piece.start;
+            for (range, ruby) in &self.annotations {
+                if range.start < piece.start || range.end > piece.end {
+                    continue;
+                }
+                line.push_text(&self.text[position..range.start]);
+                line.pieces
+                    .push((self.text[range.clone()].to_string(), Some(ruby.clone())));
+                position = range.end;
+            }
+            line.push_text(&self.text[position..piece.end]);
+        }
+        line
+    }
+}
+
+impl RubyLine {
+    fn push_text(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        match self.pieces.last_mut() {
+            Some((base, None)) => base.push_str(text),
+            _ => self.pieces.push((text.to_string(), None)),
+        }
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.pieces.iter().map(|(base, _)| base.as_str()).collect()
+    }
+
+    pub(crate) fn has_ruby(&self) -> bool {
+        self.pieces.iter().any(|(_, ruby)| ruby.is_some())
+    }
+
+    pub(crate) fn length(
+        &self,
+        metrics: &RubyMetrics,
+        character_spacing: Pt,
+    ) -> Result<Pt, font::Error> {
+        self.layout(metrics, character_spacing)
+            .map(|(_, length)| length)
+    }
+
+    /// Places the pieces along the line and returns them with the line's
+    /// length. Ruby longer than its base spreads the base characters over its
+    /// length; shorter group ruby is spread over the base, and mono-ruby is
+    /// centred on it.
+    pub(crate) fn layout(
+        &self,
+        metrics: &RubyMetrics,
+        character_spacing: Pt,
+    ) -> Result<(Vec<Placed<'_>>, Pt), font::Error> {
+        let mut placed = Vec::with_capacity(self.pieces.len());
+        let mut position = 0.0;
+        for (base, ruby) in &self.pieces {
+            if !placed.is_empty() {
+                position += character_spacing.0;
+            }
+            let base_length = metrics
+                .spec
+                .width_of_text_at_size(base, metrics.size, character_spacing)?
+                .0;
+            let Some(ruby) = ruby else {
+                placed.push(Placed {
+                    base: Run {
+                        text: base,
+                        offset: Pt(position),
+                        character_spacing,
+                    },
+                    ruby: None,
+                });
+                position += base_length;
+                continue;
+            };
+
+            let ruby_length = metrics
+                .spec
+                .width_of_text_at_size(&ruby.text, metrics.ruby_size, Pt(0.0))?
+                .0;
+            let length = base_length.max(ruby_length);
+            // 余った長さは文字の前後に 1:2:1 で割り付ける
+            let base_gap = (length - base_length) / cluster_count(base) as f32;
+            let (ruby_offset, ruby_gap) = if ruby.mono {
+                ((length - ruby_length) / 2.0, 0.0)
+            } else {
+                let gap = (length - ruby_length) / cluster_count(&ruby.text) as f32;
+                (gap / 2.0, gap)
+            };
+            placed.push(Placed {
+                base: Run {
+                    text: base,
+                    offset: Pt(position + base_gap / 2.0),
+                    character_spacing: character_spacing + Pt(base_gap),
+                },
+                ruby: Some(Run {
+                    text: &ruby.text,
+                    offset: Pt(position + ruby_offset),
+                    character_spacing: Pt(ruby_gap),
+                }),
+            });
+            position += length;
+        }
+        Ok((placed, Pt(position)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::ParsedFont;
+    use std::path::PathBuf;
+    use std::sync::{Arc, OnceLock};
+
+    fn test_spec() -> FontSpec {
+        static FONT: OnceLock<Arc<ParsedFont>> = OnceLock::new();
+        let font = FONT.get_or_init(|| {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join("fonts")
+                .join("NotoSansJP-Regular.ttf");
+            let bytes = std::fs::read(path).expect("test font should be readable");
+            Arc::new(ParsedFont::from_bytes(&bytes, 0, &mut Vec::new()).expect("test font"))
+        });
+        FontSpec::new(font.clone())
+    }
+
+    fn metrics(spec: &FontSpec) -> RubyMetrics<'_> {
+        RubyMetrics {
+            spec,
+            size: Pt(10.0),
+            ruby_size: Pt(5.0),
+        }
+    }
+
+    fn annotations(parsed: &RubyText) -> Vec<(&str, &str, bool)> {
+        parsed
+            .annotations
+            .iter()
+            .map(|(range, ruby)| (&parsed.text[range.clone()], ruby.text.as_str(), ruby.mono))
+            .collect()
+    }
+
+    #[test]
+    fn markup_annotates_groups_and_single_characters() {
+        let ruby = RubyText::parse("{田中|たなか}{太郎|た|ろう}様").unwrap();
+        assert_eq!(ruby.text, "田中太郎様");
+        assert_eq!(
+            annotations(&ruby),
+            [
+                ("田中", "たなか", false),
+                ("太", "た", true),
+                ("郎", "ろう", true)
+            ]
+        );
+
+        // An empty mono reading leaves its character bare.
+        let ruby = RubyText::parse("{取り|と|}").unwrap();
+        assert_eq!(annotations(&ruby), [("取", "と", true)]);
+    }
+
+    #[test]
+    fn unmatched_braces_and_escapes_stay_text() {
+        let ruby = RubyText::parse(r"{x} {|y} {a|} \{a|b}").unwrap();
+        assert_eq!(ruby.text, "{x} {|y} {a|} {a|b}");
+        assert!(ruby.annotations.is_empty());
+    }
+
+    #[test]
+    fn mono_ruby_needs_one_reading_per_character() {
+        let error = RubyText::parse("{田中|た|な|か}").unwrap_err();
+        assert!(matches!(error, Error::Ruby { .. }), "{error}");
+    }
+
+    #[test]
+    fn layout_spreads_the_shorter_of_base_and_ruby() {
+        let spec = test_spec();
+        let metrics = metrics(&spec);
+
+        // Four kana at 5pt outrun one 10pt kanji, which is centred under them.
+        let line = RubyText::parse("{曜|にちよう}")
+            .unwrap()
+            .line(std::iter::once(0..3));
+        let (placed, length) = line.layout(&metrics, Pt(0.0)).unwrap();
+        assert_eq!(length, Pt(20.0));
+        assert_eq!(placed[0].base.offset, Pt(5.0));
+        assert_eq!(placed[0].ruby.as_ref().unwrap().offset, Pt(0.0));
+
+        // Five kana over three kanji: 5pt spread 1:2:1 over the group.
+        let line = RubyText::parse("{大阪府|おおさかふ}")
+            .unwrap()
+            .line(std::iter::once(0..9));
+        let (placed, length) = line.layout(&metrics, Pt(0.0)).unwrap();
+        assert_eq!(length, Pt(30.0));
+        let ruby = placed[0].ruby.as_ref().unwrap();
+        assert_eq!((ruby.offset, ruby.character_spacing), (Pt(0.5), Pt(1.0)));
+    }
+
+    #[test]
+    fn lines_do_not_break_inside_group_ruby() {
+        let spec = test_spec();
+        let lines = RubyText::parse("あ{東京都|とうきょうと}{日|ひ}")
+            .unwrap()
+            .lines(&metrics(&spec), LineBreakMode::Char, Pt(35.0), Pt(0.0))
+            .unwrap();
+        let texts: Vec<String> = lines.iter().map(RubyLine::text).collect();
+        assert_eq!(texts, ["あ", "東京都日"]);
+        assert!(!lines[0].has_ruby());
+        assert!(lines[1].has_ruby());
+    }
+}
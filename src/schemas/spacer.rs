@@ -4,6 +4,7 @@ use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct JsonSpacerSchema {
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     pub height: f32,
 }
 
@@ -44,14 +45,14 @@ impl Spacer {
     }
 
     pub(crate) fn advance(&self, base_pdf: &BasePdf, cursor: &mut FlowCursor) {
-        let y = cursor.y.unwrap_or(base_pdf.padding.top);
-        let bottom = base_pdf.height - base_pdf.padding.bottom;
+        let y = cursor.y.unwrap_or(base_pdf.flow_top(cursor.page));
+        let bottom = base_pdf.flow_bottom(cursor.page);
 
         if y + self.base.height <= bottom {
             cursor.y = Some(y + self.base.height);
         } else {
             cursor.page += 1;
-            cursor.y = Some(base_pdf.padding.top);
+            cursor.y = Some(base_pdf.flow_top(cursor.page));
         }
     }
 }
@@ -72,17 +73,16 @@ mod tests {
     use crate::schemas::Frame;
 
     fn base_pdf() -> BasePdf {
-        BasePdf {
-            width: Mm(210.0),
-            height: Mm(297.0),
-            padding: Frame {
+        BasePdf::new(
+            Mm(210.0),
+            Mm(297.0),
+            Frame {
                 top: Mm(10.0),
                 right: Mm(10.0),
                 bottom: Mm(10.0),
                 left: Mm(10.0),
             },
-            static_schema: vec![],
-        }
+        )
     }
 
     fn spacer(height: f32) -> Spacer {
@@ -12,7 +12,9 @@ use snafu::{OptionExt, ResultExt};
 pub struct JsonSvgSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     content: String,
     rotate: Option<f32>,
@@ -0,0 +1,483 @@
+//! Two-dimensional symbologies drawn by the `qrCode` schema.
+//!
+//! Every symbology implements [`SymbolEncoder`], which turns the content into
+//! a grid of modules plus the quiet zone it needs. The grid is square except
+//! for PDF417, whose rows are several modules high. The schema then places
+//! and rotates that grid the same way whatever the symbology.
+
+use serde::Deserialize;
+
+use super::{aztec, datamatrix, pdf417, Error};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Symbology {
+    #[default]
+    Qr,
+    MicroQr,
+    DataMatrix,
+    Aztec,
+    Pdf417,
+}
+
+impl Symbology {
+    pub fn name(self) -> &'static str {
+        match self {
+            Symbology::Qr => "QR code",
+            Symbology::MicroQr => "Micro QR code",
+            Symbology::DataMatrix => "Data Matrix",
+            Symbology::Aztec => "Aztec code",
+            Symbology::Pdf417 => "PDF417",
+        }
+    }
+
+    pub(crate) fn encoder(self) -> &'static dyn SymbolEncoder {
+        match self {
+            Symbology::Qr => &QrEncoder,
+            Symbology::MicroQr => &MicroQrEncoder,
+            Symbology::DataMatrix => &datamatrix::DataMatrixEncoder,
+            Symbology::Aztec => &aztec::AztecEncoder,
+            Symbology::Pdf417 => &pdf417::Pdf417Encoder,
+        }
+    }
+}
+
+/// QR code error correction level, recovering about 7%, 15%, 25% or 30%
+/// of the symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ErrorCorrectionLevel {
+    L,
+    M,
+    Q,
+    H,
+}
+
+impl From<ErrorCorrectionLevel> for qrcode::EcLevel {
+    fn from(level: ErrorCorrectionLevel) -> Self {
+        match level {
+            ErrorCorrectionLevel::L => qrcode::EcLevel::L,
+            ErrorCorrectionLevel::M => qrcode::EcLevel::M,
+            ErrorCorrectionLevel::Q => qrcode::EcLevel::Q,
+            ErrorCorrectionLevel::H => qrcode::EcLevel::H,
+        }
+    }
+}
+
+/// Encoder settings beyond the content itself.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SymbolOptions {
+    pub(crate) error_correction: Option<ErrorCorrectionLevel>,
+    /// The smallest version to use; larger content still picks a larger one.
+    pub(crate) min_version: Option<u8>,
+    /// Marks the content as a GS1 element string with FNC1 first, the
+    /// group separators in it standing for FNC1.
+    pub(crate) gs1: bool,
+}
+
+impl SymbolOptions {
+    /// Fails for symbologies that choose their own size and error correction.
+    pub(crate) fn reject(&self, symbology: Symbology) -> Result<(), Error> {
+        if self.error_correction.is_some() || self.min_version.is_some() {
+            return Err(encoding_error(
+                symbology,
+                "errorCorrectionLevel and version only apply to qr and microQr",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Fails for symbologies that cannot mark their data as GS1.
+    pub(crate) fn reject_gs1(&self, symbology: Symbology) -> Result<(), Error> {
+        if self.gs1 {
+            return Err(encoding_error(
+                symbology,
+                "gs1 elementString only applies to qr and dataMatrix; use digitalLink instead",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A grid of modules, `true` for dark, in rows from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SymbolMatrix {
+    pub(crate) width: usize,
+    /// Rows of modules; the same as `width` for square symbols.
+    pub(crate) height: usize,
+    /// Height of every row in module widths.
+    pub(crate) row_height: usize,
+    pub(crate) modules: Vec<bool>,
+    /// Light modules required around the symbol.
+    pub(crate) quiet_zone: u32,
+    /// Where the codewords of a QR code lie; `None` for other symbologies.
+    pub(crate) codewords: Option<CodewordMap>,
+}
+
+/// The codeword each module of a QR code carries, and how many damaged
+/// codewords the error correction can repair.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CodewordMap {
+    /// Codeword index per module, in rows from the top; `None` for the
+    /// finder, timing, alignment, format and version patterns.
+    pub(crate) index: Vec<Option<usize>>,
+    pub(crate) correctable: usize,
+}
+
+impl SymbolMatrix {
+    pub(crate) fn new(width: usize, quiet_zone: u32) -> Self {
+        Self::rows(width, width, 1, quiet_zone)
+    }
+
+    /// A `width` × `height` grid whose rows are `row_height` modules high.
+    pub(crate) fn rows(width: usize, height: usize, row_height: usize, quiet_zone: u32) -> Self {
+        SymbolMatrix {
+            width,
+            height,
+            row_height,
+            modules: vec![false; width * height],
+            quiet_zone,
+            codewords: None,
+        }
+    }
+
+    /// Modules across and down the symbol, quiet zone included, counting a
+    /// row as `row_height` modules.
+    pub(crate) fn extent(&self, quiet_zone: usize) -> (usize, usize) {
+        (
+            self.width + 2 * quiet_zone,
+            self.height * self.row_height + 2 * quiet_zone,
+        )
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.width + x]
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.width + x] = dark;
+    }
+}
+
+pub(crate) trait SymbolEncoder: Sync {
+    fn encode(&self, content: &str, options: &SymbolOptions) -> Result<SymbolMatrix, Error>;
+}
+
+pub(crate) fn encoding_error(symbology: Symbology, message: impl Into<String>) -> Error {
+    Error::SymbolEncoding {
+        symbology: symbology.name().to_string(),
+        message: message.into(),
+    }
+}
+
+/// The smallest version from `options.min_version` up to `max_version` that
+/// holds `content`.
+fn smallest_version(
+    symbology: Symbology,
+    content: &str,
+    options: &SymbolOptions,
+    default_level: ErrorCorrectionLevel,
+    max_version: u8,
+    version: fn(i16) -> qrcode::Version,
+) -> Result<qrcode::QrCode, Error> {
+    let level = options.error_correction.unwrap_or(default_level);
+    let min_version = options.min_version.unwrap_or(1);
+    if !(1..=max_version).contains(&min_version) {
+        return Err(encoding_error(
+            symbology,
+            format!("version must be between 1 and {max_version}"),
+        ));
+    }
+    (min_version..=max_version)
+        .find_map(|number| {
+            let version = version(i16::from(number));
+            if options.gs1 {
+                gs1_code(content, version, level.into())
+            } else {
+                qrcode::QrCode::with_version(content, version, level.into()).ok()
+            }
+        })
+        .ok_or_else(|| {
+            encoding_error(
+                symbology,
+                format!(
+                    "the content does not fit in version {max_version} at error correction level {level:?}"
+                ),
+            )
+        })
+}
+
+/// A QR code in FNC1 first position mode, which marks the data as a GS1
+/// element string. Byte mode reads the group separator as FNC1 while
+/// alphanumeric mode reads `%` as one, so content holding a `%` is kept
+/// whole in byte mode.
+fn gs1_code(
+    content: &str,
+    version: qrcode::Version,
+    level: qrcode::EcLevel,
+) -> Option<qrcode::QrCode> {
+    let mut bits = qrcode::bits::Bits::new(version);
+    bits.push_fnc1_first_position().ok()?;
+    if content.contains('%') {
+        bits.push_byte_data(content.as_bytes()).ok()?;
+    } else {
+        bits.push_optimal_data(content.as_bytes()).ok()?;
+    }
+    bits.push_terminator(level).ok()?;
+    qrcode::QrCode::with_bits(bits, level).ok()
+}
+
+struct QrEncoder;
+
+impl SymbolEncoder for QrEncoder {
+    fn encode(&self, content: &str, options: &SymbolOptions) -> Result<SymbolMatrix, Error> {
+        let code = smallest_version(
+            Symbology::Qr,
+            content,
+            options,
+            ErrorCorrectionLevel::M,
+            40,
+            qrcode::Version::Normal,
+        )?;
+        Ok(SymbolMatrix {
+            width: code.width(),
+            height: code.width(),
+            row_height: 1,
+            modules: dark_modules(&code),
+            quiet_zone: 4,
+            codewords: Some(codeword_map(&code)),
+        })
+    }
+}
+
+struct MicroQrEncoder;
+
+impl SymbolEncoder for MicroQrEncoder {
+    fn encode(&self, content: &str, options: &SymbolOptions) -> Result<SymbolMatrix, Error> {
+        options.reject_gs1(Symbology::MicroQr)?;
+        // M1〜M4 のうち収まる最小のバージョンを選ぶ
+        let code = smallest_version(
+            Symbology::MicroQr,
+            content,
+            options,
+            ErrorCorrectionLevel::L,
+            4,
+            qrcode::Version::Micro,
+        )?;
+        Ok(SymbolMatrix {
+            width: code.width(),
+            height: code.width(),
+            row_height: 1,
+            modules: dark_modules(&code),
+            quiet_zone: 2,
+            codewords: None,
+        })
+    }
+}
+
+/// Runs of dark modules in a row as `(first module, length)`.
+pub(crate) fn dark_runs(modules: &[bool]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        while start < modules.len() && !modules[start] {
+            start += 1;
+        }
+        if start >= modules.len() {
+            return None;
+        }
+        let end = modules[start..]
+            .iter()
+            .position(|dark| !dark)
+            .map_or(modules.len(), |offset| start + offset);
+        let run = (start, end - start);
+        start = end;
+        Some(run)
+    })
+}
+
+/// Follows the two-module-wide zigzag a QR code fills its data modules in,
+/// from the bottom-right corner, numbering every eight modules as one
+/// codeword. The remainder bits at the end count as one more.
+fn codeword_map(code: &qrcode::QrCode) -> CodewordMap {
+    let width = code.width();
+    let version = match code.version() {
+        qrcode::Version::Normal(number) => number as usize,
+        qrcode::Version::Micro(_) => unreachable!("Micro QR codes have no codeword map"),
+    };
+    // is_functional はバージョン情報の領域を含まないので別に除く
+    let is_version_info = |x: usize, y: usize| {
+        version >= 7
+            && ((x + 11 >= width && x + 8 < width && y < 6)
+                || (y + 11 >= width && y + 8 < width && x < 6))
+    };
+
+    let mut index = vec![None; width * width];
+    let mut bit = 0;
+    let mut upward = true;
+    let mut right = width - 1;
+    loop {
+        // 縦のタイミングパターンの列は飛ばす
+        if right == 6 {
+            right = 5;
+        }
+        for step in 0..width {
+            let y = if upward { width - 1 - step } else { step };
+            for x in [right, right - 1] {
+                if !code.is_functional(x, y) && !is_version_info(x, y) {
+                    index[y * width + x] = Some(bit / 8);
+                    bit += 1;
+                }
+            }
+        }
+        if right < 2 {
+            break;
+        }
+        upward = !upward;
+        right -= 2;
+    }
+
+    CodewordMap {
+        index,
+        correctable: code.max_allowed_errors(),
+    }
+}
+
+fn dark_modules(code: &qrcode::QrCode) -> Vec<bool> {
+    code.to_colors()
+        .into_iter()
+        .map(|color| color == qrcode::Color::Dark)
+        .collect()
+}
+
+/// Arithmetic in GF(2^m) for the Reed–Solomon codes of Data Matrix and Aztec.
+/// PDF417 computes in the prime field GF(929) instead.
+pub(crate) struct GaloisField {
+    exp: Vec<u16>,
+    log: Vec<u16>,
+}
+
+impl GaloisField {
+    /// The field of `size` elements generated by the `primitive` polynomial.
+    pub(crate) fn new(primitive: u32, size: usize) -> Self {
+        let mut exp = vec![0u16; size];
+        let mut log = vec![0u16; size];
+        let mut value = 1u32;
+        for (power, slot) in exp.iter_mut().enumerate() {
+            *slot = value as u16;
+            value <<= 1;
+            if value as usize >= size {
+                value ^= primitive;
+            }
+            if power < size - 1 {
+                log[usize::from(*slot)] = power as u16;
+            }
+        }
+        GaloisField { exp, log }
+    }
+
+    fn multiply(&self, a: u16, b: u16) -> u16 {
+        if a == 0 || b == 0 {
+            return 0;
+        }
+        let order = self.exp.len() - 1;
+        let power =
+            (usize::from(self.log[usize::from(a)]) + usize::from(self.log[usize::from(b)])) % order;
+        self.exp[power]
+    }
+
+    /// `count` check words for `data`, with the generator's roots at α¹…αⁿ.
+    pub(crate) fn check_words(&self, data: &[u16], count: usize) -> Vec<u16> {
+        let mut generator = vec![1u16];
+        for root in 1..=count {
+            let alpha = self.exp[root % (self.exp.len() - 1)];
+            let mut next = vec![0u16; generator.len() + 1];
+            for (index, &coefficient) in generator.iter().enumerate() {
+                next[index] ^= coefficient;
+                next[index + 1] ^= self.multiply(coefficient, alpha);
+            }
+            generator = next;
+        }
+
+        let mut remainder = vec![0u16; count];
+        for &word in data {
+            let feedback = word ^ remainder[0];
+            remainder.rotate_left(1);
+            remainder[count - 1] = 0;
+            for (slot, &coefficient) in remainder.iter_mut().zip(&generator[1..]) {
+                *slot ^= self.multiply(feedback, coefficient);
+            }
+        }
+        remainder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn qr_and_micro_qr_share_the_matrix_type() {
+        let qr = Symbology::Qr
+            .encoder()
+            .encode("HELLO", &SymbolOptions::default())
+            .unwrap();
+        assert_eq!((qr.width, qr.quiet_zone), (21, 4));
+        let micro = Symbology::MicroQr
+            .encoder()
+            .encode("12345", &SymbolOptions::default())
+            .unwrap();
+        // Five digits fit in M1.
+        assert_eq!((micro.width, micro.quiet_zone), (11, 2));
+        assert!(Symbology::MicroQr
+            .encoder()
+            .encode(&"X".repeat(100), &SymbolOptions::default())
+            .is_err());
+    }
+
+    #[test]
+    fn codeword_maps_cover_every_data_module() {
+        // Data modules per version: 26 codewords in version 1, 44 plus 7
+        // remainder bits in version 2 and 196 in version 7, whose version
+        // information must not be counted as data.
+        for (version, data_modules) in [(1, 26 * 8), (2, 44 * 8 + 7), (7, 196 * 8)] {
+            let options = SymbolOptions {
+                error_correction: None,
+                min_version: Some(version),
+                gs1: false,
+            };
+            let symbol = Symbology::Qr.encoder().encode("A", &options).unwrap();
+            let map = symbol.codewords.unwrap();
+            let data = map.index.iter().flatten().count();
+            assert_eq!(data, data_modules, "version {version}");
+            // Codewords are numbered in order, remainder bits included.
+            let last = map.index.iter().flatten().max().copied().unwrap();
+            assert_eq!(last, (data_modules - 1) / 8, "version {version}");
+        }
+        // Version 1-M has 10 error correction codewords; two of them guard
+        // against misdecoding, so four damaged codewords can be repaired.
+        let symbol = Symbology::Qr
+            .encoder()
+            .encode("A", &SymbolOptions::default())
+            .unwrap();
+        assert_eq!(symbol.codewords.unwrap().correctable, 4);
+    }
+
+    #[test]
+    fn dark_runs_join_neighbouring_modules() {
+        let modules = [true, true, false, true, false, false, true];
+        assert_eq!(
+            dark_runs(&modules).collect::<Vec<_>>(),
+            vec![(0, 2), (3, 1), (6, 1)]
+        );
+    }
+
+    #[test]
+    fn check_words_match_the_data_matrix_reference() {
+        // ISO/IEC 16022 worked example: "123456" in a 10 × 10 symbol.
+        let field = GaloisField::new(0x12d, 256);
+        assert_eq!(
+            field.check_words(&[142, 164, 186], 5),
+            vec![114, 25, 5, 88, 102]
+        );
+    }
+}
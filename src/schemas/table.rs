@@ -1,13 +1,14 @@
+use super::color::PdfColor;
 use super::{base::BaseSchema, BasePdf, HasBaseSchema, InvalidColorSnafu, Schema};
 use super::{qrcode, BoundingBox, Frame, JsonFrame, SchemaTrait, VerticalAlignment};
-use crate::font::{FontMap, LineBreakMode};
+use crate::font::{FontMap, FontName, LineBreakMode};
 use crate::schemas::pdf_utils::{draw_table_cell, DrawCell};
 use crate::schemas::text;
 use crate::{
     schemas::{Alignment, Error, JsonPosition},
     utils::OpBuffer,
 };
-use printpdf::{Color, Mm, PdfDocument, Pt, Rgb};
+use printpdf::{Mm, PdfDocument, Pt};
 use serde::Deserialize;
 use snafu::prelude::*;
 use std::cmp::max;
@@ -41,8 +42,10 @@ pub enum JsonColumnWidth {
 }
 
 impl JsonColumnWidth {
-    /// Accepts `25`, `"25mm"`, `"20%"`, `"2fr"` and `"fr"` (= `1fr`). Suffixes are
-    /// case-insensitive and surrounding whitespace is ignored.
+    /// Accepts `25`, `"25mm"`, `"20%"`, `"2fr"` and `"fr"` (= `1fr`), plus any
+    /// other fixed length `units::parse_length_mm` understands (`"1in"`,
+    /// `"72pt"`, ...). Suffixes are case-insensitive and surrounding
+    /// whitespace is ignored.
     fn parse(&self, column_index: usize) -> Result<ColumnWidth, Error> {
         let width = match self {
             JsonColumnWidth::Number(value) => ColumnWidth::Fixed(*value as f64),
@@ -59,10 +62,13 @@ impl JsonColumnWidth {
                     ColumnWidth::Percent(parse_number(number, text, column_index)?)
                 } else if let Some(number) = lowered.strip_suffix("mm") {
                     ColumnWidth::Fixed(parse_number(number, text, column_index)?)
+                } else if let Some(mm) = super::units::parse_length_mm(text) {
+                    ColumnWidth::Fixed(mm as f64)
                 } else {
                     whatever!(
                         "column {column_index}: invalid width {text:?}; \
-                         expected a number (mm) or one of \"25mm\", \"20%\", \"2fr\""
+                         expected a number (mm), a length such as \"25mm\" or \"1in\", \
+                         or one of \"20%\", \"2fr\""
                     )
                 }
             }
@@ -117,6 +123,7 @@ pub struct JsonColumn {
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct JsonTableStyles {
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     border_width: f32,
     border_color: String,
 }
@@ -132,7 +139,7 @@ pub enum JsonSchema {
 #[serde(rename_all = "camelCase")]
 pub struct JsonHeadStyles {
     font_size: f32,
-    font_name: String,
+    font_name: FontName,
     character_spacing: Option<f32>,
     alignment: Option<Alignment>,
     vertical_alignment: Option<VerticalAlignment>,
@@ -148,14 +155,14 @@ pub struct JsonHeadStyles {
 #[derive(Debug, Clone)]
 pub struct HeadStyles {
     font_size: Pt,
-    font_name: String,
+    font_name: FontName,
     character_spacing: f32,
     alignment: Alignment,
     vertical_alignment: VerticalAlignment,
     line_height: f32,
-    font_color: csscolorparser::Color,
-    border_color: csscolorparser::Color,
-    background_color: csscolorparser::Color,
+    font_color: PdfColor,
+    border_color: PdfColor,
+    background_color: PdfColor,
     border_width: Frame,
     padding: Frame,
     line_break_mode: Option<LineBreakMode>,
@@ -189,7 +196,7 @@ impl HeadStyles {
 pub struct JsonHead {
     content: String,
     font_size: Option<f32>,
-    font_name: Option<String>,
+    font_name: Option<FontName>,
     character_spacing: Option<f32>,
     alignment: Option<Alignment>,
     vertical_alignment: Option<VerticalAlignment>,
@@ -215,22 +222,22 @@ pub struct BodyStyles {
     alignment: Alignment,
     vertical_alignment: VerticalAlignment,
     character_spacing: f32,
-    font_color: csscolorparser::Color,
+    font_color: PdfColor,
     line_height: f32,
-    background_color: csscolorparser::Color,
-    alternate_background_color: Option<csscolorparser::Color>,
+    background_color: PdfColor,
+    alternate_background_color: Option<PdfColor>,
     padding: Frame,
     line_break_mode: LineBreakMode,
 }
 
 impl BodyStyles {
     pub fn from_json(json: JsonBodyStyles) -> Result<Self, Error> {
-        let font_color = csscolorparser::parse(&json.font_color).context(InvalidColorSnafu)?;
+        let font_color = PdfColor::parse(&json.font_color).context(InvalidColorSnafu)?;
 
         let background_color =
-            csscolorparser::parse(&json.background_color).context(InvalidColorSnafu)?;
+            PdfColor::parse(&json.background_color).context(InvalidColorSnafu)?;
         let alternate_background_color = match json.alternate_background_color {
-            Some(color) => Some(csscolorparser::parse(&color).context(InvalidColorSnafu)?),
+            Some(color) => Some(PdfColor::parse(&color).context(InvalidColorSnafu)?),
             None => None,
         };
         Ok(Self {
@@ -266,7 +273,9 @@ impl BodyStyles {
 pub struct JsonTableSchema {
     pub name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     show_head: bool,
     head_styles: JsonHeadStyles,
@@ -282,12 +291,12 @@ pub struct JsonTableSchema {
 #[derive(Debug, Clone)]
 pub struct TableStyles {
     border_width: Mm,
-    border_color: csscolorparser::Color,
+    border_color: PdfColor,
 }
 
 impl TableStyles {
     pub fn from_json(json: JsonTableStyles) -> Result<Self, Error> {
-        let border_color = csscolorparser::parse(&json.border_color).context(InvalidColorSnafu)?;
+        let border_color = PdfColor::parse(&json.border_color).context(InvalidColorSnafu)?;
         Ok(Self {
             border_width: Mm(json.border_width),
             border_color,
@@ -319,27 +328,18 @@ pub struct Table {
 /// Visual styling applied to every cell rectangle in a single table row.
 #[derive(Debug, Clone)]
 struct RowCellStyle {
-    background: csscolorparser::Color,
-    border_color: csscolorparser::Color,
+    background: PdfColor,
+    border_color: PdfColor,
     border_width: Frame,
 }
 
-fn to_rgb(color: &csscolorparser::Color) -> Color {
-    Color::Rgb(Rgb {
-        r: color.r,
-        g: color.g,
-        b: color.b,
-        icc_profile: None,
-    })
-}
-
-/// Parse a CSS color, treating an empty/blank string as "unset" and falling
+/// Parse a color, treating an empty/blank string as "unset" and falling
 /// back to `default`. Templates commonly use `""` to mean a field was left
 /// unspecified (e.g. a header with `borderWidth: 0` and `borderColor: ""`).
-fn parse_color_or(value: &str, default: &str) -> Result<csscolorparser::Color, Error> {
+fn parse_color_or(value: &str, default: &str) -> Result<PdfColor, Error> {
     let value = value.trim();
     let source = if value.is_empty() { default } else { value };
-    csscolorparser::parse(source).context(InvalidColorSnafu)
+    PdfColor::parse(source).context(InvalidColorSnafu)
 }
 
 impl Table {
@@ -355,8 +355,13 @@ impl Table {
     /// `padding.left` deliberately plays no part — it would subtract space the
     /// table does not necessarily start at, and horizontal placement is fully
     /// described by `position.x`.
+    ///
+    /// The boundary is taken on the page the table is declared on. With
+    /// mirrored margins the table moves with them on the pages it continues
+    /// onto, so the space it has stays the same there.
     fn effective_width(&self, base_pdf: &BasePdf) -> Mm {
-        let available_width = (base_pdf.width - base_pdf.padding.right - self.base.x).max(Mm(0.0));
+        let right = base_pdf.padding_on(base_pdf.origin_page).right;
+        let available_width = (base_pdf.width - right - self.base.x).max(Mm(0.0));
         // The schema's own width is not validated anywhere, and a negative one
         // would flip the overflow scale factor and hand back negative columns.
         self.base.width.max(Mm(0.0)).min(available_width)
@@ -595,11 +600,15 @@ impl Table {
         doc: &mut PdfDocument,
         buffer: &mut OpBuffer,
     ) -> Result<(), Error> {
-        let fill = to_rgb(&cell_style.background);
-        let border_color = to_rgb(&cell_style.border_color);
+        let fill = &cell_style.background;
+        let border_color = &cell_style.border_color;
+        let shift = base_pdf.horizontal_shift(page_index);
 
         // Render each cell in the row
         for (col_index, schema) in schemas.iter().enumerate() {
+            // Follow the mirrored margins onto the page the row lands on.
+            let mut schema = schema.clone();
+            schema.base_mut().x += shift;
             let base = schema.get_base_copy();
             let width = cell_widths[col_index];
 
@@ -737,10 +746,9 @@ impl Table {
         current_top_mm: Option<Mm>,
         buffer: &mut OpBuffer,
     ) -> Result<(usize, Option<Mm>), Error> {
-        let top_margin_in_mm = base_pdf.padding.top;
         let mut internal_page_counter: usize = 0;
         let y_top_mm: Mm = current_top_mm.unwrap_or(self.base.y);
-        let y_bottom_mm = base_pdf.height - base_pdf.padding.bottom;
+        let mut y_bottom_mm = base_pdf.flow_bottom(current_page);
         let mut y_line_mm: Mm = y_top_mm;
         let cell_widths = self.resolve_column_widths(base_pdf);
 
@@ -748,7 +756,7 @@ impl Table {
         let mut visual_row_index: usize = 0;
 
         let (header_row, header_height) = if self.show_head {
-            let header = self.create_header_row(top_margin_in_mm, &cell_widths)?;
+            let header = self.create_header_row(base_pdf.flow_top(current_page), &cell_widths)?;
             (Some(header.0), header.1)
         } else {
             (None, Mm(0.0))
@@ -765,7 +773,8 @@ impl Table {
                 if y_line_mm + header_height + max_height > y_bottom_mm {
                     // If the header row exceeds the page height, we need to create a new page
                     internal_page_counter += 1;
-                    y_line_mm = top_margin_in_mm;
+                    y_line_mm = base_pdf.flow_top(current_page + internal_page_counter);
+                    y_bottom_mm = base_pdf.flow_bottom(current_page + internal_page_counter);
                 }
 
                 let updated_header_row = match header_row.clone() {
@@ -832,6 +841,9 @@ impl Table {
                     // If it doesn't, just continue to the next row
                     if y_line_mm + max_height > y_bottom_mm {
                         internal_page_counter += 1;
+                        let top_margin_in_mm =
+                            base_pdf.flow_top(current_page + internal_page_counter);
+                        y_bottom_mm = base_pdf.flow_bottom(current_page + internal_page_counter);
 
                         y_line_mm = top_margin_in_mm;
 
@@ -972,7 +984,7 @@ mod tests {
     fn create_test_head_styles() -> JsonHeadStyles {
         JsonHeadStyles {
             font_size: 12.0,
-            font_name: "TestFont".to_string(),
+            font_name: "TestFont".into(),
             character_spacing: Some(0.0),
             alignment: Some(Alignment::Center),
             vertical_alignment: Some(VerticalAlignment::Middle),
@@ -1013,7 +1025,7 @@ mod tests {
             header: JsonHead {
                 content: label.to_string(),
                 font_size: Some(12.0),
-                font_name: Some("TestFont".to_string()),
+                font_name: Some("TestFont".into()),
                 character_spacing: Some(0.0),
                 alignment: Some(Alignment::Center),
                 vertical_alignment: Some(VerticalAlignment::Middle),
@@ -1087,17 +1099,16 @@ mod tests {
     }
 
     fn create_test_base_pdf() -> BasePdf {
-        BasePdf {
-            width: Mm(210.0),
-            height: Mm(297.0),
-            padding: Frame {
+        BasePdf::new(
+            Mm(210.0),
+            Mm(297.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(0.0),
                 bottom: Mm(0.0),
                 left: Mm(0.0),
             },
-            static_schema: vec![],
-        }
+        )
     }
 
     #[test]
@@ -1108,20 +1119,17 @@ mod tests {
         assert!(result.is_ok());
         let head_styles = result.unwrap();
         assert_eq!(head_styles.font_size, Pt(12.0));
-        assert_eq!(head_styles.font_name, "TestFont");
+        assert_eq!(head_styles.font_name, FontName::from("TestFont"));
         assert_eq!(head_styles.character_spacing, 0.0);
         assert_eq!(head_styles.line_height, 1.0);
-        assert_eq!(
-            head_styles.font_color,
-            csscolorparser::parse("#ffffff").unwrap()
-        );
+        assert_eq!(head_styles.font_color, PdfColor::parse("#ffffff").unwrap());
         assert_eq!(
             head_styles.background_color,
-            csscolorparser::parse("#2980ba").unwrap()
+            PdfColor::parse("#2980ba").unwrap()
         );
         assert_eq!(
             head_styles.border_color,
-            csscolorparser::parse("#000000").unwrap()
+            PdfColor::parse("#000000").unwrap()
         );
     }
 
@@ -1210,6 +1218,18 @@ mod tests {
         }
     }
 
+    #[test]
+    fn column_width_accepts_other_length_units_as_fixed_widths() {
+        for (input, expected_mm) in [("1in", 25.4), ("2cm", 20.0), ("72pt", 25.4)] {
+            match JsonColumnWidth::Text(input.into()).parse(0).unwrap() {
+                ColumnWidth::Fixed(mm) => {
+                    assert!((mm - expected_mm).abs() < 1e-4, "{input:?} gave {mm}mm")
+                }
+                other => panic!("{input:?} should be a fixed width, got {other:?}"),
+            }
+        }
+    }
+
     #[test]
     fn column_width_rejects_malformed_and_non_positive_values() {
         // `f64::from_str` accepts "NaN"/"inf", so those must be caught explicitly.
@@ -1306,17 +1326,16 @@ mod tests {
     }
 
     fn page_with_horizontal_padding(left: f32, right: f32) -> BasePdf {
-        BasePdf {
-            width: Mm(210.0),
-            height: Mm(297.0),
-            padding: Frame {
+        BasePdf::new(
+            Mm(210.0),
+            Mm(297.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(right),
                 bottom: Mm(0.0),
                 left: Mm(left),
             },
-            static_schema: vec![],
-        }
+        )
     }
 
     /// The x each header cell is actually laid out at.
@@ -1381,6 +1400,23 @@ mod tests {
         assert!((total_width(&table, &tight) - 140.0).abs() < 1e-3);
     }
 
+    #[test]
+    fn mirrored_margins_take_the_right_boundary_from_the_declaring_page() {
+        // inside 30mm, outside 10mm: the right side is the outside margin on
+        // odd pages and the inside margin on even ones.
+        let table = table_at(10.0, 190.0);
+        let mut odd = page_with_horizontal_padding(30.0, 10.0);
+        odd.mirror_margins = true;
+        let even = BasePdf {
+            page_offset: 1,
+            ..odd.clone()
+        };
+
+        assert!((total_width(&table, &odd) - 190.0).abs() < 1e-3);
+        // 210 - 30 - 10 = 170mm left of the inside margin.
+        assert!((total_width(&table, &even) - 170.0).abs() < 1e-3);
+    }
+
     #[test]
     fn declared_width_is_kept_when_it_reaches_the_right_boundary() {
         // templates/quote.json: x=10, width=190 on a 210mm page with 10mm right
@@ -1566,17 +1602,16 @@ mod tests {
         let font_map = create_real_test_font_map();
         let table = Table::from_json(json, &font_map).unwrap();
 
-        let base_pdf = BasePdf {
-            width: Mm(210.0),
-            height: Mm(297.0),
-            padding: Frame {
+        let base_pdf = BasePdf::new(
+            Mm(210.0),
+            Mm(297.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(150.0),
                 bottom: Mm(0.0),
                 left: Mm(150.0),
             },
-            static_schema: vec![],
-        };
+        );
 
         for width in table.resolve_column_widths(&base_pdf) {
             assert!(width.0.is_finite());
@@ -1594,17 +1629,16 @@ mod tests {
         let font_map = create_real_test_font_map();
         let table = Table::from_json(json, &font_map).unwrap();
 
-        let base_pdf = BasePdf {
-            width: Mm(210.0),
-            height: Mm(297.0),
-            padding: Frame {
+        let base_pdf = BasePdf::new(
+            Mm(210.0),
+            Mm(297.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(20.0),
                 bottom: Mm(0.0),
                 left: Mm(20.0),
             },
-            static_schema: vec![],
-        };
+        );
 
         let widths = table.resolve_column_widths(&base_pdf);
         assert!((widths[0].0 - 180.0).abs() < 1e-3, "got {}", widths[0].0);
@@ -1698,17 +1732,16 @@ mod tests {
     }
 
     fn create_base_pdf() -> BasePdf {
-        BasePdf {
-            width: Mm(200.0),
-            height: Mm(300.0),
-            padding: Frame {
+        BasePdf::new(
+            Mm(200.0),
+            Mm(300.0),
+            Frame {
                 top: Mm(0.0),
                 right: Mm(0.0),
                 bottom: Mm(0.0),
                 left: Mm(0.0),
             },
-            static_schema: vec![],
-        }
+        )
     }
 
     fn create_text_table_schema(
@@ -1927,8 +1960,8 @@ mod tests {
         let header = table.header_row_style();
         let body = table.body_row_style(0);
 
-        assert_eq!(header.background, csscolorparser::parse("#2980ba").unwrap());
-        assert_eq!(body.background, csscolorparser::parse("#ffffff").unwrap());
+        assert_eq!(header.background, PdfColor::parse("#2980ba").unwrap());
+        assert_eq!(body.background, PdfColor::parse("#ffffff").unwrap());
         assert_ne!(header.background, body.background);
     }
 
@@ -1939,7 +1972,7 @@ mod tests {
 
         assert_eq!(
             *table.columns[0].header.font_color(),
-            csscolorparser::parse("#ffffff").unwrap()
+            PdfColor::parse("#ffffff").unwrap()
         );
     }
 
@@ -1952,7 +1985,7 @@ mod tests {
 
         assert_eq!(
             table.body_row_style(0).border_color,
-            csscolorparser::parse("#ff0000").unwrap()
+            PdfColor::parse("#ff0000").unwrap()
         );
     }
 
@@ -1963,11 +1996,11 @@ mod tests {
 
         assert_eq!(
             table.body_row_style(0).background,
-            csscolorparser::parse("#ffffff").unwrap()
+            PdfColor::parse("#ffffff").unwrap()
         );
         assert_eq!(
             table.body_row_style(1).background,
-            csscolorparser::parse("#f8f8f8").unwrap()
+            PdfColor::parse("#f8f8f8").unwrap()
         );
     }
 
@@ -1994,11 +2027,11 @@ mod tests {
     fn parse_color_or_falls_back_on_empty_or_blank() {
         assert_eq!(
             parse_color_or("", "#123456").unwrap(),
-            csscolorparser::parse("#123456").unwrap()
+            PdfColor::parse("#123456").unwrap()
         );
         assert_eq!(
             parse_color_or("   ", "#123456").unwrap(),
-            csscolorparser::parse("#123456").unwrap()
+            PdfColor::parse("#123456").unwrap()
         );
     }
 
@@ -2006,7 +2039,7 @@ mod tests {
     fn parse_color_or_uses_non_empty_value() {
         assert_eq!(
             parse_color_or("#ff0000", "#000000").unwrap(),
-            csscolorparser::parse("#ff0000").unwrap()
+            PdfColor::parse("#ff0000").unwrap()
         );
     }
 
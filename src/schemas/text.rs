@@ -1,9 +1,14 @@
+use super::color::PdfColor;
+use super::rich_text::{RichLine, RichText, SpanStyle};
+use super::ruby::{self, RubyLine, RubyMetrics, RubyText};
+use super::vertical::{VerticalFontSpec, WritingMode};
 use super::{
     Alignment, Error, FontSnafu, Frame, HasBaseSchema, InvalidColorSnafu, JsonPosition,
-    VerticalAlignment,
+    RichTextSnafu, RubySnafu, VerticalAlignment,
 };
 use crate::font::{
-    DynamicFontSize, FontMap, FontSize, FontSpec, FontSpecTrait, JsonFontSize, LineBreakMode,
+    self, DynamicFontSize, FontChain, FontMap, FontName, FontSelector, FontSize, FontSpec,
+    FontSpecTrait, FontStyle, FontWeight, JsonFontSize, LineBreakMode,
 };
 use crate::schemas::base::BaseSchema;
 
@@ -18,10 +23,15 @@ use std::sync::Arc;
 pub struct JsonTextSchema {
     name: String,
     position: JsonPosition,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     width: f32,
+    #[serde(deserialize_with = "super::units::deserialize_mm")]
     height: f32,
     content: String,
-    font_name: String,
+    font_name: Option<FontName>,
+    font_family: Option<String>,
+    font_weight: Option<FontWeight>,
+    font_style: Option<FontStyle>,
     alignment: Option<Alignment>,
     vertical_alignment: Option<VerticalAlignment>,
     character_spacing: Option<f32>,
@@ -36,6 +46,13 @@ pub struct JsonTextSchema {
     border_color: Option<String>,
     border_width: Option<f32>,
     line_break_mode: Option<LineBreakMode>,
+    #[serde(default)]
+    rich_text: bool,
+    writing_mode: Option<WritingMode>,
+    tate_chu_yoko: Option<usize>,
+    #[serde(default)]
+    ruby: bool,
+    ruby_size: Option<f32>,
 }
 
 /// Fallback values for the text-responsibility fields of a [`Text`], used
@@ -49,7 +66,7 @@ pub(crate) struct TextStyleDefaults {
     pub vertical_alignment: VerticalAlignment,
     pub character_spacing: f32,
     pub line_height: f32,
-    pub font_color: csscolorparser::Color,
+    pub font_color: PdfColor,
     pub padding: Option<Frame>,
     pub line_break_mode: LineBreakMode,
 }
@@ -63,18 +80,24 @@ pub struct Text {
     character_spacing: Pt,
     line_height: Option<f32>,
     font_size: FontSize,
-    font_id: FontId,
     font_spec: Arc<dyn FontSpecTrait>,
-    font: Arc<ParsedFont>,
-    font_color: csscolorparser::Color,
-    background_color: Option<csscolorparser::Color>,
+    fonts: FontChain,
+    font_color: PdfColor,
+    background_color: Option<PdfColor>,
     padding: Option<Frame>,
     rotate: Option<f32>,
     scale_x: Option<f32>,
     scale_y: Option<f32>,
-    border_color: Option<csscolorparser::Color>,
+    border_color: Option<PdfColor>,
     border_width: Option<Pt>,
     line_break_mode: Option<LineBreakMode>,
+    /// Fonts `<font=…>` may name, present when the content is rich text.
+    markup_fonts: Option<FontMap>,
+    /// Column layout, present when the text is written vertically.
+    vertical: Option<VerticalFontSpec>,
+    /// Ruby size relative to the font size, present when the content carries
+    /// ruby markup.
+    ruby_size: Option<f32>,
 }
 
 impl Text {
@@ -83,7 +106,7 @@ impl Text {
         y: Mm,
         width: Mm,
         height: Mm,
-        font_name: String,
+        font_name: impl Into<FontName>,
         font_size: Pt,
         content: String,
         alignment: Alignment,
@@ -91,10 +114,9 @@ impl Text {
         font_map: &FontMap,
         padding: Option<Frame>,
     ) -> Result<Self, Error> {
-        let (font_id, font) = font_map
-            .find(&font_name)
+        let fonts = font_map
+            .find_chain(&font_name.into())
             .whatever_context("Font specified in the schema is not loaded")?;
-        let font_spec = FontSpec::new(font.clone());
         let base = BaseSchema::new(String::from("cell"), x, y, width, height);
 
         Ok(Self {
@@ -105,12 +127,9 @@ impl Text {
             character_spacing: Pt(0.0),
             line_height: None,
             font_size: FontSize::Fixed(font_size),
-            font_id: font_id.clone(),
-            font_spec: Arc::new(font_spec.clone()),
-            font: font.clone(),
-            font_color: "#000"
-                .parse::<csscolorparser::Color>()
-                .context(InvalidColorSnafu)?,
+            font_spec: Arc::new(fonts.spec()),
+            fonts,
+            font_color: "#000".parse::<PdfColor>().context(InvalidColorSnafu)?,
             background_color: None,
             padding,
             rotate: None,
@@ -119,6 +138,9 @@ impl Text {
             border_color: None,
             border_width: None,
             line_break_mode: None,
+            markup_fonts: None,
+            vertical: None,
+            ruby_size: None,
         })
     }
 
@@ -134,7 +156,7 @@ impl Text {
             .unwrap_or(VerticalAlignment::Top);
         let character_spacing = json.character_spacing.map(Pt).unwrap_or(Pt(0.0));
         let line_height = json.line_height;
-        let font_color = csscolorparser::parse(json.font_color.as_deref().unwrap_or("#000000"))
+        let font_color = PdfColor::parse(json.font_color.as_deref().unwrap_or("#000000"))
             .context(InvalidColorSnafu)?;
         let padding = json.padding.clone();
         let line_break_mode = json.line_break_mode;
@@ -168,7 +190,7 @@ impl Text {
         let character_spacing = Pt(json.character_spacing.unwrap_or(defaults.character_spacing));
         let line_height = Some(json.line_height.unwrap_or(defaults.line_height));
         let font_color = match json.font_color.as_deref() {
-            Some(color) => csscolorparser::parse(color).context(InvalidColorSnafu)?,
+            Some(color) => PdfColor::parse(color).context(InvalidColorSnafu)?,
             None => defaults.font_color.clone(),
         };
         let padding = json.padding.clone().or_else(|| defaults.padding.clone());
@@ -198,15 +220,21 @@ impl Text {
         vertical_alignment: VerticalAlignment,
         character_spacing: Pt,
         line_height: Option<f32>,
-        font_color: csscolorparser::Color,
+        font_color: PdfColor,
         padding: Option<Frame>,
         line_break_mode: Option<LineBreakMode>,
         font_map: &FontMap,
     ) -> Result<Text, Error> {
-        let (font_id, font) = font_map
-            .find(&json.font_name)
+        let selector = FontSelector {
+            name: json.font_name,
+            family: json.font_family,
+            weight: json.font_weight.unwrap_or_default(),
+            style: json.font_style.unwrap_or_default(),
+        };
+        ensure_whatever!(!selector.is_empty(), "fontName or fontFamily is required");
+        let fonts = font_map
+            .select(&selector)
             .whatever_context("Font specified in the schema is not loaded")?;
-        let font_spec = FontSpec::new(font.clone());
 
         let base = BaseSchema::new(
             json.name,
@@ -222,17 +250,54 @@ impl Text {
             }
             JsonFontSize::Fixed(f) => FontSize::Fixed(Pt(f)),
         };
+        let markup_fonts = json.rich_text.then(|| font_map.clone());
+
+        let vertical = (json.writing_mode == Some(WritingMode::VerticalRl)).then(|| {
+            VerticalFontSpec::new(fonts.clone(), json.tate_chu_yoko.unwrap_or(0))
+                .with_line_break_mode(line_break_mode.unwrap_or_default())
+        });
+        if json.rich_text && vertical.is_some() {
+            return RichTextSnafu {
+                message: "richText is not supported in vertical writing",
+            }
+            .fail();
+        }
+        // ルビは固定サイズの通常テキスト（横書き・縦書き）でのみ使用できる
+        if json.ruby {
+            let message = if json.rich_text {
+                Some("ruby cannot be combined with richText")
+            } else if matches!(font_size, FontSize::Dynamic(_)) {
+                Some("ruby needs a fixed fontSize")
+            } else {
+                None
+            };
+            if let Some(message) = message {
+                return RubySnafu { message }.fail();
+            }
+        }
+        let ruby_size = json
+            .ruby
+            .then(|| json.ruby_size.unwrap_or(ruby::DEFAULT_RUBY_SIZE));
+
+        let font_spec: Arc<dyn FontSpecTrait> = match &vertical {
+            Some(vertical) => Arc::new(vertical.clone()),
+            None => Arc::new(
+                fonts
+                    .spec()
+                    .with_line_break_mode(line_break_mode.unwrap_or_default()),
+            ),
+        };
 
         let background_color = json
             .background_color
             .as_ref()
-            .map(|c| csscolorparser::parse(c).context(InvalidColorSnafu))
+            .map(|c| PdfColor::parse(c).context(InvalidColorSnafu))
             .transpose()?;
 
         let border_color = json
             .border_color
             .as_ref()
-            .map(|c| csscolorparser::parse(c).context(InvalidColorSnafu))
+            .map(|c| PdfColor::parse(c).context(InvalidColorSnafu))
             .transpose()?;
 
         Ok(Text {
@@ -243,11 +308,8 @@ impl Text {
             vertical_alignment,
             line_height,
             font_size,
-            font_id: font_id.clone(),
-            font_spec: Arc::new(
-                font_spec.with_line_break_mode(line_break_mode.unwrap_or_default()),
-            ),
-            font: font.clone(),
+            font_spec,
+            fonts,
             font_color,
             background_color,
             padding,
@@ -257,6 +319,9 @@ impl Text {
             border_color,
             border_width: json.border_width.map(Pt),
             line_break_mode,
+            markup_fonts,
+            vertical,
+            ruby_size,
         })
     }
 
@@ -266,6 +331,16 @@ impl Text {
         current_page: usize,
         buffer: &mut OpBuffer,
     ) -> Result<(), Error> {
+        if let Some(lines) = self.ruby_lines()? {
+            return self.render_ruby(&lines, parent_height, current_page, buffer);
+        }
+        if let Some(vertical) = &self.vertical {
+            return self.render_vertical(vertical, parent_height, current_page, buffer);
+        }
+        if let Some(lines) = self.rich_lines()? {
+            return self.render_rich_text(&lines, parent_height, current_page, buffer);
+        }
+
         let font_size = self.get_font_size()?;
         let (box_width, box_height) = self.get_effective_box_size();
 
@@ -287,24 +362,7 @@ impl Text {
 
         let y_offset = self.calculate_vertical_offset(box_height, total_height);
 
-        let mut ops: Vec<Op> = vec![];
-
-        let bounding_matrix = super::pdf_utils::calculate_transform_matrix_with_center_pivot(
-            self.base.x,
-            parent_height - self.base.y - self.base.height,
-            self.base.width,
-            self.base.height,
-            self.rotate,
-        );
-        // 背景色があれば背景を描画
-        if let Some(bg_ops) = self.create_background_ops(bounding_matrix) {
-            ops.extend_from_slice(&bg_ops);
-        }
-
-        // Draw border if specified
-        if let Some(border_ops) = self.create_border_ops(bounding_matrix) {
-            ops.extend_from_slice(&border_ops);
-        }
+        let (bounding_matrix, mut ops) = self.create_box_ops(parent_height);
 
         // 各行のテキストを描画
         for (index, line) in splitted_paragraphs.iter().enumerate() {
@@ -314,8 +372,12 @@ impl Text {
                 .context(FontSnafu)?
                 .into();
 
-            let (x_line, character_spacing) =
-                self.calculate_horizontal_alignment(box_width, line_width, line);
+            let (x_line, character_spacing) = self.calculate_inline_alignment(
+                box_width,
+                line_width,
+                line,
+                self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
+            );
 
             let y = self.base.height
                 - (y_offset
@@ -339,6 +401,341 @@ impl Text {
         Ok(())
     }
 
+    // リッチテキストの各行を行ごとの文字サイズで積み上げて描画
+    fn render_rich_text(
+        &self,
+        lines: &[RichLine],
+        parent_height: Mm,
+        current_page: usize,
+        buffer: &mut OpBuffer,
+    ) -> Result<(), Error> {
+        let (box_width, box_height) = self.get_effective_box_size();
+        let line_heights: Vec<Mm> = lines
+            .iter()
+            .map(|line| (line.size * self.line_height.unwrap_or(1.0)).into())
+            .collect();
+        let total_height = line_heights.iter().fold(Mm(0.0), |total, &h| total + h);
+
+        let y_offset = self.calculate_vertical_offset(box_height, total_height);
+        let (bounding_matrix, mut ops) = self.create_box_ops(parent_height);
+
+        let mut line_top = y_offset + self.padding.as_ref().map_or(Mm(0.0), |p| p.top);
+        for (line, &line_height) in lines.iter().zip(&line_heights) {
+            let line_width: Mm = line
+                .width(self.character_spacing)
+                .context(FontSnafu)?
+                .into();
+            let (x_line, character_spacing) = self.calculate_inline_alignment(
+                box_width,
+                line_width,
+                &line.text(),
+                self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
+            );
+
+            let font_size_in_mm: Mm = line.size.into();
+            let half_leading_in_mm = (line_height - font_size_in_mm) / 2.0;
+            let y = self.base.height - (line_top + font_size_in_mm + half_leading_in_mm);
+            line_top += line_height;
+
+            let line_ops = line
+                .ops(
+                    bounding_matrix,
+                    x_line,
+                    y,
+                    self.scale_x,
+                    self.scale_y,
+                    character_spacing,
+                )
+                .context(FontSnafu)?;
+            ops.extend(line_ops);
+        }
+
+        buffer.insert(current_page, ops);
+        Ok(())
+    }
+
+    /// The content laid out as rich text, or `None` when markup is off. A
+    /// dynamic font size is fitted by laying the styled runs out at each
+    /// candidate size.
+    fn rich_lines(&self) -> Result<Option<Vec<RichLine>>, Error> {
+        let Some(fonts) = &self.markup_fonts else {
+            return Ok(None);
+        };
+        let font_size = match self.font_size.clone() {
+            FontSize::Fixed(font_size) => font_size,
+            FontSize::Dynamic(dynamic_font_size) => {
+                let (box_width, box_height) = self.get_inline_box_size();
+                let line_height = self.line_height.unwrap_or(1.0);
+                font::fit_font_size(dynamic_font_size, box_width, box_height, |font_size| {
+                    let mut width = Mm(0.0);
+                    let mut height = Mm(0.0);
+                    for line in self.rich_lines_at(fonts, font_size)? {
+                        let line_width: Mm = line
+                            .width(self.character_spacing)
+                            .context(FontSnafu)?
+                            .into();
+                        width = width.max(line_width);
+                        height += (line.size * line_height).into();
+                    }
+                    Ok::<_, Error>((width, height))
+                })?
+            }
+        };
+        self.rich_lines_at(fonts, font_size).map(Some)
+    }
+
+    /// The rich text wrapped to the box with `font_size` outside any tag.
+    fn rich_lines_at(&self, fonts: &FontMap, font_size: Pt) -> Result<Vec<RichLine>, Error> {
+        let base = SpanStyle::new(self.fonts.clone(), font_size, self.font_color.clone());
+        let (box_width, _) = self.get_effective_box_size();
+        RichText::parse(&self.content, base, fonts)?
+            .lines(
+                self.resolved_line_break_mode(),
+                box_width.into(),
+                self.character_spacing,
+            )
+            .context(FontSnafu)
+    }
+
+    // ルビ付きテキスト：ルビのある行は行送りを広げ、親文字の上（縦書きでは右）にルビを描画
+    fn render_ruby(
+        &self,
+        lines: &[RubyLine],
+        parent_height: Mm,
+        current_page: usize,
+        buffer: &mut OpBuffer,
+    ) -> Result<(), Error> {
+        let metrics = self.ruby_metrics()?;
+        let (line_length, box_breadth) = self.get_inline_box_size();
+        let line_height = self.line_height.unwrap_or(1.0);
+        let line_pitch: Mm = (metrics.size * line_height).into();
+        let extra: Mm = ruby::extra_line_space(
+            metrics.size,
+            metrics.ruby_size,
+            line_height,
+            self.vertical.is_some(),
+        )
+        .into();
+        let pitches: Vec<(Mm, Mm)> = lines
+            .iter()
+            .map(|line| {
+                let extra = if line.has_ruby() { extra } else { Mm(0.0) };
+                (line_pitch + extra, extra)
+            })
+            .collect();
+        let total = pitches
+            .iter()
+            .fold(Mm(0.0), |total, &(pitch, _)| total + pitch);
+
+        let block_offset = self.calculate_vertical_offset(box_breadth, total);
+        let (bounding_matrix, mut ops) = self.create_box_ops(parent_height);
+        let padding = |side: fn(&Frame) -> Mm| self.padding.as_ref().map_or(Mm(0.0), side);
+        let font_size_in_mm: Mm = metrics.size.into();
+        let ruby_size_in_mm: Mm = metrics.ruby_size.into();
+        let half_leading_in_mm = (line_pitch - font_size_in_mm) / 2.0;
+        let ruby_rise: Mm = ruby::ruby_rise(metrics.size, metrics.ruby_size).into();
+
+        let mut line_start = block_offset;
+        for (line, &(pitch, extra)) in lines.iter().zip(&pitches) {
+            let length: Mm = line
+                .length(&metrics, self.character_spacing)
+                .context(FontSnafu)?
+                .into();
+            let inline_start = match self.vertical {
+                Some(_) => padding(|p| p.top),
+                None => padding(|p| p.left),
+            };
+            let (start, character_spacing) =
+                self.calculate_inline_alignment(line_length, length, &line.text(), inline_start);
+            let (placed, _) = line
+                .layout(&metrics, character_spacing)
+                .context(FontSnafu)?;
+
+            for piece in &placed {
+                match &self.vertical {
+                    Some(vertical) => {
+                        // 列は右から左へ並べ、ルビは列の右側に置く
+                        let center_x = self.base.width
+                            - padding(|p| p.right)
+                            - line_start
+                            - extra
+                            - line_pitch / 2.0;
+                        let ruby_x = center_x + (font_size_in_mm + ruby_size_in_mm) / 2.0;
+                        let runs = std::iter::once((metrics.size, center_x, &piece.base)).chain(
+                            piece
+                                .ruby
+                                .as_ref()
+                                .map(|ruby| (metrics.ruby_size, ruby_x, ruby)),
+                        );
+                        for (size, center_x, run) in runs {
+                            let offset: Mm = run.offset.into();
+                            let column_ops = vertical
+                                .column_ops(
+                                    bounding_matrix,
+                                    size,
+                                    center_x,
+                                    self.base.height - start - offset,
+                                    run.character_spacing,
+                                    run.text,
+                                    &self.font_color,
+                                )
+                                .context(FontSnafu)?;
+                            ops.extend(column_ops);
+                        }
+                    }
+                    None => {
+                        let y = self.base.height
+                            - (padding(|p| p.top)
+                                + line_start
+                                + extra
+                                + half_leading_in_mm
+                                + font_size_in_mm);
+                        let runs = std::iter::once((metrics.size, y, &piece.base)).chain(
+                            piece
+                                .ruby
+                                .as_ref()
+                                .map(|ruby| (metrics.ruby_size, y + ruby_rise, ruby)),
+                        );
+                        for (size, y, run) in runs {
+                            let offset: Mm = run.offset.into();
+                            ops.extend(self.create_text_ops(
+                                bounding_matrix,
+                                size,
+                                start + offset,
+                                y,
+                                run.character_spacing,
+                                run.text,
+                            ));
+                        }
+                    }
+                }
+            }
+            line_start += pitch;
+        }
+
+        buffer.insert(current_page, ops);
+        Ok(())
+    }
+
+    fn ruby_metrics(&self) -> Result<RubyMetrics<'_>, Error> {
+        let size = self.get_font_size()?;
+        Ok(RubyMetrics {
+            spec: self.font_spec.as_ref(),
+            size,
+            ruby_size: size * self.ruby_size.unwrap_or(ruby::DEFAULT_RUBY_SIZE),
+        })
+    }
+
+    /// The content laid out with ruby, or `None` when ruby markup is off.
+    fn ruby_lines(&self) -> Result<Option<Vec<RubyLine>>, Error> {
+        if self.ruby_size.is_none() {
+            return Ok(None);
+        }
+        let (line_length, _) = self.get_inline_box_size();
+        RubyText::parse(&self.content)?
+            .lines(
+                &self.ruby_metrics()?,
+                self.resolved_line_break_mode(),
+                line_length.into(),
+                self.character_spacing,
+            )
+            .context(FontSnafu)
+            .map(Some)
+    }
+
+    // ボックスの変換行列と背景・枠線のオペレーションを作成
+    fn create_box_ops(&self, parent_height: Mm) -> ([f32; 6], Vec<Op>) {
+        let mut ops: Vec<Op> = vec![];
+
+        let bounding_matrix = super::pdf_utils::calculate_transform_matrix_with_center_pivot(
+            self.base.x,
+            parent_height - self.base.y - self.base.height,
+            self.base.width,
+            self.base.height,
+            self.rotate,
+        );
+        // 背景色があれば背景を描画
+        if let Some(bg_ops) = self.create_background_ops(bounding_matrix) {
+            ops.extend_from_slice(&bg_ops);
+        }
+
+        // Draw border if specified
+        if let Some(border_ops) = self.create_border_ops(bounding_matrix) {
+            ops.extend_from_slice(&border_ops);
+        }
+
+        (bounding_matrix, ops)
+    }
+
+    // 縦書き：列を右から左へ並べ、各列を上から下へ描画
+    fn render_vertical(
+        &self,
+        vertical: &VerticalFontSpec,
+        parent_height: Mm,
+        current_page: usize,
+        buffer: &mut OpBuffer,
+    ) -> Result<(), Error> {
+        let font_size = self.get_font_size()?;
+        let (column_length, box_width) = self.get_inline_box_size();
+
+        let columns = self
+            .font_spec
+            .split_text_to_size(
+                &self.content,
+                font_size,
+                column_length.into(),
+                self.character_spacing,
+            )
+            .context(FontSnafu)?;
+
+        let column_width: Mm = (font_size * self.line_height.unwrap_or(1.0)).into();
+        let total_width = column_width * columns.len() as f32;
+        // verticalAlignment は行送り方向（右から左）に適用する
+        let x_offset = self.calculate_vertical_offset(box_width, total_width);
+
+        let (bounding_matrix, mut ops) = self.create_box_ops(parent_height);
+        let (top, right) = self
+            .padding
+            .as_ref()
+            .map_or((Mm(0.0), Mm(0.0)), |p| (p.top, p.right));
+
+        for (index, column) in columns.iter().enumerate() {
+            let column_height: Mm = self
+                .font_spec
+                .width_of_text_at_size(column, font_size, self.character_spacing)
+                .context(FontSnafu)?
+                .into();
+            let (y_start, character_spacing) =
+                self.calculate_inline_alignment(column_length, column_height, column, top);
+            let center_x = self.base.width - right - x_offset - column_width * (index as f32 + 0.5);
+
+            let column_ops = vertical
+                .column_ops(
+                    bounding_matrix,
+                    font_size,
+                    center_x,
+                    self.base.height - y_start,
+                    character_spacing,
+                    column,
+                    &self.font_color,
+                )
+                .context(FontSnafu)?;
+            ops.extend(column_ops);
+        }
+
+        buffer.insert(current_page, ops);
+        Ok(())
+    }
+
+    // 行方向・行送り方向のボックスサイズ（縦書きでは縦横が入れ替わる）
+    fn get_inline_box_size(&self) -> (Mm, Mm) {
+        let (box_width, box_height) = self.get_effective_box_size();
+        match self.vertical {
+            Some(_) => (box_height, box_width),
+            None => (box_width, box_height),
+        }
+    }
+
     // パディングを考慮したボックスの有効サイズを計算
     fn get_effective_box_size(&self) -> (Mm, Mm) {
         let box_width =
@@ -359,20 +756,21 @@ impl Text {
         }
     }
 
-    // 水平方向の配置とキャラクタ間隔を計算
-    fn calculate_horizontal_alignment(
+    // 行方向の配置とキャラクタ間隔を計算（start は行頭側のパディング）
+    fn calculate_inline_alignment(
         &self,
         box_width: Mm,
         line_width: Mm,
         line: &str,
+        start: Mm,
     ) -> (Mm, Pt) {
         let residual: Mm = box_width - line_width;
 
         let x_line = match self.alignment {
-            Alignment::Left => self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
-            Alignment::Center => residual / 2.0 + self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
-            Alignment::Right => residual + self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
-            Alignment::Justify => self.padding.as_ref().map_or(Mm(0.0), |p| p.left),
+            Alignment::Left => start,
+            Alignment::Center => residual / 2.0 + start,
+            Alignment::Right => residual + start,
+            Alignment::Justify => start,
         };
 
         let character_spacing = match self.alignment {
@@ -400,18 +798,10 @@ impl Text {
         let x2: Pt = self.base.width.into();
         let y1: Pt = Pt(0.0);
         let y2: Pt = self.base.height.into();
-        self.background_color.clone().map(|bg_color| {
-            vec![
-                Op::SaveGraphicsState,
-                matrix,
-                Op::SetFillColor {
-                    col: Color::Rgb(Rgb {
-                        r: bg_color.r,
-                        g: bg_color.g,
-                        b: bg_color.b,
-                        icc_profile: None,
-                    }),
-                },
+        self.background_color.as_ref().map(|bg_color| {
+            let mut ops = vec![Op::SaveGraphicsState, matrix];
+            ops.extend(bg_color.fill_ops());
+            ops.extend([
                 Op::DrawPolygon {
                     polygon: Polygon {
                         rings: vec![PolygonRing {
@@ -439,7 +829,8 @@ impl Text {
                     },
                 },
                 Op::RestoreGraphicsState,
-            ]
+            ]);
+            ops
         })
     }
 
@@ -457,17 +848,9 @@ impl Text {
         let y1: Pt = Pt(0.0);
         let y2: Pt = self.base.height.into();
 
-        Some(vec![
-            Op::SaveGraphicsState,
-            matrix,
-            Op::SetOutlineColor {
-                col: Color::Rgb(Rgb {
-                    r: border_color.r,
-                    g: border_color.g,
-                    b: border_color.b,
-                    icc_profile: None,
-                }),
-            },
+        let mut ops = vec![Op::SaveGraphicsState, matrix];
+        ops.extend(border_color.stroke_ops());
+        ops.extend([
             Op::SetOutlineThickness {
                 pt: (*border_width),
             },
@@ -498,7 +881,8 @@ impl Text {
                 },
             },
             Op::RestoreGraphicsState,
-        ])
+        ]);
+        Some(ops)
     }
 
     // テキスト描画のオペレーションを作成
@@ -513,7 +897,7 @@ impl Text {
     ) -> Vec<Op> {
         super::pdf_utils::create_text_ops_with_font(
             matrix,
-            &self.font_id,
+            self.fonts.id(),
             font_size,
             x_line,
             y,
@@ -523,7 +907,7 @@ impl Text {
             line,
             self.line_height,
             &self.font_color,
-            Some(&self.font),
+            Some(&self.fonts),
         )
     }
 
@@ -550,10 +934,10 @@ impl Text {
     pub fn set_content(&mut self, content: String) {
         self.content = content;
     }
-    pub fn set_font_color(&mut self, font_color: csscolorparser::Color) {
+    pub fn set_font_color(&mut self, font_color: PdfColor) {
         self.font_color = font_color;
     }
-    pub fn font_color(&self) -> &csscolorparser::Color {
+    pub fn font_color(&self) -> &PdfColor {
         &self.font_color
     }
     pub fn set_character_spacing(&mut self, character_spacing: Pt) {
@@ -576,11 +960,79 @@ impl Text {
     }
     pub fn set_line_break_mode(&mut self, line_break_mode: Option<LineBreakMode>) {
         self.line_break_mode = line_break_mode;
-        self.font_spec = Arc::new(
-            FontSpec::new(self.font.clone()).with_line_break_mode(self.resolved_line_break_mode()),
-        );
+        let line_break_mode = self.resolved_line_break_mode();
+        self.font_spec = match self.vertical.take() {
+            Some(vertical) => {
+                let vertical = vertical.with_line_break_mode(line_break_mode);
+                self.vertical = Some(vertical.clone());
+                Arc::new(vertical)
+            }
+            None => Arc::new(self.fonts.spec().with_line_break_mode(line_break_mode)),
+        };
     }
     pub fn get_height(&self) -> Result<Mm, Error> {
+        let padding = self.padding.as_ref().map_or(Mm(0.0), |p| p.top + p.bottom);
+        if let Some(lines) = self.ruby_lines()? {
+            let metrics = self.ruby_metrics()?;
+            if self.vertical.is_some() {
+                // 縦書きの高さは最も長い列の長さ
+                let mut height = Pt(0.0);
+                for line in &lines {
+                    let length = line
+                        .length(&metrics, self.character_spacing)
+                        .context(FontSnafu)?;
+                    if length > height {
+                        height = length;
+                    }
+                }
+                let height_in_mm: Mm = height.into();
+                return Ok(height_in_mm + padding);
+            }
+            let line_height = self.line_height.unwrap_or(1.0);
+            let extra = ruby::extra_line_space(metrics.size, metrics.ruby_size, line_height, false);
+            let height: f32 = lines
+                .iter()
+                .map(|line| {
+                    let extra = if line.has_ruby() { extra.0 } else { 0.0 };
+                    metrics.size.0 * line_height + extra
+                })
+                .sum();
+            let height_in_mm: Mm = Pt(height).into();
+            return Ok(height_in_mm + padding);
+        }
+        if let Some(lines) = self.rich_lines()? {
+            let line_height = self.line_height.unwrap_or(1.0);
+            let height: f32 = lines.iter().map(|line| line.size.0 * line_height).sum();
+            let height_in_mm: Mm = Pt(height).into();
+            return Ok(height_in_mm + padding);
+        }
+        if self.vertical.is_some() {
+            // 縦書きの高さは最も長い列の長さ
+            let font_size = self.get_font_size()?;
+            let (column_length, _) = self.get_inline_box_size();
+            let columns = self
+                .font_spec
+                .split_text_to_size(
+                    &self.content,
+                    font_size,
+                    column_length.into(),
+                    self.character_spacing,
+                )
+                .context(FontSnafu)?;
+            let mut height = Pt(0.0);
+            for column in &columns {
+                let length = self
+                    .font_spec
+                    .width_of_text_at_size(column, font_size, self.character_spacing)
+                    .context(FontSnafu)?;
+                if length > height {
+                    height = length;
+                }
+            }
+            let height_in_mm: Mm = height.into();
+            return Ok(height_in_mm + padding);
+        }
+
         let font_size = self.get_font_size()?;
         // Must be the same width `render` wraps at, or the reported height does
         // not describe what actually gets drawn.
@@ -599,7 +1051,7 @@ impl Text {
         let line_height = self.line_height.unwrap_or(1.0);
         let height_in_mm: Mm = Pt(lines.len() as f32 * font_size.0 * line_height).into();
 
-        Ok(height_in_mm + self.padding.as_ref().map_or(Mm(0.0), |p| p.top + p.bottom))
+        Ok(height_in_mm + padding)
     }
 
     fn get_font_size(&self) -> Result<Pt, Error> {
@@ -609,7 +1061,7 @@ impl Text {
                 // Auto-sizing fits text to a box, and the box text is drawn into
                 // is the padded one — sizing against the full box picks a font
                 // that overflows.
-                let (box_width, box_height) = self.get_effective_box_size();
+                let (box_width, box_height) = self.get_inline_box_size();
                 self.font_spec
                     .calculate_dynamic_font_size(
                         dynamic_font_size,
@@ -1053,13 +1505,91 @@ mod tests {
         assert_eq!(text.resolved_line_break_mode(), LineBreakMode::Char);
     }
 
+    fn rich_text(content: &str, font_size: serde_json::Value) -> Result<Text, Error> {
+        let json: JsonTextSchema = serde_json::from_value(json!({
+            "name": "text",
+            "position": { "x": 0.0, "y": 0.0 },
+            "width": 80.0,
+            "height": 20.0,
+            "content": content,
+            "fontName": "TestFont",
+            "fontSize": font_size,
+            "lineHeight": 1.5,
+            "richText": true
+        }))
+        .unwrap();
+        Text::from_json(json, &test_font_map())
+    }
+
+    #[test]
+    fn rich_text_height_follows_the_largest_size_on_each_line() {
+        let plain = rich_text("one\ntwo", json!(10.0)).unwrap();
+        let larger = rich_text("one\n<size=20>t</size>wo", json!(10.0)).unwrap();
+        let scripts = rich_text("one\ntw<sup>o</sup>", json!(10.0)).unwrap();
+
+        let plain_height = plain.get_height().unwrap();
+        let expected: Mm = Pt((10.0 + 20.0) * 1.5).into();
+        assert!((larger.get_height().unwrap().0 - expected.0).abs() < 1e-4);
+        // A superscript is drawn smaller but does not change the line.
+        assert_eq!(scripts.get_height().unwrap(), plain_height);
+    }
+
+    #[test]
+    fn dynamic_rich_text_fits_its_styled_lines_in_the_box() {
+        let dynamic = json!({ "min": 4.0, "max": 40.0, "fit": "vertical" });
+        let plain = rich_text("one\ntwo", dynamic.clone()).unwrap();
+        let larger = rich_text("one\n<size=200%>two</size>", dynamic).unwrap();
+
+        let (plain_size, larger_size) = (
+            plain.rich_lines().unwrap().unwrap()[0].size,
+            larger.rich_lines().unwrap().unwrap()[0].size,
+        );
+        // The doubled second line leaves less room, so the base size shrinks,
+        // and both still fit the 20mm box.
+        assert!(
+            larger_size < plain_size,
+            "{larger_size:?} >= {plain_size:?}"
+        );
+        for text in [&plain, &larger] {
+            assert!(text.get_height().unwrap() <= Mm(20.0));
+        }
+    }
+
+    fn ruby_text(content: &str) -> Text {
+        let json: JsonTextSchema = serde_json::from_value(json!({
+            "name": "text",
+            "position": { "x": 0.0, "y": 0.0 },
+            "width": 80.0,
+            "height": 20.0,
+            "content": content,
+            "fontName": "TestFont",
+            "fontSize": 10.0,
+            "lineHeight": 1.5,
+            "ruby": true
+        }))
+        .unwrap();
+        Text::from_json(json, &test_font_map()).unwrap()
+    }
+
+    #[test]
+    fn ruby_lines_grow_by_what_the_leading_cannot_hold() {
+        let plain = ruby_text("田中\n様").get_height().unwrap();
+        let annotated = ruby_text("{田中|たなか}\n様").get_height().unwrap();
+
+        assert_eq!(plain, Pt(30.0).into());
+        // 5pt ruby minus the 2.5pt half-leading and the 1.2pt below the
+        // baseline leaves 1.3pt to add to the first line only.
+        let expected: Mm = Pt(31.3).into();
+        assert!((annotated.0 - expected.0).abs() < 1e-4, "{annotated:?}");
+    }
+
     fn sample_defaults() -> TextStyleDefaults {
         TextStyleDefaults {
             alignment: Alignment::Right,
             vertical_alignment: VerticalAlignment::Bottom,
             character_spacing: 3.0,
             line_height: 2.0,
-            font_color: csscolorparser::parse("#ff0000").unwrap(),
+            font_color: PdfColor::parse("#ff0000").unwrap(),
             padding: Some(Frame::uniform(Mm(4.0))),
             line_break_mode: LineBreakMode::Word,
         }
@@ -1085,10 +1615,7 @@ mod tests {
         assert!(matches!(text.vertical_alignment, VerticalAlignment::Bottom));
         assert_eq!(text.character_spacing, Pt(3.0));
         assert_eq!(text.line_height, Some(2.0));
-        assert_eq!(
-            *text.font_color(),
-            csscolorparser::parse("#ff0000").unwrap()
-        );
+        assert_eq!(*text.font_color(), PdfColor::parse("#ff0000").unwrap());
         assert_eq!(text.line_break_mode, Some(LineBreakMode::Word));
         assert!(text.padding.is_some());
     }
@@ -1115,10 +1642,7 @@ mod tests {
 
         assert!(matches!(text.alignment, Alignment::Center));
         assert_eq!(text.character_spacing, Pt(1.0));
-        assert_eq!(
-            *text.font_color(),
-            csscolorparser::parse("#00ff00").unwrap()
-        );
+        assert_eq!(*text.font_color(), PdfColor::parse("#00ff00").unwrap());
         assert_eq!(text.line_break_mode, Some(LineBreakMode::Char));
     }
 }
@@ -0,0 +1,200 @@
+//! Length values in templates.
+//!
+//! Every length in a template is stored in millimetres, but may be written
+//! either as a bare number (millimetres) or as a string carrying a unit:
+//! `"12mm"`, `"2cm"`, `"1in"`, `"72pt"`, `"96px"` (CSS pixels, 96 per inch) or
+//! `"300px@300dpi"`. The `deserialize_*` functions below are meant for
+//! `#[serde(deserialize_with = ...)]` on the `Json*` structs, so the parsed
+//! structs keep their plain `f32` millimetre fields.
+
+use printpdf::Mm;
+use serde::{Deserialize, Deserializer};
+
+const MM_PER_INCH: f32 = 25.4;
+const POINTS_PER_INCH: f32 = 72.0;
+const CSS_PIXELS_PER_INCH: f32 = 96.0;
+
+/// Raw length value: either a bare number (millimetres) or a suffixed string.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum JsonLength {
+    Number(f32),
+    Text(String),
+}
+
+impl JsonLength {
+    fn to_mm(&self) -> Result<f32, String> {
+        match self {
+            JsonLength::Number(value) => Ok(*value),
+            JsonLength::Text(raw) => parse_length_mm(raw).ok_or_else(|| {
+                format!(
+                    "invalid length {raw:?}; expected a number (mm) or a string such as \
+                     \"12mm\", \"2cm\", \"1in\", \"72pt\", \"96px\" or \"300px@300dpi\""
+                )
+            }),
+        }
+    }
+}
+
+/// Parses a length literal and returns it in millimetres.
+///
+/// Accepts a bare number (millimetres) and the `mm`, `cm`, `in`, `pt` and `px`
+/// suffixes; `px` is a CSS pixel (1/96in) unless a resolution is given as
+/// `"<n>px@<dpi>dpi"`. Suffixes are case-insensitive and surrounding
+/// whitespace is ignored. Returns `None` for anything else, including
+/// non-finite numbers.
+pub fn parse_length_mm(raw: &str) -> Option<f32> {
+    let lowered = raw.trim().to_ascii_lowercase();
+
+    let mm = if let Some((pixels, dpi)) = lowered.split_once("px@") {
+        let dpi = parse_number(dpi.strip_suffix("dpi")?)?;
+        if dpi <= 0.0 {
+            return None;
+        }
+        parse_number(pixels)? / dpi * MM_PER_INCH
+    } else if let Some(number) = lowered.strip_suffix("mm") {
+        parse_number(number)?
+    } else if let Some(number) = lowered.strip_suffix("cm") {
+        parse_number(number)? * 10.0
+    } else if let Some(number) = lowered.strip_suffix("in") {
+        parse_number(number)? * MM_PER_INCH
+    } else if let Some(number) = lowered.strip_suffix("pt") {
+        parse_number(number)? / POINTS_PER_INCH * MM_PER_INCH
+    } else if let Some(number) = lowered.strip_suffix("px") {
+        parse_number(number)? / CSS_PIXELS_PER_INCH * MM_PER_INCH
+    } else {
+        parse_number(&lowered)?
+    };
+
+    mm.is_finite().then_some(mm)
+}
+
+/// Parses the numeric part of a length literal. Like `JsonColumnWidth`, the
+/// number itself is not trimmed: `"12 mm"` is a typo, not a length.
+fn parse_number(number: &str) -> Option<f32> {
+    number.parse::<f32>().ok().filter(|value| value.is_finite())
+}
+
+/// Deserializes a required length into millimetres.
+pub(crate) fn deserialize_mm<'de, D>(deserializer: D) -> Result<f32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    JsonLength::deserialize(deserializer)?
+        .to_mm()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserializes a required length into [`Mm`].
+pub(crate) fn deserialize_length<'de, D>(deserializer: D) -> Result<Mm, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_mm(deserializer).map(Mm)
+}
+
+/// Deserializes an optional length into millimetres. Use together with
+/// `#[serde(default)]` so an absent field stays `None`.
+pub(crate) fn deserialize_optional_mm<'de, D>(deserializer: D) -> Result<Option<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<JsonLength>::deserialize(deserializer)?
+        .map(|length| length.to_mm())
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Deserializes a list of lengths (such as `basePdf.padding`) into millimetres.
+pub(crate) fn deserialize_mm_vec<'de, D>(deserializer: D) -> Result<Vec<f32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Vec::<JsonLength>::deserialize(deserializer)?
+        .iter()
+        .map(JsonLength::to_mm)
+        .collect::<Result<_, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mm(raw: &str, expected: f32) {
+        let actual = parse_length_mm(raw).unwrap_or_else(|| panic!("{raw:?} should parse"));
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{raw:?}: expected {expected}mm, got {actual}mm"
+        );
+    }
+
+    #[test]
+    fn every_unit_converts_to_millimetres() {
+        assert_mm("12", 12.0);
+        assert_mm("12mm", 12.0);
+        assert_mm("2cm", 20.0);
+        assert_mm("1in", 25.4);
+        assert_mm("72pt", 25.4);
+        assert_mm("96px", 25.4);
+        assert_mm("300px@300dpi", 25.4);
+        assert_mm("150px@600dpi", 6.35);
+    }
+
+    #[test]
+    fn suffixes_are_case_insensitive_and_outer_whitespace_is_ignored() {
+        assert_mm(" 1IN ", 25.4);
+        assert_mm("72PT", 25.4);
+        assert_mm("300PX@300DPI", 25.4);
+    }
+
+    #[test]
+    fn malformed_lengths_are_rejected() {
+        for raw in [
+            "",
+            "mm",
+            "12 mm",
+            "12em",
+            "1in2",
+            "nan",
+            "infmm",
+            "10px@0dpi",
+            "10px@dpi",
+            "10px@300",
+        ] {
+            assert_eq!(parse_length_mm(raw), None, "{raw:?} should be rejected");
+        }
+    }
+
+    #[test]
+    fn numbers_and_strings_deserialize_through_serde() {
+        #[derive(Deserialize)]
+        struct Sample {
+            #[serde(deserialize_with = "deserialize_mm")]
+            width: f32,
+            #[serde(default, deserialize_with = "deserialize_optional_mm")]
+            height: Option<f32>,
+            #[serde(deserialize_with = "deserialize_mm_vec")]
+            padding: Vec<f32>,
+        }
+
+        let sample: Sample = serde_json::from_value(serde_json::json!({
+            "width": "1in",
+            "padding": [10, "1cm", "72pt", 0]
+        }))
+        .unwrap();
+
+        assert!((sample.width - 25.4).abs() < 1e-4);
+        assert_eq!(sample.height, None);
+        assert_eq!(sample.padding.len(), 4);
+        assert!((sample.padding[2] - 25.4).abs() < 1e-4);
+
+        let error = serde_json::from_value::<Sample>(serde_json::json!({
+            "width": "wide",
+            "padding": []
+        }))
+        .err()
+        .expect("an invalid length should fail");
+        assert!(error.to_string().contains("invalid length"));
+    }
+}
@@ -0,0 +1,637 @@
+//! Vertical writing (`writingMode: "vertical-rl"`) for the text schemas.
+//!
+//! Columns run top to bottom and are stacked right to left. Japanese and
+//! other CJK characters stand upright and take the font's vertical metrics
+//! (`vmtx`/`VORG`), punctuation and brackets use the glyphs the font's `vert`
+//! feature substitutes, and runs of Latin text are turned 90° clockwise.
+//! Short runs of digits can instead be set across the column
+//! (tate-chu-yoko).
+//!
+//! printpdf only writes horizontal (`Identity-H`) fonts, so every upright
+//! glyph is placed with its own text matrix rather than through a vertical
+//! CMap.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use printpdf::{
+    Codepoint, CurTransMat, Mm, Op, ParsedFont, PdfFontHandle, Pt, TextItem, TextMatrix,
+};
+use serde::Deserialize;
+use ttf_parser::gsub::SubstitutionSubtable;
+use ttf_parser::{Face, GlyphId, Tag};
+
+use super::color::PdfColor;
+use crate::font::{
+    self, filter_end_jp, filter_start_jp, fit_dynamic_font_size, sanitize_text_for_font,
+    wrap_paragraph, DynamicFontSize, FontChain, FontSpec, FontSpecTrait, LineBreakMode,
+};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WritingMode {
+    #[default]
+    HorizontalTb,
+    VerticalRl,
+}
+
+/// How a part of a column is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit<'a> {
+    /// One grapheme cluster standing upright.
+    Upright(&'a str),
+    /// A run of horizontal text turned 90° clockwise.
+    Rotated(&'a str),
+    /// A short run of digits set across the column in one em.
+    TateChuYoko(&'a str),
+}
+
+/// An upright glyph and its vertical metrics, in font units.
+struct VerticalGlyph {
+    id: u16,
+    /// Whether `vert` replaced the glyph the cmap maps to.
+    substituted: bool,
+    advance: f32,
+    /// Height of the vertical origin above the baseline.
+    origin_y: f32,
+    width: f32,
+}
+
+/// A font of the chain with what setting it upright needs.
+#[derive(Debug, Clone)]
+struct VerticalFont {
+    font: Arc<ParsedFont>,
+    face_index: u32,
+    /// Glyphs of the `vert` feature, by the glyph they replace.
+    vert: Arc<BTreeMap<u16, u16>>,
+}
+
+impl VerticalFont {
+    fn new(font: Arc<ParsedFont>) -> Self {
+        let face_index = face_index(&font);
+        let vert = font
+            .source_bytes()
+            .and_then(|bytes| {
+                let face = Face::parse(&bytes, face_index).ok()?;
+                Some(vert_substitutions(&face))
+            })
+            .unwrap_or_default();
+        VerticalFont {
+            font,
+            face_index,
+            vert: Arc::new(vert),
+        }
+    }
+
+    fn glyph(&self, face: &Face, ch: char) -> Option<VerticalGlyph> {
+        let cmap = face.glyph_index(ch)?;
+        let id = self.vert.get(&cmap.0).map_or(cmap, |&id| GlyphId(id));
+        let units_per_em = face.units_per_em() as f32;
+        Some(VerticalGlyph {
+            id: id.0,
+            substituted: id != cmap,
+            advance: face
+                .glyph_ver_advance(id)
+                .map_or(units_per_em, |advance| advance as f32),
+            origin_y: vertical_origin(face, id) as f32,
+            width: face
+                .glyph_hor_advance(id)
+                .map_or(units_per_em, |width| width as f32),
+        })
+    }
+}
+
+/// A [`FontSpecTrait`] that measures text along a vertical column, so the
+/// shared wrapping and dynamic sizing work on the column length.
+///
+/// Each grapheme cluster is set in the font of the chain that draws it
+/// horizontally, with that font's vertical metrics and `vert` glyphs.
+#[derive(Debug, Clone)]
+pub struct VerticalFontSpec {
+    fonts: FontChain,
+    /// One per font of the chain, in the same order.
+    vertical_fonts: Arc<Vec<VerticalFont>>,
+    font_spec: FontSpec,
+    line_break_mode: LineBreakMode,
+    tate_chu_yoko: usize,
+}
+
+impl VerticalFontSpec {
+    /// `tate_chu_yoko` is the longest run of digits set across the column;
+    /// 0 turns every run.
+    pub fn new(fonts: FontChain, tate_chu_yoko: usize) -> Self {
+        let vertical_fonts = fonts
+            .fonts()
+            .iter()
+            .map(|(_, font)| VerticalFont::new(font.clone()))
+            .collect();
+        VerticalFontSpec {
+            font_spec: fonts.spec(),
+            fonts,
+            vertical_fonts: Arc::new(vertical_fonts),
+            line_break_mode: LineBreakMode::default(),
+            tate_chu_yoko,
+        }
+    }
+
+    pub fn with_line_break_mode(mut self, line_break_mode: LineBreakMode) -> Self {
+        self.line_break_mode = line_break_mode;
+        self.font_spec = self.font_spec.with_line_break_mode(line_break_mode);
+        self
+    }
+
+    /// Calls `f` with the face of every font of the chain, `None` where one
+    /// cannot be parsed.
+    fn with_faces<T>(&self, f: impl FnOnce(&[Option<Face>]) -> T) -> T {
+        let bytes: Vec<_> = self
+            .vertical_fonts
+            .iter()
+            .map(|font| font.font.source_bytes())
+            .collect();
+        let faces: Vec<Option<Face>> = bytes
+            .iter()
+            .zip(self.vertical_fonts.iter())
+            .map(|(bytes, font)| {
+                bytes
+                    .as_deref()
+                    .and_then(|bytes| Face::parse(bytes, font.face_index).ok())
+            })
+            .collect();
+        f(&faces)
+    }
+
+    /// Splits a column into upright clusters, turned runs and tate-chu-yoko,
+    /// each with the index of its font in the chain and its cluster count.
+    fn units<'a>(&self, faces: &[Option<Face>], text: &'a str) -> Vec<(Unit<'a>, usize, usize)> {
+        let mut units: Vec<(Unit<'a>, usize, usize)> = Vec::new();
+        let mut offset = 0;
+        for cluster in FontSpec::split_text_by_grapheme_cluster(text) {
+            let range = offset..offset + cluster.len();
+            offset = range.end;
+            let index = self.fonts.font_index(&cluster);
+            let upright = cluster.chars().next().is_some_and(|ch| {
+                let substituted = faces[index]
+                    .as_ref()
+                    .and_then(|face| self.vertical_fonts[index].glyph(face, ch))
+                    .is_some_and(|glyph| glyph.substituted);
+                substituted || (is_upright(ch) && !is_rotated_without_vert(ch))
+            });
+            match units.last_mut() {
+                _ if upright => units.push((Unit::Upright(&text[range]), index, 1)),
+                // 横向きの連はフォントが変わるところで分ける
+                Some((Unit::Rotated(run), font, clusters)) if *font == index => {
+                    let start = run.as_ptr() as usize - text.as_ptr() as usize;
+                    *run = &text[start..range.end];
+                    *clusters += 1;
+                }
+                _ => units.push((Unit::Rotated(&text[range]), index, 1)),
+            }
+        }
+        for (unit, _, clusters) in &mut units {
+            if let Unit::Rotated(run) = *unit {
+                if *clusters <= self.tate_chu_yoko && run.chars().all(|ch| ch.is_ascii_digit()) {
+                    *unit = Unit::TateChuYoko(run);
+                }
+            }
+        }
+        units
+    }
+
+    /// Length of `text` along the column, character spacing included.
+    fn length_of(
+        &self,
+        faces: &[Option<Face>],
+        text: &str,
+        font_size: Pt,
+        character_spacing: Pt,
+    ) -> Result<Pt, font::Error> {
+        let mut length = 0.0;
+        let mut clusters = 0;
+        for (unit, index, count) in self.units(faces, text) {
+            clusters += count;
+            length += match unit {
+                Unit::Upright(cluster) => {
+                    self.upright_advance(faces[index].as_ref(), index, cluster, font_size)
+                }
+                Unit::Rotated(run) => {
+                    self.font_spec
+                        .width_of_text_at_size(run, font_size, character_spacing)?
+                        .0
+                        // 連の中の字間は最後にまとめて加える
+                        - character_spacing.0 * (count - 1) as f32
+                }
+                Unit::TateChuYoko(_) => font_size.0,
+            };
+        }
+        Ok(Pt(
+            length + character_spacing.0 * clusters.saturating_sub(1) as f32
+        ))
+    }
+
+    fn upright_advance(
+        &self,
+        face: Option<&Face>,
+        index: usize,
+        cluster: &str,
+        font_size: Pt,
+    ) -> f32 {
+        let advance = face.and_then(|face| {
+            let glyph = self.vertical_fonts[index].glyph(face, cluster.chars().next()?)?;
+            Some(glyph.advance / face.units_per_em() as f32)
+        });
+        advance.unwrap_or(1.0) * font_size.0
+    }
+
+    /// One text section drawing `column` downwards from `top`, centred on
+    /// `center_x`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn column_ops(
+        &self,
+        bounding_matrix: [f32; 6],
+        font_size: Pt,
+        center_x: Mm,
+        top: Mm,
+        character_spacing: Pt,
+        column: &str,
+        font_color: &PdfColor,
+    ) -> Result<Vec<Op>, font::Error> {
+        let Pt(center_x) = center_x.into();
+        let Pt(mut pen) = top.into();
+        let size = font_size.0;
+
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(bounding_matrix),
+            },
+            Op::StartTextSection,
+        ];
+        ops.extend(font_color.fill_ops());
+
+        self.with_faces(|faces| {
+            let mut current_font = None;
+            for (unit, index, clusters) in self.units(faces, column) {
+                let vertical_font = &self.vertical_fonts[index];
+                if current_font != Some(index) {
+                    ops.push(Op::SetFont {
+                        font: PdfFontHandle::External(self.fonts.fonts()[index].0.clone()),
+                        size: font_size,
+                    });
+                    current_font = Some(index);
+                }
+
+                let face = faces[index].as_ref();
+                let units_per_em = face.map_or(1000.0, |face| face.units_per_em() as f32);
+                let scale = size / units_per_em;
+                // 仮想ボディ（全角の枠）の上端。横向きの連や縦中横もこの枠に合わせて置く
+                let em_top = face.map_or(units_per_em * 0.88, |face| {
+                    vertical_origin(face, GlyphId(0)) as f32
+                });
+
+                let (matrix, spacing, item, advance) = match unit {
+                    Unit::Upright(cluster) => {
+                        let ch = cluster.chars().next().unwrap_or(' ');
+                        let glyph = face.and_then(|face| vertical_font.glyph(face, ch));
+                        let (item, width, origin_y, advance) = match glyph {
+                            Some(glyph) if glyph.substituted && cluster.chars().count() == 1 => (
+                                TextItem::GlyphIds(vec![Codepoint::with_cid(
+                                    glyph.id,
+                                    0.0,
+                                    cluster.to_string(),
+                                )]),
+                                glyph.width * scale,
+                                glyph.origin_y * scale,
+                                glyph.advance * scale,
+                            ),
+                            glyph => {
+                                let width = self
+                                    .font_spec
+                                    .width_of_text_at_size(cluster, font_size, Pt(0.0))?
+                                    .0;
+                                let origin_y = glyph
+                                    .as_ref()
+                                    .map_or(em_top * scale, |glyph| glyph.origin_y * scale);
+                                let advance = glyph.map_or(size, |glyph| glyph.advance * scale);
+                                (
+                                    TextItem::Text(sanitize_text_for_font(
+                                        cluster,
+                                        &vertical_font.font,
+                                    )),
+                                    width,
+                                    origin_y,
+                                    advance,
+                                )
+                            }
+                        };
+                        let matrix = [1.0, 0.0, 0.0, 1.0, center_x - width / 2.0, pen - origin_y];
+                        (matrix, 0.0, item, advance)
+                    }
+                    Unit::Rotated(run) => {
+                        let length = self
+                            .font_spec
+                            .width_of_text_at_size(run, font_size, character_spacing)?
+                            .0;
+                        let baseline = center_x - (em_top - units_per_em / 2.0) * scale;
+                        let matrix = [0.0, -1.0, 1.0, 0.0, baseline, pen];
+                        let item = TextItem::Text(sanitize_text_for_font(run, &vertical_font.font));
+                        let advance = length - character_spacing.0 * (clusters - 1) as f32;
+                        (matrix, character_spacing.0, item, advance)
+                    }
+                    Unit::TateChuYoko(digits) => {
+                        let width = self
+                            .font_spec
+                            .width_of_text_at_size(digits, font_size, Pt(0.0))?
+                            .0;
+                        // 1字分に収まらなければ横に詰める
+                        let squeeze = if width > size { size / width } else { 1.0 };
+                        let baseline = pen - em_top * scale;
+                        let matrix = [
+                            squeeze,
+                            0.0,
+                            0.0,
+                            1.0,
+                            center_x - width * squeeze / 2.0,
+                            baseline,
+                        ];
+                        let item =
+                            TextItem::Text(sanitize_text_for_font(digits, &vertical_font.font));
+                        (matrix, 0.0, item, size)
+                    }
+                };
+                ops.extend([
+                    Op::SetTextMatrix {
+                        matrix: TextMatrix::Raw(matrix),
+                    },
+                    Op::SetCharacterSpacing {
+                        multiplier: spacing,
+                    },
+                    Op::ShowText { items: vec![item] },
+                ]);
+                pen -= advance + character_spacing.0 * clusters as f32;
+            }
+            Ok::<_, font::Error>(())
+        })?;
+
+        ops.extend([Op::EndTextSection, Op::RestoreGraphicsState]);
+        Ok(ops)
+    }
+}
+
+impl FontSpecTrait for VerticalFontSpec {
+    fn calculate_dynamic_font_size(
+        &self,
+        dynamic: DynamicFontSize,
+        line_height: Option<f32>,
+        character_spacing: Pt,
+        width: Mm,
+        height: Mm,
+        content: &str,
+    ) -> Result<Pt, font::Error> {
+        fit_dynamic_font_size(
+            self,
+            dynamic,
+            line_height,
+            character_spacing,
+            width,
+            height,
+            content,
+        )
+    }
+
+    fn split_text_to_size(
+        &self,
+        content: &str,
+        font_size: Pt,
+        box_width: Pt,
+        character_spacing: Pt,
+    ) -> Result<Vec<String>, font::Error> {
+        self.with_faces(|faces| {
+            let mut columns = Vec::new();
+            for paragraph in content.lines() {
+                let lines = wrap_paragraph(paragraph, self.line_break_mode, box_width, |range| {
+                    self.length_of(faces, &paragraph[range], font_size, character_spacing)
+                })?;
+                let lines = lines
+                    .into_iter()
+                    .map(|range| paragraph[range].to_string())
+                    .collect();
+                // 縦組みの禁則も横組みと同じ行頭・行末禁則文字で処理する
+                columns.extend(filter_end_jp(filter_start_jp(lines)));
+            }
+            Ok(columns)
+        })
+    }
+
+    fn width_of_text_at_size(
+        &self,
+        text: &str,
+        font_size: Pt,
+        character_spacing: Pt,
+    ) -> Result<Pt, font::Error> {
+        self.with_faces(|faces| self.length_of(faces, text, font_size, character_spacing))
+    }
+}
+
+/// The face of a font collection `font` was parsed from, found by where its
+/// `hmtx` table sits in the file.
+fn face_index(font: &ParsedFont) -> u32 {
+    let Some(bytes) = font.source_bytes() else {
+        return 0;
+    };
+    let faces = ttf_parser::fonts_in_collection(&bytes).unwrap_or(1);
+    (0..faces)
+        .find(|&index| {
+            Face::parse(&bytes, index)
+                .ok()
+                .and_then(|face| face.raw_face().table(Tag::from_bytes(b"hmtx")))
+                .is_some_and(|hmtx| {
+                    hmtx.as_ptr() as usize - bytes.as_ptr() as usize == font.hmtx_range.0
+                })
+        })
+        .unwrap_or(0)
+}
+
+/// Every single substitution of the font's `vert` feature.
+fn vert_substitutions(face: &Face) -> BTreeMap<u16, u16> {
+    let mut substitutions = BTreeMap::new();
+    let Some(gsub) = face.tables().gsub else {
+        return substitutions;
+    };
+    let mut lookups: Vec<u16> = gsub
+        .features
+        .into_iter()
+        .filter(|feature| feature.tag == Tag::from_bytes(b"vert"))
+        .flat_map(|feature| feature.lookup_indices)
+        .collect();
+    lookups.sort_unstable();
+    lookups.dedup();
+
+    for lookup in lookups
+        .into_iter()
+        .filter_map(|index| gsub.lookups.get(index))
+    {
+        for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+            let SubstitutionSubtable::Single(single) = subtable else {
+                continue;
+            };
+            let coverage = single.coverage();
+            for id in 0..face.number_of_glyphs() {
+                let Some(index) = coverage.get(GlyphId(id)) else {
+                    continue;
+                };
+                let substitute = match single {
+                    ttf_parser::gsub::SingleSubstitution::Format1 { delta, .. } => {
+                        Some(GlyphId((id as i32 + delta as i32) as u16))
+                    }
+                    ttf_parser::gsub::SingleSubstitution::Format2 { substitutes, .. } => {
+                        substitutes.get(index)
+                    }
+                };
+                if let Some(substitute) = substitute {
+                    substitutions.entry(id).or_insert(substitute.0);
+                }
+            }
+        }
+    }
+    substitutions
+}
+
+/// Height of a glyph's vertical origin above the baseline: `VORG` for CFF
+/// fonts, the top side bearing above the glyph's top for TrueType ones.
+fn vertical_origin(face: &Face, id: GlyphId) -> i16 {
+    face.glyph_y_origin(id)
+        .or_else(|| {
+            let bearing = face.glyph_ver_side_bearing(id)?;
+            let bounds = face.glyph_bounding_box(id)?;
+            Some(bearing + bounds.y_max)
+        })
+        .unwrap_or_else(|| face.ascender())
+}
+
+/// Whether a character stands upright in vertical text (Unicode
+/// Vertical_Orientation U or Tu, roughly).
+fn is_upright(ch: char) -> bool {
+    matches!(ch as u32,
+        0x00A7 | 0x00A9 | 0x00AE | 0x00B1 | 0x00BC..=0x00BE | 0x00D7 | 0x00F7
+        | 0x1100..=0x11FF
+        | 0x2016 | 0x2020 | 0x2021 | 0x2030 | 0x2031 | 0x203B | 0x203C
+        | 0x2042 | 0x2047..=0x2049 | 0x2051
+        | 0x2100..=0x218F
+        | 0x2460..=0x24FF
+        | 0x25A0..=0x27BF
+        | 0x2E80..=0x2FFF
+        | 0x3000..=0x303F
+        | 0x3040..=0x9FFF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7FF
+        | 0xF900..=0xFAFF
+        | 0xFE10..=0xFE1F
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFFEF
+        | 0x1F000..=0x1FAFF
+        | 0x20000..=0x3FFFF)
+}
+
+/// Characters that have to change shape in vertical text (Vertical_Orientation
+/// Tr): the `vert` feature supplies their vertical glyph, and without one they
+/// are turned like Latin text instead.
+fn is_rotated_without_vert(ch: char) -> bool {
+    matches!(ch as u32,
+        0x3008..=0x3011 | 0x3014..=0x301F | 0x3030 | 0x30A0 | 0x30FC
+        | 0xFF08 | 0xFF09 | 0xFF0D | 0xFF1A..=0xFF1E | 0xFF3B | 0xFF3D | 0xFF3F
+        | 0xFF5B..=0xFF60 | 0xFFE3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use printpdf::FontId;
+    use std::path::PathBuf;
+    use std::sync::OnceLock;
+
+    fn test_font() -> Arc<ParsedFont> {
+        static FONT: OnceLock<Arc<ParsedFont>> = OnceLock::new();
+        FONT.get_or_init(|| {
+            let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("assets")
+                .join("fonts")
+                .join("NotoSansJP-Regular.ttf");
+            let bytes = std::fs::read(path).expect("test font should be readable");
+            Arc::new(ParsedFont::from_bytes(&bytes, 0, &mut Vec::new()).expect("test font"))
+        })
+        .clone()
+    }
+
+    fn test_chain() -> FontChain {
+        FontChain::new(FontId::new(), test_font())
+    }
+
+    fn units(spec: &VerticalFontSpec, text: &str) -> Vec<Unit<'static>> {
+        let text: &'static str = Box::leak(text.to_string().into_boxed_str());
+        spec.with_faces(|faces| {
+            spec.units(faces, text)
+                .into_iter()
+                .map(|(unit, _, _)| unit)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn punctuation_and_brackets_take_their_vert_glyphs() {
+        let spec = VerticalFontSpec::new(test_chain(), 0);
+        spec.with_faces(|faces| {
+            let (font, face) = (&spec.vertical_fonts[0], faces[0].as_ref().unwrap());
+            for ch in ['、', '。', '「', '」', 'ー', '（'] {
+                assert!(font.glyph(face, ch).unwrap().substituted, "{ch}");
+            }
+            assert!(!font.glyph(face, '漢').unwrap().substituted);
+        });
+    }
+
+    #[test]
+    fn latin_runs_turn_and_short_digit_runs_stand_across() {
+        let spec = VerticalFontSpec::new(test_chain(), 2);
+        assert_eq!(
+            units(&spec, "令和7年12月 PDF 2025"),
+            [
+                Unit::Upright("令"),
+                Unit::Upright("和"),
+                Unit::TateChuYoko("7"),
+                Unit::Upright("年"),
+                Unit::TateChuYoko("12"),
+                Unit::Upright("月"),
+                Unit::Rotated(" PDF 2025"),
+            ]
+        );
+
+        let spec = VerticalFontSpec::new(test_chain(), 0);
+        assert_eq!(units(&spec, "12月")[0], Unit::Rotated("12"));
+    }
+
+    #[test]
+    fn columns_are_measured_with_vertical_advances() {
+        let spec = VerticalFontSpec::new(test_chain(), 2);
+        let length = spec
+            .width_of_text_at_size("あいう", Pt(10.0), Pt(1.0))
+            .unwrap();
+        assert_eq!(length, Pt(32.0));
+        // A tate-chu-yoko run is one em long however many digits it holds.
+        let length = spec
+            .width_of_text_at_size("12月", Pt(10.0), Pt(0.0))
+            .unwrap();
+        assert_eq!(length, Pt(20.0));
+    }
+
+    #[test]
+    fn columns_wrap_with_kinsoku() {
+        let spec = VerticalFontSpec::new(test_chain(), 0).with_line_break_mode(LineBreakMode::Char);
+        let columns = spec
+            .split_text_to_size("あいう。えお", Pt(10.0), Pt(30.0), Pt(0.0))
+            .unwrap();
+        assert!(columns.len() > 1);
+        assert!(
+            columns.iter().all(|column| !column.starts_with('。')),
+            "{columns:?}"
+        );
+    }
+}
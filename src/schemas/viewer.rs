@@ -0,0 +1,285 @@
+//! Document-level viewer and print preferences.
+//!
+//! `basePdf.viewerPreferences` is written into the document catalog once the
+//! PDF has been saved: print dialog defaults go into `/ViewerPreferences`,
+//! while `pageLayout`, `pageMode` and `openZoom` become the catalog's
+//! `/PageLayout`, `/PageMode` and `/OpenAction`.
+
+use lopdf::{Dictionary, Document, Object};
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU32;
+
+use super::Error;
+
+/// Whether the print dialog should scale pages to the paper.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PrintScaling {
+    /// Print at 100%. Use this for labels and barcodes.
+    None,
+    AppDefault,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Duplex {
+    Simplex,
+    DuplexFlipShortEdge,
+    DuplexFlipLongEdge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageLayout {
+    SinglePage,
+    OneColumn,
+    TwoColumnLeft,
+    TwoColumnRight,
+    TwoPageLeft,
+    TwoPageRight,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PageMode {
+    UseNone,
+    UseOutlines,
+    UseThumbs,
+    FullScreen,
+    #[serde(rename = "useOC")]
+    UseOc,
+    UseAttachments,
+}
+
+/// Initial zoom of the first page when the document is opened.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Zoom {
+    /// A percentage, `100` being actual size.
+    Percent(f32),
+    Fit(ZoomFit),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ZoomFit {
+    Fit,
+    FitWidth,
+    FitHeight,
+}
+
+/// `basePdf.viewerPreferences`. Every field is optional; absent fields are
+/// left to the viewer.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ViewerPreferences {
+    pub print_scaling: Option<PrintScaling>,
+    pub duplex: Option<Duplex>,
+    pub num_copies: Option<NonZeroU32>,
+    #[serde(rename = "pickTrayByPDFSize")]
+    pub pick_tray_by_pdf_size: Option<bool>,
+    pub hide_toolbar: Option<bool>,
+    pub hide_menubar: Option<bool>,
+    #[serde(rename = "hideWindowUI")]
+    pub hide_window_ui: Option<bool>,
+    pub fit_window: Option<bool>,
+    pub center_window: Option<bool>,
+    pub display_doc_title: Option<bool>,
+    pub page_layout: Option<PageLayout>,
+    pub page_mode: Option<PageMode>,
+    pub open_zoom: Option<Zoom>,
+}
+
+impl ViewerPreferences {
+    /// Fills every field left unset here from `fallback`.
+    pub fn or(self, fallback: &ViewerPreferences) -> ViewerPreferences {
+        ViewerPreferences {
+            print_scaling: self.print_scaling.or(fallback.print_scaling),
+            duplex: self.duplex.or(fallback.duplex),
+            num_copies: self.num_copies.or(fallback.num_copies),
+            pick_tray_by_pdf_size: self
+                .pick_tray_by_pdf_size
+                .or(fallback.pick_tray_by_pdf_size),
+            hide_toolbar: self.hide_toolbar.or(fallback.hide_toolbar),
+            hide_menubar: self.hide_menubar.or(fallback.hide_menubar),
+            hide_window_ui: self.hide_window_ui.or(fallback.hide_window_ui),
+            fit_window: self.fit_window.or(fallback.fit_window),
+            center_window: self.center_window.or(fallback.center_window),
+            display_doc_title: self.display_doc_title.or(fallback.display_doc_title),
+            page_layout: self.page_layout.or(fallback.page_layout),
+            page_mode: self.page_mode.or(fallback.page_mode),
+            open_zoom: self.open_zoom.or(fallback.open_zoom),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == ViewerPreferences::default()
+    }
+
+    fn viewer_preferences_dictionary(&self) -> Dictionary {
+        let mut dictionary = Dictionary::new();
+        if let Some(print_scaling) = self.print_scaling {
+            let name = match print_scaling {
+                PrintScaling::None => "None",
+                PrintScaling::AppDefault => "AppDefault",
+            };
+            dictionary.set("PrintScaling", Object::Name(name.into()));
+        }
+        if let Some(duplex) = self.duplex {
+            let name = match duplex {
+                Duplex::Simplex => "Simplex",
+                Duplex::DuplexFlipShortEdge => "DuplexFlipShortEdge",
+                Duplex::DuplexFlipLongEdge => "DuplexFlipLongEdge",
+            };
+            dictionary.set("Duplex", Object::Name(name.into()));
+        }
+        if let Some(num_copies) = self.num_copies {
+            dictionary.set("NumCopies", num_copies.get() as i64);
+        }
+        let flags = [
+            ("PickTrayByPDFSize", self.pick_tray_by_pdf_size),
+            ("HideToolbar", self.hide_toolbar),
+            ("HideMenubar", self.hide_menubar),
+            ("HideWindowUI", self.hide_window_ui),
+            ("FitWindow", self.fit_window),
+            ("CenterWindow", self.center_window),
+            ("DisplayDocTitle", self.display_doc_title),
+        ];
+        for (key, value) in flags {
+            if let Some(value) = value {
+                dictionary.set(key, value);
+            }
+        }
+        dictionary
+    }
+}
+
+/// Writes `preferences` into the catalog of a saved PDF. Returns the bytes
+/// unchanged when no preference is set.
+pub(crate) fn apply(pdf: Vec<u8>, preferences: &ViewerPreferences) -> Result<Vec<u8>, Error> {
+    if preferences.is_empty() {
+        return Ok(pdf);
+    }
+
+    fn pdf_error(e: impl std::fmt::Display) -> Error {
+        Error::Whatever {
+            message: format!("Failed to write viewer preferences: {e}"),
+            source: None,
+        }
+    }
+
+    let mut document = Document::load_mem(&pdf).map_err(pdf_error)?;
+    let first_page = document.get_pages().get(&1).copied();
+    let catalog = document.catalog_mut().map_err(pdf_error)?;
+
+    let viewer_preferences = preferences.viewer_preferences_dictionary();
+    if !viewer_preferences.is_empty() {
+        catalog.set("ViewerPreferences", viewer_preferences);
+    }
+    if let Some(page_layout) = preferences.page_layout {
+        let name = match page_layout {
+            PageLayout::SinglePage => "SinglePage",
+            PageLayout::OneColumn => "OneColumn",
+            PageLayout::TwoColumnLeft => "TwoColumnLeft",
+            PageLayout::TwoColumnRight => "TwoColumnRight",
+            PageLayout::TwoPageLeft => "TwoPageLeft",
+            PageLayout::TwoPageRight => "TwoPageRight",
+        };
+        catalog.set("PageLayout", Object::Name(name.into()));
+    }
+    if let Some(page_mode) = preferences.page_mode {
+        let name = match page_mode {
+            PageMode::UseNone => "UseNone",
+            PageMode::UseOutlines => "UseOutlines",
+            PageMode::UseThumbs => "UseThumbs",
+            PageMode::FullScreen => "FullScreen",
+            PageMode::UseOc => "UseOC",
+            PageMode::UseAttachments => "UseAttachments",
+        };
+        catalog.set("PageMode", Object::Name(name.into()));
+    }
+    if let (Some(zoom), Some(first_page)) = (preferences.open_zoom, first_page) {
+        let destination: Vec<Object> = match zoom {
+            Zoom::Percent(percent) => vec![
+                first_page.into(),
+                Object::Name(b"XYZ".to_vec()),
+                Object::Null,
+                Object::Null,
+                (percent / 100.0).into(),
+            ],
+            Zoom::Fit(ZoomFit::Fit) => vec![first_page.into(), Object::Name(b"Fit".to_vec())],
+            Zoom::Fit(ZoomFit::FitWidth) => vec![
+                first_page.into(),
+                Object::Name(b"FitH".to_vec()),
+                Object::Null,
+            ],
+            Zoom::Fit(ZoomFit::FitHeight) => vec![
+                first_page.into(),
+                Object::Name(b"FitV".to_vec()),
+                Object::Null,
+            ],
+        };
+        catalog.set("OpenAction", destination);
+    }
+
+    let mut bytes = Vec::new();
+    document.save_to(&mut bytes).map_err(pdf_error)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preferences_parse_from_camel_case_json() {
+        let preferences: ViewerPreferences = serde_json::from_value(serde_json::json!({
+            "printScaling": "none",
+            "duplex": "duplexFlipLongEdge",
+            "numCopies": 2,
+            "pickTrayByPDFSize": true,
+            "hideWindowUI": true,
+            "pageLayout": "twoPageRight",
+            "pageMode": "useOC",
+            "openZoom": "fitWidth"
+        }))
+        .unwrap();
+
+        assert_eq!(preferences.print_scaling, Some(PrintScaling::None));
+        assert_eq!(preferences.num_copies, NonZeroU32::new(2));
+        assert_eq!(preferences.page_mode, Some(PageMode::UseOc));
+        assert_eq!(preferences.open_zoom, Some(Zoom::Fit(ZoomFit::FitWidth)));
+
+        let percent: ViewerPreferences =
+            serde_json::from_value(serde_json::json!({ "openZoom": 150 })).unwrap();
+        assert_eq!(percent.open_zoom, Some(Zoom::Percent(150.0)));
+    }
+
+    #[test]
+    fn zero_copies_and_unknown_keys_are_rejected() {
+        for json in [
+            serde_json::json!({ "numCopies": 0 }),
+            serde_json::json!({ "printScale": "none" }),
+        ] {
+            assert!(serde_json::from_value::<ViewerPreferences>(json).is_err());
+        }
+    }
+
+    #[test]
+    fn unset_fields_fall_back() {
+        let template = ViewerPreferences {
+            duplex: Some(Duplex::Simplex),
+            ..Default::default()
+        };
+        let forge = ViewerPreferences {
+            duplex: Some(Duplex::DuplexFlipLongEdge),
+            print_scaling: Some(PrintScaling::None),
+            ..Default::default()
+        };
+
+        let merged = template.or(&forge);
+
+        assert_eq!(merged.duplex, Some(Duplex::Simplex));
+        assert_eq!(merged.print_scaling, Some(PrintScaling::None));
+    }
+}
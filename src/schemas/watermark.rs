@@ -0,0 +1,345 @@
+//! `basePdf.watermark`: a text or image stamp painted on every laid-out page.
+//!
+//! The stamp is rotated about the centre of the page it is painted on, so the
+//! same template centres it on any page size, including per-page overrides
+//! and auto-height pages. `"layout": "tile"` repeats it across the whole page
+//! instead, and `"zOrder"` puts it behind or above the page content.
+
+use image::DynamicImage;
+use printpdf::{
+    CurTransMat, FontId, Mm, Op, PdfDocument, PdfFontHandle, Pt, TextItem, TextMatrix,
+    XObjectTransform,
+};
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt};
+
+use super::base::XOBJECT_DPI;
+use super::color::PdfColor;
+use super::image::Image;
+use super::{pdf_utils, units, Error, FontSnafu, InvalidColorSnafu};
+use crate::font::{FontMap, FontSpec, FontSpecTrait};
+
+const DEFAULT_FONT_SIZE: f32 = 72.0;
+const DEFAULT_ROTATE: f32 = 45.0;
+const DEFAULT_OPACITY: f32 = 0.15;
+const DEFAULT_GAP: Mm = Mm(20.0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WatermarkLayout {
+    #[default]
+    Center,
+    Tile,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ZOrder {
+    Behind,
+    #[default]
+    Above,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct JsonWatermark {
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    image: Option<String>,
+    #[serde(default)]
+    font_name: Option<String>,
+    #[serde(default)]
+    font_size: Option<f32>,
+    #[serde(default)]
+    font_color: Option<String>,
+    #[serde(default, deserialize_with = "units::deserialize_optional_mm")]
+    width: Option<f32>,
+    #[serde(default)]
+    rotate: Option<f32>,
+    #[serde(default)]
+    opacity: Option<f32>,
+    #[serde(default)]
+    layout: WatermarkLayout,
+    #[serde(default, deserialize_with = "units::deserialize_optional_mm")]
+    gap: Option<f32>,
+    #[serde(default)]
+    z_order: ZOrder,
+}
+
+#[derive(Debug, Clone)]
+enum Stamp {
+    Text {
+        content: String,
+        font_name: String,
+        font_size: Pt,
+        color: PdfColor,
+    },
+    Image {
+        content: DynamicImage,
+        /// Stamp width; `None` takes half the page width.
+        width: Option<Mm>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    stamp: Stamp,
+    rotate: f32,
+    opacity: f32,
+    layout: WatermarkLayout,
+    gap: Mm,
+    pub z_order: ZOrder,
+}
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidWatermark {
+        message: message.to_string(),
+    }
+}
+
+impl Watermark {
+    pub(crate) fn from_json(json: JsonWatermark) -> Result<Watermark, Error> {
+        let stamp = match (json.text, json.image) {
+            (Some(content), None) => {
+                if json.width.is_some() {
+                    return Err(invalid("width only applies to an image watermark"));
+                }
+                let font_size = json.font_size.unwrap_or(DEFAULT_FONT_SIZE);
+                if font_size <= 0.0 {
+                    return Err(invalid("fontSize must be positive"));
+                }
+                Stamp::Text {
+                    content,
+                    font_name: json
+                        .font_name
+                        .ok_or_else(|| invalid("a text watermark needs a fontName"))?,
+                    font_size: Pt(font_size),
+                    color: PdfColor::parse(json.font_color.as_deref().unwrap_or("#808080"))
+                        .context(InvalidColorSnafu)?,
+                }
+            }
+            (None, Some(image)) => {
+                if json.font_name.is_some() || json.font_size.is_some() || json.font_color.is_some()
+                {
+                    return Err(invalid("font settings only apply to a text watermark"));
+                }
+                if json.width.is_some_and(|width| width <= 0.0) {
+                    return Err(invalid("width must be positive"));
+                }
+                Stamp::Image {
+                    content: Image::decode_base64_to_image_buffer(&image)?,
+                    width: json.width.map(Mm),
+                }
+            }
+            _ => return Err(invalid("set exactly one of text or image")),
+        };
+
+        let opacity = json.opacity.unwrap_or(DEFAULT_OPACITY);
+        if !(0.0..=1.0).contains(&opacity) {
+            return Err(invalid("opacity must be between 0 and 1"));
+        }
+        let gap = json.gap.map(Mm).unwrap_or(DEFAULT_GAP);
+        if gap.0 < 0.0 {
+            return Err(invalid("gap must not be negative"));
+        }
+
+        Ok(Watermark {
+            stamp,
+            rotate: json.rotate.unwrap_or(DEFAULT_ROTATE),
+            opacity,
+            layout: json.layout,
+            gap,
+            z_order: json.z_order,
+        })
+    }
+
+    /// Ops painting the watermark on a page `width` wide whose visible part
+    /// is the top `visible_height` of a `layout_height` tall layout.
+    pub(crate) fn render(
+        &self,
+        font_map: &FontMap,
+        doc: &mut PdfDocument,
+        width: Mm,
+        layout_height: Mm,
+        visible_height: Mm,
+    ) -> Result<Vec<Op>, Error> {
+        // ページ中心を原点にして回転した座標系で描く
+        let center_x: Pt = (width / 2.0).into();
+        let center_y: Pt = (layout_height - visible_height / 2.0).into();
+        let radians = self.rotate.to_radians();
+        let (sin, cos) = radians.sin_cos();
+        let mut ops = vec![
+            Op::SaveGraphicsState,
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw([cos, sin, -sin, cos, center_x.0, center_y.0]),
+            },
+        ];
+
+        match &self.stamp {
+            Stamp::Text {
+                content,
+                font_name,
+                font_size,
+                color,
+            } => {
+                let (font_id, font) = font_map
+                    .find(font_name)
+                    .whatever_context("Font specified in the watermark is not loaded")?;
+                let text_width = FontSpec::new(font.clone())
+                    .width_of_text_at_size(content, *font_size, Pt(0.0))
+                    .context(FontSnafu)?;
+                let stamp_width: Mm = text_width.into();
+                let stamp_height: Mm = (*font_size).into();
+                let content = pdf_utils::sanitize_text_for_font(content, font);
+                ops.extend(Self::text_ops(font_id, *font_size, color));
+                for (x, y) in self.positions(width, visible_height, stamp_width, stamp_height) {
+                    // ベースラインを字面の中央に寄せる（大文字の高さを約0.7emとみなす）
+                    let baseline: Pt = (y + stamp_height * 0.15).into();
+                    let x: Pt = x.into();
+                    ops.push(Op::SetTextMatrix {
+                        matrix: TextMatrix::Raw([1.0, 0.0, 0.0, 1.0, x.0, baseline.0]),
+                    });
+                    ops.push(Op::ShowText {
+                        items: vec![TextItem::Text(content.clone())],
+                    });
+                }
+                ops.push(Op::EndTextSection);
+            }
+            Stamp::Image {
+                content,
+                width: stamp_width,
+            } => {
+                let image = Image::to_raw_image(content)?;
+                let stamp_width = stamp_width.unwrap_or(width / 2.0);
+                let natural_width = Mm(image.width as f32 / (XOBJECT_DPI / 25.4));
+                let scale = stamp_width.0 / natural_width.0;
+                let stamp_height = stamp_width * (image.height as f32 / image.width as f32);
+                let id = doc.add_image(&image);
+                for (x, y) in self.positions(width, visible_height, stamp_width, stamp_height) {
+                    ops.push(Op::UseXobject {
+                        id: id.clone(),
+                        transform: XObjectTransform {
+                            translate_x: Some(x.into()),
+                            translate_y: Some(y.into()),
+                            rotate: None,
+                            scale_x: Some(scale),
+                            scale_y: Some(scale),
+                            dpi: Some(XOBJECT_DPI),
+                            no_auto_scale: false,
+                        },
+                    });
+                }
+            }
+        }
+        ops.push(Op::RestoreGraphicsState);
+
+        Ok(pdf_utils::wrap_ops_with_opacity(
+            doc,
+            Some(self.opacity),
+            ops,
+        ))
+    }
+
+    fn text_ops(font_id: &FontId, font_size: Pt, color: &PdfColor) -> Vec<Op> {
+        let mut ops = vec![Op::StartTextSection];
+        ops.extend(color.fill_ops());
+        ops.push(Op::SetFont {
+            font: PdfFontHandle::External(font_id.clone()),
+            size: font_size,
+        });
+        ops
+    }
+
+    /// Lower-left corners of the stamps in the rotated frame centred on the
+    /// page. A tiled watermark covers the circle around the page, so the
+    /// page stays covered whatever the rotation.
+    fn positions(
+        &self,
+        page_width: Mm,
+        page_height: Mm,
+        stamp_width: Mm,
+        stamp_height: Mm,
+    ) -> Vec<(Mm, Mm)> {
+        let origin = (stamp_width / -2.0, stamp_height / -2.0);
+        if self.layout == WatermarkLayout::Center {
+            return vec![origin];
+        }
+
+        let radius = page_width.0.hypot(page_height.0) / 2.0;
+        let step_x = stamp_width.0 + self.gap.0;
+        let step_y = stamp_height.0 + self.gap.0;
+        if step_x <= 0.0 || step_y <= 0.0 {
+            return vec![origin];
+        }
+        let columns = (radius / step_x).ceil() as i32;
+        let rows = (radius / step_y).ceil() as i32;
+        let mut positions = Vec::new();
+        for row in -rows..=rows {
+            for column in -columns..=columns {
+                positions.push((
+                    origin.0 + Mm(column as f32 * step_x),
+                    origin.1 + Mm(row as f32 * step_y),
+                ));
+            }
+        }
+        positions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn watermark(json: serde_json::Value) -> Result<Watermark, Error> {
+        Watermark::from_json(serde_json::from_value(json).expect("watermark json"))
+    }
+
+    #[test]
+    fn a_centered_stamp_sits_on_the_page_centre() {
+        let watermark = watermark(serde_json::json!({ "text": "DRAFT", "fontName": "Any" }))
+            .expect("text watermark");
+        assert_eq!(
+            watermark.positions(Mm(210.0), Mm(297.0), Mm(40.0), Mm(10.0)),
+            vec![(Mm(-20.0), Mm(-5.0))]
+        );
+    }
+
+    #[test]
+    fn a_tiled_stamp_covers_the_page_in_any_direction() {
+        let watermark = watermark(serde_json::json!({
+            "text": "DRAFT",
+            "fontName": "Any",
+            "layout": "tile",
+            "gap": 10
+        }))
+        .expect("text watermark");
+        let positions = watermark.positions(Mm(100.0), Mm(100.0), Mm(40.0), Mm(10.0));
+        let reach = |pick: fn(&(Mm, Mm)) -> f32| {
+            positions
+                .iter()
+                .map(pick)
+                .fold(0.0_f32, |a, b| a.max(b.abs()))
+        };
+
+        // Half the page diagonal is ~70.7mm.
+        assert!(reach(|(x, _)| x.0) >= 70.0);
+        assert!(reach(|(_, y)| y.0) >= 70.0);
+    }
+
+    #[test]
+    fn malformed_watermarks_are_rejected() {
+        for json in [
+            serde_json::json!({}),
+            serde_json::json!({ "text": "DRAFT" }),
+            serde_json::json!({ "text": "DRAFT", "fontName": "Any", "opacity": 1.5 }),
+            serde_json::json!({ "text": "DRAFT", "fontName": "Any", "width": 10 }),
+            serde_json::json!({ "text": "DRAFT", "fontName": "Any", "gap": -1 }),
+        ] {
+            assert!(
+                watermark(json.clone()).is_err(),
+                "{json} should be rejected"
+            );
+        }
+    }
+}
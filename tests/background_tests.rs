@@ -0,0 +1,113 @@
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const IMAGE_DATA_URL: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg==";
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn try_render(
+    name: &str,
+    background: serde_json::Value,
+) -> Result<Vec<u8>, pdforge::schemas::Error> {
+    // Enough lines to overflow onto continuation pages.
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 210.0,
+            "height": 297.0,
+            "padding": [10.0, 10.0, 10.0, 10.0],
+            "background": background
+        },
+        "schemas": [[{
+            "type": "dynamicText",
+            "name": "body",
+            "position": { "x": 10.0, "y": 10.0 },
+            "width": 100.0,
+            "height": 10.0,
+            "content": "line\n".repeat(200),
+            "fontName": "TestFont",
+            "fontSize": 10.0
+        }]]
+    });
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-background-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+
+    let forge = PDForgeBuilder::new("background".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build();
+
+    forge.render("main", vec![vec![HashMap::new()]], None, None)
+}
+
+fn page_operations(pdf: &[u8]) -> Vec<Vec<Operation>> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            Content::decode(&doc.get_page_content(*page_id))
+                .expect("content should decode")
+                .operations
+        })
+        .collect()
+}
+
+/// Whether `operator` is drawn before any text on the page.
+fn drawn_before_text(ops: &[Operation], operator: &str) -> bool {
+    let first = |name: &str| ops.iter().position(|op| op.operator == name);
+    matches!((first(operator), first("BT")), (Some(a), Some(b)) if a < b)
+}
+
+#[test]
+fn a_background_color_is_painted_first_on_continuation_pages() {
+    let pdf = try_render("color", json!({ "color": "#f5f0e1" })).expect("should render");
+    let pages = page_operations(&pdf);
+
+    assert!(pages.len() > 1, "the body should overflow");
+    for (index, ops) in pages.iter().enumerate() {
+        assert!(
+            drawn_before_text(ops, "f"),
+            "page {} has no fill",
+            index + 1
+        );
+    }
+}
+
+#[test]
+fn a_background_image_is_painted_under_the_content_on_every_page() {
+    let pdf = try_render(
+        "image",
+        json!({ "color": "#ffffff", "image": IMAGE_DATA_URL, "objectFit": "cover" }),
+    )
+    .expect("should render");
+
+    for (index, ops) in page_operations(&pdf).iter().enumerate() {
+        assert!(
+            drawn_before_text(ops, "Do"),
+            "page {} has no image",
+            index + 1
+        );
+        assert!(
+            drawn_before_text(ops, "f"),
+            "page {} has no fill",
+            index + 1
+        );
+    }
+}
+
+#[test]
+fn an_empty_background_is_rejected_when_loading() {
+    assert!(try_render("empty", json!({})).is_err());
+}
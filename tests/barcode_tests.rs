@@ -0,0 +1,281 @@
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::{PDForge, PDForgeBuilder};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn barcode(extra: serde_json::Value) -> serde_json::Value {
+    let mut schema = json!({
+        "type": "barcode",
+        "name": "code",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 60.0,
+        "height": 20.0,
+        "format": "ean13",
+        "content": "4006381333931"
+    });
+    schema
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    schema
+}
+
+fn forge(name: &str, schema: serde_json::Value) -> Result<PDForge, pdforge::schemas::Error> {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 100.0,
+            "height": 50.0,
+            "padding": [5.0, 5.0, 5.0, 5.0]
+        },
+        "schemas": [[schema]]
+    });
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-barcode-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    Ok(PDForgeBuilder::new("barcode".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build())
+}
+
+fn render(forge: &PDForge) -> Result<Vec<u8>, pdforge::schemas::Error> {
+    forge.render("main", vec![vec![HashMap::new()]], None, None)
+}
+
+fn operations(pdf: &[u8]) -> Vec<Operation> {
+    let doc = Document::load_mem(pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations
+}
+
+/// Bounding boxes `[x, y, width, height]` of the subpaths, one per bar.
+fn bars(ops: &[Operation]) -> Vec<[f32; 4]> {
+    let mut subpaths: Vec<Vec<(f32, f32)>> = Vec::new();
+    for op in ops {
+        let point = || {
+            (
+                op.operands[0].as_float().unwrap(),
+                op.operands[1].as_float().unwrap(),
+            )
+        };
+        match op.operator.as_str() {
+            "m" => subpaths.push(vec![point()]),
+            "l" => subpaths.last_mut().unwrap().push(point()),
+            _ => {}
+        }
+    }
+    subpaths
+        .iter()
+        .map(|points| {
+            let (xs, ys): (Vec<f32>, Vec<f32>) = points.iter().copied().unzip();
+            let min = |values: &[f32]| values.iter().copied().fold(f32::MAX, f32::min);
+            let max = |values: &[f32]| values.iter().copied().fold(f32::MIN, f32::max);
+            [min(&xs), min(&ys), max(&xs) - min(&xs), max(&ys) - min(&ys)]
+        })
+        .collect()
+}
+
+#[test]
+fn bars_are_drawn_as_filled_vector_bars() {
+    let pdf = render(&forge("vector", barcode(json!({}))).unwrap()).unwrap();
+    let ops = operations(&pdf);
+
+    // EAN-13 has 30 bars: 3 guards of 2 or 3 bars plus 2 bars per digit.
+    assert_eq!(bars(&ops).len(), 30);
+    assert!(ops.iter().any(|op| op.operator == "f"));
+    assert!(!ops.iter().any(|op| op.operator == "Do"));
+}
+
+#[test]
+fn check_digits_are_computed_or_verified() {
+    // 12 digits get their check digit appended.
+    assert!(
+        render(&forge("computed", barcode(json!({ "content": "400638133393" }))).unwrap()).is_ok()
+    );
+    let wrong = render(&forge("wrong", barcode(json!({ "content": "4006381333932" }))).unwrap())
+        .unwrap_err();
+    assert!(wrong.to_string().contains("EAN-13"), "{wrong}");
+}
+
+#[test]
+fn content_is_validated_per_symbology() {
+    for (format, content) in [
+        ("code39", "lower case"),
+        ("itf", "12345"),
+        ("nw7", "A1234"),
+        ("upcA", "12345"),
+        ("code128", "日本"),
+    ] {
+        let forge = forge(
+            format,
+            barcode(json!({ "format": format, "content": content })),
+        )
+        .unwrap();
+        let error = render(&forge)
+            .err()
+            .unwrap_or_else(|| panic!("{format} should reject {content:?}"));
+        assert!(
+            matches!(error, pdforge::schemas::Error::InvalidBarcode { .. }),
+            "{error}"
+        );
+    }
+    // Formats with a mandatory check digit do not take the option.
+    let forge = forge("option", barcode(json!({ "checkDigit": true }))).unwrap();
+    assert!(render(&forge).is_err());
+}
+
+#[test]
+fn human_readable_text_is_printed_under_the_bars() {
+    let pdf = render(
+        &forge(
+            "text",
+            barcode(json!({
+                "format": "code128",
+                "content": "PDF-0001",
+                "humanReadable": true,
+                "fontName": "TestFont",
+                "fontSize": 8.0
+            })),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let ops = operations(&pdf);
+
+    assert!(ops.iter().any(|op| op.operator == "BT"));
+    // The bars start above the 1.2 × 8pt text line.
+    let bottom = bars(&ops)
+        .iter()
+        .map(|rect| rect[1])
+        .fold(f32::MAX, f32::min);
+    assert!((bottom - 9.6).abs() < 0.01, "{bottom}");
+
+    let no_font = forge("no-font", barcode(json!({ "humanReadable": true }))).unwrap();
+    assert!(render(&no_font).is_err());
+}
+
+#[test]
+fn a_caption_wider_than_the_symbol_is_shrunk_to_fit() {
+    // 10 + 112 + 10 modules of 0.2mm make a 26.4mm symbol, narrower than
+    // "PDF-0001" at 40pt.
+    let caption = |name: &str, font_size: f32| {
+        let pdf = render(
+            &forge(
+                name,
+                barcode(json!({
+                    "format": "code128",
+                    "content": "PDF-0001",
+                    "moduleWidth": 0.2,
+                    "humanReadable": true,
+                    "fontName": "TestFont",
+                    "fontSize": font_size
+                })),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let ops = operations(&pdf);
+        let size = ops.iter().find(|op| op.operator == "Tf").unwrap().operands[1]
+            .as_float()
+            .unwrap();
+        let x = ops.iter().find(|op| op.operator == "Tm").unwrap().operands[4]
+            .as_float()
+            .unwrap();
+        let bottom = bars(&ops)
+            .iter()
+            .map(|rect| rect[1])
+            .fold(f32::MAX, f32::min);
+        (size, x, bottom)
+    };
+
+    let (size, x, bottom) = caption("shrunk", 40.0);
+    assert!(size < 40.0, "{size}");
+    assert!(x.abs() < 0.01, "{x}");
+    // The bars make room for the smaller text line only.
+    assert!((bottom - size * 1.2).abs() < 0.01, "{bottom}");
+    // Any size too wide ends up at the size that spans the symbol.
+    let (larger, _, _) = caption("shrunk-larger", 80.0);
+    assert!((larger - size).abs() < 0.01, "{larger} != {size}");
+    // Text that fits keeps its size.
+    let (small, _, _) = caption("fits", 6.0);
+    assert_eq!(small, 6.0);
+}
+
+#[test]
+fn module_width_sizes_the_symbol_and_alignment_places_it() {
+    let pdf = render(
+        &forge(
+            "aligned",
+            barcode(json!({ "moduleWidth": 0.33, "alignment": "right" })),
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let bars = bars(&operations(&pdf));
+
+    // 95 modules of 0.33mm after the 11-module quiet zone, right-aligned in
+    // the 60mm box with the 7-module quiet zone after them.
+    let right = bars
+        .iter()
+        .map(|rect| rect[0] + rect[2])
+        .fold(f32::MIN, f32::max);
+    let expected = (11.0 + 95.0) * 0.33 / 25.4 * 72.0;
+    assert!((right - expected).abs() < 0.01, "{right} != {expected}");
+    let matrix = operations(&pdf)
+        .into_iter()
+        .find(|op| op.operator == "cm")
+        .unwrap();
+    let x = matrix.operands[4].as_float().unwrap();
+    assert!(
+        ((60.0 - 113.0 * 0.33 + 10.0) / 25.4 * 72.0 - x).abs() < 0.05,
+        "{x}"
+    );
+
+    // Bars wider than the box are an error.
+    let too_wide = forge("too-wide", barcode(json!({ "moduleWidth": 1.0 }))).unwrap();
+    assert!(render(&too_wide).is_err());
+}
+
+#[test]
+fn bars_keep_each_format_quiet_zone_clear() {
+    for (format, content, modules, (left, right)) in [
+        ("ean13", "4006381333931", 95.0, (11.0, 7.0)),
+        ("upcA", "036000291452", 95.0, (9.0, 9.0)),
+        ("code128", "PDF-0001", 112.0, (10.0, 10.0)),
+    ] {
+        let pdf = render(
+            &forge(
+                format,
+                barcode(json!({ "format": format, "content": content })),
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let bars = bars(&operations(&pdf));
+        let first = bars.iter().map(|rect| rect[0]).fold(f32::MAX, f32::min);
+        let last = bars
+            .iter()
+            .map(|rect| rect[0] + rect[2])
+            .fold(f32::MIN, f32::max);
+
+        // Filling the 60mm box, a module is 60mm / (quiet zones + bars).
+        let module = 60.0 / (left + modules + right) / 25.4 * 72.0;
+        assert!((first - left * module).abs() < 0.01, "{format}: {first}");
+        let end = 60.0 / 25.4 * 72.0 - right * module;
+        assert!((last - end).abs() < 0.01, "{format}: {last}");
+    }
+}
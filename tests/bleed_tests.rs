@@ -0,0 +1,214 @@
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Object};
+use pdforge::schemas::imposition::{LabelSheet, PageImposition};
+use pdforge::{PDForge, PDForgeBuilder};
+use printpdf::Mm;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn text_schema() -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": "body",
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    })
+}
+
+/// A 100 × 50mm certificate with the given extra `basePdf` settings.
+fn template(base_pdf: serde_json::Value) -> serde_json::Value {
+    let mut template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 50.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[text_schema()]]
+    });
+    for (key, value) in base_pdf.as_object().unwrap() {
+        template["basePdf"][key] = value.clone();
+    }
+    template
+}
+
+fn forge(name: &str, template: serde_json::Value) -> Result<PDForge, pdforge::schemas::Error> {
+    let path =
+        std::env::temp_dir().join(format!("pdforge-bleed-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    Ok(PDForgeBuilder::new("bleed".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build())
+}
+
+fn render(name: &str, base_pdf: serde_json::Value) -> Vec<u8> {
+    forge(name, template(base_pdf))
+        .expect("template should load")
+        .render("main", vec![vec![HashMap::new()]], None, None)
+        .expect("template should render")
+}
+
+fn first_page(doc: &Document) -> &Dictionary {
+    let page_id = *doc.get_pages().values().next().unwrap();
+    doc.get_dictionary(page_id).unwrap()
+}
+
+/// A page box in whole millimetres, or `None` when the page has none.
+fn page_box(pdf: &[u8], name: &str) -> Option<[i32; 4]> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    let rect = first_page(&doc)
+        .get(name.as_bytes())
+        .ok()?
+        .as_array()
+        .ok()?;
+    let mm = |object: &Object| (object.as_float().unwrap() * 25.4 / 72.0).round() as i32;
+    Some([mm(&rect[0]), mm(&rect[1]), mm(&rect[2]), mm(&rect[3])])
+}
+
+fn operators(pdf: &[u8]) -> Vec<String> {
+    let doc = Document::load_mem(pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations
+        .into_iter()
+        .map(|op| op.operator)
+        .collect()
+}
+
+#[test]
+fn a_bleed_enlarges_the_media_box_around_the_trim_box() {
+    let pdf = render("bleed", json!({ "bleed": 3 }));
+
+    assert_eq!(page_box(&pdf, "MediaBox"), Some([0, 0, 106, 56]));
+    assert_eq!(page_box(&pdf, "TrimBox"), Some([3, 3, 103, 53]));
+    assert_eq!(page_box(&pdf, "ArtBox"), Some([3, 3, 103, 53]));
+    assert_eq!(page_box(&pdf, "BleedBox"), Some([0, 0, 106, 56]));
+}
+
+#[test]
+fn a_page_without_bleed_is_trimmed_at_its_media_box() {
+    let pdf = render("no-bleed", json!({}));
+
+    assert_eq!(page_box(&pdf, "MediaBox"), Some([0, 0, 100, 50]));
+    assert_eq!(page_box(&pdf, "TrimBox"), Some([0, 0, 100, 50]));
+    assert_eq!(page_box(&pdf, "BleedBox"), None);
+}
+
+#[test]
+fn template_coordinates_stay_relative_to_the_trim() {
+    let pdf = render("coordinates", json!({ "bleed": 3 }));
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    let content = Content::decode(&doc.get_page_content(page_id)).unwrap();
+    let translations: Vec<(i32, i32)> = content
+        .operations
+        .iter()
+        .filter(|op| op.operator == "cm")
+        .map(|op| {
+            let mm = |index: usize| {
+                (op.operands[index].as_float().unwrap() * 25.4 / 72.0).round() as i32
+            };
+            (mm(4), mm(5))
+        })
+        .collect();
+
+    // The whole page moves in by the bleed; the text keeps its x = 10mm.
+    assert_eq!(translations.first(), Some(&(3, 3)));
+    assert!(translations.iter().skip(1).any(|&(x, _)| x == 10));
+}
+
+#[test]
+fn the_background_extends_into_the_bleed() {
+    let pdf = render(
+        "background",
+        json!({ "bleed": 3, "background": { "color": "#336699" } }),
+    );
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    let content = Content::decode(&doc.get_page_content(page_id)).unwrap();
+    let fill_origin = content
+        .operations
+        .iter()
+        .filter(|op| op.operator == "cm")
+        .nth(1)
+        .map(|op| (op.operands[4].as_float().unwrap() * 25.4 / 72.0).round() as i32);
+
+    assert_eq!(
+        fill_origin,
+        Some(-3),
+        "the fill starts 3mm outside the trim"
+    );
+}
+
+#[test]
+fn printer_marks_are_drawn_in_a_slug_outside_the_bleed() {
+    let pdf = render(
+        "marks",
+        json!({
+            "bleed": 3,
+            "printerMarks": { "crop": true, "registration": true, "colorBar": true }
+        }),
+    );
+
+    // 3mm offset + 5mm marks + 2mm gap on every side of the trim.
+    assert_eq!(page_box(&pdf, "MediaBox"), Some([0, 0, 120, 70]));
+    assert_eq!(page_box(&pdf, "TrimBox"), Some([10, 10, 110, 60]));
+    assert_eq!(page_box(&pdf, "BleedBox"), Some([7, 7, 113, 63]));
+
+    let ops = operators(&pdf);
+    // Marks stroke in registration CMYK; colour bar patches fill in CMYK.
+    assert!(ops.iter().any(|op| op == "K"));
+    assert!(ops.iter().filter(|op| op.as_str() == "k").count() >= 4);
+}
+
+#[test]
+fn imposition_places_pages_by_their_trim_box() {
+    let forge = forge("imposed", template(json!({ "bleed": 3 }))).unwrap();
+    let pdf = forge
+        .render_imposed(
+            &[
+                ("main", vec![vec![HashMap::new()]]),
+                ("main", vec![vec![HashMap::new()]]),
+            ],
+            None,
+            None,
+            &PageImposition::two_up(),
+        )
+        .expect("imposed document should render");
+
+    // Two 100mm trimmed pages side by side, not two 106mm bleed pages.
+    assert_eq!(page_box(&pdf, "MediaBox"), Some([0, 0, 200, 50]));
+}
+
+#[test]
+fn label_sheets_reject_a_bleed() {
+    let forge = forge("label", template(json!({ "bleed": 3 }))).unwrap();
+    let result = forge.render_label_sheet(
+        "main",
+        vec![HashMap::new()],
+        None,
+        None,
+        &LabelSheet::new(Mm(210.0), Mm(297.0), 2, 2),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn a_negative_bleed_is_rejected_when_loading() {
+    assert!(forge("negative", template(json!({ "bleed": -1 }))).is_err());
+}
@@ -0,0 +1,198 @@
+use lopdf::content::{Content, Operation};
+use lopdf::{Document, Object};
+use pdforge::schemas::imposition::LabelSheet;
+use pdforge::{PDForge, PDForgeBuilder};
+use printpdf::Mm;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn text(color: &str) -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": "body",
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": color
+    })
+}
+
+fn rectangle(color: &str, border_color: &str) -> serde_json::Value {
+    json!({
+        "type": "rectangle",
+        "name": "box",
+        "position": { "x": 10.0, "y": 30.0 },
+        "width": 40.0,
+        "height": 10.0,
+        "borderWidth": 1,
+        "borderColor": border_color,
+        "color": color
+    })
+}
+
+fn forge(
+    name: &str,
+    spot_colors: serde_json::Value,
+    schemas: serde_json::Value,
+) -> Result<PDForge, pdforge::schemas::Error> {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 100.0,
+            "height": 50.0,
+            "padding": [5.0, 5.0, 5.0, 5.0],
+            "spotColors": spot_colors
+        },
+        "schemas": [schemas]
+    });
+    let path =
+        std::env::temp_dir().join(format!("pdforge-color-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    Ok(PDForgeBuilder::new("color".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build())
+}
+
+fn gold() -> serde_json::Value {
+    json!({ "PANTONE 871 C": "cmyk(0%, 20%, 60%, 20%)" })
+}
+
+fn color_spaces(doc: &Document) -> &lopdf::Dictionary {
+    let page_id = *doc.get_pages().values().next().unwrap();
+    doc.get_dictionary(page_id)
+        .and_then(|page| page.get_deref(b"Resources", doc))
+        .and_then(Object::as_dict)
+        .and_then(|resources| resources.get_deref(b"ColorSpace", doc))
+        .and_then(Object::as_dict)
+        .expect("the page defines color spaces")
+}
+
+fn operations(doc: &Document) -> Vec<Operation> {
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations
+}
+
+fn operands(ops: &[Operation], operator: &str) -> Vec<Vec<f32>> {
+    ops.iter()
+        .filter(|op| op.operator == operator)
+        .map(|op| {
+            op.operands
+                .iter()
+                .map(|operand| operand.as_float().unwrap())
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn process_colors_use_their_own_device_operators() {
+    let pdf = forge(
+        "process",
+        json!({}),
+        json!([
+            text("cmyk(0%, 0%, 0%, 100%)"),
+            rectangle("gray(90%)", "#ff0000")
+        ]),
+    )
+    .unwrap()
+    .render("main", vec![vec![HashMap::new()]], None, None)
+    .expect("template should render");
+    let ops = operations(&Document::load_mem(&pdf).unwrap());
+
+    // Pure K black text, a DeviceGray fill and an RGB border.
+    assert!(operands(&ops, "k").contains(&vec![0.0, 0.0, 0.0, 1.0]));
+    assert_eq!(operands(&ops, "g"), vec![vec![0.9]]);
+    assert_eq!(operands(&ops, "RG"), vec![vec![1.0, 0.0, 0.0]]);
+}
+
+#[test]
+fn spot_colors_are_drawn_in_a_separation_color_space() {
+    let pdf = forge(
+        "spot",
+        gold(),
+        json!([
+            text("spot(PANTONE 871 C)"),
+            rectangle("spot(PANTONE 871 C, 30%)", "#000000")
+        ]),
+    )
+    .unwrap()
+    .render("main", vec![vec![HashMap::new()]], None, None)
+    .expect("template should render");
+    let doc = Document::load_mem(&pdf).unwrap();
+    let ops = operations(&doc);
+
+    let selected: Vec<&[u8]> = ops
+        .iter()
+        .filter(|op| op.operator == "cs")
+        .map(|op| op.operands[0].as_name().unwrap())
+        .collect();
+    assert_eq!(selected, vec![b"CS_PANTONE 871 C".as_slice(); 2]);
+    assert_eq!(operands(&ops, "scn"), vec![vec![1.0], vec![0.3]]);
+
+    let color_space = color_spaces(&doc)
+        .get(b"CS_PANTONE 871 C")
+        .and_then(Object::as_array)
+        .expect("the spot color is defined on the page");
+    assert_eq!(color_space[0].as_name().unwrap(), b"Separation");
+    assert_eq!(color_space[1].as_name().unwrap(), b"PANTONE 871 C");
+    assert_eq!(color_space[2].as_name().unwrap(), b"DeviceCMYK");
+}
+
+#[test]
+fn label_sheets_define_the_spot_colors_of_their_labels() {
+    let pdf = forge("label", gold(), json!([text("spot(PANTONE 871 C)")]))
+        .unwrap()
+        .render_label_sheet(
+            "main",
+            vec![HashMap::new()],
+            None,
+            None,
+            &LabelSheet::new(Mm(210.0), Mm(297.0), 2, 2),
+        )
+        .expect("label sheet should render");
+    let doc = Document::load_mem(&pdf).unwrap();
+
+    assert!(color_spaces(&doc).has(b"CS_PANTONE 871 C"));
+}
+
+#[test]
+fn an_undefined_spot_color_fails_to_render() {
+    let forge = forge("undefined", gold(), json!([text("spot(PANTONE 185 C)")])).unwrap();
+    assert!(forge
+        .render("main", vec![vec![HashMap::new()]], None, None)
+        .is_err());
+}
+
+#[test]
+fn malformed_colors_are_rejected() {
+    let forge_with_bad_cmyk =
+        forge("cmyk", json!({}), json!([text("cmyk(0, 91, 76, 0)")])).unwrap();
+    assert!(forge_with_bad_cmyk
+        .render("main", vec![vec![HashMap::new()]], None, None)
+        .is_err());
+    // Spot color definitions are checked when the template loads.
+    assert!(forge(
+        "definition",
+        json!({ "Gold": "spot(Silver)" }),
+        json!([text("#000")])
+    )
+    .is_err());
+}
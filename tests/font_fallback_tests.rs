@@ -0,0 +1,160 @@
+//! Tests for font fallback chains: a `fontName` array per schema and the
+//! builder's global `font_fallbacks`.
+
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::schemas::Error;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn render(
+    name: &str,
+    schema: serde_json::Value,
+    fallbacks: &[&str],
+) -> Result<Vec<Operation>, Error> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let noto = root
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf");
+    // Face 0 of the fixture only has a glyph for `A`.
+    let latin = root
+        .join("tests")
+        .join("fixtures")
+        .join("two-face-test.ttc");
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 100.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[schema]]
+    });
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-fallback-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    let pdf = PDForgeBuilder::new("fallback".to_string())
+        .add_font_from_file("Japanese", noto.to_str().unwrap())?
+        .add_font_from_file_with_index("Latin", latin.to_str().unwrap(), 0)?
+        .font_fallbacks(fallbacks)
+        .load_template("main", path.to_str().unwrap())?
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)?;
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Ok(Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations)
+}
+
+fn text(font_name: serde_json::Value, content: &str) -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": "customer",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 60.0,
+        "height": 20.0,
+        "content": content,
+        "fontName": font_name,
+        "fontSize": 10.0
+    })
+}
+
+/// Each shown string with the font it is drawn in, fonts numbered in order
+/// of first use since resource names differ between documents.
+fn runs(ops: &[Operation]) -> Vec<(usize, String)> {
+    let mut fonts: Vec<Vec<u8>> = Vec::new();
+    let mut font = 0;
+    let mut runs = Vec::new();
+    for op in ops {
+        match op.operator.as_str() {
+            "Tf" => {
+                let name = op.operands[0].as_name().unwrap().to_vec();
+                font = fonts.iter().position(|f| *f == name).unwrap_or_else(|| {
+                    fonts.push(name);
+                    fonts.len() - 1
+                });
+            }
+            "Tj" | "TJ" => runs.push((font, format!("{:?}", op.operands))),
+            _ => {}
+        }
+    }
+    runs
+}
+
+#[test]
+fn a_font_name_array_draws_missing_glyphs_in_the_next_font() {
+    let ops = render("array", text(json!(["Latin", "Japanese"]), "AB漢A"), &[]).unwrap();
+    let fonts: Vec<usize> = runs(&ops).iter().map(|(font, _)| *font).collect();
+
+    assert_eq!(fonts, [0, 1, 0]);
+    // The runs share one text object, so each continues where the last ended.
+    assert_eq!(ops.iter().filter(|op| op.operator == "BT").count(), 1);
+}
+
+#[test]
+fn global_fallbacks_follow_the_schema_fonts() {
+    let with_array = render("global-a", text(json!(["Latin", "Japanese"]), "AB漢A"), &[]).unwrap();
+    let with_global = render("global-b", text(json!("Latin"), "AB漢A"), &["Japanese"]).unwrap();
+
+    assert_eq!(runs(&with_global).len(), 3);
+    assert_eq!(runs(&with_array), runs(&with_global));
+}
+
+#[test]
+fn a_single_font_still_draws_one_run() {
+    let ops = render("single", text(json!("Japanese"), "AB漢A"), &[]).unwrap();
+    assert_eq!(runs(&ops).len(), 1);
+}
+
+#[test]
+fn every_font_in_the_chain_must_be_loaded() {
+    let error = render("missing", text(json!(["Latin", "Missing"]), "A"), &[]).unwrap_err();
+    assert!(error.to_string().contains("not loaded"), "{error}");
+}
+
+fn vertical(font_name: serde_json::Value, content: &str) -> serde_json::Value {
+    let mut schema = text(font_name, content);
+    schema["writingMode"] = json!("vertical-rl");
+    schema
+}
+
+#[test]
+fn vertical_writing_sets_each_cluster_in_the_font_that_has_it() {
+    let ops = render(
+        "vertical",
+        vertical(json!(["Latin", "Japanese"]), "A漢、"),
+        &[],
+    )
+    .unwrap();
+    let fonts: Vec<usize> = runs(&ops).iter().map(|(font, _)| *font).collect();
+    assert_eq!(fonts, [0, 1, 1]);
+
+    // `A` is turned in the Latin font; `漢` stands upright in the Japanese
+    // one, and `、` takes the Japanese font's `vert` glyph.
+    let matrices: Vec<Vec<f32>> = ops
+        .iter()
+        .filter(|op| op.operator == "Tm")
+        .map(|op| {
+            op.operands[..4]
+                .iter()
+                .map(|o| o.as_float().unwrap())
+                .collect()
+        })
+        .collect();
+    assert_eq!(matrices[0], [0.0, -1.0, 1.0, 0.0]);
+    assert_eq!(matrices[1], [1.0, 0.0, 0.0, 1.0]);
+    let single = render("vertical-single", vertical(json!("Japanese"), "、"), &[]).unwrap();
+    assert_eq!(runs(&ops)[2].1, runs(&single)[0].1);
+}
+
+#[test]
+fn vertical_ruby_falls_back_like_its_base() {
+    let mut schema = vertical(json!(["Latin", "Japanese"]), "{A|漢}");
+    schema["ruby"] = json!(true);
+    let ops = render("vertical-ruby", schema, &[]).unwrap();
+    let fonts: Vec<usize> = runs(&ops).iter().map(|(font, _)| *font).collect();
+    assert_eq!(fonts, [0, 1]);
+}
@@ -0,0 +1,154 @@
+//! Tests for font families: faces registered with `font_face` and picked by
+//! `fontFamily`, `fontWeight` and `fontStyle`, synthesized when missing.
+
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::font::{FontStyle, FontWeight};
+use pdforge::schemas::Error;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn render(name: &str, schema: serde_json::Value, bold_face: bool) -> Result<Vec<Operation>, Error> {
+    let fonts = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts");
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 100.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[schema]]
+    });
+    let path =
+        std::env::temp_dir().join(format!("pdforge-family-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+
+    let mut builder = PDForgeBuilder::new("family".to_string())
+        .add_font_from_file(
+            "Sans-Regular",
+            fonts.join("NotoSansJP-Regular.ttf").to_str().unwrap(),
+        )?
+        .add_font_from_file(
+            "Serif-Regular",
+            fonts.join("NotoSerifJP-Regular.ttf").to_str().unwrap(),
+        )?
+        .font_face(
+            "Sans",
+            FontWeight::NORMAL,
+            FontStyle::Normal,
+            "Sans-Regular",
+        );
+    // The serif face stands in for a real bold face of the family.
+    if bold_face {
+        builder = builder.font_face("Sans", FontWeight::BOLD, FontStyle::Normal, "Serif-Regular");
+    }
+    let pdf = builder
+        .load_template("main", path.to_str().unwrap())?
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)?;
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Ok(Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations)
+}
+
+fn text(font: serde_json::Value) -> serde_json::Value {
+    let mut schema = json!({
+        "type": "text",
+        "name": "title",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 60.0,
+        "height": 20.0,
+        "content": "請求書",
+        "fontSize": 12.0
+    });
+    schema
+        .as_object_mut()
+        .unwrap()
+        .extend(font.as_object().unwrap().clone());
+    schema
+}
+
+fn fonts_used(ops: &[Operation]) -> Vec<Vec<u8>> {
+    ops.iter()
+        .filter(|op| op.operator == "Tf")
+        .map(|op| op.operands[0].as_name().unwrap().to_vec())
+        .collect()
+}
+
+fn rendering_modes(ops: &[Operation]) -> Vec<i64> {
+    ops.iter()
+        .filter(|op| op.operator == "Tr")
+        .map(|op| op.operands[0].as_i64().unwrap())
+        .collect()
+}
+
+fn text_matrix(ops: &[Operation]) -> Vec<f32> {
+    let tm = ops.iter().find(|op| op.operator == "Tm").unwrap();
+    tm.operands.iter().map(|v| v.as_float().unwrap()).collect()
+}
+
+#[test]
+fn bold_without_a_bold_face_is_stroked() {
+    let ops = render(
+        "synthetic-bold",
+        text(json!({ "fontFamily": "Sans", "fontWeight": "bold" })),
+        false,
+    )
+    .unwrap();
+
+    // Fill, then stroke (mode 2) with a width proportional to the size.
+    assert_eq!(rendering_modes(&ops), [2]);
+    let width = ops.iter().find(|op| op.operator == "w").unwrap();
+    assert!((width.operands[0].as_float().unwrap() - 0.36).abs() < 1e-4);
+}
+
+#[test]
+fn bold_with_a_bold_face_draws_that_face() {
+    let regular = render("regular", text(json!({ "fontFamily": "Sans" })), true).unwrap();
+    let bold = render(
+        "bold-face",
+        text(json!({ "fontFamily": "Sans", "fontWeight": 700 })),
+        true,
+    )
+    .unwrap();
+
+    assert!(rendering_modes(&bold).is_empty());
+    assert_ne!(fonts_used(&regular), fonts_used(&bold));
+}
+
+#[test]
+fn italic_without_an_italic_face_skews_the_text_matrix() {
+    let upright = render("upright", text(json!({ "fontFamily": "Sans" })), false).unwrap();
+    let italic = render(
+        "italic",
+        text(json!({ "fontFamily": "Sans", "fontStyle": "italic" })),
+        false,
+    )
+    .unwrap();
+
+    assert_eq!(text_matrix(&upright)[2], 0.0);
+    assert!((text_matrix(&italic)[2] - 0.2126).abs() < 1e-4);
+}
+
+#[test]
+fn weight_applies_to_fonts_named_directly() {
+    let ops = render(
+        "named",
+        text(json!({ "fontName": "Sans-Regular", "fontWeight": 600 })),
+        false,
+    )
+    .unwrap();
+    assert_eq!(rendering_modes(&ops), [2]);
+}
+
+#[test]
+fn a_schema_must_name_a_font_or_family() {
+    let error = render("no-font", text(json!({})), false).unwrap_err();
+    assert!(error.to_string().contains("fontFamily"), "{error}");
+
+    let error = render("no-family", text(json!({ "fontFamily": "Mono" })), false).unwrap_err();
+    assert!(error.to_string().contains("not loaded"), "{error}");
+}
@@ -0,0 +1,141 @@
+//! Tests for GS1 element strings in the `barcode` (GS1-128) and `qrCode`
+//! (GS1 QR Code, GS1 DataMatrix and GS1 Digital Link) schemas.
+
+use pdforge::schemas::qrcode::JsonQrCodeSchema;
+use pdforge::schemas::{Error, Schema, SchemaTrait};
+use pdforge::utils::OpBuffer;
+use pdforge::PDForgeBuilder;
+use printpdf::{Mm, Op, PdfDocument};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const ELEMENT_STRING: &str = "(01)09501101530003(17)250101(10)ABC123";
+
+fn render_qr(options: serde_json::Value) -> Result<Vec<Op>, Error> {
+    let mut schema = json!({
+        "name": "code",
+        "content": ELEMENT_STRING,
+        "position": { "x": 10.0, "y": 20.0 },
+        "width": 40.0,
+        "height": 40.0
+    });
+    let fields = schema.as_object_mut().unwrap();
+    fields.extend(options.as_object().unwrap().clone());
+
+    let json_schema: JsonQrCodeSchema = serde_json::from_value(schema).unwrap();
+    let schema: Schema = json_schema.into();
+    let mut doc = PdfDocument::new("gs1_test");
+    let mut buffer = OpBuffer::default();
+    schema.render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)?;
+    Ok(buffer.buffer[0].clone())
+}
+
+/// The drawing as text: printpdf's `Point` never equals one on an axis, so
+/// ops are compared through their debug form.
+fn drawing(options: serde_json::Value) -> String {
+    format!("{:?}", render_qr(options).unwrap())
+}
+
+fn render_barcode(content: &str) -> Result<Vec<u8>, Error> {
+    let font = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf");
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 120.0, "height": 50.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[{
+            "type": "barcode",
+            "name": "code",
+            "position": { "x": 10.0, "y": 10.0 },
+            "width": 100.0,
+            "height": 25.0,
+            "format": "gs1128",
+            "content": content,
+            "humanReadable": true,
+            "fontName": "TestFont"
+        }]]
+    });
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-gs1-{}-{}.json",
+        content.len(),
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    let forge = PDForgeBuilder::new("gs1".to_string())
+        .add_font_from_file("TestFont", font.to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build();
+    forge.render("main", vec![vec![HashMap::new()]], None, None)
+}
+
+#[test]
+fn gs1_128_barcodes_validate_their_element_string() {
+    assert!(render_barcode(ELEMENT_STRING).is_ok());
+
+    for content in [
+        // Wrong GTIN check digit, unknown AI, no AI brackets.
+        "(01)09501101530004(10)ABC123",
+        "(01)09501101530003(999)1",
+        "0109501101530003",
+    ] {
+        let error = render_barcode(content).unwrap_err();
+        assert!(
+            matches!(error, Error::InvalidGs1 { .. }),
+            "{content}: {error}"
+        );
+    }
+}
+
+#[test]
+fn digital_link_encodes_the_uri() {
+    assert_eq!(
+        drawing(json!({ "gs1": "digitalLink" })),
+        drawing(json!({
+            "content": "https://id.gs1.org/01/09501101530003/10/ABC123?17=250101"
+        }))
+    );
+    assert_eq!(
+        drawing(json!({
+            "gs1": "digitalLink",
+            "digitalLinkDomain": "https://example.com/"
+        })),
+        drawing(json!({
+            "content": "https://example.com/01/09501101530003/10/ABC123?17=250101"
+        }))
+    );
+}
+
+#[test]
+fn element_strings_are_marked_with_fnc1() {
+    // FNC1 first position mode changes the data, so the symbol differs
+    // from one holding the same characters as plain text.
+    assert_ne!(
+        drawing(json!({ "gs1": "elementString" })),
+        drawing(json!({ "content": "010950110153000317250101\u{1d}10ABC123" }))
+    );
+
+    assert!(render_qr(json!({ "gs1": "elementString", "symbology": "dataMatrix" })).is_ok());
+    for symbology in ["microQr", "aztec"] {
+        let error =
+            render_qr(json!({ "gs1": "elementString", "symbology": symbology })).unwrap_err();
+        assert!(
+            matches!(error, Error::SymbolEncoding { .. }),
+            "{symbology}: {error}"
+        );
+    }
+}
+
+#[test]
+fn invalid_gs1_options_are_rejected() {
+    let error = render_qr(json!({ "gs1": "elementString", "content": "(17)251301" })).unwrap_err();
+    assert!(matches!(error, Error::InvalidGs1 { .. }), "{error}");
+
+    // A Digital Link needs a primary key such as the GTIN.
+    let error = render_qr(json!({ "gs1": "digitalLink", "content": "(10)ABC" })).unwrap_err();
+    assert!(matches!(error, Error::InvalidGs1 { .. }), "{error}");
+
+    let error = render_qr(json!({ "digitalLinkDomain": "https://example.com" })).unwrap_err();
+    assert!(matches!(error, Error::InvalidGs1 { .. }), "{error}");
+}
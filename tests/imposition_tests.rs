@@ -0,0 +1,247 @@
+use lopdf::{Dictionary, Document, Object};
+use pdforge::schemas::imposition::PageImposition;
+use pdforge::{PDForge, PDForgeBuilder};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn text_schema(content: &str) -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": content,
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    })
+}
+
+/// A5 pages whose heights are 200mm + page index, so each source page can be
+/// recognised by its Form XObject's BBox after imposition.
+fn numbered_template(pages: usize) -> serde_json::Value {
+    let schemas: Vec<serde_json::Value> = (0..pages)
+        .map(|index| {
+            json!({
+                "basePdf": { "height": 200 + index },
+                "schemas": [text_schema(&format!("page {}", index + 1))]
+            })
+        })
+        .collect();
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 148, "height": 210, "padding": [10, 10, 10, 10] },
+        "schemas": schemas
+    })
+}
+
+fn chapter_template(static_content: &str) -> serde_json::Value {
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "size": "A5",
+            "padding": [10, 10, 10, 10],
+            "staticSchema": [text_schema(static_content)]
+        },
+        "schemas": [[text_schema("chapter")]]
+    })
+}
+
+fn forge(templates: &[(&str, serde_json::Value)]) -> PDForge {
+    let mut builder = PDForgeBuilder::new("imposition".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())
+        .expect("test font should load");
+    for (name, template) in templates {
+        let path = std::env::temp_dir().join(format!(
+            "pdforge-imposition-{name}-{}.json",
+            std::process::id()
+        ));
+        std::fs::write(&path, template.to_string()).expect("template should be writable");
+        builder = builder
+            .load_template(name, path.to_str().unwrap())
+            .expect("template should load");
+    }
+    builder.build()
+}
+
+fn one_page_inputs(pages: usize) -> Vec<Vec<HashMap<&'static str, String>>> {
+    vec![vec![HashMap::new()]; pages]
+}
+
+fn to_mm(points: f32) -> f32 {
+    points * 25.4 / 72.0
+}
+
+fn number(object: &Object) -> f32 {
+    object.as_float().expect("number")
+}
+
+fn media_box(dict: &Dictionary) -> (f32, f32) {
+    let media_box = dict.get(b"MediaBox").and_then(Object::as_array).unwrap();
+    (number(&media_box[2]), number(&media_box[3]))
+}
+
+/// Source page numbers (1-based) placed on each sheet side, in slot order.
+fn placed_pages(pdf: &[u8]) -> Vec<Vec<usize>> {
+    let doc = Document::load_mem(pdf).expect("imposed PDF should parse");
+    doc.get_pages()
+        .into_values()
+        .map(|page_id| {
+            let page = doc.get_dictionary(page_id).unwrap();
+            let resources = page.get(b"Resources").and_then(Object::as_dict).unwrap();
+            let xobjects = resources.get(b"XObject").and_then(Object::as_dict).unwrap();
+            let mut slots: Vec<(String, usize)> = xobjects
+                .iter()
+                .map(|(name, reference)| {
+                    let form = doc
+                        .get_object(reference.as_reference().unwrap())
+                        .and_then(Object::as_stream)
+                        .unwrap();
+                    let bbox = form.dict.get(b"BBox").and_then(Object::as_array).unwrap();
+                    let height_mm = to_mm(number(&bbox[3])).round() as usize;
+                    (String::from_utf8_lossy(name).into_owned(), height_mm - 199)
+                })
+                .collect();
+            slots.sort();
+            slots.into_iter().map(|(_, page)| page).collect()
+        })
+        .collect()
+}
+
+#[test]
+fn booklet_folds_pages_into_reading_order() {
+    let forge = forge(&[("numbered", numbered_template(8))]);
+
+    let pdf = forge
+        .render_imposed(
+            &[("numbered", one_page_inputs(8))],
+            None,
+            None,
+            &PageImposition::booklet(),
+        )
+        .expect("booklet should render");
+
+    // Without a sheet size the sheet is two cells the size of the first page.
+    let doc = Document::load_mem(&pdf).unwrap();
+    for page_id in doc.get_pages().into_values() {
+        let (width, height) = media_box(doc.get_dictionary(page_id).unwrap());
+        assert!(
+            (to_mm(width) - 296.0).abs() < 0.1,
+            "two 148mm pages side by side"
+        );
+        assert!((to_mm(height) - 200.0).abs() < 0.1);
+    }
+    assert_eq!(
+        placed_pages(&pdf),
+        vec![vec![8, 1], vec![2, 7], vec![6, 3], vec![4, 5]]
+    );
+}
+
+#[test]
+fn booklet_pads_the_page_count_to_a_multiple_of_four() {
+    let forge = forge(&[("numbered", numbered_template(5))]);
+
+    let pdf = forge
+        .render_imposed(
+            &[("numbered", one_page_inputs(5))],
+            None,
+            None,
+            &PageImposition::booklet(),
+        )
+        .expect("booklet should render");
+
+    // Five pages become eight: two sheets, four sides.
+    let placed = placed_pages(&pdf);
+    assert_eq!(placed.len(), 4);
+    assert_eq!(placed[0][1], 1);
+    assert_eq!(placed[3], vec![4, 5]);
+}
+
+#[test]
+fn four_up_fills_sheets_in_reading_order() {
+    let forge = forge(&[("numbered", numbered_template(5))]);
+
+    let pdf = forge
+        .render_imposed(
+            &[("numbered", one_page_inputs(5))],
+            None,
+            None,
+            &PageImposition::four_up().with_paper_size("A4").unwrap(),
+        )
+        .expect("4-up should render");
+
+    assert_eq!(placed_pages(&pdf), vec![vec![1, 2, 3, 4], vec![5]]);
+    let doc = Document::load_mem(&pdf).unwrap();
+    let first = doc.get_pages()[&1];
+    let (width, height) = media_box(doc.get_dictionary(first).unwrap());
+    assert!((to_mm(width) - 210.0).abs() < 0.1 && (to_mm(height) - 297.0).abs() < 0.1);
+}
+
+#[test]
+fn chapters_start_on_recto_and_blank_pages_are_numbered() {
+    // `missing` is never defined, so Tera fails the render if a guard is
+    // reached: the second chapter must be page 3 of 4 after one blank verso
+    // and one page of end padding.
+    let forge = forge(&[
+        (
+            "first",
+            chapter_template(
+                "{% if totalPages != 4 or currentPage != 1 %}{{ missing }}{% endif %}",
+            ),
+        ),
+        (
+            "second",
+            chapter_template(
+                "{% if totalPages != 4 or currentPage != 3 %}{{ missing }}{% endif %}",
+            ),
+        ),
+    ]);
+
+    let pdf = forge
+        .render_imposed(
+            &[
+                ("first", one_page_inputs(1)),
+                ("second", one_page_inputs(1)),
+            ],
+            None,
+            None,
+            &PageImposition::new(pdforge::schemas::imposition::ImpositionLayout::Single)
+                .with_chapters_on_recto(true)
+                .with_pad_to_multiple(4),
+        )
+        .expect("chapters should render");
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    assert_eq!(doc.get_pages().len(), 4);
+}
+
+#[test]
+fn invalid_impositions_are_rejected() {
+    let forge = forge(&[("numbered", numbered_template(1))]);
+
+    for imposition in [
+        PageImposition::two_up().with_pad_to_multiple(0),
+        PageImposition::new(pdforge::schemas::imposition::ImpositionLayout::NUp {
+            columns: 0,
+            rows: 2,
+        }),
+    ] {
+        let error = forge
+            .render_imposed(&[("numbered", one_page_inputs(1))], None, None, &imposition)
+            .expect_err("imposition should be rejected");
+        assert!(error.to_string().contains("Invalid imposition"), "{error}");
+    }
+}
@@ -0,0 +1,162 @@
+use lopdf::content::Content;
+use lopdf::{Document, Object};
+use pdforge::schemas::imposition::LabelSheet;
+use pdforge::{PDForge, PDForgeBuilder};
+use printpdf::Mm;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn label_template(pages: usize) -> serde_json::Value {
+    let page = json!([{
+        "type": "text",
+        "name": "code",
+        "position": { "x": 5.0, "y": 5.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": "{{ code }}",
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    }]);
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 90, "height": 50, "padding": [0, 0, 0, 0] },
+        "schemas": vec![page; pages]
+    })
+}
+
+fn forge(name: &str, template: serde_json::Value) -> PDForge {
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-label-sheet-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    PDForgeBuilder::new("labels".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())
+        .expect("test font should load")
+        .load_template("label", path.to_str().unwrap())
+        .expect("template should load")
+        .build()
+}
+
+fn records(count: usize) -> Vec<HashMap<&'static str, String>> {
+    (0..count)
+        .map(|index| HashMap::from([("code", format!("TAG-{index:03}"))]))
+        .collect()
+}
+
+fn pages(pdf: &[u8]) -> (Document, Vec<lopdf::ObjectId>) {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    let ids = doc.get_pages().into_values().collect();
+    (doc, ids)
+}
+
+fn media_box(doc: &Document, page: lopdf::ObjectId) -> (f32, f32) {
+    let media_box = doc
+        .get_dictionary(page)
+        .unwrap()
+        .get(b"MediaBox")
+        .and_then(Object::as_array)
+        .unwrap();
+    (
+        media_box[2].as_float().unwrap(),
+        media_box[3].as_float().unwrap(),
+    )
+}
+
+fn operator_count(doc: &Document, page: lopdf::ObjectId, operator: &str) -> usize {
+    let content = Content::decode(&doc.get_page_content(page)).unwrap();
+    content
+        .operations
+        .iter()
+        .filter(|operation| operation.operator == operator)
+        .count()
+}
+
+#[test]
+fn records_fill_a4_sheets_in_order() {
+    let forge = forge("fill", label_template(1));
+    let sheet = LabelSheet::with_paper_size("A4", 5, 2).unwrap();
+
+    let pdf = forge
+        .render_label_sheet("label", records(12), None, None, &sheet)
+        .expect("labels should render");
+
+    let (doc, ids) = pages(&pdf);
+    assert_eq!(ids.len(), 2, "12 labels need two 10-up sheets");
+    for id in &ids {
+        let (width, height) = media_box(&doc, *id);
+        assert!((width - 595.27).abs() < 0.5 && (height - 841.89).abs() < 0.5);
+    }
+    // Ten labels on the first sheet, the remaining two on the second.
+    assert_eq!(operator_count(&doc, ids[0], "BT"), 10);
+    assert_eq!(operator_count(&doc, ids[1], "BT"), 2);
+}
+
+#[test]
+fn start_offset_skips_used_cells_on_the_first_sheet() {
+    let forge = forge("offset", label_template(1));
+    let sheet = LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2).with_start_offset(9);
+
+    let pdf = forge
+        .render_label_sheet("label", records(3), None, None, &sheet)
+        .expect("labels should render");
+
+    let (doc, ids) = pages(&pdf);
+    assert_eq!(ids.len(), 2);
+    assert_eq!(operator_count(&doc, ids[0], "BT"), 1);
+    assert_eq!(operator_count(&doc, ids[1], "BT"), 2);
+}
+
+#[test]
+fn crop_marks_are_drawn_on_every_sheet() {
+    let forge = forge("marks", label_template(1));
+    let plain = LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2);
+    let marked = plain.clone().with_crop_marks(true);
+
+    let (doc, ids) = pages(
+        &forge
+            .render_label_sheet("label", records(1), None, None, &plain)
+            .unwrap(),
+    );
+    assert_eq!(operator_count(&doc, ids[0], "S"), 0);
+
+    let (doc, ids) = pages(
+        &forge
+            .render_label_sheet("label", records(11), None, None, &marked)
+            .unwrap(),
+    );
+    // Three vertical and six horizontal cut lines, marked at both ends.
+    for id in ids {
+        assert_eq!(operator_count(&doc, id, "S"), 18);
+    }
+}
+
+#[test]
+fn multi_page_templates_and_oversized_grids_are_rejected() {
+    let forge_two_pages = forge("two-pages", label_template(2));
+    let sheet = LabelSheet::new(Mm(210.0), Mm(297.0), 5, 2);
+    let error = forge_two_pages
+        .render_label_sheet("label", records(2), None, None, &sheet)
+        .expect_err("a label template must be one page");
+    assert!(error.to_string().contains("exactly one page"), "{error}");
+
+    let forge = forge("too-big", label_template(1));
+    let too_many_columns = LabelSheet::new(Mm(210.0), Mm(297.0), 5, 3);
+    let error = forge
+        .render_label_sheet("label", records(2), None, None, &too_many_columns)
+        .expect_err("three 90mm columns do not fit on A4");
+    assert!(error.to_string().contains("does not fit"), "{error}");
+}
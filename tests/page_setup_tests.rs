@@ -0,0 +1,364 @@
+use lopdf::{Document, Object};
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn render(name: &str, template: serde_json::Value, pages: usize) -> Vec<u8> {
+    try_render(name, template, pages).expect("template should render")
+}
+
+fn try_render(
+    name: &str,
+    template: serde_json::Value,
+    pages: usize,
+) -> Result<Vec<u8>, pdforge::schemas::Error> {
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-page-setup-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+
+    let forge = PDForgeBuilder::new("page-setup".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build();
+
+    forge.render("main", vec![vec![HashMap::new()]; pages], None, None)
+}
+
+fn media_boxes(pdf: &[u8]) -> Vec<(f32, f32)> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            let page = doc.get_dictionary(*page_id).expect("page should exist");
+            let media_box = page
+                .get(b"MediaBox")
+                .and_then(Object::as_array)
+                .expect("page should have a MediaBox");
+            let number = |object: &Object| object.as_float().expect("MediaBox entry");
+            (number(&media_box[2]), number(&media_box[3]))
+        })
+        .collect()
+}
+
+fn mm(points: f32) -> f32 {
+    points * 25.4 / 72.0
+}
+
+fn assert_size(actual: (f32, f32), width_mm: f32, height_mm: f32) {
+    assert!(
+        (mm(actual.0) - width_mm).abs() < 0.1 && (mm(actual.1) - height_mm).abs() < 0.1,
+        "expected {width_mm}x{height_mm}mm, got {:.2}x{:.2}mm",
+        mm(actual.0),
+        mm(actual.1)
+    );
+}
+
+fn dynamic_text(content: &str) -> serde_json::Value {
+    json!({
+        "type": "dynamicText",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 100.0,
+        "height": 10.0,
+        "content": content,
+        "fontName": "TestFont",
+        "fontSize": 10.0
+    })
+}
+
+fn base_pdf() -> serde_json::Value {
+    json!({
+        "width": 210.0,
+        "height": 297.0,
+        "padding": [10.0, 10.0, 10.0, 10.0],
+        "staticSchema": []
+    })
+}
+
+#[test]
+fn a_page_can_override_the_template_page_size() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf(),
+        "schemas": [
+            [dynamic_text("portrait")],
+            {
+                "basePdf": { "width": 297.0, "height": 210.0 },
+                "schemas": [dynamic_text("landscape")]
+            }
+        ]
+    });
+
+    let sizes = media_boxes(&render("override", template, 2));
+
+    assert_eq!(sizes.len(), 2);
+    assert_size(sizes[0], 210.0, 297.0);
+    assert_size(sizes[1], 297.0, 210.0);
+}
+
+#[test]
+fn continuation_pages_inherit_the_overriding_page_size() {
+    let long_text = "line\n".repeat(120);
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf(),
+        "schemas": [{
+            "basePdf": { "width": 148.0, "height": 105.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+            "schemas": [dynamic_text(&long_text)]
+        }]
+    });
+
+    let sizes = media_boxes(&render("continuation", template, 1));
+
+    assert!(sizes.len() > 1, "long text should overflow: {sizes:?}");
+    for size in sizes {
+        assert_size(size, 148.0, 105.0);
+    }
+}
+
+#[test]
+fn an_invalid_page_override_is_rejected_when_loading() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf(),
+        "schemas": [{
+            "basePdf": { "padding": [1.0, 2.0] },
+            "schemas": [dynamic_text("x")]
+        }]
+    });
+
+    assert!(try_render("bad-override", template, 1).is_err());
+}
+
+#[test]
+fn named_sizes_and_orientation_resolve_page_dimensions() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "size": "A5",
+            "orientation": "landscape",
+            "padding": ["10mm", "1cm", "0.5in", "36pt"],
+            "staticSchema": []
+        },
+        "schemas": [
+            [dynamic_text("landscape A5")],
+            {
+                "basePdf": { "size": "JIS-B5" },
+                "schemas": [dynamic_text("portrait JIS B5")]
+            },
+            {
+                "basePdf": { "orientation": "portrait" },
+                "schemas": [dynamic_text("template size turned upright")]
+            }
+        ]
+    });
+
+    let sizes = media_boxes(&render("named-size", template, 3));
+
+    assert_size(sizes[0], 210.0, 148.0);
+    assert_size(sizes[1], 182.0, 257.0);
+    assert_size(sizes[2], 148.0, 210.0);
+}
+
+#[test]
+fn receipt_rolls_take_their_length_from_height() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "size": "Receipt-80mm",
+            "height": "8in",
+            "padding": [5, 5, 5, 5]
+        },
+        "schemas": [[dynamic_text("receipt")]]
+    });
+
+    let sizes = media_boxes(&render("receipt-roll", template, 1));
+
+    assert_size(sizes[0], 80.0, 203.2);
+}
+
+#[test]
+fn schema_positions_and_sizes_accept_unit_strings() {
+    let mut text = dynamic_text("units");
+    text["position"] = json!({ "x": "1cm", "y": "72pt" });
+    text["width"] = json!("300px@300dpi");
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf(),
+        "schemas": [[text]]
+    });
+
+    assert!(try_render("units", template, 1).is_ok());
+}
+
+#[test]
+fn unknown_sizes_and_bad_lengths_are_rejected() {
+    let unknown = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "size": "A42", "padding": [0, 0, 0, 0] },
+        "schemas": [[dynamic_text("x")]]
+    });
+    let error = try_render("unknown-size", unknown, 1).expect_err("A42 is not a size");
+    assert!(error.to_string().contains("A42"), "{error}");
+
+    let mut text = dynamic_text("x");
+    text["width"] = json!("12 furlongs");
+    let bad_length = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf(),
+        "schemas": [[text]]
+    });
+    assert!(try_render("bad-length", bad_length, 1).is_err());
+}
+
+fn receipt(base_pdf: serde_json::Value, content: &str) -> serde_json::Value {
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": base_pdf,
+        "schemas": [[dynamic_text(content)]]
+    })
+}
+
+#[test]
+fn auto_height_pages_end_below_their_content() {
+    let base = json!({ "size": "Receipt-80mm", "height": "auto", "padding": [5, 5, 5, 5] });
+    let short = media_boxes(&render("auto-short", receipt(base.clone(), "one line"), 1));
+    let long = media_boxes(&render(
+        "auto-long",
+        receipt(base, &"line\n".repeat(200)),
+        1,
+    ));
+
+    assert_eq!(short.len(), 1);
+    assert_eq!(long.len(), 1, "auto height must not paginate: {long:?}");
+    assert_eq!(mm(short[0].0).round(), 80.0);
+    // One 10pt line starting at y=10mm, plus 5mm bottom padding.
+    let line_mm = 10.0 * 25.4 / 72.0;
+    assert!(
+        (mm(short[0].1) - (10.0 + line_mm + 5.0)).abs() < 0.1,
+        "{short:?}"
+    );
+    assert!(
+        (mm(long[0].1) - (10.0 + 200.0 * line_mm + 5.0)).abs() < 0.5,
+        "{long:?}"
+    );
+}
+
+#[test]
+fn auto_height_paginates_once_max_height_is_reached() {
+    let template = receipt(
+        json!({ "width": 58, "height": "AUTO", "maxHeight": "100mm", "padding": [5, 5, 5, 5] }),
+        &"line\n".repeat(60),
+    );
+
+    let sizes = media_boxes(&render("auto-max", template, 1));
+
+    assert!(
+        sizes.len() > 1,
+        "content taller than maxHeight should paginate"
+    );
+    assert_size(sizes[0], 58.0, 100.0);
+    let last = *sizes.last().unwrap();
+    assert!(
+        mm(last.1) < 100.0,
+        "the last page shrinks to its content: {sizes:?}"
+    );
+}
+
+#[test]
+fn max_height_without_auto_or_auto_in_landscape_is_rejected() {
+    let fixed = receipt(
+        json!({ "width": 58, "height": 200, "maxHeight": 300, "padding": [0, 0, 0, 0] }),
+        "x",
+    );
+    let error = try_render("max-without-auto", fixed, 1).expect_err("maxHeight needs auto");
+    assert!(error.to_string().contains("maxHeight"), "{error}");
+
+    let landscape = receipt(
+        json!({
+            "size": "Receipt-58mm",
+            "height": "auto",
+            "orientation": "landscape",
+            "padding": [0, 0, 0, 0]
+        }),
+        "x",
+    );
+    assert!(try_render("auto-landscape", landscape, 1).is_err());
+}
+
+fn page_rotations(pdf: &[u8]) -> Vec<Option<i64>> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            let page = doc.get_dictionary(*page_id).expect("page should exist");
+            page.get(b"Rotate").and_then(Object::as_i64).ok()
+        })
+        .collect()
+}
+
+#[test]
+fn rotate_sets_the_page_rotation_and_keeps_the_designed_size() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100, "height": 50, "padding": [5, 5, 5, 5], "rotate": 90 },
+        "schemas": [
+            [dynamic_text("rotated")],
+            {
+                "basePdf": { "rotate": 0 },
+                "schemas": [dynamic_text("upright")]
+            }
+        ]
+    });
+
+    let pdf = render("rotate-page", template, 2);
+
+    assert_eq!(page_rotations(&pdf), vec![Some(90), None]);
+    let sizes = media_boxes(&pdf);
+    assert_size(sizes[0], 100.0, 50.0);
+    assert_size(sizes[1], 100.0, 50.0);
+}
+
+#[test]
+fn content_rotation_swaps_the_page_size_instead() {
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 100,
+            "height": 50,
+            "padding": [5, 5, 5, 5],
+            "rotate": 270,
+            "rotateMode": "content"
+        },
+        "schemas": [[dynamic_text("printer orientation")]]
+    });
+
+    let pdf = render("rotate-content", template, 1);
+
+    assert_eq!(page_rotations(&pdf), vec![None]);
+    assert_size(media_boxes(&pdf)[0], 50.0, 100.0);
+}
+
+#[test]
+fn rotate_must_be_a_quarter_turn() {
+    let mut base = base_pdf();
+    base["rotate"] = json!(45);
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": base,
+        "schemas": [[dynamic_text("x")]]
+    });
+
+    let error = try_render("rotate-45", template, 1).expect_err("45° is not supported");
+    assert!(error.to_string().contains("parse"), "{error}");
+}
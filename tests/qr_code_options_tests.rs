@@ -0,0 +1,140 @@
+//! Tests for the QR code error correction, version, quiet zone and color
+//! options, and for the vector output they are drawn with.
+
+use pdforge::schemas::qrcode::JsonQrCodeSchema;
+use pdforge::schemas::{Error, Schema, SchemaTrait};
+use pdforge::utils::OpBuffer;
+use printpdf::{Color, Mm, Op, PdfDocument, Polygon, Rgb};
+use serde_json::json;
+
+fn render(options: serde_json::Value) -> Result<Vec<Op>, Error> {
+    let mut schema = json!({
+        "name": "code",
+        "content": "PDFORGE",
+        "position": { "x": 10.0, "y": 20.0 },
+        "width": 40.0,
+        "height": 40.0
+    });
+    let fields = schema.as_object_mut().unwrap();
+    fields.extend(options.as_object().unwrap().clone());
+
+    let json_schema: JsonQrCodeSchema = serde_json::from_value(schema).unwrap();
+    let schema: Schema = json_schema.into();
+    let mut doc = PdfDocument::new("qr_options_test");
+    let mut buffer = OpBuffer::default();
+    schema.render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)?;
+    Ok(buffer.buffer[0].clone())
+}
+
+fn polygons(ops: &[Op]) -> Vec<&Polygon> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Op::DrawPolygon { polygon } => Some(polygon),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Modules across the symbol, quiet zone included: the background side
+/// divided by the height of a module row.
+fn modules_across(ops: &[Op]) -> usize {
+    let polygons = polygons(ops);
+    let side = polygons[0].rings[0].points[2].p.x.0;
+    let ring = &polygons[1].rings[0];
+    let module = ring.points[2].p.y.0 - ring.points[1].p.y.0;
+    (side / module).round() as usize
+}
+
+fn fill_colors(ops: &[Op]) -> Vec<&Color> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Op::SetFillColor { col } => Some(col),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn modules_are_drawn_as_vector_paths_not_images() {
+    let ops = render(json!({})).unwrap();
+
+    assert!(!ops.iter().any(|op| matches!(op, Op::UseXobject { .. })));
+    // A white background square, then every dark module in one black path.
+    assert_eq!(polygons(&ops).len(), 2);
+    assert_eq!(
+        fill_colors(&ops),
+        vec![
+            &Color::Rgb(Rgb::new(1.0, 1.0, 1.0, None)),
+            &Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None))
+        ]
+    );
+}
+
+#[test]
+fn higher_error_correction_needs_a_larger_symbol() {
+    let content = "https://example.com/tags/0123456789";
+    let low = render(json!({ "content": content, "errorCorrectionLevel": "L" })).unwrap();
+    let high = render(json!({ "content": content, "errorCorrectionLevel": "H" })).unwrap();
+
+    // 2-L holds the URL while H needs version 4; 4 quiet-zone modules each side.
+    assert_eq!(modules_across(&low), 25 + 8);
+    assert_eq!(modules_across(&high), 33 + 8);
+}
+
+#[test]
+fn version_sets_the_smallest_symbol_size() {
+    let ops = render(json!({ "version": 5 })).unwrap();
+    assert_eq!(modules_across(&ops), 37 + 8);
+
+    let error = render(json!({ "version": 41 })).unwrap_err();
+    assert!(matches!(error, Error::SymbolEncoding { .. }), "{error}");
+}
+
+#[test]
+fn quiet_zone_overrides_the_symbology_default() {
+    let ops = render(json!({ "quietZone": 0 })).unwrap();
+    assert_eq!(modules_across(&ops), 21);
+
+    let ops = render(json!({ "quietZone": 10 })).unwrap();
+    assert_eq!(modules_across(&ops), 21 + 20);
+}
+
+#[test]
+fn module_and_background_colors_are_applied() {
+    let ops = render(json!({ "color": "#ff0000", "backgroundColor": "#00ff00" })).unwrap();
+    assert_eq!(
+        fill_colors(&ops),
+        vec![
+            &Color::Rgb(Rgb::new(0.0, 1.0, 0.0, None)),
+            &Color::Rgb(Rgb::new(1.0, 0.0, 0.0, None))
+        ]
+    );
+}
+
+#[test]
+fn transparent_background_is_not_painted() {
+    let ops = render(json!({ "backgroundColor": "transparent" })).unwrap();
+    assert_eq!(polygons(&ops).len(), 1);
+    assert_eq!(fill_colors(&ops).len(), 1);
+}
+
+#[test]
+fn invalid_colors_are_rejected() {
+    let error = render(json!({ "color": "not-a-color" })).unwrap_err();
+    assert!(matches!(error, Error::InvalidColor { .. }), "{error}");
+}
+
+#[test]
+fn qr_options_are_rejected_for_other_symbologies() {
+    for symbology in ["dataMatrix", "aztec"] {
+        let error =
+            render(json!({ "symbology": symbology, "errorCorrectionLevel": "H" })).unwrap_err();
+        assert!(
+            matches!(error, Error::SymbolEncoding { .. }),
+            "{symbology}: {error}"
+        );
+    }
+    // Micro QR takes the options but has no level H.
+    let error = render(json!({ "symbology": "microQr", "errorCorrectionLevel": "H" })).unwrap_err();
+    assert!(matches!(error, Error::SymbolEncoding { .. }), "{error}");
+}
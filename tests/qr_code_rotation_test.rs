@@ -2,22 +2,43 @@
 //!
 //! Verifies that `BaseSchema::get_matrix()` retains its original public
 //! signature, that `get_matrix_with_rotation()` accepts an optional
-//! `XObjectRotation`, and that `QrCode::render()` produces a `UseXobject`
-//! transform with the rotation centered on the QR image's intrinsic size.
+//! `XObjectRotation`, and that `QrCode::render()` draws the symbol inside a
+//! transformation matrix rotated about the symbol's centre.
 
 use pdforge::schemas::base::BaseSchema;
 use pdforge::schemas::qrcode::{JsonQrCodeSchema, QrCode};
 use pdforge::schemas::{Schema, SchemaTrait};
 use pdforge::utils::OpBuffer;
-use printpdf::{CurTransMat, Mm, Op, PdfDocument, Px, XObjectRotation, XObjectTransform};
+use printpdf::{CurTransMat, Mm, Op, PdfDocument, Pt, Px, XObjectRotation};
 
-fn qr_dimensions(content: &str) -> (u32, u32) {
-    let code = qrcode::QrCode::new(content).unwrap();
-    let luma = code.render::<image::Luma<u8>>().build();
-    (luma.width(), luma.height())
+/// The `cm` matrix of a rendered QR code and the side of its background
+/// square in points.
+struct RenderedSymbol {
+    matrix: [f32; 6],
+    side: f32,
 }
 
-fn rendered_transform(json: &str) -> XObjectTransform {
+fn symbol_from_ops(ops: &[Op]) -> RenderedSymbol {
+    let matrix = ops
+        .iter()
+        .find_map(|op| match op {
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(matrix),
+            } => Some(*matrix),
+            _ => None,
+        })
+        .expect("no transformation matrix found in buffer");
+    let side = ops
+        .iter()
+        .find_map(|op| match op {
+            Op::DrawPolygon { polygon } => Some(polygon.rings[0].points[2].p.x.0),
+            _ => None,
+        })
+        .expect("no background square found in buffer");
+    RenderedSymbol { matrix, side }
+}
+
+fn rendered_symbol(json: &str) -> RenderedSymbol {
     let json_schema: JsonQrCodeSchema = serde_json::from_str(json).unwrap();
     let schema: Schema = json_schema.into();
     let mut doc = PdfDocument::new("rotation_test");
@@ -26,44 +47,38 @@ fn rendered_transform(json: &str) -> XObjectTransform {
         .render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)
         .unwrap();
 
-    let ops = &buffer.buffer[0];
-    for op in ops {
-        if let Op::UseXobject { transform, .. } = op {
-            return transform.clone();
-        }
-    }
-    panic!("no UseXobject op found in buffer");
+    symbol_from_ops(&buffer.buffer[0])
 }
 
-fn rendered_bounds(transform: &XObjectTransform, width: u32, height: u32) -> [f32; 4] {
-    let matrix = transform
-        .get_ctms(Some((Px(width as usize), Px(height as usize))))
-        .into_iter()
-        .fold(CurTransMat::Identity.as_array(), |combined, next| {
-            CurTransMat::combine_matrix(combined, next.as_array())
-        });
+fn apply(matrix: [f32; 6], (x, y): (f32, f32)) -> (f32, f32) {
     let [a, b, c, d, e, f] = matrix;
-    let corners = [
-        (e, f),
-        (a + e, b + f),
-        (c + e, d + f),
-        (a + c + e, b + d + f),
-    ];
-    corners.iter().fold(
-        [
-            f32::INFINITY,
-            f32::INFINITY,
-            f32::NEG_INFINITY,
-            f32::NEG_INFINITY,
-        ],
-        |[min_x, min_y, max_x, max_y], (x, y)| {
-            [min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y)]
-        },
-    )
+    (a * x + c * y + e, b * x + d * y + f)
+}
+
+fn rendered_bounds(symbol: &RenderedSymbol) -> [f32; 4] {
+    let side = symbol.side;
+    [(0.0, 0.0), (side, 0.0), (0.0, side), (side, side)]
+        .into_iter()
+        .map(|corner| apply(symbol.matrix, corner))
+        .fold(
+            [
+                f32::INFINITY,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                f32::NEG_INFINITY,
+            ],
+            |[min_x, min_y, max_x, max_y], (x, y)| {
+                [min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)]
+            },
+        )
+}
+
+fn pt(mm: f32) -> f32 {
+    Pt::from(Mm(mm)).0
 }
 
 fn assert_approx_eq(left: f32, right: f32) {
-    assert!((left - right).abs() < 0.3, "{left} != {right}");
+    assert!((left - right).abs() < 0.01, "{left} != {right}");
 }
 
 #[test]
@@ -112,7 +127,7 @@ fn test_get_matrix_with_rotation_none_behaves_like_original() {
 }
 
 #[test]
-fn test_qrcode_no_rotation_produces_rotate_none() {
+fn test_qrcode_no_rotation_produces_unrotated_matrix() {
     let json = r#"{
         "name": "qr",
         "content": "test_no_rotate",
@@ -121,37 +136,37 @@ fn test_qrcode_no_rotation_produces_rotate_none() {
         "height": 50.0
     }"#;
 
-    let transform = rendered_transform(json);
+    let symbol = rendered_symbol(json);
 
-    assert!(transform.rotate.is_none());
+    assert_eq!(symbol.matrix[..4], [1.0, 0.0, -0.0, 1.0]);
+    assert_approx_eq(symbol.matrix[4], pt(10.0));
+    assert_approx_eq(symbol.matrix[5], pt(210.0 - 20.0 - 50.0));
 }
 
 #[test]
 fn test_qrcode_with_rotation_produces_centered_rotation() {
-    let content = "test_rotate_45";
-    let json = format!(
-        r#"{{
-            "name": "qr",
-            "content": "{}",
-            "position": {{ "x": 10.0, "y": 20.0 }},
-            "width": 50.0,
-            "height": 50.0,
-            "rotate": 45.0
-        }}"#,
-        content
-    );
+    let json = r#"{
+        "name": "qr",
+        "content": "test_rotate_45",
+        "position": { "x": 10.0, "y": 20.0 },
+        "width": 50.0,
+        "height": 50.0,
+        "rotate": 45.0
+    }"#;
 
-    let transform = rendered_transform(&json);
+    let symbol = rendered_symbol(json);
 
-    let rotation = transform.rotate.expect("rotation must be set");
-    let expected_center = Px((50.0_f32 * 300.0 / 25.4 / 2.0).round() as usize);
-    assert_eq!(rotation.angle_ccw_degrees, 45.0);
-    assert_eq!(rotation.rotation_center_x, expected_center);
-    assert_eq!(rotation.rotation_center_y, expected_center);
+    // The symbol's centre stays at the centre of its box.
+    let half = symbol.side / 2.0;
+    let (center_x, center_y) = apply(symbol.matrix, (half, half));
+    assert_approx_eq(center_x, pt(10.0 + 25.0));
+    assert_approx_eq(center_y, pt(210.0 - 20.0 - 25.0));
+    let angle = symbol.matrix[1].atan2(symbol.matrix[0]).to_degrees();
+    assert_approx_eq(angle, 45.0);
 }
 
 #[test]
-fn test_qrcode_with_rotation_uses_intrinsic_width_for_scale() {
+fn test_qrcode_with_rotation_keeps_module_size() {
     let content = "test_scale_90";
     let json = format!(
         r#"{{
@@ -165,12 +180,30 @@ fn test_qrcode_with_rotation_uses_intrinsic_width_for_scale() {
         content
     );
 
-    let transform = rendered_transform(&json);
-    let (w, _h) = qr_dimensions(content);
-
-    assert!(transform.rotate.is_some());
-    let expected_scale = 300.0 / 25.4 / (w as f32) * 50.0;
-    assert_eq!(transform.scale_x, Some(expected_scale));
+    let json_schema: JsonQrCodeSchema = serde_json::from_str(&json).unwrap();
+    let schema: Schema = json_schema.into();
+    let mut doc = PdfDocument::new("rotation_test");
+    let mut buffer = OpBuffer::default();
+    schema
+        .render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)
+        .unwrap();
+    let modules = buffer.buffer[0]
+        .iter()
+        .filter_map(|op| match op {
+            Op::DrawPolygon { polygon } => Some(polygon),
+            _ => None,
+        })
+        .nth(1)
+        .expect("no module path found in buffer");
+
+    // The top-left finder pattern starts with a run of 7 dark modules after
+    // the 4-module quiet zone.
+    let width = qrcode::QrCode::new(content).unwrap().width();
+    let module = pt(50.0) / (width + 8) as f32;
+    let first = &modules.rings[0].points;
+    assert_approx_eq(first[0].p.x.0, module * 4.0);
+    assert_approx_eq(first[1].p.x.0 - first[0].p.x.0, module * 7.0);
+    assert_approx_eq(first[2].p.y.0 - first[1].p.y.0, module);
 }
 
 #[test]
@@ -187,16 +220,17 @@ fn test_qrcode_renders_with_rotation_and_alignment_padding() {
         "padding": { "top": 10.0, "right": 10.0, "bottom": 10.0, "left": 10.0 }
     }"#;
 
-    let transform = rendered_transform(json);
+    let symbol = rendered_symbol(json);
 
     // effective box = 100 - (10+10) = 80; QR is square, so side = min(80, 80) = 80
     // center/middle offsets = (80-80)/2 = 0
     // x = 10 + 10 + 0 = 20, y = 20 + 10 + 0 = 30
-    let rotation = transform.rotate.expect("rotation must be set");
-    assert_eq!(rotation.angle_ccw_degrees, 90.0);
-    assert_eq!(transform.translate_x, Some(Mm(20.0).into()));
-    assert_eq!(transform.translate_y, Some(Mm(210.0 - 30.0 - 80.0).into()));
-    assert_eq!(transform.scale_x, transform.scale_y);
+    assert_approx_eq(symbol.side, pt(80.0));
+    let [min_x, min_y, max_x, max_y] = rendered_bounds(&symbol);
+    assert_approx_eq(min_x, pt(20.0));
+    assert_approx_eq(min_y, pt(210.0 - 30.0 - 80.0));
+    assert_approx_eq(max_x, pt(100.0));
+    assert_approx_eq(max_y, pt(210.0 - 30.0));
 }
 
 #[test]
@@ -210,26 +244,19 @@ fn test_qrcode_keeps_square_aspect_ratio_in_non_square_box() {
         "rotate": 30.0
     }"#;
 
-    let transform = rendered_transform(json);
+    let symbol = rendered_symbol(json);
 
     // QR content must stay 1:1 even when the box is non-square (45x60).
-    // side = min(45, 60) = 45 -> uniform scale, and rotation must not change size.
-    let rotation = transform.rotate.expect("rotation must be set");
-    assert_eq!(rotation.angle_ccw_degrees, 30.0);
-    assert_eq!(
-        transform.scale_x, transform.scale_y,
-        "QR must remain square"
-    );
-
-    let (w, _h) = qr_dimensions("test_square");
-    let expected_scale = 300.0 / 25.4 / (w as f32) * 45.0;
-    assert_eq!(transform.scale_x, Some(expected_scale));
+    // side = min(45, 60) = 45, and rotation must not change size.
+    assert_approx_eq(symbol.side, pt(45.0));
+    let [a, b, c, d, _, _] = symbol.matrix;
+    assert_approx_eq(a.hypot(b), 1.0);
+    assert_approx_eq(c.hypot(d), 1.0);
 }
 
 #[test]
 fn test_ninety_degree_rotation_keeps_position_and_size() {
-    let content = "same_content";
-    let unrotated = rendered_transform(
+    let unrotated = rendered_symbol(
         r#"{
             "name": "qr",
             "content": "same_content",
@@ -238,7 +265,7 @@ fn test_ninety_degree_rotation_keeps_position_and_size() {
             "height": 80.0
         }"#,
     );
-    let rotated = rendered_transform(
+    let rotated = rendered_symbol(
         r#"{
             "name": "qr",
             "content": "same_content",
@@ -249,21 +276,9 @@ fn test_ninety_degree_rotation_keeps_position_and_size() {
         }"#,
     );
 
-    assert_eq!(rotated.translate_x, unrotated.translate_x);
-    assert_eq!(rotated.translate_y, unrotated.translate_y);
-    assert_eq!(rotated.scale_x, unrotated.scale_x);
-    assert_eq!(rotated.scale_y, unrotated.scale_y);
-    assert_eq!(
-        rotated
-            .rotate
-            .expect("rotation must be set")
-            .angle_ccw_degrees,
-        90.0
-    );
-
-    let (w, h) = qr_dimensions(content);
-    let unrotated_bounds = rendered_bounds(&unrotated, w, h);
-    let rotated_bounds = rendered_bounds(&rotated, w, h);
+    assert_eq!(rotated.side, unrotated.side);
+    let unrotated_bounds = rendered_bounds(&unrotated);
+    let rotated_bounds = rendered_bounds(&rotated);
     for (actual, expected) in rotated_bounds.into_iter().zip(unrotated_bounds) {
         assert_approx_eq(actual, expected);
     }
@@ -285,14 +300,8 @@ fn test_qrcode_rotate_direction_matches_counter_clockwise_convention() {
     let mut buffer = OpBuffer::default();
     qr.render(Mm(150.0), &mut doc, 0, &mut buffer).unwrap();
 
-    let ops = &buffer.buffer[0];
-    let rotation = ops
-        .iter()
-        .find_map(|op| match op {
-            Op::UseXobject { transform, .. } => transform.rotate.clone(),
-            _ => None,
-        })
-        .expect("rotation must be set");
-
-    assert_eq!(rotation.angle_ccw_degrees, 90.0);
+    // A counter-clockwise quarter turn maps the x axis onto the y axis.
+    let symbol = symbol_from_ops(&buffer.buffer[0]);
+    assert_approx_eq(symbol.matrix[0], 0.0);
+    assert_approx_eq(symbol.matrix[1], 1.0);
 }
@@ -0,0 +1,226 @@
+//! Tests for the `logo`, `moduleStyle`, `finderStyle` and `finderColor`
+//! options of the `qrCode` schema.
+
+use pdforge::schemas::qrcode::JsonQrCodeSchema;
+use pdforge::schemas::{Error, Schema, SchemaTrait};
+use pdforge::utils::OpBuffer;
+use printpdf::{
+    Color, Mm, Op, PdfDocument, Polygon, PolygonRing, Pt, Rgb, WindingOrder, XObjectTransform,
+};
+use serde_json::json;
+
+const LOGO: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg==";
+
+fn render(options: serde_json::Value) -> Result<Vec<Op>, Error> {
+    let mut schema = json!({
+        "name": "code",
+        "content": "https://example.com/quotes/2025-0813",
+        "position": { "x": 10.0, "y": 20.0 },
+        "width": 40.0,
+        "height": 40.0
+    });
+    let fields = schema.as_object_mut().unwrap();
+    fields.extend(options.as_object().unwrap().clone());
+
+    let json_schema: JsonQrCodeSchema = serde_json::from_value(schema).unwrap();
+    let schema: Schema = json_schema.into();
+    let mut doc = PdfDocument::new("qr_style_test");
+    let mut buffer = OpBuffer::default();
+    schema.render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)?;
+    Ok(buffer.buffer[0].clone())
+}
+
+fn polygons(ops: &[Op]) -> Vec<&Polygon> {
+    ops.iter()
+        .filter_map(|op| match op {
+            Op::DrawPolygon { polygon } => Some(polygon),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `(left, bottom, right, top)` of every ring.
+fn ring_bounds(rings: &[PolygonRing]) -> Vec<[f32; 4]> {
+    rings
+        .iter()
+        .map(|ring| {
+            ring.points.iter().fold(
+                [
+                    f32::INFINITY,
+                    f32::INFINITY,
+                    f32::NEG_INFINITY,
+                    f32::NEG_INFINITY,
+                ],
+                |[left, bottom, right, top], point| {
+                    [
+                        left.min(point.p.x.0),
+                        bottom.min(point.p.y.0),
+                        right.max(point.p.x.0),
+                        top.max(point.p.y.0),
+                    ]
+                },
+            )
+        })
+        .collect()
+}
+
+fn logo_transform(ops: &[Op]) -> Option<XObjectTransform> {
+    ops.iter().find_map(|op| match op {
+        Op::UseXobject { transform, .. } => Some(*transform),
+        _ => None,
+    })
+}
+
+/// Dark modules drawn by the module path, each ring being one row's run.
+fn dark_modules(options: serde_json::Value) -> usize {
+    let ops = render(options).unwrap();
+    ring_bounds(&polygons(&ops)[1].rings)
+        .iter()
+        .map(|[left, bottom, right, top]| ((right - left) / (top - bottom)).round() as usize)
+        .sum()
+}
+
+#[test]
+fn logo_is_drawn_in_a_cleared_middle() {
+    let ops = render(json!({ "errorCorrectionLevel": "H", "logo": { "image": LOGO } })).unwrap();
+
+    let transform = logo_transform(&ops).expect("the logo must be drawn");
+    let polygons = polygons(&ops);
+    let side = ring_bounds(&polygons[0].rings)[0][2];
+    // A 1-pixel image scaled to the logo square, centred in the symbol.
+    let logo_side = 72.0 / 300.0 * transform.scale_x.unwrap();
+    let Pt(left) = transform.translate_x.unwrap();
+    let Pt(bottom) = transform.translate_y.unwrap();
+    assert!((left + logo_side / 2.0 - side / 2.0).abs() < 0.01);
+    assert!((bottom + logo_side / 2.0 - side / 2.0).abs() < 0.01);
+    assert!(logo_side > side * 0.15, "{logo_side} of {side}");
+
+    // No dark module is left under the logo.
+    for [ring_left, ring_bottom, ring_right, ring_top] in ring_bounds(&polygons[1].rings) {
+        let overlaps = ring_left < left + logo_side
+            && ring_right > left
+            && ring_bottom < bottom + logo_side
+            && ring_top > bottom;
+        assert!(!overlaps, "module at ({ring_left}, {ring_bottom})");
+    }
+}
+
+#[test]
+fn logo_size_is_a_fraction_of_the_symbol() {
+    let small = render(json!({
+        "errorCorrectionLevel": "H",
+        "logo": { "image": LOGO, "size": 0.1 }
+    }))
+    .unwrap();
+    let automatic =
+        render(json!({ "errorCorrectionLevel": "H", "logo": { "image": LOGO } })).unwrap();
+
+    let small = logo_transform(&small).unwrap().scale_x.unwrap();
+    let automatic = logo_transform(&automatic).unwrap().scale_x.unwrap();
+    assert!(small < automatic, "{small} >= {automatic}");
+}
+
+#[test]
+fn logos_beyond_the_error_correction_budget_are_rejected() {
+    let error = render(json!({
+        "errorCorrectionLevel": "L",
+        "logo": { "image": LOGO, "size": 0.2 }
+    }))
+    .unwrap_err();
+    assert!(matches!(error, Error::SymbolEncoding { .. }), "{error}");
+    assert!(error.to_string().contains("codewords"), "{error}");
+
+    // The same logo fits once the code is built with level H.
+    assert!(render(json!({
+        "errorCorrectionLevel": "H",
+        "logo": { "image": LOGO, "size": 0.2 }
+    }))
+    .is_ok());
+}
+
+#[test]
+fn styles_are_rejected_for_other_symbologies() {
+    for options in [
+        json!({ "symbology": "dataMatrix", "logo": { "image": LOGO } }),
+        json!({ "symbology": "aztec", "moduleStyle": "dot" }),
+        json!({ "symbology": "microQr", "finderStyle": "rounded" }),
+    ] {
+        let error = render(options.clone()).unwrap_err();
+        assert!(
+            matches!(error, Error::SymbolEncoding { .. }),
+            "{options}: {error}"
+        );
+    }
+}
+
+#[test]
+fn dot_modules_are_drawn_one_circle_each() {
+    let square = dark_modules(json!({}));
+    let ops = render(json!({ "moduleStyle": "dot" })).unwrap();
+    let dots = &polygons(&ops)[1];
+
+    assert_eq!(dots.rings.len(), square);
+    assert!(dots
+        .rings
+        .iter()
+        .all(|ring| ring.points.iter().any(|point| point.bezier)));
+    for [left, bottom, right, top] in ring_bounds(&dots.rings) {
+        assert!(((right - left) - (top - bottom)).abs() < 0.01);
+    }
+}
+
+#[test]
+fn rounded_modules_keep_each_row_run_together() {
+    let square = render(json!({})).unwrap();
+    let rounded = render(json!({ "moduleStyle": "rounded" })).unwrap();
+
+    let square = ring_bounds(&polygons(&square)[1].rings);
+    let rounded = ring_bounds(&polygons(&rounded)[1].rings);
+    assert_eq!(square.len(), rounded.len());
+    for (square, rounded) in square.iter().zip(&rounded) {
+        for (a, b) in square.iter().zip(rounded) {
+            assert!((a - b).abs() < 0.01, "{square:?} != {rounded:?}");
+        }
+    }
+}
+
+#[test]
+fn finder_patterns_take_their_own_style_and_color() {
+    let ops = render(json!({ "finderStyle": "circle", "finderColor": "#0000ff" })).unwrap();
+
+    let fill_colors: Vec<&Color> = ops
+        .iter()
+        .filter_map(|op| match op {
+            Op::SetFillColor { col } => Some(col),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        fill_colors.last(),
+        Some(&&Color::Rgb(Rgb::new(0.0, 0.0, 1.0, None)))
+    );
+
+    let polygons = polygons(&ops);
+    let finders = polygons[2];
+    // An outer ring, its hole and the centre for each of the three patterns.
+    assert_eq!(finders.rings.len(), 9);
+    assert_eq!(finders.winding_order, WindingOrder::EvenOdd);
+
+    // Each finder pattern has 33 dark modules that the module path now skips.
+    let square = dark_modules(json!({}));
+    let modules = dark_modules(json!({ "finderStyle": "circle" }));
+    assert_eq!(square - modules, 3 * 33);
+}
+
+#[test]
+fn unknown_styles_are_rejected() {
+    let schema = json!({
+        "name": "code",
+        "content": "x",
+        "position": { "x": 0.0, "y": 0.0 },
+        "width": 10.0,
+        "height": 10.0,
+        "moduleStyle": "star"
+    });
+    assert!(serde_json::from_value::<JsonQrCodeSchema>(schema).is_err());
+}
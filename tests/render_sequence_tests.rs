@@ -0,0 +1,172 @@
+use lopdf::{Document, Object};
+use pdforge::{PDForge, PDForgeBuilder};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn write_template(name: &str, template: serde_json::Value) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-sequence-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    path
+}
+
+fn text_schema(name: &str, content: &str) -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": name,
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": content,
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    })
+}
+
+fn template(
+    width: f32,
+    height: f32,
+    pages: usize,
+    static_schema: serde_json::Value,
+) -> serde_json::Value {
+    let schemas: Vec<serde_json::Value> = (0..pages)
+        .map(|index| json!([text_schema(&format!("body{index}"), "{{ value }}")]))
+        .collect();
+
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": width,
+            "height": height,
+            "padding": [10.0, 10.0, 10.0, 10.0],
+            "staticSchema": static_schema
+        },
+        "schemas": schemas
+    })
+}
+
+fn forge(templates: &[(&str, serde_json::Value)]) -> PDForge {
+    let mut builder = PDForgeBuilder::new("sequence".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())
+        .expect("test font should load");
+    for (name, json) in templates {
+        let path = write_template(name, json.clone());
+        builder = builder
+            .load_template(name, path.to_str().unwrap())
+            .expect("template should load");
+    }
+    builder.build()
+}
+
+fn input(value: &str) -> HashMap<&'static str, String> {
+    HashMap::from([("value", value.to_string())])
+}
+
+fn media_boxes(pdf: &[u8]) -> Vec<(f32, f32)> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            let page = doc.get_dictionary(*page_id).expect("page should exist");
+            let media_box = page
+                .get(b"MediaBox")
+                .and_then(Object::as_array)
+                .expect("page should have a MediaBox");
+            let number = |object: &Object| object.as_float().expect("MediaBox entry");
+            (number(&media_box[2]), number(&media_box[3]))
+        })
+        .collect()
+}
+
+#[test]
+fn sections_keep_their_own_page_size() {
+    let forge = forge(&[
+        ("invoice", template(210.0, 297.0, 1, json!([]))),
+        ("label", template(105.0, 148.0, 1, json!([]))),
+    ]);
+
+    let pdf = forge
+        .render_sequence(
+            &[
+                ("invoice", vec![vec![input("invoice")]]),
+                ("label", vec![vec![input("first"), input("second")]]),
+            ],
+            None,
+            None,
+        )
+        .expect("sequence should render");
+
+    let sizes = media_boxes(&pdf);
+    assert_eq!(sizes.len(), 3);
+    assert!((sizes[0].0 - 595.27).abs() < 0.5, "A4 width: {sizes:?}");
+    assert!((sizes[0].1 - 841.89).abs() < 0.5, "A4 height: {sizes:?}");
+    for size in &sizes[1..] {
+        assert!((size.0 - 297.64).abs() < 0.5, "A6 width: {sizes:?}");
+        assert!((size.1 - 419.53).abs() < 0.5, "A6 height: {sizes:?}");
+    }
+}
+
+#[test]
+fn static_page_counters_span_the_whole_document() {
+    // `missing` is never defined, so Tera fails the render whenever a guard
+    // below is reached: every page must see totalPages == 3, and the label
+    // section must continue numbering after the invoice page.
+    let invoice_static = json!([text_schema(
+        "counter",
+        "{% if totalPages != 3 or currentPage != 1 %}{{ missing }}{% endif %}{{ currentPage }}/{{ totalPages }}"
+    )]);
+    let label_static = json!([text_schema(
+        "counter",
+        "{% if totalPages != 3 or currentPage < 2 %}{{ missing }}{% endif %}{{ currentPage }}/{{ totalPages }}"
+    )]);
+    let forge = forge(&[
+        ("invoice-counted", template(210.0, 297.0, 1, invoice_static)),
+        ("label-counted", template(105.0, 148.0, 1, label_static)),
+    ]);
+
+    let pdf = forge.render_sequence(
+        &[
+            ("invoice-counted", vec![vec![input("invoice")]]),
+            ("label-counted", vec![vec![input("first"), input("second")]]),
+        ],
+        None,
+        None,
+    );
+
+    assert!(pdf.is_ok(), "counters should span sections: {pdf:?}");
+}
+
+#[test]
+fn sequence_rejects_unknown_templates_and_mismatched_inputs() {
+    let forge = forge(&[("two-pages", template(210.0, 297.0, 2, json!([])))]);
+
+    let unknown = forge
+        .render_sequence(&[("nope", vec![vec![input("x")]])], None, None)
+        .expect_err("unknown template should fail");
+    assert!(unknown.to_string().contains("Template not found"));
+
+    let mismatched = forge
+        .render_sequence(&[("two-pages", vec![vec![input("x")]])], None, None)
+        .expect_err("input groups must match the template's pages");
+    assert!(mismatched.to_string().contains("does not match"));
+
+    let empty = forge
+        .render_sequence(&[], None, None)
+        .expect_err("an empty sequence should fail");
+    assert!(empty.to_string().contains("cannot be empty"));
+}
@@ -0,0 +1,140 @@
+//! Tests for rich text markup in the `text` schema.
+
+use lopdf::content::{Content, Operation};
+use lopdf::{Document, Object};
+use pdforge::schemas::Error;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn render(name: &str, text: serde_json::Value) -> Result<Vec<Operation>, Error> {
+    let font = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf");
+    let mut schema = json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 20.0,
+        "fontName": "Sans",
+        "fontSize": 10.0,
+        "richText": true
+    });
+    schema
+        .as_object_mut()
+        .unwrap()
+        .extend(text.as_object().unwrap().clone());
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 50.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[schema]]
+    });
+    let path =
+        std::env::temp_dir().join(format!("pdforge-rich-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    let pdf = PDForgeBuilder::new("rich".to_string())
+        .add_font_from_file("Sans", font.to_str().unwrap())?
+        .add_font_from_file("Other", font.to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)?;
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Ok(Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations)
+}
+
+fn operands(ops: &[Operation], operator: &str) -> Vec<Vec<Object>> {
+    ops.iter()
+        .filter(|op| op.operator == operator)
+        .map(|op| op.operands.clone())
+        .collect()
+}
+
+fn floats(ops: &[Operation], operator: &str) -> Vec<Vec<f32>> {
+    operands(ops, operator)
+        .into_iter()
+        .map(|operands| operands.iter().map(|o| o.as_float().unwrap()).collect())
+        .collect()
+}
+
+#[test]
+fn markup_switches_font_size_color_and_weight_within_a_line() {
+    let ops = render(
+        "mixed",
+        json!({
+            "content": "a<b>b</b><color=#ff0000>c</color><size=20>d</size><font=Other>e</font>"
+        }),
+    )
+    .unwrap();
+
+    let fonts = operands(&ops, "Tf");
+    let sizes: Vec<f32> = fonts.iter().map(|f| f[1].as_float().unwrap()).collect();
+    assert_eq!(sizes, [10.0, 10.0, 10.0, 20.0, 10.0]);
+    assert_eq!(fonts[0][0], fonts[3][0]);
+    assert_ne!(fonts[0][0], fonts[4][0]);
+
+    // Bold is drawn with a stroked outline, switched off again after it.
+    assert_eq!(floats(&ops, "Tr"), [[2.0], [0.0]]);
+    assert!(floats(&ops, "rg").contains(&vec![1.0, 0.0, 0.0]));
+
+    // Every run starts to the right of the one before it.
+    let starts: Vec<f32> = floats(&ops, "Tm").iter().map(|m| m[4]).collect();
+    assert!(starts.windows(2).all(|w| w[0] < w[1]), "{starts:?}");
+}
+
+#[test]
+fn justify_spreads_the_same_spacing_across_runs() {
+    let ops = render(
+        "justify",
+        json!({ "content": "ab <b>cd</b> <i>ef</i>", "alignment": "justify" }),
+    )
+    .unwrap();
+
+    let spacing = floats(&ops, "Tc");
+    assert_eq!(spacing.len(), 4);
+    assert!(spacing[0][0] > 0.0, "{spacing:?}");
+    assert!(spacing.iter().all(|s| s == &spacing[0]), "{spacing:?}");
+}
+
+#[test]
+fn plain_text_keeps_its_markup_without_rich_text() {
+    let ops = render(
+        "plain",
+        json!({ "content": "<b>bold</b>", "richText": false }),
+    )
+    .unwrap();
+    assert_eq!(operands(&ops, "Tf").len(), 1);
+    assert!(operands(&ops, "Tr").is_empty());
+}
+
+#[test]
+fn invalid_markup_fails_the_render() {
+    let error = render("invalid", json!({ "content": "<b>unclosed" })).unwrap_err();
+    assert!(matches!(error, Error::RichText { .. }), "{error}");
+}
+
+#[test]
+fn dynamic_font_size_scales_relative_runs_together() {
+    let ops = render(
+        "dynamic",
+        json!({
+            "content": "a<size=50%>b</size>",
+            "fontSize": { "min": 6.0, "max": 30.0, "fit": "horizontal" }
+        }),
+    )
+    .unwrap();
+
+    let sizes: Vec<f32> = operands(&ops, "Tf")
+        .iter()
+        .map(|f| f[1].as_float().unwrap())
+        .collect();
+    assert_eq!(sizes.len(), 2);
+    assert!(sizes[0] > 10.0, "{sizes:?}");
+    assert!((sizes[1] - sizes[0] / 2.0).abs() < 1e-4, "{sizes:?}");
+}
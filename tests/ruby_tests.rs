@@ -0,0 +1,151 @@
+//! Tests for ruby (furigana) markup in the `text` schema.
+
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::schemas::Error;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn render_text(name: &str, options: serde_json::Value) -> Result<Vec<Operation>, Error> {
+    let font = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf");
+    let mut schema = json!({
+        "type": "text",
+        "name": "name",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 60.0,
+        "height": 40.0,
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "ruby": true
+    });
+    schema
+        .as_object_mut()
+        .unwrap()
+        .extend(options.as_object().unwrap().clone());
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 100.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [[schema]]
+    });
+    let path =
+        std::env::temp_dir().join(format!("pdforge-ruby-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    let pdf = PDForgeBuilder::new("ruby".to_string())
+        .add_font_from_file("TestFont", font.to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)?;
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    let page_id = *doc.get_pages().values().next().unwrap();
+    Ok(Content::decode(&doc.get_page_content(page_id))
+        .unwrap()
+        .operations)
+}
+
+/// The font size and text matrix of every positioned piece of text.
+fn placements(ops: &[Operation]) -> Vec<(f32, [f32; 6])> {
+    let mut size = 0.0;
+    let mut placements = Vec::new();
+    for op in ops {
+        let values: Vec<f32> = op
+            .operands
+            .iter()
+            .filter_map(|o| o.as_float().ok())
+            .collect();
+        match op.operator.as_str() {
+            "Tf" => size = values[0],
+            "Tm" => placements.push((size, values.try_into().unwrap())),
+            _ => {}
+        }
+    }
+    placements
+}
+
+fn approx(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3
+}
+
+#[test]
+fn group_ruby_is_spread_over_its_base_in_a_smaller_size() {
+    let ops = render_text("group", json!({ "content": "{田中|たなか}" })).unwrap();
+    let [(10.0, base), (5.0, ruby)] = placements(&ops)[..] else {
+        panic!("expected the base and its ruby");
+    };
+
+    // Three 5pt kana over two 10pt kanji leave 5pt, shared out 1:2:1.
+    assert!(approx(ruby[4] - base[4], 5.0 / 6.0), "{ruby:?}");
+    assert!(ops
+        .iter()
+        .any(|op| op.operator == "Tc" && approx(op.operands[0].as_float().unwrap(), 5.0 / 3.0)));
+    // The ruby em box sits on top of the base one.
+    assert!(approx(ruby[5] - base[5], 10.0 * 0.88 + 5.0 * 0.12));
+}
+
+#[test]
+fn mono_ruby_is_centred_on_each_character() {
+    let ops = render_text("mono", json!({ "content": "{田中|た|なか}" })).unwrap();
+    let placements = placements(&ops);
+    let expected = [(10.0, 0.0), (5.0, 2.5), (10.0, 10.0), (5.0, 10.0)];
+    assert_eq!(placements.len(), expected.len());
+    for ((size, matrix), (expected_size, expected_x)) in placements.iter().zip(expected) {
+        assert_eq!(*size, expected_size);
+        assert!(approx(matrix[4], expected_x), "{matrix:?}");
+    }
+}
+
+#[test]
+fn only_lines_with_ruby_make_room_for_it() {
+    let baselines = |content: &str| -> Vec<f32> {
+        placements(&render_text("lines", json!({ "content": content })).unwrap())
+            .iter()
+            .filter(|(size, _)| *size == 10.0)
+            .map(|(_, matrix)| matrix[5])
+            .collect()
+    };
+
+    // The room for ruby goes above the line that has it.
+    let [first, second] = baselines("{田中|たなか}\n様")[..] else {
+        panic!("expected two lines");
+    };
+    assert!(approx(first - second, 10.0));
+    // 5pt ruby, less the 1.2pt of the em box below the baseline.
+    let [first, second] = baselines("様\n{田中|たなか}")[..] else {
+        panic!("expected two lines");
+    };
+    assert!(approx(first - second, 10.0 + 3.8));
+}
+
+#[test]
+fn vertical_ruby_runs_down_the_right_of_its_column() {
+    let ops = render_text(
+        "vertical",
+        json!({ "content": "{漢|かん}", "writingMode": "vertical-rl" }),
+    )
+    .unwrap();
+    let placements = placements(&ops);
+    let [(10.0, base), ref ruby @ ..] = placements[..] else {
+        panic!("expected the base first");
+    };
+    assert_eq!(ruby.len(), 2);
+
+    // The column gives up 5pt on its right, where the ruby column stands.
+    let right_edge = 60.0 / 25.4 * 72.0;
+    assert!(approx(base[4], right_edge - 15.0), "{base:?}");
+    for (size, matrix) in ruby {
+        assert_eq!(*size, 5.0);
+        assert!(matrix[4] >= base[4] + 10.0 - 1e-3, "{matrix:?}");
+    }
+    assert!(ruby[0].1[5] > ruby[1].1[5]);
+}
+
+#[test]
+fn ruby_is_not_combined_with_rich_text() {
+    let error = render_text("rich", json!({ "content": "{a|b}", "richText": true })).unwrap_err();
+    assert!(matches!(error, Error::Ruby { .. }), "{error}");
+}
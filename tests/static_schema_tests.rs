@@ -0,0 +1,338 @@
+use lopdf::content::Content;
+use lopdf::Document;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn try_render(
+    name: &str,
+    template: serde_json::Value,
+    records: usize,
+) -> Result<Vec<u8>, pdforge::schemas::Error> {
+    let path =
+        std::env::temp_dir().join(format!("pdforge-static-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+
+    let forge = PDForgeBuilder::new("static".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build();
+
+    forge.render("main", vec![vec![HashMap::new(); records]], None, None)
+}
+
+fn text_schema(name: &str, y: f32, content: &str) -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": name,
+        "position": { "x": 10.0, "y": y },
+        "width": 80.0,
+        "height": 10.0,
+        "content": content,
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    })
+}
+
+/// A static text that only renders on the pages `condition` holds for;
+/// anywhere else the undefined variable makes rendering fail.
+fn guarded_static(pages: &str, condition: &str) -> serde_json::Value {
+    let mut schema = text_schema(
+        pages,
+        280.0,
+        &format!("{{% if {condition} %}}{pages}{{% else %}}{{{{ missing }}}}{{% endif %}}"),
+    );
+    schema["pages"] = json!(pages);
+    schema
+}
+
+fn template(static_schema: serde_json::Value, body: serde_json::Value) -> serde_json::Value {
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 210.0,
+            "height": 297.0,
+            "padding": [10.0, 10.0, 10.0, 10.0],
+            "staticSchema": static_schema
+        },
+        "schemas": [[body]]
+    })
+}
+
+/// Number of text objects drawn on each page.
+fn texts_per_page(pdf: &[u8]) -> Vec<usize> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            let content = doc.get_page_content(*page_id);
+            Content::decode(&content)
+                .expect("content should decode")
+                .operations
+                .iter()
+                .filter(|op| op.operator == "BT")
+                .count()
+        })
+        .collect()
+}
+
+#[test]
+fn static_schemas_are_painted_only_on_selected_pages() {
+    let statics = json!([
+        guarded_static("first", "currentPage == 1"),
+        guarded_static("last", "currentPage == totalPages"),
+        guarded_static("even", "currentPage % 2 == 0"),
+        guarded_static("2-3", "currentPage >= 2 and currentPage <= 3"),
+        guarded_static("currentPage < totalPages", "currentPage < totalPages"),
+    ]);
+    let pdf = try_render(
+        "selected",
+        template(statics, text_schema("body", 10.0, "body")),
+        4,
+    )
+    .expect("selected statics should render");
+
+    // One body text per page plus each static selected for it.
+    assert_eq!(texts_per_page(&pdf), vec![3, 4, 3, 3]);
+}
+
+#[test]
+fn a_static_schema_without_pages_is_painted_everywhere() {
+    let statics = json!([text_schema("footer", 280.0, "{{ currentPage }}")]);
+    let pdf = try_render(
+        "everywhere",
+        template(statics, text_schema("body", 10.0, "body")),
+        3,
+    )
+    .expect("template should render");
+
+    assert_eq!(texts_per_page(&pdf), vec![2, 2, 2]);
+}
+
+#[test]
+fn an_invalid_pages_selector_is_rejected_when_loading() {
+    for pages in [json!("3-1"), json!(0), json!("currentPage <")] {
+        let mut schema = text_schema("footer", 280.0, "footer");
+        schema["pages"] = pages.clone();
+        let result = try_render(
+            "invalid-pages",
+            template(json!([schema]), text_schema("body", 10.0, "body")),
+            1,
+        );
+        assert!(result.is_err(), "pages {pages} should be rejected");
+    }
+}
+
+#[test]
+fn first_page_padding_only_shortens_the_first_page() {
+    let body = json!({
+        "type": "dynamicText",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 100.0,
+        "height": 10.0,
+        "content": "line\n".repeat(40),
+        "fontName": "TestFont",
+        "fontSize": 10.0
+    });
+    let plain = template(json!([]), body.clone());
+    let mut letterhead = plain.clone();
+    // Reserve all but ~10 lines of the first page for a letterhead.
+    letterhead["basePdf"]["firstPagePadding"] = json!([10.0, 10.0, 240.0, 10.0]);
+
+    let plain_pages = texts_per_page(&try_render("plain", plain, 1).unwrap()).len();
+    let letterhead_pages = texts_per_page(&try_render("letterhead", letterhead, 1).unwrap());
+
+    assert_eq!(plain_pages, 1, "40 lines fit one A4 page");
+    assert_eq!(
+        letterhead_pages.len(),
+        2,
+        "the overflow continues under the regular padding"
+    );
+}
+
+fn long_body(y: f32, lines: usize) -> serde_json::Value {
+    json!({
+        "type": "dynamicText",
+        "name": "body",
+        "position": { "x": 10.0, "y": y },
+        "width": 100.0,
+        "height": 10.0,
+        "content": "line\n".repeat(lines),
+        "fontName": "TestFont",
+        "fontSize": 10.0
+    })
+}
+
+fn band(kind: &str, y: f32, content: &str) -> serde_json::Value {
+    let mut schema = text_schema(kind, y, content);
+    schema["band"] = json!(kind);
+    schema["height"] = json!(5.0);
+    schema
+}
+
+fn page_count(name: &str, template: serde_json::Value) -> usize {
+    texts_per_page(&try_render(name, template, 1).expect("template should render")).len()
+}
+
+#[test]
+fn flowing_content_stays_between_header_and_footer_bands() {
+    // 110 lines starting at y=150: 38 fit on the first page and 78 on a
+    // page that restarts at the top padding, but only 38 below the header.
+    let body = long_body(150.0, 110);
+    let plain = page_count("bandless", template(json!([]), body.clone()));
+    let header = page_count(
+        "header",
+        template(json!([band("header", 145.0, "header")]), body.clone()),
+    );
+    let footer = page_count(
+        "footer",
+        template(json!([band("footer", 250.0, "footer")]), body),
+    );
+
+    assert_eq!(plain, 2, "continuation pages restart at the top padding");
+    assert_eq!(header, 3, "continuation pages restart below the header");
+    assert!(
+        footer > plain,
+        "pages break above the footer: {footer} vs {plain}"
+    );
+}
+
+#[test]
+fn a_wrapping_header_reserves_its_measured_height() {
+    let body = long_body(20.0, 200);
+    let one_line = page_count(
+        "one-line-header",
+        template(json!([band("header", 10.0, "Title")]), body.clone()),
+    );
+    let wrapping = page_count(
+        "wrapping-header",
+        template(
+            json!([band(
+                "header",
+                10.0,
+                &format!(
+                    "{{% if currentPage > 1 %}}{}{{% else %}}Title{{% endif %}}",
+                    "word ".repeat(200)
+                )
+            )]),
+            body,
+        ),
+    );
+
+    assert!(
+        wrapping > one_line,
+        "continuation pages must start below the wrapped header: {wrapping} vs {one_line}"
+    );
+}
+
+#[test]
+fn an_unknown_band_is_rejected_when_loading() {
+    let mut schema = text_schema("footer", 280.0, "footer");
+    schema["band"] = json!("sidebar");
+    let result = try_render(
+        "invalid-band",
+        template(json!([schema]), text_schema("body", 10.0, "body")),
+        1,
+    );
+    assert!(result.is_err());
+}
+
+/// The distinct x offsets, in whole millimetres, that text is drawn at on
+/// each page.
+fn text_x_per_page(pdf: &[u8]) -> Vec<Vec<i32>> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            let content = Content::decode(&doc.get_page_content(*page_id)).expect("content");
+            let mut xs: Vec<i32> = content
+                .operations
+                .iter()
+                .filter(|op| op.operator == "cm")
+                .map(|op| {
+                    let points = op.operands[4].as_float().expect("cm operand");
+                    (points * 25.4 / 72.0).round() as i32
+                })
+                .collect();
+            xs.sort_unstable();
+            xs.dedup();
+            xs
+        })
+        .collect()
+}
+
+fn mirrored(mut template: serde_json::Value) -> serde_json::Value {
+    template["basePdf"]["padding"] =
+        json!({ "top": 10, "bottom": 10, "inside": 25, "outside": 10 });
+    template
+}
+
+#[test]
+fn flowing_content_follows_mirrored_margins_onto_even_pages() {
+    let mut body = long_body(10.0, 200);
+    body["position"]["x"] = json!(25.0);
+    let pdf = try_render("mirrored-flow", mirrored(template(json!([]), body)), 1)
+        .expect("template should render");
+
+    assert_eq!(
+        text_x_per_page(&pdf),
+        vec![vec![25], vec![10], vec![25]],
+        "even pages move by outside - inside"
+    );
+}
+
+#[test]
+fn mirrored_static_schemas_flip_to_the_other_side_on_even_pages() {
+    let mut page_number = text_schema("page", 280.0, "{{ currentPage }}");
+    page_number["position"]["x"] = json!(180.0);
+    page_number["width"] = json!(20.0);
+    page_number["mirror"] = json!(true);
+    let pdf = try_render(
+        "mirrored-static",
+        template(json!([page_number]), text_schema("body", 30.0, "body")),
+        3,
+    )
+    .expect("template should render");
+
+    // Body text at x=10 on every page; the page number at 180mm on odd pages
+    // and 210 - 180 - 20 = 10mm on even ones.
+    assert_eq!(
+        text_x_per_page(&pdf),
+        vec![vec![10, 180], vec![10], vec![10, 180]]
+    );
+}
+
+#[test]
+fn mirrored_padding_rejects_unknown_sides() {
+    let mut template = template(json!([]), text_schema("body", 10.0, "body"));
+    template["basePdf"]["padding"] = json!({ "top": 10, "bottom": 10, "left": 10, "right": 10 });
+    assert!(try_render("bad-mirrored", template, 1).is_err());
+}
+
+#[test]
+fn flowing_content_keeps_its_margin_after_a_mirrored_first_page_padding() {
+    let mut body = long_body(10.0, 200);
+    body["position"]["x"] = json!(40.0);
+    let mut template = mirrored(template(json!([]), body));
+    template["basePdf"]["firstPagePadding"] =
+        json!({ "top": 10, "bottom": 10, "inside": 40, "outside": 10 });
+    let pdf = try_render("mirrored-first-page", template, 1).expect("template should render");
+
+    // Declared at the first page's 40mm inside margin, the text moves to the
+    // regular outside (10mm) and inside (25mm) margins on later pages.
+    assert_eq!(text_x_per_page(&pdf), vec![vec![40], vec![10], vec![25]]);
+}
@@ -0,0 +1,177 @@
+//! Tests for the `symbology` field of the `qrCode` schema
+//!
+//! Every symbology is drawn through the same vector, alignment, padding and
+//! rotation path as a standard QR code; only the module grid and its quiet
+//! zone differ.
+
+use pdforge::schemas::qrcode::JsonQrCodeSchema;
+use pdforge::schemas::{Error, Schema, SchemaTrait};
+use pdforge::utils::OpBuffer;
+use printpdf::{CurTransMat, Mm, Op, PdfDocument, Pt};
+use serde_json::json;
+
+/// The `cm` matrix of a rendered symbol, the width and height of its
+/// background and the height of one row of modules, all in points.
+#[derive(Debug)]
+struct RenderedSymbol {
+    matrix: [f32; 6],
+    side: f32,
+    height: f32,
+    module: f32,
+}
+
+impl RenderedSymbol {
+    /// Modules across the symbol, quiet zone included.
+    fn modules(&self) -> f32 {
+        (self.side / self.module).round()
+    }
+}
+
+fn render(schema: serde_json::Value) -> Result<RenderedSymbol, Error> {
+    let json_schema: JsonQrCodeSchema = serde_json::from_value(schema).unwrap();
+    let schema: Schema = json_schema.into();
+    let mut doc = PdfDocument::new("symbology_test");
+    let mut buffer = OpBuffer::default();
+    schema.render(Mm(150.0), Mm(210.0), &mut doc, 0, &mut buffer)?;
+
+    let ops = &buffer.buffer[0];
+    let matrix = ops
+        .iter()
+        .find_map(|op| match op {
+            Op::SetTransformationMatrix {
+                matrix: CurTransMat::Raw(matrix),
+            } => Some(*matrix),
+            _ => None,
+        })
+        .expect("no transformation matrix found in buffer");
+    let mut polygons = ops.iter().filter_map(|op| match op {
+        Op::DrawPolygon { polygon } => Some(polygon),
+        _ => None,
+    });
+    let background = polygons.next().expect("no background square found");
+    let modules = polygons.next().expect("no module path found");
+    let side = background.rings[0].points[2].p.x.0;
+    let height = background.rings[0].points[2].p.y.0;
+    let module = modules
+        .rings
+        .iter()
+        .map(|ring| ring.points[2].p.y.0 - ring.points[1].p.y.0)
+        .fold(f32::INFINITY, f32::min);
+    Ok(RenderedSymbol {
+        matrix,
+        side,
+        height,
+        module,
+    })
+}
+
+fn code(symbology: &str, content: &str) -> serde_json::Value {
+    json!({
+        "name": "code",
+        "content": content,
+        "position": { "x": 10.0, "y": 20.0 },
+        "width": 40.0,
+        "height": 40.0,
+        "symbology": symbology
+    })
+}
+
+fn pt(mm: f32) -> f32 {
+    Pt::from(Mm(mm)).0
+}
+
+fn assert_approx_eq(left: f32, right: f32, context: &str) {
+    assert!((left - right).abs() < 0.01, "{context}: {left} != {right}");
+}
+
+#[test]
+fn each_symbology_is_sized_by_its_modules_and_quiet_zone() {
+    // Micro QR M1 is 11 modules with a 2-module quiet zone.
+    let micro = render(code("microQr", "12345")).unwrap();
+    assert_eq!(micro.modules(), (11 + 4) as f32);
+    // Data Matrix 10 × 10 with a 1-module quiet zone.
+    let data_matrix = render(code("dataMatrix", "123456")).unwrap();
+    assert_eq!(data_matrix.modules(), (10 + 2) as f32);
+    // A compact one-layer Aztec code is 15 modules and needs no quiet zone.
+    let aztec = render(code("aztec", "12345")).unwrap();
+    assert_eq!(aztec.modules(), 15.0);
+    // Without the field the schema stays a QR code: version 1 and 4 modules each side.
+    let mut qr = code("qr", "HELLO");
+    qr.as_object_mut().unwrap().remove("symbology");
+    let qr = render(qr).unwrap();
+    assert_eq!(qr.modules(), (21 + 8) as f32);
+    for symbol in [micro, data_matrix, aztec, qr] {
+        assert_approx_eq(symbol.side, pt(40.0), "side");
+    }
+}
+
+#[test]
+fn placement_and_rotation_apply_to_every_symbology() {
+    for symbology in ["qr", "microQr", "dataMatrix", "aztec"] {
+        let mut schema = code(symbology, "PDFORGE");
+        let fields = schema.as_object_mut().unwrap();
+        fields.insert("width".to_string(), json!(60.0));
+        fields.insert("alignment".to_string(), json!("right"));
+        fields.insert(
+            "padding".to_string(),
+            json!({ "top": 5.0, "right": 5.0, "bottom": 5.0, "left": 5.0 }),
+        );
+        fields.insert("rotate".to_string(), json!(90.0));
+
+        let symbol = render(schema).unwrap();
+
+        // Inner box 50 × 30, so a 30mm symbol right-aligned at x = 10 + 5 + 20.
+        // A quarter turn about its centre leaves the square where it was.
+        assert_approx_eq(symbol.side, pt(30.0), symbology);
+        let [a, b, c, d, e, f] = symbol.matrix;
+        assert_approx_eq(a, 0.0, symbology);
+        assert_approx_eq(b, 1.0, symbology);
+        let (left, bottom) = (c * symbol.side + e, d * symbol.side + f);
+        let (left, bottom) = (left.min(e), bottom.min(f));
+        assert_approx_eq(left, pt(35.0), symbology);
+        assert_approx_eq(bottom, pt(210.0 - 25.0 - 30.0), symbology);
+    }
+}
+
+#[test]
+fn pdf417_rows_are_three_modules_high_and_fit_the_box() {
+    // 2 columns of codewords make rows 103 modules wide; 14 rows of 3
+    // modules each, plus a 2-module quiet zone all round.
+    let mut schema = code("pdf417", "PDFORGE SHIPPING LABEL 0123456789012345");
+    schema
+        .as_object_mut()
+        .unwrap()
+        .insert("verticalAlignment".to_string(), json!("middle"));
+    let symbol = render(schema).unwrap();
+
+    let module = pt(40.0) / 107.0;
+    assert_approx_eq(symbol.side, pt(40.0), "width");
+    assert_approx_eq(symbol.height, module * 46.0, "height");
+    assert_approx_eq(symbol.module, module * 3.0, "row height");
+    // Centred vertically in the 40mm box at y = 20.
+    let [_, _, _, _, e, f] = symbol.matrix;
+    assert_approx_eq(e, pt(10.0), "x");
+    let top = pt(20.0) + (pt(40.0) - symbol.height) / 2.0;
+    assert_approx_eq(f, pt(210.0) - top - symbol.height, "y");
+}
+
+#[test]
+fn content_that_does_not_fit_is_an_encoding_error() {
+    for (symbology, content) in [
+        ("microQr", "x".repeat(40)),
+        ("dataMatrix", "x".repeat(1600)),
+        ("aztec", "x".repeat(4000)),
+        ("pdf417", "x".repeat(2000)),
+    ] {
+        let error = render(code(symbology, &content)).unwrap_err();
+        assert!(
+            matches!(error, Error::SymbolEncoding { .. }),
+            "{symbology}: {error}"
+        );
+    }
+}
+
+#[test]
+fn unknown_symbologies_are_rejected() {
+    assert!(serde_json::from_value::<JsonQrCodeSchema>(code("maxiCode", "x")).is_err());
+}
@@ -0,0 +1,253 @@
+//! Tests for vertical writing (`writingMode: "vertical-rl"`) in the `text`
+//! and `dynamicText` schemas.
+
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::schemas::Error;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn render_pages(name: &str, schema: serde_json::Value) -> Result<Vec<Vec<Operation>>, Error> {
+    render_flow(name, json!([schema]))
+}
+
+/// Renders `schemas` as one template page, so flowing ones follow each other.
+fn render_flow(name: &str, schemas: serde_json::Value) -> Result<Vec<Vec<Operation>>, Error> {
+    let font = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf");
+    let template = json!({
+        "schemaVersion": "1.0",
+        "basePdf": { "width": 100.0, "height": 100.0, "padding": [5.0, 5.0, 5.0, 5.0] },
+        "schemas": [schemas]
+    });
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-vertical-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    let pdf = PDForgeBuilder::new("vertical".to_string())
+        .add_font_from_file("TestFont", font.to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)?;
+
+    let doc = Document::load_mem(&pdf).unwrap();
+    Ok(doc
+        .get_pages()
+        .values()
+        .map(|&page_id| {
+            Content::decode(&doc.get_page_content(page_id))
+                .unwrap()
+                .operations
+        })
+        .collect())
+}
+
+fn render_text(name: &str, options: serde_json::Value) -> Vec<Operation> {
+    let mut schema = json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 40.0,
+        "height": 60.0,
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "writingMode": "vertical-rl"
+    });
+    schema
+        .as_object_mut()
+        .unwrap()
+        .extend(options.as_object().unwrap().clone());
+    render_pages(name, schema).unwrap().remove(0)
+}
+
+/// The text matrix of every `ShowText`.
+fn matrices(ops: &[Operation]) -> Vec<[f32; 6]> {
+    ops.iter()
+        .filter(|op| op.operator == "Tm")
+        .map(|op| {
+            let values: Vec<f32> = op.operands.iter().map(|o| o.as_float().unwrap()).collect();
+            values.try_into().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn upright_characters_stack_down_one_column() {
+    let ops = render_text("upright", json!({ "content": "漢字" }));
+    let [first, second] = matrices(&ops)[..] else {
+        panic!("expected two glyphs");
+    };
+
+    assert_eq!(&first[..4], &[1.0, 0.0, 0.0, 1.0]);
+    assert!((first[4] - second[4]).abs() < 0.01);
+    // One em (10pt) apart, top to bottom.
+    assert!(
+        (first[5] - second[5] - 10.0).abs() < 0.01,
+        "{first:?} {second:?}"
+    );
+}
+
+#[test]
+fn columns_run_right_to_left() {
+    let ops = render_text("columns", json!({ "content": "あ\nい" }));
+    let [first, second] = matrices(&ops)[..] else {
+        panic!("expected two glyphs");
+    };
+    assert!(first[4] > second[4], "{first:?} {second:?}");
+    assert!((first[5] - second[5]).abs() < 0.01);
+}
+
+#[test]
+fn punctuation_uses_vertical_glyphs_and_latin_turns() {
+    let ops = render_text("mixed", json!({ "content": "、PDF" }));
+    let matrices = matrices(&ops);
+
+    // The vertical comma is drawn by glyph id, the Latin run turned clockwise.
+    assert!(ops.iter().any(|op| op.operator == "TJ"));
+    assert_eq!(&matrices[1][..4], &[0.0, -1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn short_digit_runs_can_stand_across_the_column() {
+    let turned = matrices(&render_text("digits", json!({ "content": "12月" })));
+    assert_eq!(&turned[0][..4], &[0.0, -1.0, 1.0, 0.0]);
+
+    let across = matrices(&render_text(
+        "tcy",
+        json!({ "content": "12月", "tateChuYoko": 2 }),
+    ));
+    assert_eq!(across.len(), 2);
+    // Two digits squeezed into one em, then the next character an em below.
+    assert!(across[0][0] < 1.0 && across[0][1] == 0.0, "{across:?}");
+    assert!(across[0][5] > across[1][5]);
+}
+
+#[test]
+fn alignment_moves_columns_along_the_vertical_axis() {
+    let top = matrices(&render_text("top", json!({ "content": "あ" })))[0];
+    let center = matrices(&render_text(
+        "center",
+        json!({ "content": "あ", "alignment": "center", "verticalAlignment": "middle" }),
+    ))[0];
+
+    assert!(center[5] < top[5], "{top:?} {center:?}");
+    assert!(center[4] < top[4], "{top:?} {center:?}");
+}
+
+#[test]
+fn dynamic_font_size_fits_the_columns_in_the_box() {
+    let ops = render_text(
+        "dynamic-size",
+        json!({
+            "content": "縦書きの文章です",
+            "height": 30.0,
+            "fontSize": { "min": 6.0, "max": 60.0, "fit": "vertical" }
+        }),
+    );
+    let tf = ops.iter().find(|op| op.operator == "Tf").unwrap();
+    let font_size = tf.operands[1].as_float().unwrap();
+    assert!(font_size > 6.0, "{font_size}");
+
+    // Every glyph stays inside the 40mm x 30mm box.
+    let (width, height) = (40.0 * 72.0 / 25.4, 30.0 * 72.0 / 25.4);
+    for [_, _, _, _, x, y] in matrices(&ops) {
+        assert!(
+            x >= 0.0 && x + font_size <= width + 0.01,
+            "{x} at {font_size}pt"
+        );
+        // The glyph origin sits 0.88em below the top of its em box.
+        assert!(y >= 0.0 && y + 0.88 * font_size <= height + 0.01, "{y}");
+    }
+}
+
+#[test]
+fn vertical_dynamic_text_continues_on_the_next_page() {
+    let pages = render_pages(
+        "dynamic",
+        json!({
+            "type": "dynamicText",
+            "name": "body",
+            "position": { "x": 10.0, "y": 10.0 },
+            "width": 8.0,
+            "height": 30.0,
+            "content": "あ\nい\nう",
+            "fontName": "TestFont",
+            "fontSize": 10.0,
+            "writingMode": "vertical-rl"
+        }),
+    )
+    .unwrap();
+
+    // Three 10pt (3.5mm) columns, two of which fit the 8mm box.
+    assert_eq!(pages.len(), 2);
+    assert_eq!(matrices(&pages[0]).len(), 2);
+    assert_eq!(matrices(&pages[1]).len(), 1);
+}
+
+#[test]
+fn vertical_dynamic_text_moves_to_the_next_page_when_the_box_does_not_fit() {
+    let pages = render_flow(
+        "after-flow",
+        json!([
+            {
+                "type": "dynamicText",
+                "name": "lead",
+                "position": { "x": 10.0, "y": 10.0 },
+                "width": 80.0,
+                "height": 10.0,
+                "content": "line\n".repeat(22),
+                "fontName": "TestFont",
+                "fontSize": 10.0
+            },
+            {
+                "type": "dynamicText",
+                "name": "body",
+                "position": { "x": 10.0, "y": 10.0 },
+                "width": 8.0,
+                "height": 30.0,
+                "content": "あい",
+                "fontName": "TestFont",
+                "fontSize": 10.0,
+                "writingMode": "vertical-rl"
+            }
+        ]),
+    )
+    .unwrap();
+
+    // The lead text ends about 88mm down, so the 30mm box would cross the
+    // 95mm bottom padding; it starts at the next page's top padding instead.
+    assert_eq!(pages.len(), 2);
+    let box_bottom = pages[1]
+        .iter()
+        .find(|op| op.operator == "cm")
+        .map(|op| op.operands[5].as_float().unwrap())
+        .unwrap();
+    let expected = (100.0 - 5.0 - 30.0) * 72.0 / 25.4;
+    assert!(
+        (box_bottom - expected).abs() < 0.1,
+        "{box_bottom} vs {expected}"
+    );
+}
+
+#[test]
+fn rich_text_is_rejected_in_vertical_writing() {
+    let schema = json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 40.0,
+        "height": 60.0,
+        "content": "<b>a</b>",
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "richText": true,
+        "writingMode": "vertical-rl"
+    });
+    let error = render_pages("rich", schema).unwrap_err();
+    assert!(matches!(error, Error::RichText { .. }), "{error}");
+}
@@ -0,0 +1,190 @@
+use lopdf::{Dictionary, Document, Object};
+use pdforge::schemas::viewer::{Duplex, PrintScaling, ViewerPreferences};
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn template(viewer_preferences: serde_json::Value) -> serde_json::Value {
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 100,
+            "height": 50,
+            "padding": [5, 5, 5, 5],
+            "viewerPreferences": viewer_preferences
+        },
+        "schemas": [[{
+            "type": "dynamicText",
+            "name": "body",
+            "position": { "x": 5.0, "y": 5.0 },
+            "width": 90.0,
+            "height": 10.0,
+            "content": "label",
+            "fontName": "TestFont",
+            "fontSize": 10.0
+        }]]
+    })
+}
+
+fn write_template(name: &str, template: serde_json::Value) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("pdforge-viewer-{name}-{}.json", std::process::id()));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+    path
+}
+
+fn render(name: &str, template: serde_json::Value, defaults: ViewerPreferences) -> Document {
+    let path = write_template(name, template);
+    let pdf = PDForgeBuilder::new("viewer".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())
+        .expect("test font should load")
+        .load_template("main", path.to_str().unwrap())
+        .expect("template should load")
+        .viewer_preferences(defaults)
+        .build()
+        .render("main", vec![vec![HashMap::new()]], None, None)
+        .expect("template should render");
+    Document::load_mem(&pdf).expect("rendered PDF should parse")
+}
+
+fn name(dictionary: &Dictionary, key: &[u8]) -> String {
+    String::from_utf8(
+        dictionary
+            .get(key)
+            .and_then(Object::as_name)
+            .unwrap()
+            .to_vec(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn template_preferences_are_written_to_the_catalog() {
+    let doc = render(
+        "template",
+        template(json!({
+            "printScaling": "none",
+            "duplex": "duplexFlipShortEdge",
+            "numCopies": 3,
+            "pickTrayByPDFSize": true,
+            "hideToolbar": true,
+            "pageLayout": "singlePage",
+            "pageMode": "useThumbs",
+            "openZoom": 100
+        })),
+        ViewerPreferences::default(),
+    );
+
+    let catalog = doc.catalog().unwrap();
+    let preferences = catalog
+        .get(b"ViewerPreferences")
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(name(preferences, b"PrintScaling"), "None");
+    assert_eq!(name(preferences, b"Duplex"), "DuplexFlipShortEdge");
+    assert_eq!(preferences.get(b"NumCopies").unwrap().as_i64().unwrap(), 3);
+    assert!(preferences
+        .get(b"PickTrayByPDFSize")
+        .unwrap()
+        .as_bool()
+        .unwrap());
+    assert!(preferences.get(b"HideToolbar").unwrap().as_bool().unwrap());
+    assert_eq!(name(catalog, b"PageLayout"), "SinglePage");
+    assert_eq!(name(catalog, b"PageMode"), "UseThumbs");
+
+    let open_action = catalog
+        .get(b"OpenAction")
+        .and_then(Object::as_array)
+        .unwrap();
+    let first_page = *doc.get_pages().get(&1).unwrap();
+    assert_eq!(open_action[0].as_reference().unwrap(), first_page);
+    assert_eq!(open_action[1].as_name().unwrap(), b"XYZ");
+    assert_eq!(open_action[4].as_float().unwrap(), 1.0);
+}
+
+#[test]
+fn forge_defaults_fill_fields_the_template_leaves_unset() {
+    let defaults = ViewerPreferences {
+        print_scaling: Some(PrintScaling::None),
+        duplex: Some(Duplex::DuplexFlipLongEdge),
+        ..Default::default()
+    };
+
+    let doc = render(
+        "defaults",
+        template(json!({ "duplex": "simplex" })),
+        defaults,
+    );
+
+    let preferences = doc
+        .catalog()
+        .unwrap()
+        .get(b"ViewerPreferences")
+        .and_then(Object::as_dict)
+        .unwrap();
+    assert_eq!(name(preferences, b"PrintScaling"), "None");
+    assert_eq!(name(preferences, b"Duplex"), "Simplex");
+}
+
+#[test]
+fn documents_without_preferences_have_no_viewer_preferences() {
+    let doc = render("none", template(json!({})), ViewerPreferences::default());
+
+    let catalog = doc.catalog().unwrap();
+    assert!(catalog.get(b"ViewerPreferences").is_err());
+    assert!(catalog.get(b"OpenAction").is_err());
+}
+
+#[test]
+fn a_sequence_merges_the_preferences_of_every_section() {
+    let first = write_template("first", template(json!({ "printScaling": "none" })));
+    let second = write_template(
+        "second",
+        template(json!({ "printScaling": "appDefault", "duplex": "simplex" })),
+    );
+    let defaults = ViewerPreferences {
+        duplex: Some(Duplex::DuplexFlipLongEdge),
+        num_copies: std::num::NonZeroU32::new(2),
+        ..Default::default()
+    };
+
+    let pdf = PDForgeBuilder::new("viewer".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())
+        .expect("test font should load")
+        .load_template("first", first.to_str().unwrap())
+        .expect("template should load")
+        .load_template("second", second.to_str().unwrap())
+        .expect("template should load")
+        .viewer_preferences(defaults)
+        .build()
+        .render_sequence(
+            &[
+                ("first", vec![vec![HashMap::new()]]),
+                ("second", vec![vec![HashMap::new()]]),
+            ],
+            None,
+            None,
+        )
+        .expect("sequence should render");
+    let doc = Document::load_mem(&pdf).expect("rendered PDF should parse");
+
+    let preferences = doc
+        .catalog()
+        .unwrap()
+        .get(b"ViewerPreferences")
+        .and_then(Object::as_dict)
+        .unwrap();
+    // The first section that sets a field wins, a later section fills the
+    // fields earlier ones leave unset, and the forge defaults come last.
+    assert_eq!(name(preferences, b"PrintScaling"), "None");
+    assert_eq!(name(preferences, b"Duplex"), "Simplex");
+    assert_eq!(preferences.get(b"NumCopies").unwrap().as_i64().unwrap(), 2);
+}
@@ -0,0 +1,183 @@
+use lopdf::content::{Content, Operation};
+use lopdf::Document;
+use pdforge::PDForgeBuilder;
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const IMAGE_DATA_URL: &str = "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8/5+hHgAHggJ/PchI7wAAAABJRU5ErkJggg==";
+
+fn font_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join("fonts")
+        .join("NotoSansJP-Regular.ttf")
+}
+
+fn try_render(name: &str, template: serde_json::Value) -> Result<Vec<u8>, pdforge::schemas::Error> {
+    let path = std::env::temp_dir().join(format!(
+        "pdforge-watermark-{name}-{}.json",
+        std::process::id()
+    ));
+    std::fs::write(&path, template.to_string()).expect("template should be writable");
+
+    let forge = PDForgeBuilder::new("watermark".to_string())
+        .add_font_from_file("TestFont", font_path().to_str().unwrap())?
+        .load_template("main", path.to_str().unwrap())?
+        .build();
+
+    let pages = template["schemas"].as_array().map_or(1, Vec::len);
+    forge.render("main", vec![vec![HashMap::new()]; pages], None, None)
+}
+
+fn body() -> serde_json::Value {
+    json!({
+        "type": "text",
+        "name": "body",
+        "position": { "x": 10.0, "y": 10.0 },
+        "width": 80.0,
+        "height": 10.0,
+        "content": "body",
+        "fontName": "TestFont",
+        "fontSize": 10.0,
+        "alignment": "left",
+        "verticalAlignment": "top",
+        "characterSpacing": 0.0,
+        "lineHeight": 1.0,
+        "fontColor": "#000000"
+    })
+}
+
+fn template(watermark: serde_json::Value, pages: serde_json::Value) -> serde_json::Value {
+    json!({
+        "schemaVersion": "1.0",
+        "basePdf": {
+            "width": 210.0,
+            "height": 297.0,
+            "padding": [10.0, 10.0, 10.0, 10.0],
+            "watermark": watermark
+        },
+        "schemas": pages
+    })
+}
+
+fn draft() -> serde_json::Value {
+    json!({ "text": "DRAFT", "fontName": "TestFont", "rotate": 30 })
+}
+
+fn page_operations(pdf: &[u8]) -> Vec<Vec<Operation>> {
+    let doc = Document::load_mem(pdf).expect("rendered PDF should parse");
+    doc.get_pages()
+        .values()
+        .map(|page_id| {
+            Content::decode(&doc.get_page_content(*page_id))
+                .expect("content should decode")
+                .operations
+        })
+        .collect()
+}
+
+fn operands_mm(op: &Operation) -> Vec<f32> {
+    op.operands
+        .iter()
+        .map(|operand| operand.as_float().expect("numeric operand"))
+        .collect()
+}
+
+/// Where the rotated watermark frame is centred on each page, in whole mm.
+fn watermark_centres(pdf: &[u8]) -> Vec<(i32, i32)> {
+    page_operations(pdf)
+        .iter()
+        .map(|ops| {
+            let cm = ops
+                .iter()
+                .find(|op| op.operator == "cm" && operands_mm(op)[1] != 0.0)
+                .expect("the watermark is drawn in a rotated frame");
+            let m = operands_mm(cm);
+            let mm = |points: f32| (points * 25.4 / 72.0).round() as i32;
+            (mm(m[4]), mm(m[5]))
+        })
+        .collect()
+}
+
+fn position_of(ops: &[Operation], operator: &str) -> usize {
+    ops.iter()
+        .position(|op| op.operator == operator)
+        .unwrap_or_else(|| panic!("no {operator} on the page"))
+}
+
+#[test]
+fn the_watermark_is_centred_on_every_page_size() {
+    let pdf = try_render(
+        "centred",
+        template(
+            draft(),
+            json!([
+                [body()],
+                { "basePdf": { "size": "A5", "orientation": "landscape" }, "schemas": [body()] }
+            ]),
+        ),
+    )
+    .expect("watermarked template should render");
+
+    assert_eq!(watermark_centres(&pdf), vec![(105, 149), (105, 74)]);
+}
+
+#[test]
+fn z_order_puts_the_watermark_behind_or_above_the_content() {
+    let behind = {
+        let mut watermark = draft();
+        watermark["zOrder"] = json!("behind");
+        page_operations(&try_render("behind", template(watermark, json!([[body()]]))).unwrap())
+    };
+    let above =
+        page_operations(&try_render("above", template(draft(), json!([[body()]]))).unwrap());
+
+    // The watermark runs under its own graphics state; the body text does not.
+    assert!(position_of(&behind[0], "gs") < position_of(&behind[0], "BT"));
+    assert!(position_of(&above[0], "gs") > position_of(&above[0], "BT"));
+}
+
+#[test]
+fn a_tiled_image_watermark_repeats_across_the_page() {
+    let pdf = try_render(
+        "tiled",
+        template(
+            json!({ "image": IMAGE_DATA_URL, "width": 20, "layout": "tile", "opacity": 0.3 }),
+            json!([[body()]]),
+        ),
+    )
+    .expect("tiled watermark should render");
+
+    let stamps = page_operations(&pdf)[0]
+        .iter()
+        .filter(|op| op.operator == "Do")
+        .count();
+    assert!(stamps > 20, "only {stamps} stamps drawn");
+}
+
+#[test]
+fn an_auto_height_page_centres_the_watermark_on_its_final_height() {
+    let mut template = template(draft(), json!([[body()]]));
+    template["basePdf"]["height"] = json!("auto");
+    template["basePdf"]["maxHeight"] = json!(200.0);
+    let pdf = try_render("auto-height", template).expect("auto-height page should render");
+
+    let ops = &page_operations(&pdf)[0];
+    // The page shrinks to the body's bottom edge plus padding: 20 + 10 = 30mm.
+    let shift = operands_mm(&ops[position_of(ops, "cm")])[5] * 25.4 / 72.0;
+    let (_, centre) = watermark_centres(&pdf)[0];
+    assert_eq!((centre as f32 + shift).round() as i32, 15);
+}
+
+#[test]
+fn a_malformed_watermark_is_rejected_when_loading() {
+    let result = try_render(
+        "malformed",
+        template(
+            json!({ "text": "DRAFT", "image": IMAGE_DATA_URL }),
+            json!([[body()]]),
+        ),
+    );
+    assert!(result.is_err());
+}